@@ -128,6 +128,13 @@ impl ComprehensiveTestSuite {
             anyhow::bail!("Expected v2 server, got: {}", name);
         }
 
+        let initialized = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        });
+        writeln!(self.stdin, "{}", serde_json::to_string(&initialized)?)?;
+        self.stdin.flush()?;
+
         Ok(())
     }
 
@@ -191,7 +198,9 @@ impl ComprehensiveTestSuite {
             .and_then(|o| o.as_str())
             .ok_or_else(|| anyhow::anyhow!("No output field"))?;
 
-        if !output.contains("Breakpoint 1:") {
+        // Panic breakpoints set by debug_run take the first IDs
+        let id = result.get("id").and_then(|id| id.as_u64());
+        if !output.contains("Breakpoint ") || id.is_none() {
             anyhow::bail!("No breakpoint output found");
         }
 
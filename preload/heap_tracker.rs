@@ -0,0 +1,231 @@
+//! Allocation tracker that `debug_heap_profile` preloads into the debugged program on
+//! Linux.
+//!
+//! It wraps `malloc`, `calloc`, `realloc`, `posix_memalign`, `aligned_alloc`, and
+//! `free`, and appends one line per event to the file named by `FERROSCOPE_HEAP_LOG`:
+//! `+ <pid> <address> <size> <return addresses...>` for an allocation and
+//! `- <pid> <address>` for a free, numbers in hex. Ferroscope adds up the allocations
+//! that were never freed. The library is built by ferroscope with plain `rustc` and
+//! must not allocate itself: lines are formatted on the stack and written with `write`.
+
+use std::cell::Cell;
+use std::ffi::{c_char, c_int, c_void};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+
+extern "C" {
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int;
+    fn getenv(name: *const c_char) -> *const c_char;
+    fn getpid() -> c_int;
+    fn open(path: *const c_char, flags: c_int, mode: c_int) -> c_int;
+    fn write(fd: c_int, buffer: *const c_void, count: usize) -> isize;
+}
+
+const RTLD_NEXT: *mut c_void = -1isize as *mut c_void;
+const O_WRONLY: c_int = 0o1;
+const O_CREAT: c_int = 0o100;
+const O_APPEND: c_int = 0o2000;
+const O_CLOEXEC: c_int = 0o2000000;
+
+/// Return addresses recorded per allocation, the tracker's own two frames included.
+const FRAMES: usize = 24;
+
+/// Memory handed out while `dlsym` looks up the real functions, which it may allocate.
+static mut BOOTSTRAP: [u8; 8192] = [0; 8192];
+static BOOTSTRAP_USED: AtomicUsize = AtomicUsize::new(0);
+
+static REAL_MALLOC: AtomicUsize = AtomicUsize::new(0);
+static REAL_CALLOC: AtomicUsize = AtomicUsize::new(0);
+static REAL_REALLOC: AtomicUsize = AtomicUsize::new(0);
+static REAL_FREE: AtomicUsize = AtomicUsize::new(0);
+static REAL_POSIX_MEMALIGN: AtomicUsize = AtomicUsize::new(0);
+static REAL_ALIGNED_ALLOC: AtomicUsize = AtomicUsize::new(0);
+
+/// The log's file descriptor: -1 before it is opened, -2 if there is none.
+static LOG: AtomicI32 = AtomicI32::new(-1);
+
+thread_local! {
+    /// Set while this thread records an event or resolves a function, so allocations
+    /// made by `backtrace` and `dlsym` pass through untracked.
+    static BUSY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Looks up the next definition of `name`, e.g. glibc's `malloc`, once.
+unsafe fn real(slot: &AtomicUsize, name: &[u8]) -> usize {
+    let function = slot.load(Ordering::Acquire);
+    if function != 0 {
+        return function;
+    }
+    let was_busy = BUSY.with(|busy| busy.replace(true));
+    let function = dlsym(RTLD_NEXT, name.as_ptr().cast()) as usize;
+    BUSY.with(|busy| busy.set(was_busy));
+    slot.store(function, Ordering::Release);
+    function
+}
+
+fn resolving() -> bool {
+    REAL_MALLOC.load(Ordering::Acquire) == 0 || REAL_CALLOC.load(Ordering::Acquire) == 0
+}
+
+unsafe fn bootstrap_alloc(size: usize) -> *mut c_void {
+    let size = (size + 15) & !15;
+    let start = BOOTSTRAP_USED.fetch_add(size, Ordering::Relaxed);
+    if start + size > 8192 {
+        return std::ptr::null_mut();
+    }
+    std::ptr::addr_of_mut!(BOOTSTRAP).cast::<u8>().add(start).cast()
+}
+
+fn is_bootstrap(pointer: *mut c_void) -> bool {
+    let start = std::ptr::addr_of!(BOOTSTRAP) as usize;
+    (start..start + 8192).contains(&(pointer as usize))
+}
+
+/// A line being formatted on the stack.
+struct Line {
+    bytes: [u8; 32 + 19 * (FRAMES + 3)],
+    len: usize,
+}
+
+impl Line {
+    fn push(&mut self, byte: u8) {
+        if self.len < self.bytes.len() {
+            self.bytes[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_hex(&mut self, value: usize) {
+        self.push(b' ');
+        let digits = (usize::BITS - value.leading_zeros()).div_ceil(4).max(1);
+        for shift in (0..digits).rev() {
+            self.push(b"0123456789abcdef"[(value >> (shift * 4)) & 15]);
+        }
+    }
+}
+
+unsafe fn log_fd() -> c_int {
+    let fd = LOG.load(Ordering::Acquire);
+    if fd != -1 {
+        return fd;
+    }
+    let path = getenv(c"FERROSCOPE_HEAP_LOG".as_ptr());
+    let fd = if path.is_null() {
+        -2
+    } else {
+        open(path, O_WRONLY | O_CREAT | O_APPEND | O_CLOEXEC, 0o600)
+    };
+    let fd = if fd < 0 { -2 } else { fd };
+    LOG.store(fd, Ordering::Release);
+    fd
+}
+
+/// Appends an event for `pointer`: an allocation of `size` bytes, or a free.
+#[inline(never)]
+unsafe fn record(allocated: Option<usize>, pointer: *mut c_void) {
+    if pointer.is_null() || BUSY.with(|busy| busy.replace(true)) {
+        return;
+    }
+    let fd = log_fd();
+    if fd >= 0 {
+        let mut line = Line {
+            bytes: [0; 32 + 19 * (FRAMES + 3)],
+            len: 0,
+        };
+        line.push(if allocated.is_some() { b'+' } else { b'-' });
+        line.push_hex(getpid() as usize);
+        line.push_hex(pointer as usize);
+        if let Some(size) = allocated {
+            line.push_hex(size);
+            let mut frames = [std::ptr::null_mut(); FRAMES];
+            let count = backtrace(frames.as_mut_ptr(), FRAMES as c_int).max(0) as usize;
+            // The first two frames are this function and the wrapper calling it
+            for frame in frames.iter().take(count).skip(2) {
+                line.push_hex(*frame as usize);
+            }
+        }
+        line.push(b'\n');
+        write(fd, line.bytes.as_ptr().cast(), line.len);
+    }
+    BUSY.with(|busy| busy.set(false));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    if resolving() && BUSY.with(|busy| busy.get()) {
+        return bootstrap_alloc(size);
+    }
+    let real: extern "C" fn(usize) -> *mut c_void =
+        std::mem::transmute(real(&REAL_MALLOC, b"malloc\0"));
+    let pointer = real(size);
+    record(Some(size), pointer);
+    pointer
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn calloc(count: usize, size: usize) -> *mut c_void {
+    if resolving() && BUSY.with(|busy| busy.get()) {
+        // Bootstrap memory is zeroed and never reused
+        return bootstrap_alloc(count.saturating_mul(size));
+    }
+    let real: extern "C" fn(usize, usize) -> *mut c_void =
+        std::mem::transmute(real(&REAL_CALLOC, b"calloc\0"));
+    let pointer = real(count, size);
+    record(Some(count.saturating_mul(size)), pointer);
+    pointer
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn realloc(pointer: *mut c_void, size: usize) -> *mut c_void {
+    if is_bootstrap(pointer) {
+        let moved = malloc(size);
+        if !moved.is_null() {
+            let end = std::ptr::addr_of!(BOOTSTRAP) as usize + 8192;
+            let size = size.min(end - pointer as usize);
+            std::ptr::copy_nonoverlapping(pointer.cast::<u8>(), moved.cast::<u8>(), size);
+        }
+        return moved;
+    }
+    let real: extern "C" fn(*mut c_void, usize) -> *mut c_void =
+        std::mem::transmute(real(&REAL_REALLOC, b"realloc\0"));
+    let moved = real(pointer, size);
+    if !moved.is_null() || size == 0 {
+        record(None, pointer);
+    }
+    record(Some(size), moved);
+    moved
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn posix_memalign(
+    out: *mut *mut c_void,
+    alignment: usize,
+    size: usize,
+) -> c_int {
+    let real: extern "C" fn(*mut *mut c_void, usize, usize) -> c_int =
+        std::mem::transmute(real(&REAL_POSIX_MEMALIGN, b"posix_memalign\0"));
+    let result = real(out, alignment, size);
+    if result == 0 {
+        record(Some(size), *out);
+    }
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aligned_alloc(alignment: usize, size: usize) -> *mut c_void {
+    let real: extern "C" fn(usize, usize) -> *mut c_void =
+        std::mem::transmute(real(&REAL_ALIGNED_ALLOC, b"aligned_alloc\0"));
+    let pointer = real(alignment, size);
+    record(Some(size), pointer);
+    pointer
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn free(pointer: *mut c_void) {
+    if pointer.is_null() || is_bootstrap(pointer) {
+        return;
+    }
+    record(None, pointer);
+    let real: extern "C" fn(*mut c_void) = std::mem::transmute(real(&REAL_FREE, b"free\0"));
+    real(pointer);
+}
@@ -0,0 +1,403 @@
+//! The debuggers ferroscope can drive, and writing commands for them.
+//!
+//! [`DebuggerKind`] selects between LLDB's command interpreter and the GDB/MI machine
+//! interface; the quoting helpers build command lines for each, [`lldb_sentinel`]
+//! marks where an LLDB reply ends, [`sandbox_command`] confines a debugger for
+//! `--sandbox`, and `forward_debugger_output` and `forward_debugger_stderr` pump a
+//! debugger's stdout and stderr into the session.
+
+use crate::error::ErrorKind;
+use crate::parse::{notable_stderr, stop_event};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{ChildStderr, ChildStdout};
+use tokio::sync::{broadcast, mpsc, Notify};
+
+/// Which debugger a session drives and how ferroscope talks to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebuggerKind {
+    /// LLDB's interactive command interpreter, framed by sentinel markers, or by
+    /// prompt detection when its Python support is missing
+    Lldb,
+    /// `lldb-mi`, LLDB's GDB/MI-compatible machine interface
+    LldbMi,
+    /// GDB running its MI2 machine interface
+    Gdb,
+}
+
+impl DebuggerKind {
+    /// Parses a debugger name as accepted by the `debugger` tool argument.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "lldb" => Ok(DebuggerKind::Lldb),
+            "lldb-mi" => Ok(DebuggerKind::LldbMi),
+            "gdb" => Ok(DebuggerKind::Gdb),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown debugger: {} (expected lldb, lldb-mi or gdb)",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebuggerKind::Lldb => "lldb",
+            DebuggerKind::LldbMi => "lldb-mi",
+            DebuggerKind::Gdb => "gdb",
+        }
+    }
+
+    /// Returns true if this debugger speaks GDB/MI rather than an interactive prompt.
+    pub fn uses_mi(&self) -> bool {
+        !matches!(self, DebuggerKind::Lldb)
+    }
+
+    /// Builds the command that spawns this debugger.
+    pub(crate) fn command(&self) -> tokio::process::Command {
+        self.command_at(self.name())
+    }
+
+    /// Builds the command that spawns this debugger from the executable at `path`.
+    pub(crate) fn command_at(&self, path: &str) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new(path);
+        if *self == DebuggerKind::Gdb {
+            cmd.args(["--interpreter=mi2", "--quiet"]);
+        }
+        cmd
+    }
+}
+
+/// Quotes a string as an MI C-string argument.
+pub(crate) fn mi_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a string as a single argument to an LLDB command.
+pub(crate) fn lldb_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Wraps a native debugger command so it can be sent over GDB/MI. A line break is
+/// left unescaped, so [`single_line`] still refuses the command.
+pub(crate) fn mi_console_command(command: &str) -> String {
+    let lines: Vec<String> = command.split('\n').map(mi_quote).collect();
+    format!("-interpreter-exec console {}", lines.join("\n"))
+}
+
+/// Refuses a command that spans several lines. The debugger would run each line as a
+/// command of its own, so a line break in a tool argument could smuggle in any
+/// command past the raw command and `--sandbox` checks.
+pub(crate) fn single_line(command: &str) -> Result<()> {
+    if command.contains(['\n', '\r']) {
+        return Err(ErrorKind::InvalidArgument.error(format!(
+            "Debugger commands must be a single line, but got {:?}",
+            command
+        )));
+    }
+    Ok(())
+}
+
+/// Start of the marker line LLDB prints after each command's reply.
+pub(crate) const SENTINEL_PREFIX: &str = "<<<FERRO-";
+
+/// Returns the marker for LLDB command number `n` and the command that prints it.
+///
+/// The command splits the marker into two Python string literals, so an LLDB that
+/// echoes its input never shows the marker until the command actually runs.
+pub(crate) fn lldb_sentinel(n: u64) -> (String, String) {
+    (
+        format!("{}{}>>>", SENTINEL_PREFIX, n),
+        format!("script print(\"{}\" \"{}>>>\")", SENTINEL_PREFIX, n),
+    )
+}
+
+/// Returns true if `line` is a marker printed by an [`lldb_sentinel`] command.
+pub(crate) fn is_sentinel(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with(SENTINEL_PREFIX) && line.ends_with(">>>")
+}
+
+/// CPU time each process under `--sandbox` may use, in seconds.
+pub(crate) const SANDBOX_CPU_SECONDS: u64 = 600;
+
+/// Address space each process under `--sandbox` may map, in KiB (8 GiB).
+pub(crate) const SANDBOX_MEMORY_KIB: u64 = 8 * 1024 * 1024;
+
+/// Debugger commands that reach the host: they start programs, run scripts with the
+/// debugger's privileges, or use the host platform, or define commands that could
+/// wrap one of those. `--sandbox` refuses them on breakpoint hits, and
+/// `debug_raw_command` unless allowed.
+pub(crate) const HOST_COMMANDS: [&str; 15] = [
+    "shell",
+    "pipe",
+    "make",
+    "python",
+    "python-interactive",
+    "guile",
+    "source",
+    "script",
+    "command script",
+    "command source",
+    "platform",
+    "command alias",
+    "command regex",
+    "alias",
+    "define",
+];
+
+/// Debugger commands `debug_raw_command` refuses unless allowed, besides
+/// [`HOST_COMMANDS`]: they run, stop, or replace the program, write its memory,
+/// registers, or variables, or change the breakpoints, behind the back of the tools
+/// that track and gate them.
+pub(crate) const RAW_COMMANDS_DENIED: [&str; 54] = [
+    "run",
+    "r",
+    "start",
+    "starti",
+    "continue",
+    "c",
+    "next",
+    "n",
+    "step",
+    "s",
+    "nexti",
+    "ni",
+    "stepi",
+    "si",
+    "finish",
+    "until",
+    "advance",
+    "reverse-continue",
+    "reverse-next",
+    "reverse-step",
+    "reverse-finish",
+    "jump",
+    "return",
+    "signal",
+    "kill",
+    "k",
+    "attach",
+    "detach",
+    "file",
+    "target",
+    "process",
+    "thread step-in",
+    "thread step-over",
+    "thread step-out",
+    "thread step-inst",
+    "thread step-inst-over",
+    "thread continue",
+    "thread until",
+    "thread jump",
+    "thread return",
+    "memory write",
+    "register write",
+    "set var",
+    "break",
+    "b",
+    "tbreak",
+    "delete",
+    "d",
+    "breakpoint",
+    "watch",
+    "watchpoint",
+    "quit",
+    "q",
+    "eval",
+];
+
+/// Wraps the debugger command `cmd` for `--sandbox`. The debugger, and so every program
+/// it launches, gets the CPU and memory limits above and a cleaned environment keeping
+/// only `PATH` and `LANG`. On Linux it also runs in new user and network namespaces,
+/// leaving it only a loopback interface that is down.
+pub(crate) fn sandbox_command(cmd: &tokio::process::Command) -> tokio::process::Command {
+    let limits = format!(
+        "ulimit -t {} && ulimit -v {} && exec \"$0\" \"$@\"",
+        SANDBOX_CPU_SECONDS, SANDBOX_MEMORY_KIB
+    );
+    let mut sandboxed = if cfg!(target_os = "linux") {
+        let mut unshare = tokio::process::Command::new("unshare");
+        unshare.args(["--user", "--map-root-user", "--net", "--", "sh", "-c"]);
+        unshare
+    } else {
+        let mut sh = tokio::process::Command::new("sh");
+        sh.arg("-c");
+        sh
+    };
+    let cmd = cmd.as_std();
+    sandboxed
+        .arg(limits)
+        .arg(cmd.get_program())
+        .args(cmd.get_args());
+    sandboxed.env_clear().env("HOME", std::env::temp_dir());
+    for name in ["PATH", "LANG"] {
+        if let Some(value) = std::env::var_os(name) {
+            sandboxed.env(name, value);
+        }
+    }
+    sandboxed
+}
+
+/// The first of `patterns` that `command` runs, if any. A pattern is one or more
+/// command words, e.g. `command script`. Each word matches when one of the two
+/// abbreviates the other, as the debuggers allow, though a single letter only matches
+/// itself as the first word, where the debuggers keep them for aliases such as `n`;
+/// `!` and `|` are GDB's shorthands for `shell` and `pipe`.
+pub(crate) fn matching_command<'a>(
+    command: &str,
+    patterns: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let command = command.trim_start();
+    let mut words: Vec<&str> = command.split_whitespace().collect();
+    if let Some(rest) = command.strip_prefix('!') {
+        words = vec!["shell", rest];
+    } else if let Some(rest) = command.strip_prefix('|') {
+        words = vec!["pipe", rest];
+    }
+    patterns.into_iter().find(|pattern| {
+        let pattern: Vec<&str> = pattern.split_whitespace().collect();
+        pattern.len() <= words.len()
+            && pattern
+                .iter()
+                .zip(&words)
+                .enumerate()
+                .all(|(i, (expected, word))| {
+                    word == expected
+                        || ((i > 0 || word.len() >= 2) && expected.starts_with(word))
+                        || (expected.len() >= 2 && word.starts_with(expected))
+                })
+    })
+}
+
+/// Debugger commands that evaluate an expression. `debug_raw_command` refuses them,
+/// unless allowed, when the expression could change the program, as `debug_eval` does.
+pub(crate) const EVALUATION_COMMANDS: [&str; 9] = [
+    "expression",
+    "e",
+    "print",
+    "p",
+    "call",
+    "output",
+    "inspect",
+    "po",
+    "dwim-print",
+];
+
+/// The first of [`EVALUATION_COMMANDS`] that `command` runs, if any, and the expression
+/// it evaluates: what follows the command word, a GDB `/x` format, and LLDB options
+/// ending in `--`.
+pub(crate) fn evaluated_expression(command: &str) -> Option<(&'static str, &str)> {
+    let command = command.trim_start();
+    let (word, rest) = command
+        .split_once(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or((command, ""));
+    let evaluates = matching_command(word, EVALUATION_COMMANDS)?;
+    let rest = rest.trim_start();
+    let rest = match rest.split_once("-- ") {
+        Some((options, expression))
+            if options.is_empty() || (options.starts_with('-') && options.ends_with(' ')) =>
+        {
+            expression
+        }
+        // `p/x value` leaves `x value`
+        _ if command[word.len()..].starts_with('/') => {
+            rest.split_once(char::is_whitespace).map_or("", |(_, e)| e)
+        }
+        _ => rest,
+    };
+    Some((evaluates, rest.trim()))
+}
+
+/// Quotes a string for a POSIX shell.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Forwards the debugger's standard output line by line until it closes, waking
+/// `output_ready` after each line.
+///
+/// When `notify_on_stop` is set — the last command left the program running — the
+/// first stop or exit is also announced to the client as an MCP
+/// `notifications/message` on `notifications`, so the agent learns about it without
+/// polling. DAP mode reports stops itself and passes no channel.
+pub(crate) async fn forward_debugger_output(
+    stdout: ChildStdout,
+    lines: mpsc::UnboundedSender<String>,
+    notify_on_stop: Arc<AtomicBool>,
+    notifications: Option<broadcast::Sender<Value>>,
+    output_ready: Arc<Notify>,
+) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        if let Some(event) = stop_event(&line) {
+            let notifications = notifications
+                .as_ref()
+                .filter(|_| notify_on_stop.swap(false, Ordering::SeqCst));
+            if let Some(notifications) = notifications {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": {
+                        "level": "info",
+                        "logger": "ferroscope",
+                        "data": {
+                            "event": event,
+                            "output": line.trim(),
+                            "hint": "Call debug_poll or debug_wait_for_stop for details"
+                        }
+                    }
+                });
+                let _ = notifications.send(notification);
+            }
+        }
+
+        if lines.send(line).is_err() {
+            break;
+        }
+        output_ready.notify_one();
+    }
+}
+
+/// Reads a debugger's stderr until it closes, so a chatty debugger never blocks on a
+/// full pipe. Every line is logged; notable ones (see [`notable_stderr`]), such as
+/// warnings about missing debug info, are also sent on `notable` for the next tool
+/// result to report.
+pub(crate) async fn forward_debugger_stderr(
+    stderr: ChildStderr,
+    notable: mpsc::UnboundedSender<String>,
+) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if notable_stderr(&line) {
+            tracing::warn!(target: "ferroscope.debugger", stderr = %line);
+            let _ = notable.send(line);
+        } else {
+            tracing::debug!(target: "ferroscope.debugger", stderr = %line);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Settings read from `ferroscope.toml` when `debug_run` starts a session.
+//!
+//! Two files are read, both optional: the user's `~/.config/ferroscope/config.toml`
+//! (under `$XDG_CONFIG_HOME` when set), then the `ferroscope.toml` of the project, found
+//! in the directory of the target or the nearest one above it. Project settings win;
+//! `env`, `source_map`, and `limits` entries are merged and `breakpoints` added up:
+//!
+//! ```toml
+//! debugger = "gdb"
+//! debugger_path = "/opt/gdb-15/bin/gdb"
+//! timeout_ms = 30000
+//! idle_timeout_secs = 7200
+//! break_on_panic = true
+//! breakpoints = ["my_crate::init", "src/db.rs:40"]
+//! profile = "release"
+//!
+//! [env]
+//! RUST_LOG = "debug"
+//!
+//! [source_map]
+//! "/build/app" = "/home/me/app"
+//!
+//! [limits]
+//! cpu_seconds = 60
+//! memory_mb = 2048
+//! open_files = 1024
+//! output_mb = 100
+//! ```
+
+use crate::error::ErrorKind;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the project configuration file.
+pub const PROJECT_FILE: &str = "ferroscope.toml";
+
+/// Settings for `debug_run`, merged from the configuration files that exist.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectConfig {
+    /// Files the settings were read from, the user's first
+    pub files: Vec<PathBuf>,
+    /// Debugger to use when `debug_run` names none: `lldb`, `lldb-mi`, or `gdb`
+    pub debugger: Option<String>,
+    /// Debugger executable to run instead of the one on `PATH`
+    pub debugger_path: Option<String>,
+    /// How long debugger commands may take when a tool call sets no `timeout_ms`
+    pub timeout: Option<Duration>,
+    /// How long the session may go without a tool call before it is ended; zero never
+    pub idle_timeout: Option<Duration>,
+    /// Whether to stop on panics when `debug_run` does not say
+    pub break_on_panic: Option<bool>,
+    /// Breakpoint locations set once the program is loaded
+    pub breakpoints: Vec<String>,
+    /// Environment variables for the program; those `debug_run` passes take precedence
+    pub env: Vec<(String, String)>,
+    /// Source path prefixes to rewrite, as `--source-map` gives them
+    pub source_map: Vec<(String, String)>,
+    /// Cargo profile to build with when `debug_run` names none
+    pub profile: Option<String>,
+    /// Caps on what the program may use, over those of the `--limit-*` flags
+    pub limits: ResourceLimits,
+}
+
+/// Caps on the resources a debugged program may use; a program that goes over one is
+/// killed. Unset limits do not apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// CPU time, in seconds
+    pub cpu_seconds: Option<u64>,
+    /// Resident memory, in MiB
+    pub memory_mb: Option<u64>,
+    /// Open file descriptors, counted on Linux only
+    pub open_files: Option<u64>,
+    /// Bytes written to stdout and stderr together, in MiB
+    pub output_mb: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Whether no limit is set.
+    pub fn is_empty(&self) -> bool {
+        self.to_pairs().iter().all(|(_, limit)| limit.is_none())
+    }
+
+    /// These limits, with those unset taken from `defaults`.
+    pub fn or(self, defaults: ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            cpu_seconds: self.cpu_seconds.or(defaults.cpu_seconds),
+            memory_mb: self.memory_mb.or(defaults.memory_mb),
+            open_files: self.open_files.or(defaults.open_files),
+            output_mb: self.output_mb.or(defaults.output_mb),
+        }
+    }
+
+    /// Each limit by its setting name, e.g. `cpu_seconds`.
+    pub fn to_pairs(&self) -> [(&'static str, Option<u64>); 4] {
+        [
+            ("cpu_seconds", self.cpu_seconds),
+            ("memory_mb", self.memory_mb),
+            ("open_files", self.open_files),
+            ("output_mb", self.output_mb),
+        ]
+    }
+
+    /// The limits as reported in `debug_run`'s `limits`.
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.to_pairs()
+                .into_iter()
+                .map(|(name, limit)| (name.to_string(), json!(limit)))
+                .collect(),
+        )
+    }
+}
+
+impl ProjectConfig {
+    /// Reads the user's configuration, or `user_file` instead, and that of the project
+    /// holding `target`, a project directory or a binary.
+    pub fn load(target: &Path, user_file: Option<&Path>) -> Result<Self> {
+        let mut config = ProjectConfig::default();
+        let user_file = match user_file {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_user_file(),
+        };
+        let files = [user_file, project_file(target)];
+        for path in files.into_iter().flatten() {
+            let text = std::fs::read_to_string(&path)?;
+            config.merge(&text).map_err(|e| {
+                ErrorKind::InvalidArgument.error(format!("{}: {}", path.display(), e))
+            })?;
+            config.files.push(path);
+        }
+        Ok(config)
+    }
+
+    /// Applies the settings in the TOML document `text` over the current ones.
+    pub fn merge(&mut self, text: &str) -> Result<()> {
+        let table: toml::Table = text.parse()?;
+        for (key, value) in &table {
+            let string = || {
+                value
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow::anyhow!("{} must be a string", key))
+            };
+            let strings = |value: &toml::Value| -> Result<Vec<(String, String)>> {
+                let table = value
+                    .as_table()
+                    .ok_or_else(|| anyhow::anyhow!("{} must be a table", key))?;
+                table
+                    .iter()
+                    .map(|(name, value)| match value.as_str() {
+                        Some(value) => Ok((name.clone(), value.to_string())),
+                        None => Err(anyhow::anyhow!("{}.{} must be a string", key, name)),
+                    })
+                    .collect()
+            };
+            match key.as_str() {
+                "debugger" => self.debugger = Some(string()?),
+                "debugger_path" => self.debugger_path = Some(string()?),
+                "profile" => self.profile = Some(string()?),
+                "timeout_ms" => {
+                    let millis = value
+                        .as_integer()
+                        .filter(|&ms| ms > 0)
+                        .ok_or_else(|| anyhow::anyhow!("timeout_ms must be a positive integer"))?;
+                    self.timeout = Some(Duration::from_millis(millis as u64));
+                }
+                "idle_timeout_secs" => {
+                    let secs = value
+                        .as_integer()
+                        .filter(|&secs| secs >= 0)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("idle_timeout_secs must be a non-negative integer")
+                        })?;
+                    self.idle_timeout = Some(Duration::from_secs(secs as u64));
+                }
+                "break_on_panic" => {
+                    let enabled = value
+                        .as_bool()
+                        .ok_or_else(|| anyhow::anyhow!("break_on_panic must be a boolean"))?;
+                    self.break_on_panic = Some(enabled);
+                }
+                "breakpoints" => {
+                    let locations = value
+                        .as_array()
+                        .and_then(|items| {
+                            items
+                                .iter()
+                                .map(|item| item.as_str().map(str::to_string))
+                                .collect::<Option<Vec<_>>>()
+                        })
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("breakpoints must be an array of strings")
+                        })?;
+                    self.breakpoints.extend(locations);
+                }
+                "env" => {
+                    for (name, value) in strings(value)? {
+                        self.env.retain(|(existing, _)| *existing != name);
+                        self.env.push((name, value));
+                    }
+                }
+                "source_map" => {
+                    for (from, to) in strings(value)? {
+                        self.source_map.retain(|(existing, _)| *existing != from);
+                        self.source_map.push((from, to));
+                    }
+                }
+                "limits" => {
+                    let table = value
+                        .as_table()
+                        .ok_or_else(|| anyhow::anyhow!("limits must be a table"))?;
+                    for (name, value) in table {
+                        let limit = match name.as_str() {
+                            "cpu_seconds" => &mut self.limits.cpu_seconds,
+                            "memory_mb" => &mut self.limits.memory_mb,
+                            "open_files" => &mut self.limits.open_files,
+                            "output_mb" => &mut self.limits.output_mb,
+                            _ => return Err(anyhow::anyhow!("unknown limit {}", name)),
+                        };
+                        let value = value.as_integer().filter(|&n| n > 0).ok_or_else(|| {
+                            anyhow::anyhow!("limits.{} must be a positive integer", name)
+                        })?;
+                        *limit = Some(value as u64);
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("unknown setting {}", key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the `debug_run` arguments the call left out: `debugger`,
+    /// `break_on_panic`, `profile`, and the `env` variables it does not set itself.
+    pub fn apply(&self, arguments: &mut Value) {
+        let defaults = [
+            ("debugger", self.debugger.as_ref().map(|v| json!(v))),
+            ("break_on_panic", self.break_on_panic.map(|v| json!(v))),
+            ("profile", self.profile.as_ref().map(|v| json!(v))),
+        ];
+        for (key, default) in defaults {
+            if let Some(default) = default.filter(|_| arguments.get(key).is_none()) {
+                arguments[key] = default;
+            }
+        }
+        if self.env.is_empty() {
+            return;
+        }
+        let mut env: serde_json::Map<String, Value> = self
+            .env
+            .iter()
+            .map(|(name, value)| (name.clone(), json!(value)))
+            .collect();
+        // A malformed env argument is left for debug_run to reject
+        match arguments.get("env") {
+            Some(Value::Object(given)) => env.extend(given.clone()),
+            Some(_) => return,
+            None => {}
+        }
+        arguments["env"] = Value::Object(env);
+    }
+
+    /// The settings as reported in `debug_run`'s `config`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "files": self.files,
+            "debugger_path": self.debugger_path,
+            "timeout_ms": self.timeout.map(|timeout| timeout.as_millis() as u64),
+            "idle_timeout_secs": self.idle_timeout.map(|timeout| timeout.as_secs()),
+            "breakpoints": self.breakpoints,
+            "limits": self.limits.to_json(),
+            "source_map": self.source_map
+                .iter()
+                .map(|(from, to)| json!({"from": from, "to": to}))
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+/// The user's configuration file, if there is one.
+fn default_user_file() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    let path = base.join("ferroscope").join("config.toml");
+    path.is_file().then_some(path)
+}
+
+/// The `ferroscope.toml` in the directory of `target` or the nearest one above it.
+fn project_file(target: &Path) -> Option<PathBuf> {
+    let target = std::fs::canonicalize(target).ok()?;
+    let start = if target.is_dir() {
+        target.as_path()
+    } else {
+        target.parent()?
+    };
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A user file followed by a project file that overrides it.
+    fn layered() -> ProjectConfig {
+        let mut config = ProjectConfig::default();
+        config
+            .merge("debugger = \"lldb\"\nbreakpoints = [\"main\"]\n[env]\nRUST_LOG = \"info\"\nA = \"1\"\n")
+            .unwrap();
+        config
+            .merge("debugger = \"gdb\"\ntimeout_ms = 30000\nbreakpoints = [\"src/db.rs:40\"]\n[env]\nRUST_LOG = \"debug\"\n[source_map]\n\"/build\" = \"/src\"\n")
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn later_files_override_settings_and_add_breakpoints() {
+        let config = layered();
+        assert_eq!(config.debugger.as_deref(), Some("gdb"));
+        assert_eq!(config.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(config.breakpoints, ["main", "src/db.rs:40"]);
+        assert_eq!(
+            config.source_map,
+            [("/build".to_string(), "/src".to_string())]
+        );
+    }
+
+    #[test]
+    fn tool_arguments_override_the_config() {
+        let mut arguments = json!({"debugger": "lldb", "env": {"A": "2"}});
+        layered().apply(&mut arguments);
+        assert_eq!(arguments["debugger"], "lldb");
+        assert_eq!(arguments["env"], json!({"RUST_LOG": "debug", "A": "2"}));
+    }
+
+    #[test]
+    fn limits_merge_field_by_field_over_the_defaults() {
+        let mut config = ProjectConfig::default();
+        config
+            .merge("[limits]\nmemory_mb = 512\noutput_mb = 10\n")
+            .unwrap();
+        config.merge("[limits]\nmemory_mb = 1024\n").unwrap();
+        let defaults = ResourceLimits {
+            cpu_seconds: Some(60),
+            memory_mb: Some(256),
+            ..ResourceLimits::default()
+        };
+        assert_eq!(
+            config.limits.or(defaults),
+            ResourceLimits {
+                cpu_seconds: Some(60),
+                memory_mb: Some(1024),
+                open_files: None,
+                output_mb: Some(10),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        let mut config = ProjectConfig::default();
+        assert!(config.merge("[limits]\nmemory_mb = -1\n").is_err());
+        assert!(config.merge("[limits]\nthreads = 4\n").is_err());
+    }
+
+    #[test]
+    fn idle_timeouts_may_be_zero_but_not_negative() {
+        let mut config = ProjectConfig::default();
+        config.merge("idle_timeout_secs = 0").unwrap();
+        assert_eq!(config.idle_timeout, Some(Duration::ZERO));
+        assert!(config.merge("idle_timeout_secs = -1").is_err());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let mut config = ProjectConfig::default();
+        assert!(config.merge("timeout_ms = 0").is_err());
+        assert!(config.merge("breakpoint = [\"main\"]").is_err());
+    }
+}
@@ -0,0 +1,639 @@
+//! Debug Adapter Protocol front end, started with `--dap <address>`.
+//!
+//! Editors that speak DAP connect over TCP and drive the same session layer as the MCP
+//! tools: each request is translated into the matching `debug_*` call on
+//! [`DebugServer`], and the JSON it returns into DAP response bodies and events.
+//! Clients are served one at a time, since a server holds a single debugging session.
+
+use crate::parse::Variable;
+use crate::session::{BreakpointAction, BuildConfig, EvalOptions, LaunchConfig};
+use crate::DebugServer;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+/// How often a running program is checked for a stop to report.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Largest message body accepted.
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+/// Accepts DAP clients on `address` until the process is stopped.
+pub async fn serve(server: &DebugServer, address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    tracing::info!("DAP server listening on {}", listener.local_addr()?);
+
+    let clients = async {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            tracing::info!("DAP client connected from {}", peer);
+            let (read, writer) = stream.into_split();
+            if let Err(e) = DapConnection::new(server, writer).run(read).await {
+                tracing::warn!("DAP connection error: {}", e);
+            }
+            server.shutdown().await;
+        }
+    };
+    tokio::select! {
+        result = clients => result,
+        _ = server.pump_debugger_output() => Ok(()),
+        _ = server.reap_idle_session() => Ok(()),
+    }
+}
+
+/// Reads `Content-Length` framed DAP messages into `messages` until the client leaves.
+async fn read_messages(read: OwnedReadHalf, messages: mpsc::UnboundedSender<Value>) -> Result<()> {
+    let mut reader = BufReader::new(read);
+    loop {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    length = value.trim().parse().ok();
+                }
+            }
+        }
+
+        let length: usize =
+            length.ok_or_else(|| anyhow::anyhow!("DAP message without Content-Length"))?;
+        if length > MAX_MESSAGE {
+            return Err(anyhow::anyhow!(
+                "DAP message of {} bytes is too large",
+                length
+            ));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await?;
+        if messages.send(serde_json::from_slice(&body)?).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Turns a tool result with `success: false` into an error carrying its message.
+fn check(result: Value) -> Result<Value> {
+    if result.get("success").and_then(|v| v.as_bool()) == Some(false) {
+        let message = result
+            .get("error")
+            .or_else(|| result.get("output"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("Debugger command failed");
+        return Err(anyhow::anyhow!("{}", message));
+    }
+    Ok(result)
+}
+
+/// One connected DAP client.
+struct DapConnection<'a> {
+    server: &'a DebugServer,
+    writer: OwnedWriteHalf,
+    /// Sequence number of the next message we send
+    seq: u64,
+    /// Breakpoint IDs set by `setBreakpoints`, per source path
+    breakpoints: HashMap<String, Vec<u32>>,
+    /// `(thread, frame index)` behind each frame ID handed out since the last stop
+    frames: Vec<(Option<u32>, u32)>,
+    /// Variables behind each `variablesReference` handed out since the last stop
+    variables: Vec<Vec<Value>>,
+    /// Threads from the last stop, reported while the program runs
+    threads: Vec<Value>,
+    /// Whether the program was left running and must be polled for a stop
+    running: bool,
+}
+
+impl<'a> DapConnection<'a> {
+    fn new(server: &'a DebugServer, writer: OwnedWriteHalf) -> Self {
+        Self {
+            server,
+            writer,
+            seq: 1,
+            breakpoints: HashMap::new(),
+            frames: Vec::new(),
+            variables: Vec::new(),
+            threads: Vec::new(),
+            running: false,
+        }
+    }
+
+    async fn run(mut self, read: OwnedReadHalf) -> Result<()> {
+        let (messages_tx, mut messages) = mpsc::unbounded_channel();
+        let reader = tokio::spawn(read_messages(read, messages_tx));
+        let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                message = messages.recv() => {
+                    let Some(message) = message else { break };
+                    if !self.handle(message).await? {
+                        break;
+                    }
+                }
+                _ = poll.tick(), if self.running => {
+                    let result = self.server.wait_for_stop(Duration::ZERO).await?;
+                    let events = self.stop_events(&result, "breakpoint").await;
+                    for (event, body) in events {
+                        self.send_event(&event, body).await?;
+                    }
+                }
+            }
+        }
+
+        reader.abort();
+        Ok(())
+    }
+
+    async fn send(&mut self, mut message: Value) -> Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let body = serde_json::to_string(&message)?;
+        self.writer
+            .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+            .await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn send_event(&mut self, event: &str, body: Value) -> Result<()> {
+        self.send(json!({"type": "event", "event": event, "body": body}))
+            .await
+    }
+
+    /// Answers one request, then sends the events it caused. Returns false once the
+    /// client has disconnected.
+    async fn handle(&mut self, request: Value) -> Result<bool> {
+        let command = request
+            .get("command")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let arguments = request.get("arguments").cloned().unwrap_or(json!({}));
+
+        let span = tracing::info_span!("dap_request", seq = %request["seq"], command);
+        let dispatch = self.dispatch(&command, &arguments).instrument(span);
+        let (success, message, body, events) = match dispatch.await {
+            Ok((body, events)) => (true, None, body, events),
+            Err(e) => (false, Some(e.to_string()), Value::Null, Vec::new()),
+        };
+        self.server.mark_session_used().await;
+
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.get("seq"),
+            "command": command,
+            "success": success,
+            "message": message,
+            "body": body
+        }))
+        .await?;
+        for (event, body) in events {
+            self.send_event(&event, body).await?;
+        }
+
+        Ok(command != "disconnect")
+    }
+
+    /// Runs a request, returning its response body and the events to send after it.
+    async fn dispatch(
+        &mut self,
+        command: &str,
+        arguments: &Value,
+    ) -> Result<(Value, Vec<(String, Value)>)> {
+        let server = self.server;
+        match command {
+            "initialize" => Ok((
+                json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsConditionalBreakpoints": true,
+                    "supportsHitConditionalBreakpoints": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsTerminateRequest": true
+                }),
+                Vec::new(),
+            )),
+            "launch" => {
+                let program = arguments
+                    .get("program")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("program required"))?;
+                let debugger = server.debugger_argument(arguments)?;
+                let launch = LaunchConfig::from_arguments(arguments)?;
+                let build = BuildConfig::from_arguments(arguments)?;
+                let break_on_panic = arguments
+                    .get("breakOnPanic")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                check(
+                    server
+                        .debug_run(program, debugger, launch, build, break_on_panic)
+                        .await?,
+                )?;
+                Ok((json!({}), vec![("initialized".to_string(), json!({}))]))
+            }
+            "attach" => {
+                let pid = arguments
+                    .get("pid")
+                    .and_then(|v| v.as_u64())
+                    .map(u32::try_from)
+                    .transpose()?;
+                let process_name = arguments.get("processName").and_then(|v| v.as_str());
+                let debugger = server.debugger_argument(arguments)?;
+                check(server.debug_attach(pid, process_name, debugger).await?)?;
+                Ok((json!({}), vec![("initialized".to_string(), json!({}))]))
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments).await,
+            "setExceptionBreakpoints" => Ok((json!({"breakpoints": []}), Vec::new())),
+            "configurationDone" | "continue" => {
+                let result = check(server.debug_continue(None, false).await?)?;
+                let events = self.stop_events(&result, "breakpoint").await;
+                Ok((json!({"allThreadsContinued": true}), events))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                self.select_thread(arguments.get("threadId")).await?;
+                let result = match command {
+                    "next" => server.debug_step().await?,
+                    "stepIn" => server.debug_step_into().await?,
+                    _ => server.debug_step_out().await?,
+                };
+                let result = check(result)?;
+                let events = self.stop_events(&result, "step").await;
+                Ok((json!({}), events))
+            }
+            "pause" => {
+                let result = check(server.debug_pause().await?)?;
+                let events = self.stop_events(&result, "pause").await;
+                Ok((json!({}), events))
+            }
+            "threads" => {
+                if let Ok(result) = check(server.debug_threads().await?) {
+                    self.threads = result
+                        .get("threads")
+                        .and_then(|v| v.as_array())
+                        .map(|threads| {
+                            threads
+                                .iter()
+                                .map(|thread| {
+                                    let id = thread.get("id").cloned().unwrap_or(json!(1));
+                                    let name = match thread.get("name").and_then(|v| v.as_str()) {
+                                        Some(name) => name.to_string(),
+                                        None => format!("Thread {}", id),
+                                    };
+                                    json!({"id": id, "name": name})
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                }
+                if self.threads.is_empty() {
+                    let thread = self.select_thread(None).await?.unwrap_or(1);
+                    self.threads =
+                        vec![json!({"id": thread, "name": format!("Thread {}", thread)})];
+                }
+                Ok((json!({"threads": self.threads}), Vec::new()))
+            }
+            "stackTrace" => self.stack_trace(arguments).await,
+            "scopes" => {
+                self.select_frame(arguments.get("frameId")).await?;
+                let result = check(server.debug_locals(1).await?)?;
+                let variables = result
+                    .get("variables")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                let reference = self.store_variables(variables);
+                Ok((
+                    json!({
+                        "scopes": [{
+                            "name": "Locals",
+                            "presentationHint": "locals",
+                            "variablesReference": reference,
+                            "expensive": false
+                        }]
+                    }),
+                    Vec::new(),
+                ))
+            }
+            "variables" => {
+                let reference = arguments
+                    .get("variablesReference")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0) as usize;
+                let variables = reference
+                    .checked_sub(1)
+                    .and_then(|index| self.variables.get(index))
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Unknown variablesReference {}", reference))?;
+                let variables: Vec<Value> = variables
+                    .into_iter()
+                    .map(|variable| self.dap_variable(variable))
+                    .collect();
+                Ok((json!({"variables": variables}), Vec::new()))
+            }
+            "evaluate" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("expression required"))?;
+                if arguments.get("frameId").is_some() {
+                    self.select_frame(arguments.get("frameId")).await?;
+                }
+                // Hovers and watches must not run code; what is typed into the console may
+                let options = EvalOptions {
+                    allow_side_effects: arguments.get("context").and_then(|v| v.as_str())
+                        == Some("repl"),
+                    ..EvalOptions::default()
+                };
+                let result = check(server.debug_eval(expression, &options).await?)?;
+                let output = result.get("output").and_then(|v| v.as_str()).unwrap_or("");
+                // LLDB answers `(i32) $0 = 5`; show just the value when it parses
+                let value = match result
+                    .get("display")
+                    .and_then(|v| v.as_str())
+                    .or_else(|| result.get("value").and_then(|v| v.as_str()))
+                {
+                    Some(value) => value.to_string(),
+                    None => Variable::parse_lldb_tree(output)
+                        .into_iter()
+                        .next()
+                        .and_then(|variable| variable.value)
+                        .unwrap_or_else(|| output.to_string()),
+                };
+                Ok((
+                    json!({"result": value, "variablesReference": 0}),
+                    Vec::new(),
+                ))
+            }
+            "terminate" | "disconnect" => {
+                server.debug_kill().await?;
+                self.running = false;
+                Ok((json!({}), vec![("terminated".to_string(), json!({}))]))
+            }
+            _ => Err(anyhow::anyhow!("Unsupported request: {}", command)),
+        }
+    }
+
+    /// Replaces the breakpoints of one source file, as `setBreakpoints` requires.
+    async fn set_breakpoints(
+        &mut self,
+        arguments: &Value,
+    ) -> Result<(Value, Vec<(String, Value)>)> {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("source.path required"))?
+            .to_string();
+
+        for id in self.breakpoints.remove(&path).unwrap_or_default() {
+            self.server
+                .debug_break_modify(BreakpointAction::Delete, Some(id), None)
+                .await?;
+        }
+
+        let requested = arguments
+            .get("breakpoints")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let mut ids = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in requested {
+            let line = breakpoint.get("line").and_then(|v| v.as_u64()).unwrap_or(0);
+            let condition = breakpoint.get("condition").and_then(|v| v.as_str());
+            // A hit condition of N stops on the Nth hit, i.e. after skipping N - 1
+            let ignore_count = breakpoint
+                .get("hitCondition")
+                .and_then(|v| v.as_str())
+                .and_then(|hits| hits.trim().parse::<u64>().ok())
+                .map(|hits| hits.saturating_sub(1));
+
+            let result = self
+                .server
+                .debug_break(&format!("{}:{}", path, line), condition, ignore_count)
+                .await?;
+            let id = result
+                .get("id")
+                .and_then(|v| v.as_u64())
+                .map(|id| id as u32);
+            ids.extend(id);
+            let verified = result
+                .get("resolved_locations")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+                > 0;
+            breakpoints.push(json!({
+                "id": id,
+                "verified": verified,
+                "line": line,
+                "message": result.get("error")
+            }));
+        }
+        self.breakpoints.insert(path, ids);
+
+        Ok((json!({"breakpoints": breakpoints}), Vec::new()))
+    }
+
+    async fn stack_trace(&mut self, arguments: &Value) -> Result<(Value, Vec<(String, Value)>)> {
+        let thread = self.select_thread(arguments.get("threadId")).await?;
+        let start = arguments
+            .get("startFrame")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+        let levels = arguments
+            .get("levels")
+            .and_then(|v| v.as_u64())
+            .filter(|levels| *levels > 0);
+        let max_frames = levels.map(|levels| (start as u64 + levels) as u32);
+
+        let result = check(self.server.debug_backtrace(max_frames, false).await?)?;
+        let frames = result
+            .get("frames")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut stack_frames = Vec::new();
+        for frame in frames.iter().skip(start) {
+            let index = frame.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+            self.frames.push((thread, index));
+            let name = frame
+                .get("function")
+                .and_then(|v| v.as_str())
+                .or_else(|| frame.get("pc").and_then(|v| v.as_str()))
+                .unwrap_or("??");
+            let source = frame.get("file").and_then(|v| v.as_str()).map(|file| {
+                let name = std::path::Path::new(file)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string());
+                json!({"name": name, "path": file})
+            });
+            stack_frames.push(json!({
+                "id": self.frames.len(),
+                "name": name,
+                "source": source,
+                "line": frame.get("line").and_then(|v| v.as_u64()).unwrap_or(0),
+                "column": 0,
+                "instructionPointerReference": frame.get("pc"),
+                "presentationHint": if frame.get("is_rust_frame") == Some(&json!(true)) { "normal" } else { "subtle" }
+            }));
+        }
+
+        Ok((
+            json!({"stackFrames": stack_frames, "totalFrames": frames.len()}),
+            Vec::new(),
+        ))
+    }
+
+    /// Makes `thread_id` the debugger's selected thread, returning the thread in effect.
+    async fn select_thread(&self, thread_id: Option<&Value>) -> Result<Option<u32>> {
+        let selected = {
+            let session_guard = self.server.session.lock().await;
+            session_guard.as_ref().and_then(|s| s.selected_thread)
+        };
+        let Some(thread_id) = thread_id.and_then(|v| v.as_u64()).map(|id| id as u32) else {
+            return Ok(selected);
+        };
+        if selected != Some(thread_id) {
+            check(self.server.debug_select_thread(thread_id).await?)?;
+        }
+        Ok(Some(thread_id))
+    }
+
+    /// Selects the thread and frame behind a frame ID from `stackTrace`.
+    async fn select_frame(&self, frame_id: Option<&Value>) -> Result<()> {
+        let Some(&(thread, index)) = frame_id
+            .and_then(|v| v.as_u64())
+            .and_then(|id| (id as usize).checked_sub(1))
+            .and_then(|index| self.frames.get(index))
+        else {
+            return Ok(());
+        };
+        if let Some(thread) = thread {
+            self.select_thread(Some(&json!(thread))).await?;
+        }
+        check(self.server.debug_select_frame(index).await?)?;
+        Ok(())
+    }
+
+    fn store_variables(&mut self, variables: Vec<Value>) -> usize {
+        self.variables.push(variables);
+        self.variables.len()
+    }
+
+    /// Converts a `debug_locals` variable into a DAP variable, storing its children.
+    fn dap_variable(&mut self, variable: Value) -> Value {
+        let children = variable
+            .get("children")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let reference = if children.is_empty() {
+            0
+        } else {
+            self.store_variables(children)
+        };
+        let value = variable
+            .get("display")
+            .or_else(|| variable.get("value"))
+            .and_then(|v| v.as_str())
+            .or_else(|| variable.get("type").and_then(|v| v.as_str()))
+            .unwrap_or("");
+        json!({
+            "name": variable.get("name"),
+            "value": value,
+            "type": variable.get("type"),
+            "variablesReference": reference
+        })
+    }
+
+    /// Builds the `output`, `stopped`, `exited`, and `terminated` events for the result
+    /// of an execution command. `reason` describes a stop without a signal.
+    async fn stop_events(&mut self, result: &Value, reason: &str) -> Vec<(String, Value)> {
+        let mut events = Vec::new();
+        for (field, category) in [("program_stdout", "stdout"), ("program_stderr", "stderr")] {
+            if let Some(output) = result.get(field).and_then(|v| v.as_str()) {
+                if !output.is_empty() {
+                    events.push((
+                        "output".to_string(),
+                        json!({"category": category, "output": output}),
+                    ));
+                }
+            }
+        }
+
+        match result.get("state").and_then(|v| v.as_str()) {
+            Some("running") => self.running = true,
+            Some("stopped") => {
+                self.running = false;
+                self.frames.clear();
+                self.variables.clear();
+                let thread = {
+                    let session_guard = self.server.session.lock().await;
+                    session_guard.as_ref().and_then(|s| s.selected_thread)
+                };
+                let signal = result
+                    .get("signal")
+                    .and_then(|signal| signal.get("name"))
+                    .and_then(|v| v.as_str());
+                events.push((
+                    "stopped".to_string(),
+                    json!({
+                        "reason": if signal.is_some() { "exception" } else { reason },
+                        "description": signal,
+                        "threadId": thread.unwrap_or(1),
+                        "allThreadsStopped": true
+                    }),
+                ));
+            }
+            Some("completed") | Some("crashed") => {
+                self.running = false;
+                let exit_code = result
+                    .get("exit_code")
+                    .and_then(|v| v.as_i64())
+                    .unwrap_or(0);
+                events.push(("exited".to_string(), json!({"exitCode": exit_code})));
+                events.push(("terminated".to_string(), json!({})));
+            }
+            _ => {}
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn oversized_messages_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        client
+            .write_all(b"Content-Length: 99999999999\r\n\r\n{}")
+            .await
+            .unwrap();
+
+        let (read, _writer) = stream.into_split();
+        let (messages, mut received) = mpsc::unbounded_channel();
+        let e = read_messages(read, messages).await.unwrap_err();
+        assert!(e.to_string().contains("too large"), "{}", e);
+        assert!(received.recv().await.is_none());
+    }
+}
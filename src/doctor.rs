@@ -0,0 +1,401 @@
+//! `ferroscope doctor` and the `debug_doctor` tool: check that the tools a debugging
+//! session needs are installed, that the configuration files parse, and that the
+//! default debugger can stop a freshly built probe program, so a broken setup shows up
+//! before an assistant's first `debug_run` fails.
+
+use crate::backend::DebuggerKind;
+use crate::config::ProjectConfig;
+use crate::{DebugServer, ServerOptions};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+
+/// The probe program; the doctor stops it on the `println!` line and reads `answer`.
+const PROBE_SOURCE: &str = "fn main() {\n    let answer = 42;\n    println!(\"{}\", answer);\n}\n";
+
+/// The outcome of one check.
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// What was checked, e.g. `gdb`
+    pub name: String,
+    /// Whether it is usable
+    pub ok: bool,
+    /// Whether the server cannot work without it
+    pub required: bool,
+    /// The version found, or what is wrong
+    pub detail: String,
+    /// What to run or change when the check does not pass
+    pub fix: Option<String>,
+}
+
+impl Check {
+    /// Whether this check makes the setup unusable.
+    pub fn failed(&self) -> bool {
+        self.required && !self.ok
+    }
+
+    /// The check as reported in `debug_doctor`'s `checks`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "ok": self.ok,
+            "required": self.required,
+            "detail": self.detail,
+            "fix": self.fix
+        })
+    }
+
+    fn new(name: &str, ok: bool, required: bool, detail: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            ok,
+            required,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        if !self.ok {
+            self.fix = Some(fix.into());
+        }
+        self
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match (self.ok, self.required) {
+            (true, _) => "ok",
+            (false, true) => "FAIL",
+            (false, false) => "--",
+        };
+        write!(f, "{:<5} {:<16} {}", status, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n{:<22} fix: {}", "", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check for a server started with `options`, from the current directory.
+pub async fn checks(options: &ServerOptions) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let config = ProjectConfig::load(&cwd, options.config.as_deref());
+    let debugger_path = config
+        .as_ref()
+        .ok()
+        .and_then(|config| config.debugger_path.clone());
+    checks.push(match &config {
+        Ok(config) if config.files.is_empty() => {
+            Check::new("config", true, true, "no configuration files")
+        }
+        Ok(config) => Check::new(
+            "config",
+            true,
+            true,
+            config
+                .files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Err(e) => Check::new("config", false, true, e.to_string())
+            .fix("correct or remove the setting named above"),
+    });
+
+    let default = options.debugger.unwrap_or(DebuggerKind::Lldb);
+    let mut available = Vec::new();
+    for kind in [DebuggerKind::Lldb, DebuggerKind::LldbMi, DebuggerKind::Gdb] {
+        let version = version(kind.name()).await;
+        if version.is_some() {
+            available.push(kind);
+        }
+        checks.push(Check::new(
+            kind.name(),
+            version.is_some(),
+            false,
+            version.unwrap_or_else(|| "not found on PATH".to_string()),
+        ));
+    }
+    if let Some(path) = debugger_path {
+        let version = version(&path).await;
+        checks.push(
+            Check::new(
+                "debugger_path",
+                version.is_some(),
+                true,
+                version.unwrap_or_else(|| format!("{} does not run", path)),
+            )
+            .fix("point debugger_path in the configuration at a working debugger"),
+        );
+    }
+    let default_ok = available.contains(&default);
+    let check = Check::new(
+        "default debugger",
+        default_ok,
+        true,
+        if default_ok {
+            default.name().to_string()
+        } else {
+            format!("{} is missing", default.name())
+        },
+    );
+    checks.push(match available.first() {
+        Some(other) => check.fix(format!(
+            "install {} or start ferroscope with --debugger {}",
+            default.name(),
+            other.name()
+        )),
+        None => check.fix(install_hint()),
+    });
+
+    for (tool, purpose) in [
+        ("cargo", "needed to debug project directories and tests"),
+        (
+            "rustc",
+            "needed for Rust pretty-printers and heap profiling",
+        ),
+    ] {
+        let version = version(tool).await;
+        checks.push(
+            Check::new(
+                tool,
+                version.is_some(),
+                false,
+                version.unwrap_or_else(|| format!("not found on PATH; {}", purpose)),
+            )
+            .fix("install Rust with rustup from https://rustup.rs"),
+        );
+    }
+    checks.push(formatters(default).await);
+    if let Some(check) = attach_permission() {
+        checks.push(check);
+    }
+    if options.sandbox {
+        checks.push(sandbox().await);
+    }
+    if default_ok {
+        // Boxed: the probe calls tools, and debug_doctor calls this
+        checks.push(Box::pin(probe(options, default)).await);
+    }
+
+    checks
+}
+
+/// How to install a debugger on this platform.
+fn install_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "install LLDB with `xcode-select --install`"
+    } else {
+        "install LLDB or GDB, e.g. `sudo apt install lldb` or `sudo apt install gdb`"
+    }
+}
+
+/// Whether the toolchain ships the formatters `debug_run` loads into `debugger`.
+async fn formatters(debugger: DebuggerKind) -> Check {
+    let scripts: &[&str] = if debugger.uses_mi() {
+        &["gdb_load_rust_pretty_printers.py"]
+    } else {
+        &["lldb_lookup.py", "lldb_commands"]
+    };
+    let Some(sysroot) = DebugServer::rust_sysroot().await else {
+        return Check::new("formatters", false, false, "rustc --print sysroot failed")
+            .fix("install Rust with rustup from https://rustup.rs");
+    };
+    let etc = Path::new(&sysroot).join("lib/rustlib/etc");
+    let missing: Vec<&str> = scripts
+        .iter()
+        .copied()
+        .filter(|script| !etc.join(script).exists())
+        .collect();
+    let detail = if missing.is_empty() {
+        etc.display().to_string()
+    } else {
+        format!(
+            "{} missing from {}; Option, Vec, and String print as raw layouts",
+            missing.join(", "),
+            etc.display()
+        )
+    };
+    Check::new("formatters", missing.is_empty(), false, detail)
+        .fix("install the toolchain with rustup, whose toolchains include them")
+}
+
+/// Whether the debugger may attach to running processes; `None` where there is
+/// nothing to check. Launched programs are the debugger's children, which every
+/// setting allows.
+fn attach_permission() -> Option<Check> {
+    if cfg!(target_os = "macos") {
+        let output = std::process::Command::new("DevToolsSecurity")
+            .stdin(Stdio::null())
+            .arg("-status")
+            .output()
+            .ok()?;
+        let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let enabled = status.contains("enabled");
+        return Some(
+            Check::new("attach", enabled, false, status)
+                .fix("run `sudo DevToolsSecurity -enable` so debug_attach needs no password"),
+        );
+    }
+    let scope = std::fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope").ok()?;
+    let scope = scope.trim();
+    Some(
+        Check::new(
+            "ptrace_scope",
+            scope == "0",
+            false,
+            if scope == "0" {
+                "0".to_string()
+            } else {
+                format!("{}; debug_attach can only attach as root", scope)
+            },
+        )
+        .fix("run `echo 0 | sudo tee /proc/sys/kernel/yama/ptrace_scope` or grant CAP_SYS_PTRACE"),
+    )
+}
+
+/// Whether `--sandbox` can confine the debugger. On Linux that takes unprivileged user
+/// namespaces, which `unshare` needs to cut the network off.
+async fn sandbox() -> Check {
+    if !cfg!(target_os = "linux") {
+        return Check::new(
+            "sandbox",
+            true,
+            true,
+            "limits and a cleaned environment; the network stays reachable on this platform",
+        );
+    }
+    let output = tokio::process::Command::new("unshare")
+        .stdin(Stdio::null())
+        .args(["--user", "--map-root-user", "--net", "true"])
+        .output()
+        .await;
+    let (ok, detail) = match output {
+        Ok(output) if output.status.success() => (true, "user and network namespaces".to_string()),
+        Ok(output) => (
+            false,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ),
+        Err(e) => (false, format!("unshare: {}", e)),
+    };
+    Check::new("sandbox", ok, true, detail).fix(
+        "install util-linux and allow unprivileged user namespaces, e.g. `sudo sysctl kernel.unprivileged_userns_clone=1`",
+    )
+}
+
+/// Builds the probe program, stops it under `debugger` on its second line, and reads a
+/// local, as a first debugging session would.
+async fn probe(options: &ServerOptions, debugger: DebuggerKind) -> Check {
+    let dir = std::env::temp_dir().join(format!("ferroscope-doctor-{}", std::process::id()));
+    let result = run_probe(options, debugger, &dir).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    match result {
+        Ok(detail) => Check::new("probe", true, true, detail),
+        Err((step, error, fix)) => {
+            Check::new("probe", false, true, format!("{} failed: {}", step, error)).fix(fix)
+        }
+    }
+}
+
+/// The probe's steps; a failure names the step, the error, and a fix.
+async fn run_probe(
+    options: &ServerOptions,
+    debugger: DebuggerKind,
+    dir: &Path,
+) -> Result<String, (&'static str, String, &'static str)> {
+    let build_fix = "check that rustc can build a hello-world program";
+    let source = dir.join("probe.rs");
+    let binary = dir.join("probe");
+    std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&source, PROBE_SOURCE))
+        .map_err(|e| ("writing the probe", e.to_string(), build_fix))?;
+    let output = tokio::process::Command::new("rustc")
+        .stdin(Stdio::null())
+        .arg("-g")
+        .arg("-o")
+        .arg(&binary)
+        .arg(&source)
+        .output()
+        .await
+        .map_err(|e| ("building the probe", e.to_string(), build_fix))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(("building the probe", stderr.trim().to_string(), build_fix));
+    }
+
+    // A server of its own, so a session the caller has open is left alone
+    let server = DebugServer::new(options.clone());
+    let steps = [
+        (
+            "debug_run",
+            json!({"binary_path": binary, "debugger": debugger.name(), "break_on_panic": false}),
+            "run the debugger by hand on a small program to see why it cannot load it",
+        ),
+        (
+            "debug_break",
+            json!({"location": "probe.rs:3"}),
+            "the debugger cannot read the probe's debug info; try a newer debugger",
+        ),
+        (
+            "debug_continue",
+            json!({}),
+            "the debugger cannot launch programs; check ptrace permissions and that no seccomp profile blocks it",
+        ),
+        (
+            "debug_eval",
+            json!({"expression": "answer"}),
+            "the debugger cannot read locals; try a newer debugger",
+        ),
+    ];
+    let mut detail = String::new();
+    for (tool, arguments, fix) in steps {
+        let result = server
+            .handle_call_tool(tool, arguments)
+            .await
+            .map_err(|e| (tool, e.to_string(), fix))?;
+        let error = if result["success"] != true {
+            Some(result["error"].as_str().unwrap_or("failed").to_string())
+        } else if tool == "debug_continue" && result["state"] != "stopped" {
+            Some(format!("the probe did not stop: {}", result["state"]))
+        } else if tool == "debug_eval" && !result.to_string().contains("42") {
+            Some(format!("read {} instead of 42", result["value"]))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            let _ = server.handle_call_tool("debug_kill", json!({})).await;
+            return Err((tool, error, fix));
+        }
+        if tool == "debug_continue" {
+            detail = format!(
+                "{} stopped the probe at {} and read answer = 42",
+                debugger.name(),
+                result["location"].as_str().unwrap_or("probe.rs:3")
+            );
+        }
+    }
+    let _ = server.handle_call_tool("debug_kill", json!({})).await;
+    Ok(detail)
+}
+
+/// The first line `program --version` prints, if it runs.
+async fn version(program: &str) -> Option<String> {
+    // Not the server's stdin, which carries MCP messages
+    let output = tokio::process::Command::new(program)
+        .stdin(Stdio::null())
+        .arg("--version")
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or("").trim().to_string())
+}
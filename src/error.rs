@@ -0,0 +1,147 @@
+//! Machine-readable reasons for failed tool calls.
+//!
+//! A tool fails in one of two ways: it returns a result with `success: false`, which
+//! carries the reason as `error_kind`, or it returns a [`ToolError`], which reaches the
+//! client as a JSON-RPC error with the reason in `error.data.kind`. Both use the names
+//! of [`ErrorKind`], so agents can branch on them instead of parsing messages.
+
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Why a tool call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// No debugging session is active; start one with `debug_run` or `debug_attach`
+    NoSession,
+    /// The program is in the wrong state for the tool, e.g. running when it must be stopped
+    WrongState,
+    /// The debugger process exited underneath the session
+    DebuggerCrashed,
+    /// The project did not build, or the build produced no binary
+    BuildFailed,
+    /// The debugger or the program did not respond in time
+    Timeout,
+    /// A breakpoint location or a process to attach to could not be found
+    LocationNotFound,
+    /// A tool argument is missing or malformed
+    InvalidArgument,
+    /// The tool is not available for this session, platform, or server configuration
+    Unsupported,
+    /// The debugger rejected or failed the command
+    CommandFailed,
+    /// The program went over a configured CPU, memory, open file, or output limit and
+    /// was killed
+    ResourceLimitExceeded,
+    /// Anything else, such as an I/O error while talking to the debugger
+    Internal,
+}
+
+impl ErrorKind {
+    /// Every kind, in the order they are documented.
+    pub const ALL: [ErrorKind; 11] = [
+        ErrorKind::NoSession,
+        ErrorKind::WrongState,
+        ErrorKind::DebuggerCrashed,
+        ErrorKind::BuildFailed,
+        ErrorKind::Timeout,
+        ErrorKind::LocationNotFound,
+        ErrorKind::InvalidArgument,
+        ErrorKind::Unsupported,
+        ErrorKind::CommandFailed,
+        ErrorKind::ResourceLimitExceeded,
+        ErrorKind::Internal,
+    ];
+
+    /// The name sent to clients, e.g. `wrong_state`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::NoSession => "no_session",
+            ErrorKind::WrongState => "wrong_state",
+            ErrorKind::DebuggerCrashed => "debugger_crashed",
+            ErrorKind::BuildFailed => "build_failed",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::LocationNotFound => "location_not_found",
+            ErrorKind::InvalidArgument => "invalid_argument",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::CommandFailed => "command_failed",
+            ErrorKind::ResourceLimitExceeded => "resource_limit_exceeded",
+            ErrorKind::Internal => "internal",
+        }
+    }
+
+    /// JSON-RPC error code for a [`ToolError`] of this kind: invalid params for bad
+    /// arguments, internal error for unexpected failures, and a server error otherwise.
+    pub fn code(self) -> i64 {
+        match self {
+            ErrorKind::InvalidArgument => -32602,
+            ErrorKind::Internal => -32603,
+            _ => -32000,
+        }
+    }
+
+    /// Creates an error of this kind with `message`.
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        ToolError {
+            kind: self,
+            message: message.into(),
+            details: None,
+        }
+        .into()
+    }
+
+    /// Creates an error of this kind with `message` and `details`, an object whose
+    /// fields are sent along in `error.data`.
+    pub fn error_with_details(self, message: impl Into<String>, details: Value) -> anyhow::Error {
+        ToolError {
+            kind: self,
+            message: message.into(),
+            details: Some(details),
+        }
+        .into()
+    }
+
+    /// The kind of `error`: that of the [`ToolError`] it holds, or `Internal`.
+    pub fn of(error: &anyhow::Error) -> ErrorKind {
+        error
+            .downcast_ref::<ToolError>()
+            .map_or(ErrorKind::Internal, |error| error.kind)
+    }
+}
+
+/// A failed tool call: what went wrong for the client to branch on, and a message
+/// for humans.
+#[derive(Debug, Clone)]
+pub struct ToolError {
+    /// Why the call failed
+    pub kind: ErrorKind,
+    /// What happened, e.g. `location required`
+    pub message: String,
+    /// Structured information beyond the message, e.g. the compiler's diagnostics when
+    /// a build failed
+    pub details: Option<Value>,
+}
+
+impl ToolError {
+    /// The JSON-RPC `error.data` for `error`: its `kind`, and the fields of its details.
+    pub fn data(error: &anyhow::Error) -> Value {
+        let mut data = json!({ "kind": ErrorKind::of(error).name() });
+        let details = error
+            .downcast_ref::<ToolError>()
+            .and_then(|error| error.details.as_ref())
+            .and_then(|details| details.as_object());
+        if let Some(details) = details {
+            for (key, value) in details {
+                data[key] = value.clone();
+            }
+        }
+        data
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ToolError {}
@@ -0,0 +1,289 @@
+//! Streamable HTTP transport for MCP, started with `--http <address>`.
+//!
+//! Clients POST JSON-RPC messages to `/mcp` and get the responses back as JSON; a GET on
+//! the same path opens a Server-Sent Events stream carrying the server's notifications,
+//! such as a running program stopping, and a DELETE ends the debugging session. Each
+//! connection is served by its own task, so several clients can share the server and its
+//! debugging session.
+
+use crate::DebugServer;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+/// The single MCP endpoint.
+const ENDPOINT: &str = "/mcp";
+
+/// How often an idle event stream gets a comment, to notice clients that went away.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// Accepts HTTP clients on `address` until the process is stopped.
+pub async fn serve(server: Arc<DebugServer>, address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    tracing::info!(
+        "MCP server listening on http://{}{}",
+        listener.local_addr()?,
+        ENDPOINT
+    );
+
+    let clients = async {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                let (read, writer) = stream.into_split();
+                if let Err(e) = serve_connection(&server, read, writer).await {
+                    tracing::warn!("HTTP connection error: {}", e);
+                }
+            });
+        }
+    };
+    tokio::select! {
+        result = clients => result,
+        _ = server.pump_debugger_output() => Ok(()),
+        _ = server.reap_idle_session() => Ok(()),
+    }
+}
+
+/// A parsed HTTP request.
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Reads the next request, or `None` once the client closes the connection.
+    async fn read(reader: &mut BufReader<OwnedReadHalf>) -> Result<Option<Self>> {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).await? == 0 {
+            return Ok(None);
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("").to_string();
+        let path = parts.next().unwrap_or("").to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        let mut request = Self {
+            method,
+            path,
+            headers,
+            body: Vec::new(),
+        };
+        let length: usize = request
+            .header("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        if length > MAX_BODY {
+            return Err(anyhow::anyhow!(
+                "Request body of {} bytes is too large",
+                length
+            ));
+        }
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body).await?;
+        Ok(Some(request))
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Rejects browser pages from other sites, which could otherwise reach a local
+    /// server through DNS rebinding. Requests without an `Origin` are not from a browser.
+    fn origin_allowed(&self) -> bool {
+        let Some(origin) = self.header("origin") else {
+            return true;
+        };
+        let host = origin
+            .split_once("://")
+            .map_or(origin, |(_, rest)| rest)
+            .trim_end_matches('/');
+        let host = match host.rsplit_once(':') {
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+            _ => host,
+        };
+        matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+    }
+}
+
+async fn write_response(
+    writer: &mut OwnedWriteHalf,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    writer.write_all(response.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn serve_connection(
+    server: &Arc<DebugServer>,
+    read: OwnedReadHalf,
+    mut writer: OwnedWriteHalf,
+) -> Result<()> {
+    let mut reader = BufReader::new(read);
+    while let Some(request) = HttpRequest::read(&mut reader).await? {
+        if request.path.split('?').next() != Some(ENDPOINT) {
+            write_response(&mut writer, "404 Not Found", "text/plain", "Not Found").await?;
+            continue;
+        }
+        if !request.origin_allowed() {
+            write_response(&mut writer, "403 Forbidden", "text/plain", "Forbidden").await?;
+            continue;
+        }
+
+        match request.method.as_str() {
+            "POST" => handle_post(server, &request, &mut writer).await?,
+            "GET" => {
+                // The event stream holds the connection until the client leaves
+                return stream_notifications(server.notifications.subscribe(), writer).await;
+            }
+            // A client ending its MCP session
+            "DELETE" => {
+                server.shutdown().await;
+                write_response(&mut writer, "200 OK", "text/plain", "").await?
+            }
+            _ => {
+                write_response(
+                    &mut writer,
+                    "405 Method Not Allowed",
+                    "text/plain",
+                    "Method Not Allowed",
+                )
+                .await?
+            }
+        }
+
+        if request
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Answers the JSON-RPC requests in a POST body, which holds one message or a batch.
+async fn handle_post(
+    server: &Arc<DebugServer>,
+    request: &HttpRequest,
+    writer: &mut OwnedWriteHalf,
+) -> Result<()> {
+    let message: Value = match serde_json::from_slice(&request.body) {
+        Ok(message) => message,
+        Err(e) => {
+            let error = json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {
+                    "code": -32700,
+                    "message": format!("Parse error: {}", e)
+                }
+            });
+            return write_response(
+                writer,
+                "400 Bad Request",
+                "application/json",
+                &error.to_string(),
+            )
+            .await;
+        }
+    };
+
+    let batch = message.is_array();
+    let messages = match message {
+        Value::Array(messages) => messages,
+        message => vec![message],
+    };
+
+    // Notifications and responses from the client need no answer, and neither
+    // do requests cancelled while they were handled
+    let mut responses = Vec::new();
+    for message in messages {
+        if message.get("method").is_none() {
+            continue;
+        }
+        if message.get("id").is_none() {
+            server.handle_notification(message);
+        } else if let Some(error) = server.reject_uninitialized(&message) {
+            responses.push(error);
+        } else if let Some(response) = server.handle_cancellable(message).await {
+            responses.push(response);
+        }
+    }
+
+    if responses.is_empty() {
+        return write_response(writer, "202 Accepted", "text/plain", "").await;
+    }
+    let body = if batch {
+        Value::Array(responses)
+    } else {
+        responses.remove(0)
+    };
+    write_response(writer, "200 OK", "application/json", &body.to_string()).await
+}
+
+/// Streams notifications to the client as Server-Sent Events until it disconnects.
+async fn stream_notifications(
+    mut notifications: broadcast::Receiver<Value>,
+    mut writer: OwnedWriteHalf,
+) -> Result<()> {
+    writer
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n",
+        )
+        .await?;
+    writer.flush().await?;
+
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    loop {
+        let event = tokio::select! {
+            notification = notifications.recv() => match notification {
+                Ok(notification) => format!("event: message\ndata: {}\n\n", notification),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+        };
+        // A failed write means the client went away
+        if writer.write_all(event.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! # Ferroscope
+//!
+//! A Model Context Protocol (MCP) server that enables AI assistants to debug Rust programs
+//! using LLDB and GDB debuggers.
+//!
+//! ## Overview
+//!
+//! Ferroscope bridges the gap between AI assistants and native debugging tools, allowing
+//! AI agents to perform debugging tasks like setting breakpoints, stepping through code,
+//! and inspecting variables in running Rust programs.
+//!
+//! ## Features
+//!
+//! - **Native debugging**: Uses LLDB (macOS) and GDB (Linux) debuggers
+//! - **Machine interface**: Optional GDB/MI mode (`gdb`, `lldb-mi`) with structured responses
+//! - **MCP Protocol**: Implements Model Context Protocol for AI assistant integration
+//! - **DAP mode**: `--dap <address>` serves the Debug Adapter Protocol to editors
+//! - **HTTP transport**: `--http <address>` serves MCP over Streamable HTTP with SSE
+//! - **84 debugging tools**: Complete workflow from loading to stepping through code
+//! - **State management**: Tracks debugging session state and program lifecycle
+//! - **Cross-platform**: Works on macOS and Linux (Windows support planned)
+//!
+//! ## Available Tools
+//!
+//! - `debug_run` - Load and prepare Rust programs for debugging
+//! - `debug_test` - Build a project's tests and debug one of them
+//! - `debug_miri` - Run a project or its tests under Miri and report undefined behavior
+//! - `debug_launch_command` - Launch a command or launcher script stopped at entry
+//! - `debug_attach` - Attach to a running process
+//! - `debug_open_core` - Open a core dump for post-mortem inspection
+//! - `debug_connect_target` - Connect to an embedded target's GDB server
+//! - `debug_monitor` - Send a monitor command to an embedded target's GDB server
+//! - `debug_record` - Record a run with rr
+//! - `debug_replay` - Replay an rr recording
+//! - `debug_reverse_continue` - Run a replay backwards
+//! - `debug_reverse_step` - Step a replay back over calls
+//! - `debug_reverse_step_into` - Step a replay back into calls
+//! - `debug_break` - Set breakpoints at functions or lines
+//! - `debug_break_module` - Break on every function of a crate or module
+//! - `debug_break_on_error` - Break where errors are constructed or unwinding begins
+//! - `debug_break_delete` / `debug_break_disable` / `debug_break_enable` - Manage breakpoints
+//! - `debug_watch` - Stop when a variable or address is accessed
+//! - `debug_watch_field` - Stop when a struct field is written, with the old and new value
+//! - `debug_watch_expr` / `debug_unwatch_expr` - Report expressions' values at every stop
+//! - `debug_continue` - Launch/continue program execution
+//! - `debug_run_to` - Run to a function or line without leaving a breakpoint behind
+//! - `debug_return` / `debug_jump` - Return early or skip code to test a hypothesis
+//! - `debug_step` - Step through code line by line
+//! - `debug_step_into` - Step into function calls
+//! - `debug_step_out` - Step out of current function
+//! - `debug_finish` - Step out and report the function's return value
+//! - `debug_until` - Run to a later line in the current frame
+//! - `debug_step_instruction` - Step a single machine instruction
+//! - `debug_eval` - Evaluate expressions and inspect variables, following smart pointers
+//! - `debug_eval_many` - Evaluate several expressions in one call
+//! - `debug_set_var` - Change a variable in the current frame
+//! - `debug_eval_slice` - Show the elements behind a raw pointer and a length
+//! - `debug_backtrace` - Show call stack
+//! - `debug_locals` - List the frame's arguments and locals as structured JSON
+//! - `debug_source` - Show numbered source lines around the stop
+//! - `debug_set_source_map` - Map build-time source paths to local ones
+//! - `debug_inspect` - Expand a value into a bounded nested JSON tree
+//! - `debug_memory_read` / `debug_memory_write` - Inspect and patch raw memory
+//! - `debug_signals` - Configure how signals are handled
+//! - `debug_threads` / `debug_select_thread` - List and switch threads
+//! - `debug_inferiors` / `debug_select_inferior` - List and switch processes
+//! - `debug_checkpoint` / `debug_restore_checkpoint` - Snapshot and rewind the program
+//! - `debug_async_tasks` - List spawned tokio tasks and break where one is polled
+//! - `debug_analyze_deadlock` - Report which threads wait on which locks held by whom
+//! - `debug_triage_crash` - Collect everything about a crash in one report
+//! - `debug_panic_info` - Explain the panic the program is stopped in
+//! - `debug_heap_profile` - Report live heap allocations grouped by backtrace
+//! - `debug_profile` - Sample where the program spends CPU time
+//! - `debug_syscalls` - Trace the program's system calls and their results
+//! - `debug_select_frame` / `debug_frame_info` - Navigate stack frames
+//! - `debug_list_breakpoints` - List all breakpoints
+//! - `debug_record_values` - Record an expression's value at each line of a region
+//! - `debug_trace_calls` - Trace calls of the functions matching a regex
+//! - `debug_trace_log` - Show the values recorded at hits of `log_only` breakpoints
+//! - `debug_symbols` - Find functions, types, or globals by regex
+//! - `debug_modules` - List loaded libraries and whether each has debug info
+//! - `debug_program_output` - Show the program's captured stdout/stderr
+//! - `debug_stdin_write` - Write to the program's stdin
+//! - `debug_detach` - Detach from the process and end the session
+//! - `debug_wait_for_stop` / `debug_poll` - Wait for or check on a running program
+//! - `debug_pause` - Interrupt a running or hung program
+//! - `debug_restart` - Relaunch the program, optionally rebuilding it first
+//! - `debug_rebuild` - Rebuild and reload the binary, keeping breakpoints
+//! - `debug_recover` - Start a new debugger after one exited, keeping breakpoints
+//! - `debug_kill` - Terminate the process and end the session
+//! - `debug_state` - Get current debugging session state
+//! - `debug_save_breakpoints` / `debug_load_breakpoints` - Keep breakpoints across sessions
+//! - `debug_export_session` - Export the session history for a bug report
+//! - `debug_doctor` - Check the debugging setup, as `ferroscope doctor` does
+//! - `debug_raw_command` - Send a command line to the debugger, if the server allows it
+//! - `debug_sessions` - List the active session with its age and idle time
+//! - `debug_more_output` - Page through a result that was too large to return whole
+//!
+//! ## Usage
+//!
+//! Ferroscope is designed to be used by AI assistants through the MCP protocol.
+//! It runs as a server that accepts JSON-RPC commands over stdin/stdout.
+//!
+//! The same server is available as a library, for agents that want to manage
+//! debugging sessions in-process:
+//!
+//! ```no_run
+//! use ferroscope::{DebugServer, ServerOptions};
+//! use serde_json::json;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let server = DebugServer::new(ServerOptions::default());
+//! let loaded = server
+//!     .handle_call_tool("debug_run", json!({"binary_path": "./my_project"}))
+//!     .await?;
+//! println!("{}", loaded["state"]);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Modules
+//!
+//! - [`server`] - MCP request handling and the debugging tools
+//! - [`session`] - State of a debugging session
+//! - [`backend`] - The supported debuggers and their command syntax
+//! - [`config`] - Settings read from `ferroscope.toml`
+//! - [`doctor`] - Setup checks run by `ferroscope doctor`
+//! - [`parse`] - Parsers for GDB/MI records and LLDB output
+//! - [`error`] - Machine-readable reasons for failed tool calls
+//! - [`http`] / [`dap`] - Streamable HTTP and Debug Adapter Protocol front ends
+//!
+//! ```bash
+//! # Install ferroscope
+//! cargo install ferroscope
+//!
+//! # Run the MCP server (same as `ferroscope serve`)
+//! ferroscope
+//!
+//! # Check that a debugger and the toolchain are installed
+//! ferroscope doctor
+//!
+//! # Default to GDB and keep a log of every debugger command
+//! ferroscope --debugger gdb --log-file /tmp/ferroscope.log
+//!
+//! # Also allow the debug_memory_write tool
+//! ferroscope --allow-memory-write
+//!
+//! # Also allow debug_return and debug_jump to change control flow
+//! ferroscope --allow-control-flow
+//!
+//! # Give debugger commands 30 seconds instead of 10 by default
+//! ferroscope --timeout-ms 30000
+//!
+//! # Find sources built under /build/app in the local checkout
+//! ferroscope --source-map /build/app=/home/me/app
+//!
+//! # Debug an untrusted program with limits and no network
+//! ferroscope --sandbox
+//!
+//! # Kill the program if it grows past 2 GiB or writes over 100 MiB of output
+//! ferroscope --limit-memory-mb 2048 --limit-output-mb 100
+//!
+//! # Allow raw debugger commands, including breakpoint commands
+//! ferroscope --allow-raw-commands --raw-command-allow break
+//! ```
+//!
+//! ## Example Debugging Workflow
+//!
+//! 1. Load a Rust program: `debug_run /path/to/project`
+//! 2. Set breakpoints: `debug_break main`
+//! 3. Start execution: `debug_continue`
+//! 4. At breakpoints: `debug_eval variable_name`
+//! 5. Step through code: `debug_step`
+//!
+//! ## Security Considerations
+//!
+//! ⚠️ **Security Warning**: Ferroscope runs with full user privileges and can execute
+//! arbitrary code through the debugger. Only use with trusted code and in secure environments.
+//! `--sandbox` limits the damage an untrusted program can do: it runs with CPU and memory
+//! limits, a cleaned environment, and on Linux no network, and breakpoint commands that
+//! would run host programs are refused.
+//!
+//! ## Requirements
+//!
+//! - Rust toolchain
+//! - LLDB (macOS) or GDB (Linux)
+//! - Debug symbols in target binaries
+
+// The tool list passed to `json!` outgrows the default macro recursion limit
+#![recursion_limit = "256"]
+
+pub mod backend;
+pub mod config;
+pub mod dap;
+pub mod doctor;
+pub mod error;
+pub mod http;
+pub mod parse;
+pub mod server;
+pub mod session;
+
+pub use error::{ErrorKind, ToolError};
+pub use server::{DebugServer, ServerOptions};
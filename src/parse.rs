@@ -0,0 +1,4557 @@
+//! Parsers for debugger output.
+//!
+//! GDB/MI records ([`MiRecord`], [`MiOutput`]) and LLDB's text output are turned into
+//! frames, threads, variables, stop signals, and exit statuses, which the server
+//! reports as JSON.
+
+use crate::error::ErrorKind;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// A single line of GDB/MI output.
+///
+/// MI debuggers (`gdb --interpreter=mi2`, `lldb-mi`) emit one record per line. Result
+/// records terminate a command, async records report state changes, and stream records
+/// carry free-form text. Tuples and lists in the record payload are converted to JSON.
+///
+/// MI2 lists the locations of a breakpoint with several after it as bare tuples
+/// (`bkpt={number="1",...},{number="1.1",...}`); they are nested under the
+/// breakpoint's `locations`, where MI3 puts them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MiRecord {
+    /// Result record (`^done`, `^running`, `^error`, ...) terminating a command
+    Result {
+        token: Option<u64>,
+        class: String,
+        results: Value,
+    },
+    /// Exec async record (`*stopped`, `*running`) reporting an execution state change
+    Exec { class: String, results: Value },
+    /// Status or notify async record (`=breakpoint-created`, `=thread-group-exited`, ...)
+    Notify { class: String, results: Value },
+    /// Console stream record (`~"..."`) with human-readable debugger output
+    Console(String),
+    /// Target stream record (`@"..."`) with output from the program being debugged
+    Target(String),
+    /// Log stream record (`&"..."`) with debugger-internal messages
+    Log(String),
+    /// The `(gdb)` prompt that ends a block of output
+    Prompt,
+    /// A line that is not MI syntax, usually the debuggee writing to the shared terminal
+    Other(String),
+}
+
+impl MiRecord {
+    /// Parses one line of debugger output, falling back to [`MiRecord::Other`].
+    pub fn parse(line: &str) -> MiRecord {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim() == "(gdb)" {
+            return MiRecord::Prompt;
+        }
+
+        let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+        let token = line[..digits].parse().ok();
+        let rest = &line[digits..];
+
+        let parsed = match rest.chars().next() {
+            Some(kind @ ('^' | '*' | '+' | '=')) => {
+                MiParser { rest: &rest[1..] }.parse_output_record(kind, token)
+            }
+            Some(kind @ ('~' | '@' | '&')) if token.is_none() => {
+                MiParser { rest: &rest[1..] }.parse_stream_record(kind)
+            }
+            _ => None,
+        };
+
+        parsed.unwrap_or_else(|| MiRecord::Other(line.to_string()))
+    }
+}
+
+/// Adds an MI2 breakpoint `location` to the `locations` of the breakpoint tuple `owner`.
+fn add_location(owner: &mut Value, location: Value) -> Option<()> {
+    let locations = owner
+        .as_object_mut()?
+        .entry("locations")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    locations.as_array_mut()?.push(location);
+    Some(())
+}
+
+/// Recursive-descent parser over the payload of a single MI record.
+struct MiParser<'a> {
+    rest: &'a str,
+}
+
+impl MiParser<'_> {
+    fn parse_output_record(mut self, kind: char, token: Option<u64>) -> Option<MiRecord> {
+        let class_len = self.rest.find(',').unwrap_or(self.rest.len());
+        let class = self.rest[..class_len].to_string();
+        if class.is_empty() {
+            return None;
+        }
+        self.rest = &self.rest[class_len..];
+
+        let mut results = serde_json::Map::new();
+        let mut last = None;
+        while let Some(rest) = self.rest.strip_prefix(',') {
+            self.rest = rest;
+            if self.rest.starts_with('{') {
+                let location = self.parse_value()?;
+                add_location(results.get_mut(last.as_ref()?)?, location)?;
+                continue;
+            }
+            let (name, value) = self.parse_result()?;
+            results.insert(name.clone(), value);
+            last = Some(name);
+        }
+        if !self.rest.is_empty() {
+            return None;
+        }
+
+        let results = Value::Object(results);
+        Some(match kind {
+            '^' => MiRecord::Result {
+                token,
+                class,
+                results,
+            },
+            '*' => MiRecord::Exec { class, results },
+            _ => MiRecord::Notify { class, results },
+        })
+    }
+
+    fn parse_stream_record(mut self, kind: char) -> Option<MiRecord> {
+        let text = self.parse_cstring()?;
+        if !self.rest.is_empty() {
+            return None;
+        }
+        Some(match kind {
+            '~' => MiRecord::Console(text),
+            '@' => MiRecord::Target(text),
+            _ => MiRecord::Log(text),
+        })
+    }
+
+    fn parse_result(&mut self) -> Option<(String, Value)> {
+        let name_len = self.rest.find('=')?;
+        let name = self.rest[..name_len].to_string();
+        self.rest = &self.rest[name_len + 1..];
+        Some((name, self.parse_value()?))
+    }
+
+    fn parse_value(&mut self) -> Option<Value> {
+        match self.rest.chars().next()? {
+            '"' => self.parse_cstring().map(Value::String),
+            '{' => {
+                self.rest = &self.rest[1..];
+                let mut tuple = serde_json::Map::new();
+                if let Some(rest) = self.rest.strip_prefix('}') {
+                    self.rest = rest;
+                    return Some(Value::Object(tuple));
+                }
+                loop {
+                    let (name, value) = self.parse_result()?;
+                    tuple.insert(name, value);
+                    match self.rest.chars().next()? {
+                        ',' => self.rest = &self.rest[1..],
+                        '}' => {
+                            self.rest = &self.rest[1..];
+                            return Some(Value::Object(tuple));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            '[' => {
+                self.rest = &self.rest[1..];
+                let mut list: Vec<Value> = Vec::new();
+                let mut named = false;
+                if let Some(rest) = self.rest.strip_prefix(']') {
+                    self.rest = rest;
+                    return Some(Value::Array(list));
+                }
+                loop {
+                    // Lists hold either bare values or `name=value` results; the names
+                    // are always identical (e.g. `stack=[frame={..},frame={..}]`).
+                    match self.rest.chars().next()? {
+                        // An MI2 breakpoint location in `-break-list`'s `body`
+                        '{' if named => {
+                            let location = self.parse_value()?;
+                            add_location(list.last_mut()?, location)?;
+                        }
+                        '"' | '{' | '[' => list.push(self.parse_value()?),
+                        _ => {
+                            named = true;
+                            list.push(self.parse_result()?.1);
+                        }
+                    }
+                    match self.rest.chars().next()? {
+                        ',' => self.rest = &self.rest[1..],
+                        ']' => {
+                            self.rest = &self.rest[1..];
+                            return Some(Value::Array(list));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_cstring(&mut self) -> Option<String> {
+        let bytes = self.rest.as_bytes();
+        if bytes.first() != Some(&b'"') {
+            return None;
+        }
+
+        // Octal escapes encode raw bytes (e.g. UTF-8 sequences), so decode into bytes first.
+        let mut decoded = Vec::new();
+        let mut i = 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Some(String::from_utf8_lossy(&decoded).into_owned());
+                }
+                b'\\' => {
+                    i += 1;
+                    let escaped = *bytes.get(i)?;
+                    match escaped {
+                        b'n' => decoded.push(b'\n'),
+                        b't' => decoded.push(b'\t'),
+                        b'r' => decoded.push(b'\r'),
+                        b'0'..=b'7' => {
+                            let digits = bytes[i..]
+                                .iter()
+                                .take(3)
+                                .take_while(|b| (b'0'..=b'7').contains(*b))
+                                .count();
+                            let octal = std::str::from_utf8(&bytes[i..i + digits]).ok()?;
+                            decoded.push(u8::from_str_radix(octal, 8).ok()?);
+                            i += digits - 1;
+                        }
+                        other => decoded.push(other),
+                    }
+                }
+                other => decoded.push(other),
+            }
+            i += 1;
+        }
+        None
+    }
+}
+
+/// Ends the reply to a debugger command that did not finish within its timeout.
+pub(crate) const TIMEOUT_NOTE: &str = "[TIMEOUT - Command may still be processing]";
+
+/// Every record an MI debugger produced in response to one command.
+#[derive(Debug, Default)]
+pub struct MiOutput {
+    pub records: Vec<MiRecord>,
+    /// Set when the debugger did not finish answering before the timeout
+    pub timed_out: bool,
+}
+
+impl MiOutput {
+    /// Returns the class (`done`, `running`, `error`, ...) and payload of the result record.
+    pub fn result(&self) -> Option<(&str, &Value)> {
+        self.records.iter().rev().find_map(|record| match record {
+            MiRecord::Result { class, results, .. } => Some((class.as_str(), results)),
+            _ => None,
+        })
+    }
+
+    /// Looks up a named field in the result record's payload.
+    pub fn result_field(&self, name: &str) -> Option<&Value> {
+        self.result().and_then(|(_, results)| results.get(name))
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.timed_out || matches!(self.result(), Some(("error", _)))
+    }
+
+    /// The number of the breakpoint behind a `*stopped,reason="breakpoint-hit"` record.
+    pub fn breakpoint_hit(&self) -> Option<u32> {
+        self.records.iter().rev().find_map(|record| match record {
+            MiRecord::Exec { class, results }
+                if class == "stopped"
+                    && results.get("reason").and_then(|v| v.as_str()) == Some("breakpoint-hit") =>
+            {
+                results.get("bkptno")?.as_str()?.parse().ok()
+            }
+            _ => None,
+        })
+    }
+
+    pub fn error_message(&self) -> Option<&str> {
+        match self.result() {
+            Some(("error", results)) => results.get("msg").and_then(|v| v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Renders the console and program output streams as plain text.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for record in &self.records {
+            match record {
+                MiRecord::Console(s) | MiRecord::Target(s) => text.push_str(s),
+                MiRecord::Other(s) => {
+                    text.push_str(s);
+                    text.push('\n');
+                }
+                _ => {}
+            }
+        }
+        if let Some(message) = self.error_message() {
+            text.push_str("error: ");
+            text.push_str(message);
+            text.push('\n');
+        }
+        if self.timed_out {
+            text.push_str(TIMEOUT_NOTE);
+        }
+        text
+    }
+}
+
+/// Demangles a Rust symbol name, legacy (`_ZN...E`) or v0 (`_R...`), and drops the
+/// `::h<hash>` suffix legacy names end in. Other names are returned unchanged, apart
+/// from that suffix.
+pub fn demangle(symbol: &str) -> String {
+    match rustc_demangle::try_demangle(symbol) {
+        Ok(demangled) => format!("{:#}", demangled),
+        Err(_) => match symbol.rsplit_once("::h") {
+            Some((name, hash))
+                if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                name.to_string()
+            }
+            _ => symbol.to_string(),
+        },
+    }
+}
+
+/// Demangles every mangled Rust symbol in `text`, such as a debugger's backtrace,
+/// leaving the rest of the text as it is.
+pub fn demangle_text(text: &str) -> String {
+    let is_symbol_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.');
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| is_symbol_char(c)) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !is_symbol_char(c))
+            .unwrap_or(rest.len());
+        let token = &rest[..end];
+        let mangled = ["_ZN", "__ZN", "_R"]
+            .iter()
+            .any(|prefix| token.starts_with(prefix));
+        match rustc_demangle::try_demangle(token) {
+            Ok(demangled) if mangled => result.push_str(&format!("{:#}", demangled)),
+            _ => result.push_str(token),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Formats an MI frame tuple as `file:line`, falling back to the function name.
+pub(crate) fn mi_frame_location(frame: &Value) -> Option<String> {
+    let field = |name: &str| frame.get(name).and_then(|v| v.as_str());
+    match (field("file"), field("line")) {
+        (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+        _ => field("func").map(demangle),
+    }
+}
+
+/// Renders an MI frame tuple as a one-line backtrace entry.
+pub(crate) fn format_mi_frame(frame: &Value) -> String {
+    let field = |name: &str| frame.get(name).and_then(|v| v.as_str());
+    let mut entry = format!(
+        "#{} {} in {}",
+        field("level").unwrap_or("?"),
+        field("addr").unwrap_or("??"),
+        field("func").map_or_else(|| "??".to_string(), demangle)
+    );
+    if let (Some(file), Some(line)) = (field("file"), field("line")) {
+        entry.push_str(&format!(" at {}:{}", file, line));
+    }
+    entry
+}
+
+/// One thread as reported by `debug_threads`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadInfo {
+    /// Debugger thread index used by `debug_select_thread`
+    pub id: u32,
+    /// Operating system thread ID
+    pub tid: Option<String>,
+    /// Thread name, e.g. `main` or the name given to `std::thread::Builder`
+    pub name: Option<String>,
+    /// Why the thread stopped, if it is the reason the process stopped
+    pub stop_reason: Option<String>,
+    /// Top stack frame
+    pub frame: Option<String>,
+    /// Whether this is the debugger's currently selected thread
+    pub selected: bool,
+}
+
+impl ThreadInfo {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "tid": self.tid,
+            "name": self.name,
+            "stop_reason": self.stop_reason,
+            "frame": self.frame.as_deref().map(demangle_text),
+            "selected": self.selected
+        })
+    }
+}
+
+/// One process of the session as reported by `debug_inferiors`: a GDB inferior or an
+/// LLDB target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferiorInfo {
+    /// Number used by `debug_select_inferior`: GDB's inferior number or LLDB's target index
+    pub id: u32,
+    /// Process ID, once the process runs
+    pub pid: Option<u32>,
+    /// Program the process runs
+    pub executable: Option<String>,
+    /// Process state as LLDB reports it, e.g. `stopped`
+    pub state: Option<String>,
+    /// Whether this is the debugger's currently selected inferior
+    pub selected: bool,
+}
+
+impl InferiorInfo {
+    /// Parses one line of LLDB's `target list`, e.g.
+    /// `* target #0: /tmp/app ( arch=x86_64-unknown-linux-gnu, platform=host, pid=4242, state=stopped )`,
+    /// where the leading `*` marks the selected target.
+    pub fn parse_lldb(line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        let (selected, rest) = match trimmed.strip_prefix("* ") {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let (id, rest) = rest.strip_prefix("target #")?.split_once(':')?;
+        let id = id.parse().ok()?;
+
+        let (executable, properties) = match rest.rsplit_once(" ( ") {
+            Some((executable, properties)) => (executable, properties.trim_end_matches(')')),
+            None => (rest, ""),
+        };
+        let property = |name: &str| {
+            properties.split(',').find_map(|property| {
+                property
+                    .trim()
+                    .strip_prefix(name)
+                    .and_then(|value| value.strip_prefix('='))
+                    .map(str::to_string)
+            })
+        };
+
+        Some(Self {
+            id,
+            pid: property("pid").and_then(|pid| pid.parse().ok()),
+            executable: Some(executable.trim().to_string()).filter(|e| !e.is_empty()),
+            state: property("state"),
+            selected,
+        })
+    }
+
+    /// Reads one group of GDB's `-list-thread-groups`, e.g.
+    /// `{id="i2",type="process",pid="4243",executable="/tmp/app"}`; `current` is the
+    /// number of the selected inferior.
+    pub fn from_mi(group: &Value, current: Option<u32>) -> Option<Self> {
+        let field = |name: &str| group.get(name).and_then(|v| v.as_str());
+        let id = field("id")?.trim_start_matches('i').parse().ok()?;
+        Some(Self {
+            id,
+            pid: field("pid").and_then(|pid| pid.parse().ok()),
+            executable: field("executable").map(str::to_string),
+            state: None,
+            selected: current == Some(id),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "pid": self.pid,
+            "executable": self.executable,
+            "state": self.state,
+            "selected": self.selected
+        })
+    }
+}
+
+/// A GDB checkpoint: a forked copy of the program, stopped where it was taken, that
+/// `restart` switches to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckpointInfo {
+    /// Checkpoint number; 0 is the process that was running when the first was taken
+    pub id: u32,
+    /// Process ID of the copy
+    pub pid: Option<u32>,
+    /// file:line the copy is stopped at
+    pub location: Option<String>,
+    /// Whether this is the process being debugged now
+    pub current: bool,
+}
+
+impl CheckpointInfo {
+    /// Parses one line of GDB's `info checkpoints`, e.g.
+    /// `* 0 Thread 0x7ffff7d85740 (LWP 4242) (main process) at 0x555555559ff0, file src/main.rs, line 12`
+    /// or `  1 process 4250 at 0x555555559ff0, file src/main.rs, line 12`.
+    pub fn parse_gdb(line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        let (current, rest) = match trimmed.strip_prefix('*') {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, trimmed),
+        };
+        let (id, rest) = rest.split_once(' ')?;
+        let id = id.parse().ok()?;
+        if !(rest.starts_with("process ") || rest.starts_with("Thread ")) {
+            return None;
+        }
+
+        let number_after = |marker: &str| -> Option<u32> {
+            let (_, rest) = rest.split_once(marker)?;
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            rest[..end].parse().ok()
+        };
+        let file = rest
+            .split_once(", file ")
+            .and_then(|(_, rest)| rest.split_once(", line "))
+            .map(|(file, _)| file);
+        let location = file
+            .zip(number_after(", line "))
+            .map(|(file, line)| format!("{}:{}", file, line));
+
+        Some(Self {
+            id,
+            pid: number_after("(LWP ").or_else(|| number_after("process ")),
+            location,
+            current,
+        })
+    }
+
+    /// Reads the process ID from GDB's reply to `checkpoint`, e.g.
+    /// `checkpoint 1: fork returned pid 4250.`, along with the checkpoint number.
+    pub fn parse_gdb_created(output: &str) -> Option<(u32, u32)> {
+        let (_, rest) = output.split_once("checkpoint ")?;
+        let (id, rest) = rest.split_once(": fork returned pid ")?;
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        Some((id.trim().parse().ok()?, rest[..end].parse().ok()?))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "pid": self.pid,
+            "location": self.location,
+            "current": self.current
+        })
+    }
+}
+
+/// One stack frame, parsed from LLDB output or an MI frame tuple.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameInfo {
+    /// Frame number, 0 being the innermost
+    pub index: u32,
+    /// Function name as the debugger reports it, without the `+ offset` suffix; may
+    /// be mangled
+    pub function: Option<String>,
+    /// Binary or shared library the frame's code lives in
+    pub module: Option<String>,
+    /// Source file, if debug info is available
+    pub file: Option<String>,
+    /// Source line, if debug info is available
+    pub line: Option<u32>,
+    /// Program counter
+    pub pc: Option<String>,
+}
+
+impl FrameInfo {
+    /// Parses a frame line such as
+    /// `frame #1: 0x0000000100003f94 app`app::parse(input=...) at parse.rs:10:5`.
+    ///
+    /// The leading `*` that marks the selected frame in backtraces is ignored.
+    pub fn parse_lldb(line: &str) -> Option<Self> {
+        let rest = line.trim_start().trim_start_matches("* ");
+        let rest = rest.strip_prefix("frame #")?;
+        let (index, rest) = rest.split_once(':')?;
+        let index = index.parse().ok()?;
+        let rest = rest.trim();
+
+        let (pc, rest) = match rest.split_once(' ') {
+            Some((pc, rest)) if pc.starts_with("0x") => (Some(pc.to_string()), rest),
+            _ if rest.starts_with("0x") => (Some(rest.to_string()), ""),
+            _ => (None, rest),
+        };
+
+        let (module, rest) = match rest.split_once('`') {
+            Some((module, rest)) => (Some(module.to_string()), rest),
+            None => (None, rest),
+        };
+
+        let (function, location) = match rest.rsplit_once(" at ") {
+            Some((function, location)) => (function, Some(location)),
+            None => (rest, None),
+        };
+        let function = function
+            .rsplit_once(" + ")
+            .filter(|(_, offset)| offset.chars().all(|c| c.is_ascii_digit()))
+            .map_or(function, |(function, _)| function)
+            .trim();
+
+        let (file, line) = match location.map(|l| l.split(':').collect::<Vec<_>>()) {
+            Some(parts) if !parts.is_empty() => (
+                Some(parts[0].to_string()),
+                parts.get(1).and_then(|line| line.parse().ok()),
+            ),
+            _ => (None, None),
+        };
+
+        Some(Self {
+            index,
+            function: Some(function.to_string()).filter(|f| !f.is_empty()),
+            module,
+            file,
+            line,
+            pc,
+        })
+    }
+
+    /// Reads what LLDB's `image lookup -a <pc>` says about a code address into a frame;
+    /// without a `Summary:` line, only `pc` is known.
+    pub fn from_lldb_lookup(index: u32, pc: &str, output: &str) -> Self {
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Summary: "))
+            .and_then(|summary| Self::parse_lldb(&format!("frame #{}: {} {}", index, pc, summary)))
+            .unwrap_or_else(|| Self::from_pc(index, pc))
+    }
+
+    /// Reads GDB's `info line *<pc>`, e.g. `Line 5 of "src/main.rs" starts at address
+    /// 0x5555555592b0 <app::main+16> and ends at ...`, into a frame.
+    pub fn from_gdb_info_line(index: u32, pc: &str, output: &str) -> Self {
+        let mut frame = Self::from_pc(index, pc);
+        if let Some(rest) = output.split("Line ").nth(1) {
+            if let Some((line, rest)) = rest.split_once(" of \"") {
+                frame.line = line.trim().parse().ok();
+                frame.file = rest.split_once('"').map(|(file, _)| file.to_string());
+            }
+        }
+        frame.function = output
+            .split_once(" <")
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(symbol, _)| {
+                symbol
+                    .rsplit_once('+')
+                    .filter(|(_, offset)| offset.chars().all(|c| c.is_ascii_digit()))
+                    .map_or(symbol, |(function, _)| function)
+                    .to_string()
+            });
+        frame
+    }
+
+    fn from_pc(index: u32, pc: &str) -> Self {
+        Self {
+            index,
+            function: None,
+            module: None,
+            file: None,
+            line: None,
+            pc: Some(pc.to_string()),
+        }
+    }
+
+    /// Converts an MI `frame={level=..,addr=..,func=..,file=..,line=..}` tuple.
+    pub fn from_mi(frame: &Value) -> Self {
+        let field = |name: &str| frame.get(name).and_then(|v| v.as_str());
+        Self {
+            index: field("level").and_then(|l| l.parse().ok()).unwrap_or(0),
+            function: field("func").map(str::to_string),
+            module: field("from").map(str::to_string),
+            file: field("file").map(str::to_string),
+            line: field("line").and_then(|l| l.parse().ok()),
+            pc: field("addr").map(str::to_string),
+        }
+    }
+
+    /// Guesses whether the frame belongs to Rust code rather than libc or the OS.
+    ///
+    /// A frame counts as Rust if its source file is a `.rs` file or its symbol carries
+    /// a Rust mangling: the legacy `::h<16 hex digits>` hash suffix or the v0 `_R` prefix.
+    pub fn is_rust_frame(&self) -> bool {
+        if self
+            .file
+            .as_deref()
+            .is_some_and(|file| file.ends_with(".rs"))
+        {
+            return true;
+        }
+        self.function.as_deref().is_some_and(|function| {
+            function.starts_with("_R")
+                || function.rsplit_once("::h").is_some_and(|(_, hash)| {
+                    hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit())
+                })
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "index": self.index,
+            "function": self.function.as_deref().map(demangle),
+            "raw_function": self.function,
+            "module": self.module,
+            "file": self.file,
+            "line": self.line,
+            "pc": self.pc,
+            "is_rust_frame": self.is_rust_frame()
+        })
+    }
+}
+
+/// Calls that block a thread until another thread acts, as the start and end of the
+/// function's path, and what the thread then waits for.
+const BLOCKING_CALLS: [(&str, &str, &str); 14] = [
+    ("std::sync::mutex::Mutex<", ">::lock", "mutex"),
+    ("std::sync::poison::mutex::Mutex<", ">::lock", "mutex"),
+    ("lock_api::mutex::Mutex<", ">::lock", "mutex"),
+    ("std::sync::rwlock::RwLock<", ">::read", "rwlock_read"),
+    ("std::sync::rwlock::RwLock<", ">::write", "rwlock_write"),
+    (
+        "std::sync::poison::rwlock::RwLock<",
+        ">::read",
+        "rwlock_read",
+    ),
+    (
+        "std::sync::poison::rwlock::RwLock<",
+        ">::write",
+        "rwlock_write",
+    ),
+    ("lock_api::rwlock::RwLock<", ">::read", "rwlock_read"),
+    ("lock_api::rwlock::RwLock<", ">::write", "rwlock_write"),
+    ("std::sync::condvar::Condvar::wait", "", "condvar"),
+    ("std::sync::poison::condvar::Condvar::wait", "", "condvar"),
+    ("std::thread::JoinHandle<", ">::join", "thread_join"),
+    ("std::sync::mpsc::Receiver<", ">::recv", "channel_recv"),
+    ("std::sync::mpmc::Receiver<", ">::recv", "channel_recv"),
+];
+
+/// Paths of the standard library and lock crates, whose frames are not the program's.
+const LIBRARY_PATHS: [&str; 6] = [
+    "std::",
+    "core::",
+    "alloc::",
+    "lock_api::",
+    "parking_lot::",
+    "parking_lot_core::",
+];
+
+impl FrameInfo {
+    /// Whether the frame runs the program's own Rust code rather than the standard
+    /// library's or a lock crate's.
+    pub fn is_user_frame(&self) -> bool {
+        let function = self.function.as_deref().map(demangle).unwrap_or_default();
+        let path = function.trim_start_matches('<');
+        self.is_rust_frame()
+            && !LIBRARY_PATHS
+                .iter()
+                .any(|library| path.starts_with(library))
+    }
+}
+
+/// Finds the call a thread is blocked in, from its `frames` innermost first: the index
+/// of the innermost lock, wait, join, or receive call on the stack, and what it waits
+/// for, e.g. `mutex`.
+pub fn blocking_call(frames: &[FrameInfo]) -> Option<(usize, &'static str)> {
+    frames.iter().enumerate().find_map(|(index, frame)| {
+        let function = demangle(frame.function.as_deref()?);
+        BLOCKING_CALLS
+            .iter()
+            .find(|(start, end, _)| {
+                function.starts_with(start)
+                    && function.len() >= start.len() + end.len()
+                    && function.ends_with(end)
+            })
+            .map(|(_, _, kind)| (index, *kind))
+    })
+}
+
+/// Functions of the panic machinery, which a panicking thread runs between the code
+/// that panicked and `rust_panic`.
+const PANIC_FUNCTIONS: [&str; 5] = [
+    "std::panicking::",
+    "core::panicking::",
+    "rust_panic",
+    "rust_begin_unwind",
+    "__rust_start_panic",
+];
+
+impl FrameInfo {
+    /// Whether the frame belongs to the panic machinery, e.g. `core::panicking::panic_fmt`.
+    pub fn is_panic_frame(&self) -> bool {
+        let function = self.function.as_deref().map(demangle).unwrap_or_default();
+        // Recent toolchains export the unwinding entry points as `__rustc::rust_panic`
+        let function = function.trim_start_matches("__rustc::");
+        PANIC_FUNCTIONS
+            .iter()
+            .any(|panic| function.starts_with(panic))
+    }
+}
+
+/// The frames of the program's own code in the backtrace of a panicking thread, from
+/// the innermost one, where the panic was raised, to the outermost, leaving out the
+/// panic machinery above and the runtime below. `None` unless the thread is panicking.
+pub fn panic_backtrace(frames: &[FrameInfo]) -> Option<&[FrameInfo]> {
+    let is_user = |frame: &FrameInfo| frame.is_user_frame() && !frame.is_panic_frame();
+    let first = frames.iter().position(is_user)?;
+    if !frames[..first].iter().any(FrameInfo::is_panic_frame) {
+        return None;
+    }
+    let last = frames.iter().rposition(is_user)?;
+    Some(&frames[first..=last])
+}
+
+/// A panic as the default panic hook prints it to stderr.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicMessage {
+    /// Name of the panicking thread, e.g. `main` or `<unnamed>`
+    pub thread: Option<String>,
+    /// The panic message
+    pub message: String,
+    /// Source file of the `panic!`, `unwrap`, or failed operation
+    pub file: String,
+    /// Line of the panic in `file`
+    pub line: Option<u32>,
+    /// Column of the panic in `file`
+    pub column: Option<u32>,
+}
+
+impl PanicMessage {
+    /// Reads the last panic reported in `stderr`: since Rust 1.73 as
+    /// `thread 'main' panicked at src/main.rs:5:9:` followed by the message, before
+    /// that as `thread 'main' panicked at 'message', src/main.rs:5:9`.
+    pub fn parse_stderr(stderr: &str) -> Option<Self> {
+        let (before, rest) = stderr.rsplit_once(" panicked at ")?;
+        // Recent toolchains add the thread ID: `thread 'main' (4242) panicked at`
+        let thread = before
+            .rsplit_once("thread '")
+            .and_then(|(_, name)| name.rsplit_once('\''))
+            .map(|(name, _)| name.to_string());
+
+        let (message, location) = match rest.strip_prefix('\'') {
+            Some(rest) => {
+                let (message, rest) = rest.rsplit_once("', ")?;
+                (message.to_string(), rest.lines().next()?)
+            }
+            None => {
+                let (location, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+                let message: Vec<&str> = rest
+                    .lines()
+                    .take_while(|line| {
+                        !line.starts_with("note: ") && !line.starts_with("stack backtrace:")
+                    })
+                    .collect();
+                (
+                    message.join("\n").trim_end().to_string(),
+                    location.trim_end().trim_end_matches(':'),
+                )
+            }
+        };
+
+        let mut parts = location.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok();
+        let line = parts.next()?.parse().ok();
+        let file = parts.next()?.to_string();
+        Some(Self {
+            thread,
+            message,
+            file,
+            line,
+            column,
+        })
+    }
+}
+
+/// An error report printed by AddressSanitizer, ThreadSanitizer, MemorySanitizer, or
+/// LeakSanitizer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizerReport {
+    /// Which sanitizer reported it: `address`, `thread`, `memory`, or `leak`
+    pub sanitizer: String,
+    /// The kind of error, e.g. `heap-buffer-overflow` or `data race`
+    pub kind: String,
+    /// Address the bad access was to, when the report names one
+    pub address: Option<String>,
+    /// The report's `SUMMARY:` line, without the prefix
+    pub summary: Option<String>,
+    /// Stacks in the report, each under the line introducing it, e.g.
+    /// `READ of size 4 at 0x602000000014 thread T0` or `freed by thread T0 here:`
+    pub stacks: Vec<(String, Vec<FrameInfo>)>,
+}
+
+impl SanitizerReport {
+    /// Reads every report in `stderr`, from its `==pid==ERROR: AddressSanitizer: ...`
+    /// or `WARNING: ThreadSanitizer: ...` header to its `SUMMARY:` line.
+    pub fn parse_all(stderr: &str) -> Vec<Self> {
+        let mut reports: Vec<Self> = Vec::new();
+        let mut open = false;
+        let mut title = String::new();
+        for line in stderr.lines() {
+            let trimmed = line.trim();
+            if let Some((sanitizer, kind)) = Self::parse_header(trimmed) {
+                let address = kind
+                    .split_once(" on address ")
+                    .and_then(|(_, rest)| rest.split_whitespace().next())
+                    .map(str::to_string);
+                let kind = kind
+                    .split(" on ")
+                    .next()
+                    .unwrap_or(kind)
+                    .split(" (pid=")
+                    .next()
+                    .unwrap_or(kind);
+                reports.push(Self {
+                    sanitizer,
+                    kind: kind.trim().to_string(),
+                    address,
+                    summary: None,
+                    stacks: Vec::new(),
+                });
+                open = true;
+                title.clear();
+                continue;
+            }
+            let Some(report) = reports.last_mut().filter(|_| open) else {
+                continue;
+            };
+            if let Some(summary) = trimmed.strip_prefix("SUMMARY: ") {
+                report.summary = Some(summary.to_string());
+                open = false;
+            } else if let Some(frame) = Self::parse_frame(trimmed) {
+                match report.stacks.last_mut() {
+                    Some((stack, frames)) if *stack == title && frame.index > 0 => {
+                        frames.push(frame)
+                    }
+                    _ => report.stacks.push((title.clone(), vec![frame])),
+                }
+            } else if !trimmed.is_empty() {
+                title = trimmed.to_string();
+            }
+        }
+        reports
+    }
+
+    /// Reads a report header into the sanitizer's name and the rest of the line.
+    fn parse_header(line: &str) -> Option<(String, &str)> {
+        // AddressSanitizer prefixes its lines with `==pid==`
+        let line = match line.strip_prefix("==") {
+            Some(rest) => rest.split_once("==")?.1,
+            None => line,
+        };
+        let line = line
+            .strip_prefix("ERROR: ")
+            .or_else(|| line.strip_prefix("WARNING: "))?;
+        let (name, kind) = line.split_once("Sanitizer: ")?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        Some((name.to_lowercase(), kind))
+    }
+
+    /// Parses a stack frame line, `#1 0x55d5c in app::main::h0123 /src/main.rs:5:13`
+    /// or, from ThreadSanitizer, `#0 app::main /src/main.rs:10:9 (app+0x1234)`.
+    fn parse_frame(line: &str) -> Option<FrameInfo> {
+        let (index, rest) = line.strip_prefix('#')?.split_once(' ')?;
+        let index = index.parse().ok()?;
+        let mut rest = rest.trim();
+        let mut pc = None;
+        if rest.starts_with("0x") {
+            let (address, after) = rest.split_once(' ').unwrap_or((rest, ""));
+            pc = Some(address.to_string());
+            rest = after.trim_start();
+            rest = rest.strip_prefix("in ").unwrap_or(rest);
+        }
+
+        // A trailing `(module+0xoffset)` names the binary the code is in, and may be
+        // followed by its `(BuildId: ...)`
+        if let Some((before, _)) = rest.rsplit_once(" (BuildId: ") {
+            rest = before.trim_end();
+        }
+        let mut module = None;
+        if let Some((before, inside)) = rest
+            .strip_suffix(')')
+            .and_then(|rest| rest.rsplit_once(" ("))
+        {
+            let name = inside.split('+').next().unwrap_or(inside);
+            module = Some(name.rsplit('/').next().unwrap_or(name).to_string());
+            rest = before.trim_end();
+        }
+
+        let (mut file, mut line_number) = (None, None);
+        if let Some((function, location)) = rest.rsplit_once(' ') {
+            let mut parts = location.splitn(3, ':');
+            let path = parts.next().unwrap_or_default();
+            if let Some(number) = parts.next().and_then(|number| number.parse().ok()) {
+                file = Some(path.to_string());
+                line_number = Some(number);
+                rest = function;
+            }
+        }
+        Some(FrameInfo {
+            index,
+            function: (!rest.is_empty()).then(|| rest.to_string()),
+            module,
+            file,
+            line: line_number,
+            pc,
+        })
+    }
+
+    /// The innermost frame of the program's own code in the report's first stack.
+    pub fn user_frame(&self) -> Option<&FrameInfo> {
+        // The standard library's sources are under `/rustc/<commit>/library`
+        let in_toolchain = |frame: &FrameInfo| {
+            frame
+                .file
+                .as_deref()
+                .is_some_and(|file| file.starts_with("/rustc/"))
+        };
+        self.stacks.first().and_then(|(_, frames)| {
+            frames
+                .iter()
+                .find(|frame| frame.is_user_frame() && !in_toolchain(frame))
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "sanitizer": self.sanitizer,
+            "kind": self.kind,
+            "address": self.address,
+            "summary": self.summary,
+            "user_frame": self.user_frame().map(FrameInfo::to_json),
+            "stacks": self
+                .stacks
+                .iter()
+                .map(|(title, frames)| json!({
+                    "title": title.trim_end_matches(':'),
+                    "frames": frames.iter().map(FrameInfo::to_json).collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+/// The literal parts of the `fmt::Arguments` among the `variables` of a
+/// `core::panicking::panic_fmt` frame, i.e. the panic message without its
+/// interpolated values.
+pub fn format_pieces(variables: &[Variable]) -> Vec<String> {
+    fn find<'a>(variables: &'a [Variable], name: &str) -> Option<&'a Variable> {
+        variables.iter().find_map(|variable| {
+            if variable.name == name {
+                Some(variable)
+            } else {
+                find(&variable.children, name)
+            }
+        })
+    }
+    fn strings(variable: &Variable, out: &mut Vec<String>) {
+        if let Some(text) = variable
+            .value
+            .as_deref()
+            .and_then(|value| value.strip_prefix('"'))
+            .and_then(|value| value.strip_suffix('"'))
+        {
+            out.push(text.to_string());
+        }
+        for child in &variable.children {
+            strings(child, out);
+        }
+    }
+
+    let mut pieces = Vec::new();
+    if let Some(arguments) = find(variables, "fmt") {
+        if let Some(literal) = find(std::slice::from_ref(arguments), "pieces") {
+            for piece in &literal.children {
+                strings(piece, &mut pieces);
+            }
+        }
+    }
+    pieces
+}
+
+/// Paths of async runtimes and of the `Future` plumbing between an `async fn` and the
+/// future it awaits, whose frames a logical async backtrace leaves out.
+const ASYNC_GLUE_PATHS: [&str; 15] = [
+    "tokio::",
+    "async_std::",
+    "async_executor::",
+    "async_task::",
+    "smol::",
+    "futures_core::",
+    "futures_util::",
+    "futures_executor::",
+    "tracing::instrument::",
+    "core::future::",
+    "core::pin::",
+    "core::ops::function::",
+    "core::panic::",
+    "std::panic::",
+    "std::panicking::",
+];
+
+/// Reconstructs the await chain of the task a thread is polling from the thread's
+/// physical `frames`, innermost first.
+///
+/// The bodies of `async fn`s and `async` blocks are named `{async_fn#0}` and
+/// `{async_block#0}` in debug info; bare symbols call them `{{closure}}`, which counts
+/// as async when polled by runtime code or by another async body. Frames from the
+/// innermost down to the outermost async body (the task's root future) are kept,
+/// minus the runtime and `Future` plumbing between them; each async frame gets the
+/// function it `awaits`. Returns the frames and the index of the root in `frames`,
+/// or `None` if no async body is on the stack.
+pub fn async_backtrace(frames: &[FrameInfo]) -> Option<(Vec<Value>, usize)> {
+    let names: Vec<String> = frames
+        .iter()
+        .map(|frame| frame.function.as_deref().map(demangle).unwrap_or_default())
+        .collect();
+    let is_glue = |name: &str| {
+        let path = name.trim_start_matches('<');
+        ASYNC_GLUE_PATHS.iter().any(|glue| path.starts_with(glue))
+    };
+
+    // Whether a closure is an async body depends on its caller, the next frame out
+    let mut is_async = vec![false; frames.len()];
+    for i in (0..frames.len()).rev() {
+        let name = &names[i];
+        is_async[i] = name.contains("{async_fn#")
+            || name.contains("{async_block#")
+            || (name.ends_with("{{closure}}")
+                && names
+                    .get(i + 1)
+                    .is_some_and(|caller| is_async[i + 1] || is_glue(caller)));
+    }
+    let root = is_async.iter().rposition(|&is_async| is_async)?;
+
+    let mut awaited = None;
+    let mut logical = Vec::new();
+    for i in 0..=root {
+        if is_glue(&names[i]) {
+            continue;
+        }
+        let mut frame = frames[i].to_json();
+        frame["async"] = json!(is_async[i]);
+        if is_async[i] {
+            frame["awaits"] = json!(awaited);
+            awaited = Some(names[i].clone());
+        }
+        logical.push(frame);
+    }
+    Some((logical, root))
+}
+
+/// A variable and, if it was expanded, its fields or elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    /// Variable name, field name, or element index (e.g. `[0]`)
+    pub name: String,
+    /// Static type as reported by the debugger
+    pub type_name: Option<String>,
+    /// Rendered value; absent for aggregates that are only described by their children
+    pub value: Option<String>,
+    /// Fields or elements, empty when not expanded
+    pub children: Vec<Variable>,
+}
+
+impl Variable {
+    /// Parses LLDB `frame variable -T` output into a tree.
+    ///
+    /// Each line is `(type) name = value`; a trailing `{` opens the children that
+    /// follow, one per line, until the matching `}`.
+    pub fn parse_lldb_tree(output: &str) -> Vec<Variable> {
+        let mut roots = Vec::new();
+        let mut open: Vec<Variable> = Vec::new();
+
+        for line in output.lines() {
+            let trimmed = line.trim();
+            if trimmed == "}" {
+                if let Some(done) = open.pop() {
+                    match open.last_mut() {
+                        Some(parent) => parent.children.push(done),
+                        None => roots.push(done),
+                    }
+                }
+                continue;
+            }
+
+            let Some((variable, opens)) = Self::parse_lldb_line(trimmed) else {
+                continue;
+            };
+            if opens {
+                open.push(variable);
+            } else {
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(variable),
+                    None => roots.push(variable),
+                }
+            }
+        }
+
+        // Unbalanced output (e.g. truncated by a timeout): keep what was opened
+        while let Some(done) = open.pop() {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(done),
+                None => roots.push(done),
+            }
+        }
+        roots
+    }
+
+    /// Parses one `(type) name = value` line, reporting whether it opens a `{` block.
+    fn parse_lldb_line(line: &str) -> Option<(Variable, bool)> {
+        // `-T` prefixes every variable with its type; anything else is echo or prompt
+        let rest = line.strip_prefix('(')?;
+        // Types can contain parentheses themselves, e.g. `(Option<(i32, i32)>)`
+        let mut depth = 1;
+        let close = rest.char_indices().find_map(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        })?;
+        let type_name = Some(rest[..close].to_string());
+        let rest = rest[close + 1..].trim_start();
+
+        let (name, value) = match rest.split_once(" = ") {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (rest.trim(), None),
+        };
+        if name.is_empty() || name == "..." {
+            return None;
+        }
+
+        let (value, opens) = match value {
+            Some(value) if value.ends_with('{') => (value.trim_end_matches('{').trim_end(), true),
+            Some(value) => (value, false),
+            None => ("", false),
+        };
+
+        Some((
+            Variable {
+                name: name.to_string(),
+                type_name,
+                value: Some(value.to_string()).filter(|v| !v.is_empty()),
+                children: Vec::new(),
+            },
+            opens,
+        ))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut object = json!({
+            "name": self.name,
+            "type": self.type_name,
+            "value": self.value
+        });
+        if let Some(display) = self.rust_display() {
+            object["display"] = json!(display);
+        }
+        if !self.children.is_empty() {
+            object["children"] = self.children.iter().map(Variable::to_json).collect();
+        }
+        object
+    }
+}
+
+impl Variable {
+    /// Like [`Variable::to_json`], but bounded for `debug_inspect`: each node keeps at
+    /// most `max_children` children and is marked `truncated` if it had more, and a
+    /// pointer to the same address as one of its ancestors is marked `cycle` and not
+    /// expanded again.
+    pub fn to_json_limited(&self, max_children: usize) -> Value {
+        self.limited_json(max_children, &mut Vec::new())
+    }
+
+    fn limited_json<'a>(&'a self, max_children: usize, ancestors: &mut Vec<&'a str>) -> Value {
+        let mut object = json!({
+            "name": self.name,
+            "type": self.type_name,
+            "value": self.value
+        });
+        if let Some(display) = self.rust_display() {
+            object["display"] = json!(display);
+        }
+        if self.children.is_empty() {
+            return object;
+        }
+
+        let address = self.address();
+        if address.is_some_and(|address| ancestors.contains(&address)) {
+            object["cycle"] = json!(true);
+            return object;
+        }
+        if let Some(address) = address {
+            ancestors.push(address);
+        }
+        object["children"] = self
+            .children
+            .iter()
+            .take(max_children)
+            .map(|child| child.limited_json(max_children, ancestors))
+            .collect();
+        if self.children.len() > max_children {
+            object["truncated"] = json!(true);
+        }
+        if address.is_some() {
+            ancestors.pop();
+        }
+        object
+    }
+
+    /// The non-null address a pointer-like value holds, e.g. `0x00007ff7bfeff2a0`.
+    pub fn address(&self) -> Option<&str> {
+        let value = self.value.as_deref()?.split_whitespace().next()?;
+        let digits = value.strip_prefix("0x")?;
+        let non_null = u64::from_str_radix(digits, 16).is_ok_and(|address| address != 0);
+        non_null.then_some(value)
+    }
+
+    /// Addresses of the locks held by lock guards in this variable or its expanded
+    /// fields: the `lock` of a std `MutexGuard` or `RwLockWriteGuard`, or the `mutex`
+    /// or `rwlock` of a `lock_api` (parking_lot) guard.
+    pub fn held_locks(&self) -> Vec<String> {
+        let is_guard = self.type_name.as_deref().is_some_and(|type_name| {
+            ["MutexGuard<", "RwLockWriteGuard<", "RwLockReadGuard<"]
+                .iter()
+                .any(|guard| type_name.contains(guard))
+        });
+        let own = self
+            .children
+            .iter()
+            .find(|child| is_guard && matches!(child.name.as_str(), "lock" | "mutex" | "rwlock"));
+        match own.and_then(Variable::address) {
+            Some(address) => vec![address.to_string()],
+            None => self
+                .children
+                .iter()
+                .flat_map(Variable::held_locks)
+                .collect(),
+        }
+    }
+}
+
+/// Most elements or entries a collection rendering lists before eliding the rest.
+const DISPLAY_ELEMENTS: usize = 10;
+
+impl Variable {
+    /// Renders the value the way Rust's `{:?}` would, e.g. `Some("abc")` or
+    /// `len=3 [1, 2, 3]`, for the std types debuggers print poorly: `Option`, `Result`,
+    /// strings, `Vec`, `VecDeque`, slices, maps, sets, and `Box`, `Rc`, and `Arc`.
+    ///
+    /// Works from the type name, the debugger's summary, and whatever children were
+    /// expanded, so it reads raw DWARF layouts as well as the Rust formatters' output.
+    /// Returns `None` for other types, whose debugger rendering is best left alone.
+    pub fn rust_display(&self) -> Option<String> {
+        match rust_type_base(self.type_name.as_deref()?) {
+            "Option" => self.enum_display(&["Some", "None"]),
+            "Result" => self.enum_display(&["Ok", "Err"]),
+            "String" | "str" | "OsString" | "PathBuf" => {
+                self.value.clone().filter(|value| value.starts_with('"'))
+            }
+            "Vec" | "VecDeque" | "[]" => Some(self.sequence_display()),
+            "HashMap" | "BTreeMap" => Some(self.map_display()),
+            "HashSet" | "BTreeSet" => Some(self.set_display()),
+            "Box" | "Rc" | "Arc" => self.pointee().map(Variable::display),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a `Box`, `Rc`, or `Arc`, whose value lives behind a pointer.
+    pub fn is_smart_pointer(&self) -> bool {
+        self.type_name
+            .as_deref()
+            .is_some_and(|type_name| matches!(rust_type_base(type_name), "Box" | "Rc" | "Arc"))
+    }
+
+    /// The value a smart pointer or reference points to, if it was expanded: the
+    /// formatters' `value` child, a dereferenced `*name` child, or either of those
+    /// inside the `ptr`/`pointer` wrappers of a raw `Rc` or `Arc` layout.
+    pub fn pointee(&self) -> Option<&Variable> {
+        let mut node = self;
+        loop {
+            let target = node
+                .children
+                .iter()
+                .find(|child| child.name == "value" || child.name.starts_with('*'));
+            if target.is_some() {
+                return target;
+            }
+            node = node
+                .children
+                .iter()
+                .find(|child| matches!(child.name.as_str(), "ptr" | "pointer" | "__0"))?;
+        }
+    }
+
+    /// This value inside a larger rendering: as Rust prints it when known, otherwise as
+    /// the debugger does.
+    fn display(&self) -> String {
+        if let Some(display) = self.rust_display() {
+            return display;
+        }
+        match &self.value {
+            Some(value) if self.children.is_empty() || !value.starts_with('{') => value.clone(),
+            _ if self.children.is_empty() => "{..}".to_string(),
+            _ => {
+                let fields: Vec<String> = self
+                    .children
+                    .iter()
+                    .map(|child| format!("{}: {}", child.name, child.display()))
+                    .collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+
+    /// Renders an enum whose variants are `variants`, reading the active one from the
+    /// debugger's summary (`Some(5)`) or from the child describing it.
+    fn enum_display(&self, variants: &[&str]) -> Option<String> {
+        if let Some(value) = &self.value {
+            let rendered = variants
+                .iter()
+                .any(|variant| value == variant || value.starts_with(&format!("{}(", variant)));
+            if rendered {
+                return Some(value.clone());
+            }
+        }
+
+        // Raw layouts hold the active variant as a child, e.g. `Some = { __0 = 5 }`
+        let (variant, fields) = self.children.iter().find_map(|child| {
+            let name = if variants.contains(&child.name.as_str()) {
+                child.name.as_str()
+            } else {
+                rust_type_base(child.type_name.as_deref()?)
+            };
+            variants
+                .iter()
+                .find(|variant| **variant == name)
+                .map(|variant| (*variant, &child.children))
+        })?;
+        if fields.is_empty() {
+            return Some(variant.to_string());
+        }
+        let fields: Vec<String> = fields.iter().map(Variable::display).collect();
+        Some(format!("{}({})", variant, fields.join(", ")))
+    }
+
+    /// Number of elements: from a `size=3` summary, a `len` field, or the children.
+    fn collection_len(&self) -> usize {
+        self.value
+            .as_deref()
+            .and_then(|value| value.strip_prefix("size="))
+            .and_then(|size| size.split_whitespace().next())
+            .and_then(|size| size.parse().ok())
+            .or_else(|| {
+                self.children
+                    .iter()
+                    .find(|child| child.name == "len")
+                    .and_then(|len| len.value.as_deref()?.parse().ok())
+            })
+            .unwrap_or_else(|| self.elements().count())
+    }
+
+    /// Children that are elements (`[0]`, `[1]`, ...) rather than fields.
+    fn elements(&self) -> impl Iterator<Item = &Variable> {
+        self.children
+            .iter()
+            .filter(|child| child.name.starts_with('[') && child.name.ends_with(']'))
+    }
+
+    /// `len=N` followed by the elements between `open` and `close`, or `..` when they
+    /// were not expanded.
+    fn collection_display(&self, open: &str, close: &str, items: Vec<String>) -> String {
+        let len = self.collection_len();
+        if len == 0 {
+            return format!("len=0 {}{}", open, close);
+        }
+        if items.is_empty() {
+            return format!("len={} {}..{}", len, open, close);
+        }
+        let mut shown: Vec<String> = items.into_iter().take(DISPLAY_ELEMENTS).collect();
+        if len > shown.len() {
+            shown.push("..".to_string());
+        }
+        format!("len={} {}{}{}", len, open, shown.join(", "), close)
+    }
+
+    fn sequence_display(&self) -> String {
+        let items = self.elements().map(Variable::display).collect();
+        self.collection_display("[", "]", items)
+    }
+
+    fn set_display(&self) -> String {
+        let items = self.elements().map(Variable::display).collect();
+        self.collection_display("{", "}", items)
+    }
+
+    fn map_display(&self) -> String {
+        // Entries are `(key, value)` tuples, or keys and values in alternate elements
+        let elements: Vec<&Variable> = self.elements().collect();
+        let items = if elements.iter().all(|entry| entry.children.len() == 2) {
+            elements
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{}: {}",
+                        entry.children[0].display(),
+                        entry.children[1].display()
+                    )
+                })
+                .collect()
+        } else {
+            elements
+                .chunks(2)
+                .filter(|pair| pair.len() == 2)
+                .map(|pair| format!("{}: {}", pair[0].display(), pair[1].display()))
+                .collect()
+        };
+        self.collection_display("{", "}", items)
+    }
+}
+
+/// The bare name of a Rust type: `core::option::Option<i32>` gives `Option`,
+/// `&alloc::string::String` gives `String`, and slices and arrays give `[]`.
+fn rust_type_base(type_name: &str) -> &str {
+    let mut name = type_name.trim();
+    for prefix in ["&mut ", "&", "*const ", "*mut "] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            name = rest.trim_start();
+        }
+    }
+    if name.starts_with('[') {
+        return "[]";
+    }
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name).trim()
+}
+
+/// Rewrites a value printed by GDB's Rust pretty-printers the way Rust's `{:?}` would:
+/// `core::option::Option<i32>::Some(5)` becomes `Some(5)`, `Vec(size=3) = {1, 2, 3}`
+/// becomes `len=3 [1, 2, 3]`, and `HashMap(size=1) = {["a"] = 1}` becomes
+/// `len=1 {"a": 1}`.
+pub(crate) fn tidy_gdb_value(value: &str) -> String {
+    let mut text = value.to_string();
+
+    // Drop the enum path in front of std variants, but not of `State::Something`
+    for variant in ["::Some", "::None", "::Ok", "::Err"] {
+        let mut from = 0;
+        while let Some(found) = text[from..].find(variant) {
+            let at = from + found;
+            let end = at + variant.len();
+            if text[end..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+                from = end;
+                continue;
+            }
+            let mut depth = 0;
+            let start = text[..at]
+                .char_indices()
+                .rev()
+                .find(|&(_, c)| {
+                    match c {
+                        '>' => depth += 1,
+                        '<' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0 && !(c.is_alphanumeric() || matches!(c, '_' | ':' | '>' | '<'))
+                })
+                .map_or(0, |(i, c)| i + c.len_utf8());
+            text.replace_range(start..at + 2, "");
+            from = start;
+        }
+    }
+
+    // Collections print as `Name(size=N) = {...}`
+    for (name, open, close) in [
+        ("Vec", "[", "]"),
+        ("VecDeque", "[", "]"),
+        ("HashMap", "{", "}"),
+        ("BTreeMap", "{", "}"),
+        ("HashSet", "{", "}"),
+        ("BTreeSet", "{", "}"),
+    ] {
+        let marker = format!("{}(size=", name);
+        while let Some(at) = text.find(&marker) {
+            let rest = &text[at + marker.len()..];
+            let Some(end) = rest.find(')') else { break };
+            let len = rest[..end].to_string();
+            let mut replaced = format!("len={}", len);
+            let mut consumed = marker.len() + end + 1;
+            if let Some(body) = rest[end + 1..].strip_prefix(" = {") {
+                if let Some(close_at) = matching_brace(body) {
+                    let items = body[..close_at].replace("] = ", ": ").replace(", [", ", ");
+                    let items = items.strip_prefix('[').unwrap_or(&items);
+                    replaced.push_str(&format!(" {}{}{}", open, items, close));
+                    consumed += " = {".len() + close_at + 1;
+                }
+            }
+            text.replace_range(at..at + consumed, &replaced);
+        }
+    }
+    text
+}
+
+/// Index of the `}` closing a block whose `{` was just consumed.
+fn matching_brace(body: &str) -> Option<usize> {
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// What `debug_symbols` searches for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SymbolKind {
+    Function,
+    Type,
+    Variable,
+}
+
+impl SymbolKind {
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "functions" => Ok(SymbolKind::Function),
+            "types" => Ok(SymbolKind::Type),
+            "variables" => Ok(SymbolKind::Variable),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown symbol kind: {} (expected functions, types or variables)",
+                name
+            ))),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::Function => "functions",
+            SymbolKind::Type => "types",
+            SymbolKind::Variable => "variables",
+        }
+    }
+}
+
+/// A function, type, or global variable found by `debug_symbols`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    /// Name as the debugger reports it, e.g. `my_crate::parser::parse`; may be mangled
+    pub name: String,
+    /// Where it is declared as `file:line`, if debug info is available
+    pub location: Option<String>,
+    /// Binary or shared library it lives in
+    pub module: Option<String>,
+    /// Load address, for symbols without debug info
+    pub address: Option<String>,
+}
+
+impl SymbolInfo {
+    /// Parses `image lookup --regex` output.
+    ///
+    /// Functions and symbols are reported as `Summary: app`app::parse at parse.rs:10`,
+    /// types as `id = {..}, name = "app::Config", byte-size = 48, decl = config.rs:3, ...`.
+    /// Repeated matches, e.g. from several inlined copies, are reported once.
+    pub fn parse_lldb(output: &str) -> Vec<SymbolInfo> {
+        let mut symbols: Vec<SymbolInfo> = Vec::new();
+        for line in output.lines().map(str::trim) {
+            let symbol = if let Some(summary) = line.strip_prefix("Summary:") {
+                let (module, rest) = match summary.trim().split_once('`') {
+                    Some((module, rest)) => (Some(module.to_string()), rest),
+                    None => (None, summary.trim()),
+                };
+                let (name, location) = match rest.rsplit_once(" at ") {
+                    Some((name, location)) => (name, Some(location.to_string())),
+                    None => (rest, None),
+                };
+                let name = name
+                    .rsplit_once(" + ")
+                    .filter(|(_, offset)| offset.chars().all(|c| c.is_ascii_digit()))
+                    .map_or(name, |(name, _)| name);
+                SymbolInfo {
+                    name: name.trim().to_string(),
+                    location,
+                    module,
+                    address: None,
+                }
+            } else if let Some((_, rest)) = line.split_once("name = \"") {
+                let Some((name, rest)) = rest.split_once('"') else {
+                    continue;
+                };
+                let location = rest
+                    .split_once("decl = ")
+                    .map(|(_, decl)| decl.split(',').next().unwrap_or(decl).trim().to_string());
+                SymbolInfo {
+                    name: name.to_string(),
+                    location,
+                    module: None,
+                    address: None,
+                }
+            } else {
+                continue;
+            };
+            if !symbol.name.is_empty() && !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        symbols
+    }
+
+    /// Converts the `symbols={debug=[...],nondebugging=[...]}` result of the MI
+    /// `-symbol-info-*` commands.
+    pub fn from_mi(symbols: &Value) -> Vec<SymbolInfo> {
+        let field = |value: &Value, name: &str| {
+            value.get(name).and_then(|v| v.as_str()).map(str::to_string)
+        };
+        let list = |name: &str| {
+            symbols
+                .get(name)
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let mut found = Vec::new();
+        for file in list("debug") {
+            let filename = field(&file, "filename");
+            for symbol in file
+                .get("symbols")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let Some(name) = field(symbol, "name") else {
+                    continue;
+                };
+                let location = match (&filename, field(symbol, "line")) {
+                    (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+                    (Some(file), None) => Some(file.clone()),
+                    _ => None,
+                };
+                found.push(SymbolInfo {
+                    name,
+                    location,
+                    module: None,
+                    address: None,
+                });
+            }
+        }
+        for symbol in list("nondebugging") {
+            if let Some(name) = field(&symbol, "name") {
+                found.push(SymbolInfo {
+                    name,
+                    location: None,
+                    module: None,
+                    address: field(&symbol, "address"),
+                });
+            }
+        }
+        found
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": demangle(&self.name),
+            "raw_name": self.name,
+            "location": self.location,
+            "module": self.module,
+            "address": self.address
+        })
+    }
+}
+
+/// A kind of tokio task as listed by `debug_async_tasks`: a future type the program
+/// spawns, read from the task cell tokio allocates for it
+/// (`tokio::runtime::task::core::Cell<F, S>`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsyncTask {
+    /// Type of the spawned future, e.g. `app::serve::{async_fn_env#0}`
+    pub future: String,
+    /// Function that polls the future: the body of an `async fn` or `async` block,
+    /// or the future's `Future::poll`
+    pub poll_function: String,
+}
+
+impl AsyncTask {
+    /// Reads the future out of the name of a task cell type, e.g.
+    /// `tokio::runtime::task::core::Cell<app::serve::{async_fn_env#0}, alloc::sync::Arc<tokio::runtime::scheduler::multi_thread::handle::Handle, alloc::alloc::Global>>`.
+    pub fn from_cell_type(name: &str) -> Option<Self> {
+        let (_, arguments) = name.split_once("task::core::Cell<")?;
+        // The future is the first generic argument; commas inside it are nested
+        let mut depth = 0;
+        let (end, _) = arguments.char_indices().find(|&(_, c)| {
+            match c {
+                '<' | '{' | '(' | '[' => depth += 1,
+                '>' | '}' | ')' | ']' if depth == 0 => return true,
+                '>' | '}' | ')' | ']' => depth -= 1,
+                ',' if depth == 0 => return true,
+                _ => {}
+            }
+            false
+        })?;
+        let future = arguments[..end].trim().to_string();
+        if future.is_empty() {
+            return None;
+        }
+
+        // The state machine of `async fn f` is `f::{async_fn_env#0}`, polled by `f::{async_fn#0}`
+        let async_body = future.rsplit_once("::{").and_then(|(path, closure)| {
+            let (kind, index) = closure.strip_suffix('}')?.split_once("_env#")?;
+            let is_async = matches!(kind, "async_fn" | "async_block");
+            (is_async && index.chars().all(|c| c.is_ascii_digit()))
+                .then(|| format!("{}::{{{}#{}}}", path, kind, index))
+        });
+        let poll_function = async_body
+            .unwrap_or_else(|| format!("<{} as core::future::future::Future>::poll", future));
+        Some(Self {
+            future,
+            poll_function,
+        })
+    }
+
+    /// Whether a stack frame running `function` is polling this task's future. Symbols
+    /// without debug info name async bodies as closures, e.g. `app::serve::{{closure}}`.
+    pub fn is_polled_by(&self, function: &str) -> bool {
+        if function == self.poll_function {
+            return true;
+        }
+        match self.poll_function.rsplit_once("::{async_") {
+            Some((path, _)) => function == format!("{}::{{{{closure}}}}", path),
+            None => false,
+        }
+    }
+}
+
+/// A binary or shared library loaded into the debugged process, as listed by
+/// `debug_modules`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleInfo {
+    /// Path of the image on disk
+    pub path: String,
+    /// Address the image is loaded at
+    pub load_address: Option<String>,
+    /// Build UUID or ID, which debug files must match
+    pub uuid: Option<String>,
+    /// Separate file the symbols were read from, e.g. a `.dSYM` or `.debug` file
+    pub symbol_file: Option<String>,
+    /// Whether debug info (DWARF) was found for it; `None` when the debugger does not say
+    pub has_debug_info: Option<bool>,
+}
+
+impl ModuleInfo {
+    /// Parses `image list` output: `[  0] <uuid> 0x0000555555554000 /path/app`, each
+    /// image optionally followed by an indented line naming its separate symbol file.
+    pub fn parse_lldb(output: &str) -> Vec<ModuleInfo> {
+        let mut modules: Vec<ModuleInfo> = Vec::new();
+        for line in output.lines() {
+            let trimmed = line.trim();
+            let Some(rest) = trimmed.strip_prefix('[') else {
+                // A symbol file belongs to the image above it
+                if trimmed.starts_with('/') {
+                    if let Some(module) = modules.last_mut() {
+                        module.symbol_file = Some(trimmed.to_string());
+                        module.has_debug_info = Some(true);
+                    }
+                }
+                continue;
+            };
+            let Some((index, rest)) = rest.split_once(']') else {
+                continue;
+            };
+            if index.trim().parse::<u32>().is_err() {
+                continue;
+            }
+
+            let mut uuid = None;
+            let mut load_address = None;
+            let mut rest = rest.trim();
+            while let Some((token, tail)) = rest.split_once(' ') {
+                if token.starts_with("0x") {
+                    load_address = Some(token.to_string());
+                } else if !token.starts_with('/') && uuid.is_none() && load_address.is_none() {
+                    uuid = Some(token.to_string());
+                } else {
+                    break;
+                }
+                rest = tail.trim_start();
+            }
+            modules.push(ModuleInfo {
+                path: rest.trim().to_string(),
+                load_address,
+                uuid,
+                symbol_file: None,
+                has_debug_info: None,
+            });
+        }
+        modules
+    }
+
+    /// Parses GDB's `info sharedlibrary` table, where `(*)` after `Yes` marks a library
+    /// whose symbols were read but which has no debug info:
+    ///
+    /// ```text
+    /// From                To                  Syms Read   Shared Object Library
+    /// 0x00007ffff7fc5090  0x00007ffff7fee315  Yes         /lib64/ld-linux-x86-64.so.2
+    /// 0x00007ffff7d8a700  0x00007ffff7f1c93d  Yes (*)     /lib/x86_64-linux-gnu/libc.so.6
+    /// ```
+    pub fn parse_gdb(output: &str) -> Vec<ModuleInfo> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut tokens = line.split_whitespace();
+                let from = tokens.next().filter(|from| from.starts_with("0x"))?;
+                tokens.next().filter(|to| to.starts_with("0x"))?;
+                let read = tokens.next()?;
+                let mut rest: Vec<&str> = tokens.collect();
+                let no_debug_info = rest.first() == Some(&"(*)");
+                if no_debug_info {
+                    rest.remove(0);
+                }
+                Some(ModuleInfo {
+                    path: rest.join(" "),
+                    load_address: Some(from.to_string()),
+                    uuid: None,
+                    symbol_file: None,
+                    has_debug_info: Some(read == "Yes" && !no_debug_info),
+                })
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "path": self.path,
+            "load_address": self.load_address,
+            "uuid": self.uuid,
+            "symbol_file": self.symbol_file,
+            "has_debug_info": self.has_debug_info
+        })
+    }
+}
+
+/// How `debug_memory_read` interprets raw bytes as typed values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MemoryType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Pointer,
+}
+
+impl MemoryType {
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "u8" => Ok(MemoryType::U8),
+            "i8" => Ok(MemoryType::I8),
+            "u16" => Ok(MemoryType::U16),
+            "i16" => Ok(MemoryType::I16),
+            "u32" => Ok(MemoryType::U32),
+            "i32" => Ok(MemoryType::I32),
+            "u64" | "usize" => Ok(MemoryType::U64),
+            "i64" | "isize" => Ok(MemoryType::I64),
+            "f32" => Ok(MemoryType::F32),
+            "f64" => Ok(MemoryType::F64),
+            "pointer" => Ok(MemoryType::Pointer),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown memory type: {} (expected u8, i8, u16, i16, u32, i32, u64, i64, usize, isize, f32, f64 or pointer)",
+                name
+            ))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            MemoryType::U8 | MemoryType::I8 => 1,
+            MemoryType::U16 | MemoryType::I16 => 2,
+            MemoryType::U32 | MemoryType::I32 | MemoryType::F32 => 4,
+            MemoryType::U64 | MemoryType::I64 | MemoryType::F64 | MemoryType::Pointer => 8,
+        }
+    }
+
+    /// Decodes `bytes` as consecutive little-endian values, ignoring a trailing partial value.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Vec<Value> {
+        bytes
+            .chunks_exact(self.size())
+            .map(|chunk| match self {
+                MemoryType::U8 => json!(chunk[0]),
+                MemoryType::I8 => json!(chunk[0] as i8),
+                MemoryType::U16 => json!(u16::from_le_bytes([chunk[0], chunk[1]])),
+                MemoryType::I16 => json!(i16::from_le_bytes([chunk[0], chunk[1]])),
+                MemoryType::U32 => json!(u32::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::I32 => json!(i32::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::U64 => json!(u64::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::I64 => json!(i64::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::F32 => json!(f32::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::F64 => json!(f64::from_le_bytes(chunk.try_into().unwrap())),
+                MemoryType::Pointer => {
+                    json!(format!(
+                        "{:#x}",
+                        u64::from_le_bytes(chunk.try_into().unwrap())
+                    ))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parses a hex byte string such as `"de ad be ef"`, `"0xdeadbeef"` or `"deadbeef"`.
+pub(crate) fn parse_hex_bytes(text: &str) -> Result<Vec<u8>> {
+    let digits: String = text
+        .split_whitespace()
+        .map(|chunk| chunk.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+    if digits.is_empty()
+        || !digits.len().is_multiple_of(2)
+        || !digits.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err(ErrorKind::InvalidArgument.error(format!(
+            "bytes must be an even number of hex digits, got: {}",
+            text
+        )));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&digits[i..i + 2], 16)?))
+        .collect()
+}
+
+/// Parses an integer value as a debugger prints it: decimal, or hex with `0x`.
+pub(crate) fn parse_integer(value: &str) -> Option<u64> {
+    let value = value.split_whitespace().next()?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Operators that read their operands although they are followed by `(`.
+const NON_CALL_KEYWORDS: [&str; 10] = [
+    "sizeof", "alignof", "offsetof", "typeof", "if", "while", "match", "in", "as", "return",
+];
+
+/// What in `expression` could change the program when evaluated: `a function call`
+/// (`v.len()`, `reset(x)`) or `an assignment` (`x = 1`, `n += 2`, `i++`).
+///
+/// This is a syntactic check for the common cases, not a proof that the expression is
+/// pure; text inside string literals is ignored.
+pub(crate) fn side_effect(expression: &str) -> Option<&'static str> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut in_string = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            match c {
+                '\\' => i += 1,
+                '"' => in_string = false,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+        let previous = chars[..i]
+            .iter()
+            .rev()
+            .find(|c| !c.is_whitespace())
+            .copied();
+        let next = chars.get(i + 1).copied();
+        match c {
+            '"' => in_string = true,
+            '(' => {
+                let word: String = chars[..i]
+                    .iter()
+                    .rev()
+                    .skip_while(|c| c.is_whitespace())
+                    .take_while(|c| c.is_alphanumeric() || **c == '_')
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()
+                    .collect();
+                let called = !word.is_empty()
+                    && !word.starts_with(|c: char| c.is_ascii_digit())
+                    && !NON_CALL_KEYWORDS.contains(&word.as_str());
+                // `parse::<u32>(s)`
+                let turbofish =
+                    previous == Some('>') && chars[..i].windows(3).any(|w| w == [':', ':', '<']);
+                if called || turbofish {
+                    return Some("a function call");
+                }
+            }
+            '=' => {
+                let before = if i > 0 { Some(chars[i - 1]) } else { None };
+                let shift = i > 1 && matches!(chars[i - 2..i], ['<', '<'] | ['>', '>']);
+                let comparison = matches!(before, Some('=' | '!' | '<' | '>')) && !shift;
+                if !comparison && !matches!(next, Some('=' | '>')) {
+                    return Some("an assignment");
+                }
+            }
+            '+' | '-' if next == Some(c) => return Some("an assignment"),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The regular expression matching every function in the module at `pattern`, e.g.
+/// `my_crate::db`: its functions, those of its submodules, and methods of its types,
+/// trait impls (`<my_crate::db::Pool as Drop>::drop`) included. A `pattern` that is not
+/// a plain path is already a regular expression and is returned unchanged.
+///
+/// `basic` gives the expression in POSIX basic syntax, which GDB's `rbreak` uses.
+pub(crate) fn module_regex(pattern: &str, basic: bool) -> String {
+    let path = pattern.trim().trim_end_matches("::");
+    let plain = !path.is_empty()
+        && path
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == ':');
+    if !plain {
+        return pattern.to_string();
+    }
+    if basic {
+        format!("\\(^\\|<\\){}::", path)
+    } else {
+        format!("(^|<){}::", path)
+    }
+}
+
+/// The lines of the function declared at line `start` of `source` that construct an
+/// error with `Err(..)`, and the function's last line, or `None` if no body follows.
+///
+/// The body is found by matching braces; string and character literals and comments
+/// are skipped, so an `Err(` or brace inside them does not count.
+pub(crate) fn error_lines(source: &str, start: u32) -> Option<(u32, Vec<u32>)> {
+    let mut depth = 0usize;
+    let mut opened = false;
+    let mut in_comment = false;
+    let mut lines = Vec::new();
+    for (number, line) in (1..)
+        .zip(source.lines())
+        .skip(start.saturating_sub(1) as usize)
+    {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if in_comment {
+                if chars[i..].starts_with(&['*', '/']) {
+                    in_comment = false;
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            match chars[i] {
+                '/' if chars.get(i + 1) == Some(&'/') => break,
+                '/' if chars.get(i + 1) == Some(&'*') => {
+                    in_comment = true;
+                    i += 1;
+                }
+                '"' => {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        i += if chars[i] == '\\' { 2 } else { 1 };
+                    }
+                }
+                // A character literal, not a lifetime such as `'a`
+                '\'' if chars.get(i + 1) == Some(&'\\') => {
+                    i += 2;
+                    while i < chars.len() && chars[i] != '\'' {
+                        i += 1;
+                    }
+                }
+                '\'' if chars.get(i + 2) == Some(&'\'') => i += 2,
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => {
+                    depth = depth.saturating_sub(1);
+                    if opened && depth == 0 {
+                        return Some((number, lines));
+                    }
+                }
+                'E' if opened
+                    && chars[i..].starts_with(&['E', 'r', 'r', '('])
+                    && !(i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_'))
+                    && lines.last() != Some(&number) =>
+                {
+                    lines.push(number);
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Parses `memory read --size 1 --format x` output into its start address and bytes.
+///
+/// Each line looks like `0x7ffeefbff5c0: 0x48 0x65 0x6c 0x6c 0x6f`.
+pub(crate) fn parse_lldb_memory(output: &str) -> (Option<u64>, Vec<u8>) {
+    let mut start = None;
+    let mut bytes = Vec::new();
+    for line in output.lines() {
+        let Some((address, rest)) = line.trim().split_once(": ") else {
+            continue;
+        };
+        let Some(address) = address
+            .strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        else {
+            continue;
+        };
+        start.get_or_insert(address);
+        bytes.extend(
+            rest.split_whitespace()
+                .map_while(|token| token.strip_prefix("0x"))
+                .map_while(|hex| u8::from_str_radix(hex, 16).ok()),
+        );
+    }
+    (start, bytes)
+}
+
+/// Renders bytes as `hexdump -C` style rows of 16 bytes each.
+pub(crate) fn format_hex_dump(start: u64, bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            format!(
+                "{:#018x}: {:<47}  |{}|",
+                start + row as u64 * 16,
+                hex.join(" "),
+                printable_ascii(chunk)
+            )
+        })
+        .collect()
+}
+
+/// Renders bytes as ASCII, replacing non-printable bytes with `.`.
+pub(crate) fn printable_ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// One machine instruction of a disassembly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// Address, e.g. `0x555555559ff4`
+    pub address: String,
+    /// Offset from the start of the function, e.g. 20 for `<+20>`
+    pub offset: Option<u64>,
+    /// Mnemonic and operands
+    pub text: String,
+    /// Whether the program counter is at this instruction
+    pub current: bool,
+}
+
+impl Instruction {
+    /// Parses a line of LLDB's `disassemble`, e.g.
+    /// `->  0x555555559ff4 <+20>: movl   $0x0, (%rax)`, where `->` marks the pc.
+    pub fn parse_lldb(line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        let (current, rest) = match trimmed.strip_prefix("->") {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, trimmed),
+        };
+        let (address, rest) = rest.split_once(char::is_whitespace)?;
+        if !address.starts_with("0x") {
+            return None;
+        }
+        let rest = rest.trim_start();
+        let (offset, text) = match rest
+            .strip_prefix("<+")
+            .and_then(|rest| rest.split_once(">:"))
+        {
+            Some((offset, text)) => (offset.parse().ok(), text),
+            None => (None, rest.trim_start_matches(':')),
+        };
+        Some(Self {
+            address: address.trim_end_matches(':').to_string(),
+            offset,
+            text: text.trim().to_string(),
+            current,
+        })
+    }
+
+    /// Reads an entry of GDB's `-data-disassemble` list, e.g.
+    /// `{address="0x555555559ff4",func-name="app::main",offset="20",inst="movl $0x0,(%rax)"}`;
+    /// the instruction at `pc` is the current one.
+    pub fn from_mi(insn: &Value, pc: Option<u64>) -> Option<Self> {
+        let field = |name: &str| insn.get(name).and_then(|v| v.as_str());
+        let address = field("address")?;
+        Some(Self {
+            address: address.to_string(),
+            offset: field("offset").and_then(|offset| offset.parse().ok()),
+            text: field("inst")?.to_string(),
+            current: pc.is_some() && parse_integer(address) == pc,
+        })
+    }
+
+    /// The instructions at most `radius` before or after the current one, or the first
+    /// ones when none is current.
+    pub fn around_pc(instructions: Vec<Self>, radius: usize) -> Vec<Self> {
+        let pc = instructions
+            .iter()
+            .position(|instruction| instruction.current)
+            .unwrap_or(0);
+        let start = pc.saturating_sub(radius);
+        instructions
+            .into_iter()
+            .skip(start)
+            .take(pc - start + radius + 1)
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "address": self.address,
+            "offset": self.offset,
+            "instruction": self.text,
+            "current": self.current
+        })
+    }
+}
+
+/// Reads register values from LLDB's `register read` (`rax = 0x000000000000001c`) or
+/// GDB's `info registers` (`rax  0x1c  28`), as names and hex values.
+pub(crate) fn parse_registers(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (name, value) = match line.split_once(" = ") {
+                Some((name, value)) => (name.trim(), value.split_whitespace().next()?),
+                None => {
+                    let mut parts = line.split_whitespace();
+                    (parts.next()?, parts.next()?)
+                }
+            };
+            let is_name =
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            (is_name && parse_integer(value).is_some() && value.starts_with("0x"))
+                .then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// How the program terminated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExitStatus {
+    /// Exit code, absent when the program was killed by a signal
+    pub code: Option<i32>,
+    /// Signal that terminated the program
+    pub signal: Option<String>,
+}
+
+impl ExitStatus {
+    /// Parses `Process 1234 exited with status = 1 (0x00000001)`, which LLDB may
+    /// follow with a description such as `Terminated due to signal 9`.
+    pub fn parse_lldb(response: &str) -> Option<Self> {
+        let (_, rest) = response
+            .lines()
+            .find_map(|line| line.split_once(" exited with status = "))?;
+        let code = rest
+            .split_whitespace()
+            .next()
+            .and_then(|code| code.parse().ok());
+        let signal = rest
+            .split_once("signal ")
+            .and_then(|(_, signal)| signal.split_whitespace().next())
+            .map(|signal| signal.trim_end_matches(['.', ')']).to_string());
+        Some(Self { code, signal })
+    }
+
+    /// Reads the exit details of a `*stopped` record; MI reports exit codes in octal.
+    pub fn from_mi(results: &Value) -> Option<Self> {
+        let field = |name: &str| results.get(name).and_then(|v| v.as_str());
+        match field("reason")? {
+            "exited-normally" => Some(Self {
+                code: Some(0),
+                signal: None,
+            }),
+            "exited" => Some(Self {
+                code: field("exit-code").and_then(|code| i32::from_str_radix(code, 8).ok()),
+                signal: None,
+            }),
+            "exited-signalled" => Some(Self {
+                code: None,
+                signal: field("signal-name").map(str::to_string),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A signal or machine exception that stopped the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopSignal {
+    /// Signal name (e.g. `SIGSEGV`) or Mach exception (e.g. `EXC_BAD_ACCESS`)
+    pub name: String,
+    /// The debugger's explanation, e.g. `Segmentation fault`
+    pub description: Option<String>,
+    /// Faulting address for memory access violations
+    pub fault_address: Option<String>,
+}
+
+impl StopSignal {
+    /// Parses the stop reason of LLDB stop output.
+    ///
+    /// Handles `stop reason = signal SIGSEGV: address not mapped to object (fault address: 0x0)`
+    /// on Linux and `stop reason = EXC_BAD_ACCESS (code=1, address=0x0)` on macOS.
+    pub fn parse_lldb(response: &str) -> Option<Self> {
+        let reason = response
+            .lines()
+            .find_map(|line| line.split_once("stop reason = "))
+            .map(|(_, reason)| reason.trim())?;
+
+        let (name, description) = if let Some(rest) = reason.strip_prefix("signal ") {
+            match rest.split_once(':') {
+                Some((name, description)) => (name, Some(description.trim())),
+                None => (rest, None),
+            }
+        } else if reason.starts_with("EXC_") {
+            let end = reason.find([' ', '(']).unwrap_or(reason.len());
+            (&reason[..end], Some(reason))
+        } else {
+            return None;
+        };
+
+        let fault_address = ["fault address: ", "address="].iter().find_map(|marker| {
+            let (_, rest) = reason.split_once(marker)?;
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            Some(rest[..end].to_string()).filter(|a| a.starts_with("0x"))
+        });
+
+        Some(Self {
+            name: name.trim().to_string(),
+            description: description.map(str::to_string),
+            fault_address,
+        })
+    }
+
+    /// Reads a `*stopped,reason="signal-received"` record.
+    pub fn from_mi(results: &Value) -> Option<Self> {
+        let field = |name: &str| results.get(name).and_then(|v| v.as_str());
+        if field("reason") != Some("signal-received") {
+            return None;
+        }
+        Some(Self {
+            name: field("signal-name")?.to_string(),
+            description: field("signal-meaning").map(str::to_string),
+            fault_address: None,
+        })
+    }
+
+    /// Reads GDB's `Program terminated with signal SIGSEGV, Segmentation fault.` banner,
+    /// printed when it loads a core file.
+    pub fn parse_gdb_core(output: &str) -> Option<Self> {
+        let (_, rest) = output.split_once("Program terminated with signal ")?;
+        let line = rest.lines().next()?.trim().trim_end_matches('.');
+        let (name, description) = match line.split_once(", ") {
+            Some((name, description)) => (name, Some(description.to_string())),
+            None => (line, None),
+        };
+        Some(Self {
+            name: name.to_string(),
+            description,
+            fault_address: None,
+        })
+    }
+
+    /// Signals raised by a faulting memory access, which carry a fault address.
+    pub fn is_fault(&self) -> bool {
+        matches!(self.name.as_str(), "SIGSEGV" | "SIGBUS" | "EXC_BAD_ACCESS")
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "fault_address": self.fault_address
+        })
+    }
+}
+
+/// A watchpoint that stopped the program, with the watched value before and after.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchpointHit {
+    /// The debugger's watchpoint number
+    pub id: u32,
+    /// Value before the write, if the debugger reported it
+    pub old_value: Option<String>,
+    /// Value after the write, or the value read for read watchpoints
+    pub new_value: Option<String>,
+}
+
+impl WatchpointHit {
+    /// Reads a `*stopped,reason="watchpoint-trigger"` record, or one of its
+    /// `read-watchpoint-trigger` and `access-watchpoint-trigger` variants.
+    pub fn from_mi(results: &Value) -> Option<Self> {
+        if !results
+            .get("reason")?
+            .as_str()?
+            .ends_with("watchpoint-trigger")
+        {
+            return None;
+        }
+        let id = ["wpt", "hw-rwpt", "hw-awpt"]
+            .iter()
+            .find_map(|field| results.get(field))?
+            .get("number")?
+            .as_str()?
+            .parse()
+            .ok()?;
+        let value = |name: &str| {
+            results
+                .get("value")
+                .and_then(|value| value.get(name))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        Some(Self {
+            id,
+            old_value: value("old"),
+            new_value: value("new").or_else(|| value("value")),
+        })
+    }
+
+    /// Reads the values of watchpoint `id` from LLDB's `watchpoint list <id>`, whose
+    /// `old value:` and `new value:` lines hold the last change it saw.
+    pub fn parse_lldb(id: u32, listing: &str) -> Self {
+        let value = |label: &str| {
+            listing.lines().find_map(|line| {
+                let value = line.trim().strip_prefix(label)?.trim();
+                Some(value.to_string()).filter(|value| !value.is_empty())
+            })
+        };
+        Self {
+            id,
+            old_value: value("old value:"),
+            new_value: value("new value:"),
+        }
+    }
+}
+
+/// Parses a line of cargo's progress bar, `Building [===>   ] 3/10: serde, tokio`,
+/// into the units built, the total, and the units being compiled.
+pub(crate) fn cargo_build_progress(line: &str) -> Option<(u64, u64, &str)> {
+    let (_, rest) = line.trim().strip_prefix("Building [")?.split_once("] ")?;
+    let (counts, units) = rest.split_once(": ").unwrap_or((rest, ""));
+    let (done, total) = counts.split_once('/')?;
+    Some((done.parse().ok()?, total.parse().ok()?, units.trim()))
+}
+
+/// An executable cargo built, read from a `compiler-artifact` message of
+/// `--message-format=json`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CargoArtifact {
+    /// Path of the built executable
+    pub(crate) executable: String,
+    /// Name of the target it was built from, e.g. the bin or integration test name
+    pub(crate) target_name: String,
+    /// Kinds of that target, e.g. `bin`, `lib`, `test`, or `example`
+    pub(crate) kinds: Vec<String>,
+    /// Whether it is a test harness, as built by `cargo test`
+    pub(crate) test: bool,
+    /// `Cargo.toml` of the package the target belongs to
+    pub(crate) manifest_path: String,
+    /// Optimization level it was built with, e.g. `0`, `3`, or `s`
+    pub(crate) opt_level: String,
+    /// Whether it was built with any debug info
+    pub(crate) debuginfo: bool,
+}
+
+impl CargoArtifact {
+    /// Reads one line of cargo's JSON messages. Other messages, and artifacts without
+    /// an executable such as libraries, give `None`.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let message: Value = serde_json::from_str(line).ok()?;
+        if message["reason"] != "compiler-artifact" {
+            return None;
+        }
+        let target = &message["target"];
+        Some(Self {
+            executable: message["executable"].as_str()?.to_string(),
+            target_name: target["name"].as_str()?.to_string(),
+            kinds: target["kind"]
+                .as_array()
+                .map(|kinds| {
+                    kinds
+                        .iter()
+                        .filter_map(|kind| kind.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            test: message["profile"]["test"].as_bool().unwrap_or(false),
+            manifest_path: message["manifest_path"].as_str().unwrap_or("").to_string(),
+            opt_level: message["profile"]["opt_level"]
+                .as_str()
+                .unwrap_or("0")
+                .to_string(),
+            // A level such as 2, or a name such as "line-tables-only"
+            debuginfo: match &message["profile"]["debuginfo"] {
+                Value::Number(level) => level.as_u64() != Some(0),
+                Value::String(level) => level != "none",
+                _ => false,
+            },
+        })
+    }
+}
+
+/// Whether the executable `binary` holds was built with libtest's harness, so that
+/// `--list` lists its tests: its symbols include `test::test_main_static`, which the
+/// harness's `main` calls. A `harness = false` target ignores `--list` and runs its own
+/// `main` instead.
+pub(crate) fn libtest_harness(binary: &[u8]) -> bool {
+    // The mangled path, split so this executable does not contain it
+    const CRATE: &[u8] = b"4test";
+    const FUNCTION: &[u8] = b"16test_main_static";
+    binary
+        .windows(FUNCTION.len())
+        .enumerate()
+        .any(|(i, window)| window == FUNCTION && binary[..i].ends_with(CRATE))
+}
+
+/// An error or warning from the compiler, read from a `compiler-message` message of
+/// cargo's `--message-format=json`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CompilerDiagnostic {
+    /// `error` or `warning`
+    pub(crate) level: String,
+    /// The headline, e.g. `mismatched types`
+    pub(crate) message: String,
+    /// Source file of the primary span, relative to the workspace root
+    pub(crate) file: Option<String>,
+    /// Line of the primary span
+    pub(crate) line: Option<u64>,
+    /// Column of the primary span
+    pub(crate) column: Option<u64>,
+    /// Error code such as `E0308`, if it has one
+    pub(crate) code: Option<String>,
+    /// The diagnostic as rustc prints it, with source snippets
+    pub(crate) rendered: Option<String>,
+}
+
+impl CompilerDiagnostic {
+    /// Reads one line of cargo's JSON messages. Other messages, and notes or help
+    /// that are not errors or warnings, give `None`.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let message: Value = serde_json::from_str(line).ok()?;
+        if message["reason"] != "compiler-message" {
+            return None;
+        }
+        let diagnostic = &message["message"];
+        let level = diagnostic["level"].as_str()?;
+        if !level.starts_with("error") && level != "warning" {
+            return None;
+        }
+        let span = diagnostic["spans"].as_array().and_then(|spans| {
+            spans
+                .iter()
+                .find(|span| span["is_primary"].as_bool() == Some(true))
+        });
+        Some(Self {
+            level: level.to_string(),
+            message: diagnostic["message"].as_str()?.to_string(),
+            file: span
+                .and_then(|span| span["file_name"].as_str())
+                .map(str::to_string),
+            line: span.and_then(|span| span["line_start"].as_u64()),
+            column: span.and_then(|span| span["column_start"].as_u64()),
+            code: diagnostic["code"]["code"].as_str().map(str::to_string),
+            rendered: diagnostic["rendered"].as_str().map(str::to_string),
+        })
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "level": self.level,
+            "message": self.message,
+            "file": self.file,
+            "line": self.line,
+            "column": self.column,
+            "code": self.code
+        })
+    }
+}
+
+/// Allocations made from one call stack and never freed, as added up from the log of
+/// the heap tracker `debug_heap_profile` preloads.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AllocationSite {
+    /// Return addresses of the allocating call, innermost first
+    pub(crate) stack: Vec<u64>,
+    /// Live allocations made from it
+    pub(crate) count: u64,
+    /// Bytes those allocations hold
+    pub(crate) bytes: u64,
+}
+
+/// What the heap tracker's log says about one process.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct HeapProfile {
+    /// Where the memory that is still allocated came from, most bytes first
+    pub(crate) sites: Vec<AllocationSite>,
+    /// Allocations made in total
+    pub(crate) allocations: u64,
+    /// Allocations freed
+    pub(crate) frees: u64,
+}
+
+impl HeapProfile {
+    /// Adds up the tracker's `log` for process `pid`: lines of
+    /// `+ <pid> <address> <size> <return addresses...>` and `- <pid> <address>`, in hex.
+    pub(crate) fn parse(log: &str, pid: Option<u32>) -> Self {
+        let mut profile = Self::default();
+        let mut stacks: HashMap<Vec<u64>, usize> = HashMap::new();
+        // Live allocations: size and stack by address
+        let mut live: HashMap<u64, (u64, usize)> = HashMap::new();
+        for line in log.lines() {
+            let mut fields = line.split(' ');
+            let event = fields.next();
+            let mut numbers = fields.map(|field| u64::from_str_radix(field, 16).ok());
+            let (Some(Some(event_pid)), Some(Some(address))) = (numbers.next(), numbers.next())
+            else {
+                continue;
+            };
+            if pid.is_some_and(|pid| u64::from(pid) != event_pid) {
+                continue;
+            }
+            match event {
+                Some("+") => {
+                    let Some(Some(size)) = numbers.next() else {
+                        continue;
+                    };
+                    let stack: Vec<u64> = numbers.map_while(|frame| frame).collect();
+                    let next = stacks.len();
+                    let stack = *stacks.entry(stack).or_insert(next);
+                    live.insert(address, (size, stack));
+                    profile.allocations += 1;
+                }
+                Some("-") if live.remove(&address).is_some() => profile.frees += 1,
+                _ => {}
+            }
+        }
+
+        let mut sites: Vec<AllocationSite> = Vec::new();
+        let mut by_stack: HashMap<usize, usize> = HashMap::new();
+        let mut stacks: Vec<(Vec<u64>, usize)> = stacks.into_iter().collect();
+        stacks.sort_by_key(|(_, index)| *index);
+        for (size, stack) in live.into_values() {
+            let site = *by_stack.entry(stack).or_insert_with(|| {
+                sites.push(AllocationSite {
+                    stack: stacks[stack].0.clone(),
+                    count: 0,
+                    bytes: 0,
+                });
+                sites.len() - 1
+            });
+            sites[site].count += 1;
+            sites[site].bytes += size;
+        }
+        sites.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.count.cmp(&a.count)));
+        profile.sites = sites;
+        profile
+    }
+}
+
+/// Where a sampled program spent its time: how often each call stack was on the CPU.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CpuProfile {
+    /// Call stacks, outermost function first, with how many samples caught each one,
+    /// most first
+    pub(crate) stacks: Vec<(Vec<String>, u64)>,
+}
+
+/// How much of a profile one function accounts for.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HotFunction {
+    /// The function, demangled
+    pub(crate) function: String,
+    /// Samples where it was running itself
+    pub(crate) self_samples: u64,
+    /// Samples where it was on the stack, itself or through its callees
+    pub(crate) total_samples: u64,
+}
+
+impl CpuProfile {
+    /// Parses the output of `perf script` for a `perf record -g` recording: one block per
+    /// sample, a header line followed by its frames, innermost first and indented by a
+    /// tab, e.g. `\t    55d4a1b2c3d4 app::hot+0x14 (/work/target/debug/app)`.
+    pub(crate) fn parse_perf_script(output: &str) -> Self {
+        let mut stacks: Vec<Vec<String>> = Vec::new();
+        let mut current: Option<Vec<String>> = None;
+        for line in output.lines() {
+            if line.trim().is_empty() {
+                stacks.extend(current.take());
+            } else if line.starts_with('\t') {
+                let Some(current) = current.as_mut() else {
+                    continue;
+                };
+                let mut fields = line.split_whitespace();
+                let address = fields.next().unwrap_or("");
+                let symbol = fields.next().unwrap_or("[unknown]");
+                let symbol = match symbol.rsplit_once("+0x") {
+                    Some((name, _)) => name,
+                    None => symbol,
+                };
+                current.push(if symbol == "[unknown]" {
+                    format!("0x{}", address)
+                } else {
+                    demangle(symbol)
+                });
+            } else {
+                stacks.extend(current.replace(Vec::new()));
+            }
+        }
+        stacks.extend(current);
+        Self::from_samples(stacks.into_iter().map(|mut stack| {
+            stack.reverse();
+            (stack, 1)
+        }))
+    }
+
+    /// Parses the call graph macOS's `sample` prints: a tree with the count of samples
+    /// under each frame, nested by indentation, e.g.
+    /// `    +   2457 main  (in app) + 22  [0x100003f6a]  main.rs:5`.
+    pub(crate) fn parse_sample(output: &str) -> Self {
+        // Frames from the root to the current line: indentation, name, samples, and
+        // samples in the frames called from it
+        let mut path: Vec<(usize, String, u64, u64)> = Vec::new();
+        let mut samples: Vec<(Vec<String>, u64)> = Vec::new();
+        let mut finish = |path: &mut Vec<(usize, String, u64, u64)>| {
+            let (_, _, count, in_callees) = path.last().cloned().unwrap_or_default();
+            if count > in_callees {
+                // The thread line at the root names no function
+                let stack = path.iter().skip(1).map(|(_, name, ..)| name.clone());
+                samples.push((stack.collect(), count - in_callees));
+            }
+            path.pop();
+        };
+        let graph = output
+            .lines()
+            .skip_while(|line| !line.starts_with("Call graph:"))
+            .skip(1)
+            .take_while(|line| !line.trim().is_empty());
+        for line in graph {
+            let Some(indent) = line.find(|c: char| c.is_ascii_digit()) else {
+                continue;
+            };
+            let mut fields = line[indent..].splitn(2, ' ');
+            let Some(count) = fields.next().and_then(|count| count.parse::<u64>().ok()) else {
+                continue;
+            };
+            let rest = fields.next().unwrap_or("").trim();
+            let name = rest.split("  (in ").next().unwrap_or(rest).trim();
+            while path.last().is_some_and(|(depth, ..)| *depth >= indent) {
+                finish(&mut path);
+            }
+            if let Some(parent) = path.last_mut() {
+                parent.3 += count;
+            }
+            path.push((indent, demangle(name), count, 0));
+        }
+        while !path.is_empty() {
+            finish(&mut path);
+        }
+        Self::from_samples(samples.into_iter().filter(|(stack, _)| !stack.is_empty()))
+    }
+
+    /// Adds up `samples` of identical stacks.
+    fn from_samples(samples: impl IntoIterator<Item = (Vec<String>, u64)>) -> Self {
+        let mut counts: HashMap<Vec<String>, u64> = HashMap::new();
+        for (stack, count) in samples {
+            *counts.entry(stack).or_default() += count;
+        }
+        let mut stacks: Vec<(Vec<String>, u64)> = counts.into_iter().collect();
+        stacks.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self { stacks }
+    }
+
+    /// Samples taken in total.
+    pub(crate) fn samples(&self) -> u64 {
+        self.stacks.iter().map(|(_, count)| count).sum()
+    }
+
+    /// The stacks in the folded format flame graph tools read: `main;app::run;app::hot 42`.
+    pub(crate) fn folded(&self) -> Vec<String> {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}", stack.join(";"), count))
+            .collect()
+    }
+
+    /// Functions by the samples spent in their own code, most first.
+    pub(crate) fn hottest(&self) -> Vec<HotFunction> {
+        let mut functions: HashMap<&str, HotFunction> = HashMap::new();
+        for (stack, count) in &self.stacks {
+            let mut seen = Vec::new();
+            for (depth, function) in stack.iter().enumerate() {
+                let hot = functions.entry(function).or_insert_with(|| HotFunction {
+                    function: function.clone(),
+                    self_samples: 0,
+                    total_samples: 0,
+                });
+                if depth + 1 == stack.len() {
+                    hot.self_samples += count;
+                }
+                // Recursion puts a function on the stack more than once
+                if !seen.contains(&function) {
+                    hot.total_samples += count;
+                    seen.push(function);
+                }
+            }
+        }
+        let mut functions: Vec<HotFunction> = functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            (b.self_samples, b.total_samples)
+                .cmp(&(a.self_samples, a.total_samples))
+                .then_with(|| a.function.cmp(&b.function))
+        });
+        functions
+    }
+}
+
+/// Syscalls taking a path, with the index of the path among their arguments.
+pub(crate) const SYSCALL_PATH_ARGUMENTS: &[(&str, usize)] = &[
+    ("open", 0),
+    ("openat", 1),
+    ("creat", 0),
+    ("stat", 0),
+    ("lstat", 0),
+    ("newfstatat", 1),
+    ("statx", 1),
+    ("access", 0),
+    ("faccessat", 1),
+    ("faccessat2", 1),
+    ("execve", 0),
+    ("chdir", 0),
+    ("mkdir", 0),
+    ("mkdirat", 1),
+    ("rmdir", 0),
+    ("unlink", 0),
+    ("unlinkat", 1),
+    ("rename", 0),
+    ("renameat", 1),
+    ("renameat2", 1),
+    ("readlink", 0),
+    ("readlinkat", 1),
+    ("truncate", 0),
+];
+
+/// The name of `errno` value `errno`, e.g. `ENOENT`, for the common ones.
+pub(crate) fn errno_name(errno: i64) -> Option<&'static str> {
+    // The first 34 are the same on Linux and macOS, apart from 11
+    const COMMON: [&str; 34] = [
+        "EPERM", "ENOENT", "ESRCH", "EINTR", "EIO", "ENXIO", "E2BIG", "ENOEXEC", "EBADF", "ECHILD",
+        "", "ENOMEM", "EACCES", "EFAULT", "ENOTBLK", "EBUSY", "EEXIST", "EXDEV", "ENODEV",
+        "ENOTDIR", "EISDIR", "EINVAL", "ENFILE", "EMFILE", "ENOTTY", "ETXTBSY", "EFBIG", "ENOSPC",
+        "ESPIPE", "EROFS", "EMLINK", "EPIPE", "EDOM", "ERANGE",
+    ];
+    let platform: &[(i64, &str)] = if cfg!(target_os = "macos") {
+        &[
+            (11, "EDEADLK"),
+            (35, "EAGAIN"),
+            (36, "EINPROGRESS"),
+            (48, "EADDRINUSE"),
+            (54, "ECONNRESET"),
+            (57, "ENOTCONN"),
+            (60, "ETIMEDOUT"),
+            (61, "ECONNREFUSED"),
+            (78, "ENOSYS"),
+        ]
+    } else {
+        &[
+            (11, "EAGAIN"),
+            (38, "ENOSYS"),
+            (98, "EADDRINUSE"),
+            (104, "ECONNRESET"),
+            (107, "ENOTCONN"),
+            (110, "ETIMEDOUT"),
+            (111, "ECONNREFUSED"),
+            (115, "EINPROGRESS"),
+        ]
+    };
+    platform
+        .iter()
+        .find(|(number, _)| *number == errno)
+        .map(|(_, name)| *name)
+        .or_else(|| {
+            let index = usize::try_from(errno.checked_sub(1)?).ok()?;
+            COMMON.get(index).copied().filter(|name| !name.is_empty())
+        })
+}
+
+/// A system call the program made, as traced by `debug_syscalls`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Syscall {
+    /// The syscall's name, e.g. `openat`
+    pub(crate) name: String,
+    /// Its arguments as the tracer shows them
+    pub(crate) args: Vec<String>,
+    /// The path it was given, for syscalls such as `openat` that take one
+    pub(crate) path: Option<String>,
+    /// What it returned, or `None` if it had not returned when tracing ended; -1 on
+    /// failure
+    pub(crate) result: Option<i64>,
+    /// Why it failed
+    pub(crate) errno: Option<i64>,
+    /// Thread that made it
+    pub(crate) thread: Option<u32>,
+}
+
+impl Syscall {
+    /// Sets the result from a raw return value, where -4095 to -1 is a negated `errno`
+    /// as the Linux kernel returns it.
+    pub(crate) fn set_return_value(&mut self, value: i64) {
+        if (-4095..0).contains(&value) {
+            self.result = Some(-1);
+            self.errno = Some(-value);
+        } else {
+            self.result = Some(value);
+        }
+    }
+
+    /// Parses the output of macOS's `dtruss`: one line per call, such as
+    /// `open("/etc/hosts\0", 0x0, 0x1B6) = 3 0`, where the last number is `errno`.
+    pub(crate) fn parse_dtruss(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (call, returned) = line.rsplit_once(" = ")?;
+                let (name, args) = call.trim().split_once('(')?;
+                let name = name.split_whitespace().last()?;
+                let args = args.trim_end().strip_suffix(')')?;
+                if name == "SYSCALL" || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return None;
+                }
+                let mut args = split_arguments(args);
+                for arg in &mut args {
+                    if let Some(text) = arg.strip_suffix("\\0\"") {
+                        *arg = format!("{}\"", text);
+                    }
+                }
+                let mut returned = returned.split_whitespace();
+                let result = returned.next().and_then(|value| value.parse().ok());
+                let errno = returned
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&errno| errno != 0);
+                let path = SYSCALL_PATH_ARGUMENTS
+                    .iter()
+                    .find(|(syscall, _)| *syscall == name)
+                    .and_then(|(_, index)| args.get(*index))
+                    .and_then(|arg| arg.strip_prefix('"')?.strip_suffix('"'))
+                    .map(str::to_string);
+                Some(Self {
+                    name: name.to_string(),
+                    args,
+                    path,
+                    result,
+                    errno,
+                    thread: None,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "args": self.args,
+            "path": self.path,
+            "result": self.result,
+            "errno": self.errno,
+            "error": self.errno.and_then(errno_name),
+            "thread": self.thread
+        })
+    }
+}
+
+/// Splits a comma-separated argument list, leaving commas inside quotes alone.
+fn split_arguments(args: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in args.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                result.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        result.push(current.trim().to_string());
+    }
+    result
+}
+
+/// A place in the source, as rustc prints it after `-->`: `src/main.rs:4:22`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SourceSpan {
+    /// Source file, relative to the workspace root
+    pub(crate) file: String,
+    /// Line, starting at 1
+    pub(crate) line: u64,
+    /// Column, starting at 1
+    pub(crate) column: u64,
+}
+
+impl SourceSpan {
+    /// Parses `file:line:column`, ignoring an end position such as `: 4:31` after it.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let text = text.split(": ").next()?.trim();
+        let mut parts = text.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok()?;
+        let line = parts.next()?.parse().ok()?;
+        let file = parts.next().filter(|file| !file.is_empty())?;
+        Some(Self {
+            file: file.to_string(),
+            line,
+            column,
+        })
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({"file": self.file, "line": self.line, "column": self.column})
+    }
+}
+
+/// Headlines of Miri's errors, which it prints as `error: <kind>: <message>`.
+const MIRI_ERROR_KINDS: [&str; 7] = [
+    "Undefined Behavior",
+    "unsupported operation",
+    "memory leaked",
+    "deadlock",
+    "abnormal termination",
+    "resource exhaustion",
+    "post-monomorphization error",
+];
+
+/// An error reported by `cargo miri`, read from the diagnostics it prints to stderr.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MiriDiagnostic {
+    /// What Miri found, e.g. `Undefined Behavior` or `memory leaked`; `None` for
+    /// compiler errors
+    pub(crate) kind: Option<String>,
+    /// The headline without the kind
+    pub(crate) message: String,
+    /// Error code such as `E0308`, if it has one
+    pub(crate) code: Option<String>,
+    /// Where the error occurred
+    pub(crate) span: Option<SourceSpan>,
+    /// The `help` lines, e.g. which rules the program broke
+    pub(crate) help: Vec<String>,
+    /// The `note` lines other than the backtrace
+    pub(crate) notes: Vec<String>,
+    /// Other places the error refers to, such as where the memory was allocated
+    pub(crate) related: Vec<(String, SourceSpan)>,
+    /// Functions the program was inside, innermost first
+    pub(crate) backtrace: Vec<(String, Option<SourceSpan>)>,
+}
+
+impl MiriDiagnostic {
+    /// Reads every error in `stderr`. Warnings are skipped, as are cargo's and rustc's
+    /// closing lines such as `error: aborting due to 1 previous error`.
+    pub(crate) fn parse_all(stderr: &str) -> Vec<Self> {
+        let mut diagnostics: Vec<Self> = Vec::new();
+        let mut open = false;
+        // A `help:` or `note:` whose span may follow on the next lines
+        let mut pending: Option<String> = None;
+        let mut frame: Option<String> = None;
+
+        for line in stderr.lines() {
+            if !line.starts_with(' ') && !line.starts_with('|') {
+                if let Some(header) = Self::parse_header(line) {
+                    if let (Some(diagnostic), Some(text)) = (diagnostics.last_mut(), pending.take())
+                    {
+                        diagnostic.notes.push(text);
+                    }
+                    open = header.is_some();
+                    diagnostics.extend(header);
+                    frame = None;
+                    continue;
+                }
+            }
+            let Some(diagnostic) = diagnostics.last_mut().filter(|_| open) else {
+                continue;
+            };
+            let trimmed = line.trim();
+
+            if let Some(location) = trimmed.strip_prefix("--> ") {
+                let span = SourceSpan::parse(location);
+                if let Some(function) = frame.take() {
+                    diagnostic.backtrace.push((function, span));
+                } else if let (Some(text), Some(span)) = (pending.take(), span.clone()) {
+                    diagnostic.related.push((text, span));
+                } else if diagnostic.span.is_none() {
+                    diagnostic.span = span;
+                }
+            } else if let Some(location) = trimmed.strip_prefix("at ").filter(|_| frame.is_some()) {
+                let function = frame.take().unwrap_or_default();
+                diagnostic
+                    .backtrace
+                    .push((function, SourceSpan::parse(location)));
+            } else if let Some(note) = trimmed.strip_prefix("= note: ") {
+                match note.strip_prefix("inside `") {
+                    Some(inside) => {
+                        let (function, location) = inside.split_once('`').unwrap_or((inside, ""));
+                        let span = location.strip_prefix(" at ").and_then(SourceSpan::parse);
+                        diagnostic.backtrace.push((function.to_string(), span));
+                    }
+                    None if note.starts_with("BACKTRACE") || note == "stack backtrace:" => {}
+                    None => diagnostic.notes.push(note.to_string()),
+                }
+            } else if let Some(help) = trimmed.strip_prefix("= help: ") {
+                diagnostic.help.push(help.to_string());
+            } else if let Some(inside) = trimmed
+                .strip_prefix("note: inside `")
+                .and_then(|inside| inside.strip_suffix('`'))
+            {
+                frame = Some(inside.to_string());
+            } else if let Some(text) = trimmed
+                .strip_prefix("help: ")
+                .or_else(|| trimmed.strip_prefix("note: "))
+            {
+                if let Some(text) = pending.replace(text.trim_end_matches(':').to_string()) {
+                    diagnostic.notes.push(text);
+                }
+            } else if let Some((index, function)) = trimmed.split_once(": ") {
+                // Newer backtraces number their frames: `0: app::main`, then `at file:line:col`
+                if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) {
+                    frame = Some(function.to_string());
+                }
+            }
+        }
+        if let (Some(diagnostic), Some(text)) = (diagnostics.last_mut(), pending) {
+            diagnostic.notes.push(text);
+        }
+        diagnostics
+    }
+
+    /// Reads a diagnostic's first line: `Some(Some(error))` for an error to report,
+    /// `Some(None)` for a warning or closing line, and `None` for anything else.
+    fn parse_header(line: &str) -> Option<Option<Self>> {
+        let (level, message) = line.split_once(": ")?;
+        let (level, code) = match level.split_once('[') {
+            Some((level, code)) => (level, code.strip_suffix(']').map(str::to_string)),
+            None => (level, None),
+        };
+        match level {
+            "error" => {}
+            "warning" => return Some(None),
+            _ => return None,
+        }
+        const CLOSING: [&str; 4] = [
+            "aborting due to",
+            "process didn't exit successfully",
+            "could not compile",
+            "test failed",
+        ];
+        if CLOSING.iter().any(|closing| message.starts_with(closing)) {
+            return Some(None);
+        }
+        let (kind, message) = match message.split_once(": ") {
+            Some((kind, rest)) if MIRI_ERROR_KINDS.contains(&kind) => {
+                (Some(kind.to_string()), rest)
+            }
+            _ => (None, message),
+        };
+        Some(Some(Self {
+            kind,
+            message: message.to_string(),
+            code,
+            span: None,
+            help: Vec::new(),
+            notes: Vec::new(),
+            related: Vec::new(),
+            backtrace: Vec::new(),
+        }))
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind,
+            "message": self.message,
+            "code": self.code,
+            "span": self.span.as_ref().map(SourceSpan::to_json),
+            "help": self.help,
+            "notes": self.notes,
+            "related": self
+                .related
+                .iter()
+                .map(|(message, span)| json!({"message": message, "span": span.to_json()}))
+                .collect::<Vec<_>>(),
+            "backtrace": self
+                .backtrace
+                .iter()
+                .map(|(function, span)| json!({
+                    "function": function,
+                    "span": span.as_ref().map(SourceSpan::to_json)
+                }))
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+/// A change in the program's execution reported by the debugger.
+///
+/// Events are read from whole lines of debugger output, never from substrings, so text
+/// the program prints cannot be mistaken for one. [`crate::session::DebugState::on_event`]
+/// decides which state each event leads to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebuggerEvent {
+    /// The program was launched (`Process 1234 launched: ...`)
+    Launched { pid: Option<u32> },
+    /// Execution resumed (`Process 1234 resuming`, `^running`, `*running`)
+    Resumed,
+    /// The program stopped and can be inspected (`Process 1234 stopped`, `*stopped`)
+    Stopped { pid: Option<u32> },
+    /// The program stopped on a fatal signal (`*stopped,reason="signal-received"`)
+    Crashed,
+    /// The program terminated; `signalled` when a signal killed it
+    Exited { signalled: bool },
+}
+
+impl DebuggerEvent {
+    /// Reads one line of LLDB output of the form `Process <pid> <event>`.
+    pub fn parse_lldb(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("Process ")?;
+        let (pid, rest) = rest.split_once(' ')?;
+        let pid = pid.parse().ok()?;
+
+        if rest.starts_with("launched") {
+            Some(Self::Launched { pid: Some(pid) })
+        } else if rest == "resuming" {
+            Some(Self::Resumed)
+        } else if rest == "stopped" {
+            Some(Self::Stopped { pid: Some(pid) })
+        } else if rest.starts_with("exited with status = ") {
+            Some(Self::Exited { signalled: false })
+        } else {
+            None
+        }
+    }
+
+    /// Reads an MI `running` or `*stopped` record.
+    pub fn from_mi(record: &MiRecord) -> Option<Self> {
+        match record {
+            MiRecord::Exec { class, .. } | MiRecord::Result { class, .. } if class == "running" => {
+                Some(Self::Resumed)
+            }
+            MiRecord::Exec { class, results } if class == "stopped" => {
+                let field = |name: &str| results.get(name).and_then(|v| v.as_str());
+                Some(match field("reason").unwrap_or("") {
+                    "exited-normally" | "exited" => Self::Exited { signalled: false },
+                    "exited-signalled" => Self::Exited { signalled: true },
+                    "signal-received"
+                        if matches!(field("signal-name"), Some("SIGSEGV" | "SIGABRT")) =>
+                    {
+                        Self::Crashed
+                    }
+                    _ => Self::Stopped { pid: None },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Classifies a debugger output line announcing that the program stopped or exited.
+pub fn stop_event(line: &str) -> Option<&'static str> {
+    let event = match MiRecord::parse(line) {
+        MiRecord::Other(line) => DebuggerEvent::parse_lldb(&line),
+        record => DebuggerEvent::from_mi(&record),
+    }?;
+    match event {
+        DebuggerEvent::Stopped { .. } | DebuggerEvent::Crashed => Some("stopped"),
+        DebuggerEvent::Exited { .. } => Some("exited"),
+        DebuggerEvent::Launched { .. } | DebuggerEvent::Resumed => None,
+    }
+}
+
+/// Whether a line the debugger wrote to stderr is worth showing the agent: a warning or
+/// error, such as LLDB's "unable to locate dSYM", rather than progress chatter.
+pub(crate) fn notable_stderr(line: &str) -> bool {
+    const MARKERS: [&str; 14] = [
+        "warning",
+        "error",
+        "unable to",
+        "could not",
+        "couldn't",
+        "cannot",
+        "can't",
+        "failed",
+        "not found",
+        "no such",
+        "permission denied",
+        "dsym",
+        "assert",
+        "fatal",
+    ];
+    let line = line.to_lowercase();
+    MARKERS.iter().any(|marker| line.contains(marker))
+}
+
+/// The number of the breakpoint LLDB reports stopping at, from a
+/// `stop reason = breakpoint 2.1` line.
+pub fn breakpoint_hit(response: &str) -> Option<u32> {
+    stop_reason_number(response, "stop reason = breakpoint ")
+}
+
+/// The number of the watchpoint LLDB reports stopping at, from a
+/// `stop reason = watchpoint 1` line.
+pub fn watchpoint_hit(response: &str) -> Option<u32> {
+    stop_reason_number(response, "stop reason = watchpoint ")
+}
+
+fn stop_reason_number(response: &str, marker: &str) -> Option<u32> {
+    let reason = response
+        .lines()
+        .find_map(|line| line.split_once(marker))
+        .map(|(_, reason)| reason)?;
+    let end = reason
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(reason.len());
+    reason[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lldb_events_come_from_process_lines() {
+        assert_eq!(
+            DebuggerEvent::parse_lldb("Process 4242 launched: '/bin/true' (x86_64)"),
+            Some(DebuggerEvent::Launched { pid: Some(4242) })
+        );
+        assert_eq!(
+            DebuggerEvent::parse_lldb("Process 4242 resuming\n"),
+            Some(DebuggerEvent::Resumed)
+        );
+        assert_eq!(
+            DebuggerEvent::parse_lldb("Process 4242 stopped"),
+            Some(DebuggerEvent::Stopped { pid: Some(4242) })
+        );
+        assert_eq!(
+            DebuggerEvent::parse_lldb("Process 4242 exited with status = 1 (0x00000001)"),
+            Some(DebuggerEvent::Exited { signalled: false })
+        );
+        assert_eq!(DebuggerEvent::parse_lldb("Process 4242 detached"), None);
+    }
+
+    #[test]
+    fn lldb_events_ignore_program_text() {
+        for line in [
+            "Process stopped because the queue was empty",
+            "worker: Process 12 launched the job",
+            "Processing 3 items, 1 stopped",
+            "* thread #1, name = 'crashy', stop reason = signal SIGSEGV",
+            "the process crashed",
+        ] {
+            assert_eq!(DebuggerEvent::parse_lldb(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn mi_events_come_from_records() {
+        let event = |line: &str| DebuggerEvent::from_mi(&MiRecord::parse(line));
+        assert_eq!(event("1^running"), Some(DebuggerEvent::Resumed));
+        assert_eq!(
+            event("*running,thread-id=\"all\""),
+            Some(DebuggerEvent::Resumed)
+        );
+        assert_eq!(
+            event("*stopped,reason=\"breakpoint-hit\",thread-id=\"1\""),
+            Some(DebuggerEvent::Stopped { pid: None })
+        );
+        assert_eq!(
+            event("*stopped,reason=\"signal-received\",signal-name=\"SIGSEGV\""),
+            Some(DebuggerEvent::Crashed)
+        );
+        assert_eq!(
+            event("*stopped,reason=\"signal-received\",signal-name=\"SIGINT\""),
+            Some(DebuggerEvent::Stopped { pid: None })
+        );
+        assert_eq!(
+            event("*stopped,reason=\"exited\",exit-code=\"01\""),
+            Some(DebuggerEvent::Exited { signalled: false })
+        );
+        assert_eq!(
+            event("*stopped,reason=\"exited-signalled\",signal-name=\"SIGKILL\""),
+            Some(DebuggerEvent::Exited { signalled: true })
+        );
+        assert_eq!(event("~\"Process 1 stopped\\n\""), None);
+        assert_eq!(event("1^done"), None);
+    }
+
+    #[test]
+    fn mi_result_records_parse() {
+        assert_eq!(
+            MiRecord::parse("12^done,value=\"42\",frame={level=\"0\",func=\"main\"}\n"),
+            MiRecord::Result {
+                token: Some(12),
+                class: "done".to_string(),
+                results: json!({ "value": "42", "frame": { "level": "0", "func": "main" } }),
+            }
+        );
+        assert_eq!(
+            MiRecord::parse("^error,msg=\"No symbol \\\"x\\\" in current context.\""),
+            MiRecord::Result {
+                token: None,
+                class: "error".to_string(),
+                results: json!({ "msg": "No symbol \"x\" in current context." }),
+            }
+        );
+        assert_eq!(
+            MiRecord::parse(
+                "3^done,stack=[frame={level=\"0\"},frame={level=\"1\"}],ids=[\"1\",\"2\"]"
+            ),
+            MiRecord::Result {
+                token: Some(3),
+                class: "done".to_string(),
+                results: json!({
+                    "stack": [{ "level": "0" }, { "level": "1" }],
+                    "ids": ["1", "2"]
+                }),
+            }
+        );
+        assert_eq!(MiRecord::parse("(gdb) "), MiRecord::Prompt);
+        assert_eq!(
+            MiRecord::parse("^done,value="),
+            MiRecord::Other("^done,value=".to_string())
+        );
+    }
+
+    #[test]
+    fn mi_async_records_parse() {
+        assert_eq!(
+            MiRecord::parse("*stopped,reason=\"end-stepping-range\",thread-id=\"1\""),
+            MiRecord::Exec {
+                class: "stopped".to_string(),
+                results: json!({ "reason": "end-stepping-range", "thread-id": "1" }),
+            }
+        );
+        assert_eq!(
+            MiRecord::parse("=thread-group-started,id=\"i1\",pid=\"4242\""),
+            MiRecord::Notify {
+                class: "thread-group-started".to_string(),
+                results: json!({ "id": "i1", "pid": "4242" }),
+            }
+        );
+        assert_eq!(
+            MiRecord::parse("*running"),
+            MiRecord::Exec {
+                class: "running".to_string(),
+                results: json!({}),
+            }
+        );
+    }
+
+    #[test]
+    fn mi_stream_records_parse() {
+        assert_eq!(
+            MiRecord::parse("~\"$1 = 5\\n\""),
+            MiRecord::Console("$1 = 5\n".to_string())
+        );
+        assert_eq!(
+            MiRecord::parse("@\"hello\\tworld\""),
+            MiRecord::Target("hello\tworld".to_string())
+        );
+        assert_eq!(
+            MiRecord::parse("&\"set pagination off\\n\""),
+            MiRecord::Log("set pagination off\n".to_string())
+        );
+        assert_eq!(
+            MiRecord::parse("hello from the program"),
+            MiRecord::Other("hello from the program".to_string())
+        );
+    }
+
+    #[test]
+    fn mi_octal_escapes_decode_to_utf8() {
+        assert_eq!(
+            MiRecord::parse("~\"caf\\303\\251 \\342\\234\\223\\n\""),
+            MiRecord::Console("café ✓\n".to_string())
+        );
+        assert_eq!(
+            MiRecord::parse("~\"\\0\\101\""),
+            MiRecord::Console("\0A".to_string())
+        );
+    }
+
+    #[test]
+    fn mi2_breakpoint_locations_nest_under_the_breakpoint() {
+        let MiRecord::Result { results, .. } = MiRecord::parse(
+            "2^done,bkpt={number=\"1\",type=\"breakpoint\",addr=\"<MULTIPLE>\",times=\"0\"},{number=\"1.1\",enabled=\"y\",addr=\"0x1000\",func=\"app::parse<u32>\"},{number=\"1.2\",enabled=\"y\",addr=\"0x2000\",func=\"app::parse<i64>\"}",
+        ) else {
+            panic!("not a result record");
+        };
+        assert_eq!(results["bkpt"]["number"], "1");
+        assert_eq!(results["bkpt"]["locations"][0]["number"], "1.1");
+        assert_eq!(results["bkpt"]["locations"][1]["func"], "app::parse<i64>");
+
+        // `-break-list` lists them in its body the same way
+        let MiRecord::Result { results, .. } = MiRecord::parse(
+            "3^done,BreakpointTable={nr_rows=\"2\",body=[bkpt={number=\"1\",addr=\"<MULTIPLE>\"},{number=\"1.1\",addr=\"0x1000\"},{number=\"1.2\",addr=\"0x2000\"},bkpt={number=\"2\",addr=\"0x3000\"}]}",
+        ) else {
+            panic!("not a result record");
+        };
+        let body = results["BreakpointTable"]["body"].as_array().unwrap();
+        assert_eq!(body.len(), 2);
+        assert_eq!(body[0]["locations"].as_array().unwrap().len(), 2);
+        assert_eq!(body[1]["number"], "2");
+        assert_eq!(body[1].get("locations"), None);
+
+        // MI3 already nests them
+        let MiRecord::Result { results, .. } = MiRecord::parse(
+            "4^done,bkpt={number=\"1\",addr=\"<MULTIPLE>\",locations=[{number=\"1.1\"},{number=\"1.2\"}]}",
+        ) else {
+            panic!("not a result record");
+        };
+        assert_eq!(results["bkpt"]["locations"][1]["number"], "1.2");
+    }
+
+    #[test]
+    fn stop_event_classifies_both_dialects() {
+        assert_eq!(stop_event("Process 1 stopped\n"), Some("stopped"));
+        assert_eq!(
+            stop_event("Process 1 exited with status = 0 (0x00000000)"),
+            Some("exited")
+        );
+        assert_eq!(
+            stop_event("*stopped,reason=\"exited-normally\""),
+            Some("exited")
+        );
+        assert_eq!(stop_event("Process 1 launched: 'a' (x86_64)"), None);
+        assert_eq!(stop_event("Process stopped"), None);
+    }
+
+    fn rendered(output: &str) -> Option<String> {
+        Variable::parse_lldb_tree(output).first()?.rust_display()
+    }
+
+    #[test]
+    fn rust_display_renders_formatter_summaries() {
+        assert_eq!(
+            rendered("(core::option::Option<i32>) x = Some(5)").as_deref(),
+            Some("Some(5)")
+        );
+        assert_eq!(
+            rendered("(alloc::string::String) s = \"abc\"").as_deref(),
+            Some("\"abc\"")
+        );
+        let vec = "(alloc::vec::Vec<i32, alloc::alloc::Global>) v = size=3 {\n\
+                   (i32) [0] = 1\n(i32) [1] = 2\n(i32) [2] = 3\n}";
+        assert_eq!(rendered(vec).as_deref(), Some("len=3 [1, 2, 3]"));
+        assert_eq!(
+            rendered("(alloc::vec::Vec<u8>) v = size=500").as_deref(),
+            Some("len=500 [..]")
+        );
+        let map = "(std::collections::hash::map::HashMap<&str, i32>) m = size=1 {\n\
+                   ((&str, i32)) [0] = {\n(&str) 0 = \"a\"\n(i32) 1 = 1\n}\n}";
+        assert_eq!(rendered(map).as_deref(), Some("len=1 {\"a\": 1}"));
+        assert_eq!(rendered("(i32) x = 5"), None);
+    }
+
+    #[test]
+    fn rust_display_reads_raw_enum_layouts() {
+        let some = "(core::option::Option<i32>) x = {\n\
+                    (core::option::Option<i32>::Some<i32>) Some = {\n(i32) __0 = 7\n}\n}";
+        assert_eq!(rendered(some).as_deref(), Some("Some(7)"));
+        let none = "(core::option::Option<i32>) x = {\n\
+                    (core::option::Option<i32>::None<i32>) None = {}\n}";
+        assert_eq!(rendered(none).as_deref(), Some("None"));
+    }
+
+    #[test]
+    fn gdb_values_are_tidied() {
+        assert_eq!(
+            tidy_gdb_value("core::option::Option<i32>::Some(5)"),
+            "Some(5)"
+        );
+        assert_eq!(tidy_gdb_value("Vec(size=3) = {1, 2, 3}"), "len=3 [1, 2, 3]");
+        assert_eq!(
+            tidy_gdb_value("HashMap(size=1) = {[\"a\"] = 1}"),
+            "len=1 {\"a\": 1}"
+        );
+    }
+
+    #[test]
+    fn gdb_values_keep_user_variants_named_like_std_ones() {
+        assert_eq!(
+            tidy_gdb_value("app::State::Something(2)"),
+            "app::State::Something(2)"
+        );
+        assert_eq!(tidy_gdb_value("app::Kind::NoneLeft"), "app::Kind::NoneLeft");
+        assert_eq!(
+            tidy_gdb_value("app::Kind::OkOrRetry(core::option::Option<u8>::None)"),
+            "app::Kind::OkOrRetry(None)"
+        );
+        assert_eq!(
+            tidy_gdb_value("core::result::Result<u8, app::Error>::Err(app::Error::Errno(5))"),
+            "Err(app::Error::Errno(5))"
+        );
+    }
+
+    #[test]
+    fn limited_json_truncates_and_stops_at_cycles() {
+        let output = "(Node *) head = 0x0000000000001000 {\n\
+                      (i32) value = 1\n\
+                      (Node *) next = 0x0000000000002000 {\n\
+                      (i32) value = 2\n\
+                      (Node *) next = 0x0000000000001000 {\n\
+                      (i32) value = 1\n}\n}\n}";
+        let head = &Variable::parse_lldb_tree(output)[0];
+        let json = head.to_json_limited(2);
+        let cycle = &json["children"][1]["children"][1];
+        assert_eq!(cycle["cycle"], true);
+        assert!(cycle.get("children").is_none());
+
+        let json = head.to_json_limited(1);
+        assert_eq!(json["truncated"], true);
+        assert_eq!(json["children"].as_array().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn pointee_looks_through_smart_pointer_layouts() {
+        let boxed = "(alloc::boxed::Box<i32, alloc::alloc::Global>) b = 0x0000000000001000 {\n\
+                     (i32) *b = 5\n}";
+        let boxed = &Variable::parse_lldb_tree(boxed)[0];
+        assert!(boxed.is_smart_pointer());
+        assert_eq!(boxed.pointee().and_then(|v| v.value.as_deref()), Some("5"));
+
+        let rc = "(alloc::rc::Rc<i32, alloc::alloc::Global>) r = {\n\
+                  (core::ptr::non_null::NonNull<alloc::rc::RcInner<i32>>) ptr = {\n\
+                  (alloc::rc::RcInner<i32> *) pointer = 0x0000000000002000 {\n\
+                  (core::cell::Cell<usize>) strong = {}\n(i32) value = 7\n}\n}\n}";
+        let rc = &Variable::parse_lldb_tree(rc)[0];
+        assert_eq!(rc.pointee().and_then(|v| v.value.as_deref()), Some("7"));
+        assert_eq!(rc.rust_display().as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn integers_parse_in_decimal_and_hex() {
+        assert_eq!(parse_integer("42"), Some(42));
+        assert_eq!(parse_integer("0x10 (16)"), Some(16));
+        assert_eq!(parse_integer("Some(3)"), None);
+    }
+
+    #[test]
+    fn lldb_symbol_lookups_parse() {
+        let functions = "2 matches found in /tmp/app:
+        Address: app[0x0000000100003f50] (app.__TEXT.__text + 0)
+        Summary: app`app::parse::parse_line at parse.rs:10
+        Address: app[0x0000000100003f90] (app.__TEXT.__text + 64)
+        Summary: app`app::parse::parse_file + 4 at parse.rs:30";
+        let symbols = SymbolInfo::parse_lldb(functions);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "app::parse::parse_line");
+        assert_eq!(symbols[0].module.as_deref(), Some("app"));
+        assert_eq!(symbols[1].name, "app::parse::parse_file");
+        assert_eq!(symbols[1].location.as_deref(), Some("parse.rs:30"));
+
+        let types = "Best match found in /tmp/app:
+id = {0x000001a2}, name = \"app::Config\", byte-size = 48, decl = config.rs:3, compiler_type = \"struct Config {}\"";
+        let symbols = SymbolInfo::parse_lldb(types);
+        assert_eq!(symbols[0].name, "app::Config");
+        assert_eq!(symbols[0].location.as_deref(), Some("config.rs:3"));
+    }
+
+    #[test]
+    fn rust_symbols_demangle() {
+        assert_eq!(
+            demangle("_ZN4core9panicking9panic_fmt17h32a5d8bda4b0c8d2E"),
+            "core::panicking::panic_fmt"
+        );
+        assert_eq!(demangle("_RNvCs1234_7mycrate4main"), "mycrate::main");
+        assert_eq!(demangle("app::main::h0123456789abcdef"), "app::main");
+        assert_eq!(demangle("main"), "main");
+        assert_eq!(
+            demangle_text("frame #0: 0x1000 app`_ZN3app4main17h0123456789abcdefE + 4 at main.rs:3"),
+            "frame #0: 0x1000 app`app::main + 4 at main.rs:3"
+        );
+    }
+
+    #[test]
+    fn module_lists_parse() {
+        let lldb = "[  0] 4C4C4433-5555-3144-A1D2-3D0A0B0C0D0E 0x0000555555554000 /tmp/app \n\
+                    [  1] 0x00007ffff7fc5000 /lib64/ld-linux-x86-64.so.2 \n\
+                    \x20     /usr/lib/debug/.build-id/ab/cdef.debug";
+        let modules = ModuleInfo::parse_lldb(lldb);
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].path, "/tmp/app");
+        assert_eq!(
+            modules[0].load_address.as_deref(),
+            Some("0x0000555555554000")
+        );
+        assert!(modules[0].uuid.is_some());
+        assert_eq!(modules[1].uuid, None);
+        assert_eq!(modules[1].has_debug_info, Some(true));
+
+        let gdb = "From                To                  Syms Read   Shared Object Library
+0x00007ffff7fc5090  0x00007ffff7fee315  Yes         /lib64/ld-linux-x86-64.so.2
+0x00007ffff7d8a700  0x00007ffff7f1c93d  Yes (*)     /lib/x86_64-linux-gnu/libc.so.6
+(*): Shared library is missing debugging information.";
+        let modules = ModuleInfo::parse_gdb(gdb);
+        assert_eq!(modules.len(), 2);
+        assert_eq!(modules[0].has_debug_info, Some(true));
+        assert_eq!(modules[1].path, "/lib/x86_64-linux-gnu/libc.so.6");
+        assert_eq!(modules[1].has_debug_info, Some(false));
+    }
+
+    #[test]
+    fn cargo_artifacts_need_an_executable() {
+        let test = r#"{"reason":"compiler-artifact","package_id":"path+file:///w/app#0.1.0","manifest_path":"/w/app/Cargo.toml","target":{"kind":["lib"],"crate_types":["lib"],"name":"my-app","src_path":"/w/app/src/lib.rs","test":true},"profile":{"opt_level":"0","debuginfo":2,"test":true},"features":[],"filenames":["/w/app/target/debug/deps/my_app-1a2b3c"],"executable":"/w/app/target/debug/deps/my_app-1a2b3c","fresh":false}"#;
+        let artifact = CargoArtifact::parse(test).unwrap();
+        assert_eq!(
+            artifact.executable,
+            "/w/app/target/debug/deps/my_app-1a2b3c"
+        );
+        assert_eq!(artifact.target_name, "my-app");
+        assert_eq!(artifact.kinds, ["lib"]);
+        assert!(artifact.test);
+        assert_eq!(artifact.manifest_path, "/w/app/Cargo.toml");
+        assert_eq!(artifact.opt_level, "0");
+        assert!(artifact.debuginfo);
+
+        let library = test
+            .replace(r#""test":true},"features""#, r#""test":false},"features""#)
+            .replace(
+                r#""executable":"/w/app/target/debug/deps/my_app-1a2b3c""#,
+                r#""executable":null"#,
+            );
+        assert_eq!(CargoArtifact::parse(&library), None);
+        assert_eq!(
+            CargoArtifact::parse(r#"{"reason":"build-finished","success":true}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn compiler_diagnostics_use_the_primary_span() {
+        let error = r#"{"reason":"compiler-message","package_id":"path+file:///w/app#0.1.0","manifest_path":"/w/app/Cargo.toml","target":{"kind":["bin"],"name":"app","src_path":"/w/app/src/main.rs"},"message":{"rendered":"error[E0308]: mismatched types\n","$message_type":"diagnostic","children":[],"code":{"code":"E0308","explanation":"..."},"level":"error","message":"mismatched types","spans":[{"file_name":"src/main.rs","line_start":1,"line_end":1,"column_start":20,"column_end":22,"is_primary":false,"label":"expected due to this"},{"file_name":"src/main.rs","line_start":1,"line_end":1,"column_start":25,"column_end":28,"is_primary":true,"label":"expected `u8`, found `&str`"}]}}"#;
+        let diagnostic = CompilerDiagnostic::parse(error).unwrap();
+        assert_eq!(diagnostic.level, "error");
+        assert_eq!(diagnostic.message, "mismatched types");
+        assert_eq!(diagnostic.file.as_deref(), Some("src/main.rs"));
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(1), Some(25)));
+        assert_eq!(diagnostic.code.as_deref(), Some("E0308"));
+
+        let summary = r#"{"reason":"compiler-message","message":{"rendered":"error: aborting due to 1 previous error\n","code":null,"level":"error","message":"aborting due to 1 previous error","spans":[],"children":[]}}"#;
+        let diagnostic = CompilerDiagnostic::parse(summary).unwrap();
+        assert_eq!((diagnostic.file, diagnostic.code), (None, None));
+        assert_eq!(
+            CompilerDiagnostic::parse(
+                r#"{"reason":"compiler-message","message":{"level":"note","message":"n","spans":[]}}"#
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn lldb_target_list_lines_parse() {
+        let selected = InferiorInfo::parse_lldb(
+            "* target #1: /tmp/my app ( arch=x86_64-unknown-linux-gnu, platform=host, pid=4243, state=stopped )",
+        )
+        .unwrap();
+        assert_eq!(selected.id, 1);
+        assert_eq!(selected.pid, Some(4243));
+        assert_eq!(selected.executable.as_deref(), Some("/tmp/my app"));
+        assert_eq!(selected.state.as_deref(), Some("stopped"));
+        assert!(selected.selected);
+
+        let unstarted = InferiorInfo::parse_lldb(
+            "  target #0: /tmp/app ( arch=x86_64-unknown-linux-gnu, platform=host )",
+        )
+        .unwrap();
+        assert_eq!(
+            (unstarted.id, unstarted.pid, unstarted.selected),
+            (0, None, false)
+        );
+        assert_eq!(InferiorInfo::parse_lldb("Current targets:"), None);
+    }
+
+    #[test]
+    fn async_tasks_come_from_task_cell_types() {
+        let task = AsyncTask::from_cell_type(
+            "tokio::runtime::task::core::Cell<app::serve::{async_fn_env#0}, alloc::sync::Arc<tokio::runtime::scheduler::multi_thread::handle::Handle, alloc::alloc::Global>>",
+        )
+        .unwrap();
+        assert_eq!(task.future, "app::serve::{async_fn_env#0}");
+        assert_eq!(task.poll_function, "app::serve::{async_fn#0}");
+        assert!(task.is_polled_by("app::serve::{async_fn#0}"));
+        assert!(task.is_polled_by("app::serve::{{closure}}"));
+        assert!(!task.is_polled_by("app::main::{{closure}}"));
+
+        let wrapped = AsyncTask::from_cell_type(
+            "tokio::runtime::task::core::Cell<tracing::instrument::Instrumented<app::main::{async_block_env#1}>, alloc::sync::Arc<tokio::runtime::scheduler::current_thread::Handle, alloc::alloc::Global>>",
+        )
+        .unwrap();
+        assert_eq!(
+            wrapped.future,
+            "tracing::instrument::Instrumented<app::main::{async_block_env#1}>"
+        );
+        assert_eq!(
+            wrapped.poll_function,
+            "<tracing::instrument::Instrumented<app::main::{async_block_env#1}> as core::future::future::Future>::poll"
+        );
+        assert_eq!(AsyncTask::from_cell_type("app::Config"), None);
+    }
+
+    #[test]
+    fn async_backtraces_follow_the_await_chain() {
+        let frames: Vec<FrameInfo> = [
+            "frame #0: 0x0000000000001000 app`app::parse_line at lib.rs:40:5",
+            "frame #1: 0x0000000000001100 app`app::leaf::{async_fn#0} at lib.rs:30:9",
+            "frame #2: 0x0000000000001200 app`<core::pin::Pin<&mut F> as core::future::future::Future>::poll at future.rs:124:9",
+            "frame #3: 0x0000000000001300 app`app::middle::{{closure}} at lib.rs:20:9",
+            "frame #4: 0x0000000000001400 app`app::top::{async_fn#0} at lib.rs:10:5",
+            "frame #5: 0x0000000000001500 app`tokio::runtime::task::core::Core<T,S>::poll::{{closure}} at core.rs:311:17",
+            "frame #6: 0x0000000000001600 app`std::sys::backtrace::__rust_begin_short_backtrace at backtrace.rs:152:18",
+        ]
+        .iter()
+        .filter_map(|line| FrameInfo::parse_lldb(line))
+        .collect();
+
+        let (logical, root) = async_backtrace(&frames).unwrap();
+        assert_eq!(root, 4);
+        let functions: Vec<&str> = logical
+            .iter()
+            .map(|frame| frame["function"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            functions,
+            [
+                "app::parse_line",
+                "app::leaf::{async_fn#0}",
+                "app::middle::{{closure}}",
+                "app::top::{async_fn#0}"
+            ]
+        );
+        assert_eq!(logical[0]["async"], false);
+        assert_eq!(logical[1]["awaits"], Value::Null);
+        assert_eq!(logical[2]["awaits"], "app::leaf::{async_fn#0}");
+        assert_eq!(logical[3]["awaits"], "app::middle::{{closure}}");
+
+        assert_eq!(async_backtrace(&frames[..1]), None);
+    }
+
+    #[test]
+    fn blocking_calls_and_held_guards_are_found() {
+        let frames: Vec<FrameInfo> = [
+            "frame #0: 0x0000000000001000 libc.so.6`syscall + 29",
+            "frame #1: 0x0000000000001100 app`std::sys::sync::mutex::futex::Mutex::lock_contended at futex.rs:90:13",
+            "frame #2: 0x0000000000001200 app`std::sync::poison::mutex::Mutex<i32>::lock at mutex.rs:422:18",
+            "frame #3: 0x0000000000001300 app`app::transfer at main.rs:12:22",
+        ]
+        .iter()
+        .filter_map(|line| FrameInfo::parse_lldb(line))
+        .collect();
+        assert_eq!(blocking_call(&frames), Some((2, "mutex")));
+        assert!(!frames[2].is_user_frame());
+        assert!(frames[3].is_user_frame());
+        assert_eq!(blocking_call(&frames[3..]), None);
+
+        let locals = Variable::parse_lldb_tree(
+            "(std::sync::poison::mutex::MutexGuard<i32>) first = {\n  (std::sync::poison::mutex::Mutex<i32> *) lock = 0x00005555555a0010\n  (std::sync::poison::Guard) poison = {...}\n}\n(i32) amount = 5\n",
+        );
+        let held: Vec<String> = locals.iter().flat_map(Variable::held_locks).collect();
+        assert_eq!(held, ["0x00005555555a0010"]);
+    }
+
+    #[test]
+    fn breakpoint_hits_name_the_breakpoint() {
+        assert_eq!(
+            breakpoint_hit("* thread #1, name = 'app', stop reason = breakpoint 12.1\n"),
+            Some(12)
+        );
+        assert_eq!(
+            breakpoint_hit("* thread #1, name = 'app', stop reason = signal SIGSEGV"),
+            None
+        );
+
+        let output = MiOutput {
+            records: vec![
+                MiRecord::parse("^running"),
+                MiRecord::parse(
+                    "*stopped,reason=\"breakpoint-hit\",disp=\"keep\",bkptno=\"3\",thread-id=\"1\"",
+                ),
+            ],
+            timed_out: false,
+        };
+        assert_eq!(output.breakpoint_hit(), Some(3));
+        let output = MiOutput {
+            records: vec![MiRecord::parse("*stopped,reason=\"end-stepping-range\"")],
+            timed_out: false,
+        };
+        assert_eq!(output.breakpoint_hit(), None);
+    }
+
+    #[test]
+    fn gdb_checkpoints_are_parsed() {
+        assert_eq!(
+            CheckpointInfo::parse_gdb_created("checkpoint 1: fork returned pid 4250.\n"),
+            Some((1, 4250))
+        );
+
+        let listing = "* 0 Thread 0x7ffff7d85740 (LWP 4242) (main process) at 0x555555559ff0, file src/main.rs, line 12\n  1 process 4250 at 0x555555559ff0, file src/main.rs, line 12\n";
+        let checkpoints: Vec<CheckpointInfo> = listing
+            .lines()
+            .filter_map(CheckpointInfo::parse_gdb)
+            .collect();
+        assert_eq!(
+            checkpoints,
+            [
+                CheckpointInfo {
+                    id: 0,
+                    pid: Some(4242),
+                    location: Some("src/main.rs:12".to_string()),
+                    current: true
+                },
+                CheckpointInfo {
+                    id: 1,
+                    pid: Some(4250),
+                    location: Some("src/main.rs:12".to_string()),
+                    current: false
+                }
+            ]
+        );
+        assert_eq!(CheckpointInfo::parse_gdb("No checkpoints."), None);
+    }
+
+    #[test]
+    fn disassembly_and_registers_are_parsed() {
+        let lldb = "app`app::main:\n    0x555555559ff0 <+0>:  pushq  %rbp\n    0x555555559ff1 <+1>:  movq   %rsp, %rbp\n->  0x555555559ff4 <+4>:  movl   $0x0, (%rax)\n    0x555555559ffa <+10>: popq   %rbp\n";
+        let instructions: Vec<Instruction> =
+            lldb.lines().filter_map(Instruction::parse_lldb).collect();
+        assert_eq!(instructions.len(), 4);
+        assert_eq!(
+            instructions[2],
+            Instruction {
+                address: "0x555555559ff4".to_string(),
+                offset: Some(4),
+                text: "movl   $0x0, (%rax)".to_string(),
+                current: true
+            }
+        );
+        let window = Instruction::around_pc(instructions, 1);
+        let offsets: Vec<_> = window.iter().map(|i| i.offset).collect();
+        assert_eq!(offsets, [Some(1), Some(4), Some(10)]);
+
+        let insn = json!({"address": "0x555555559ff4", "func-name": "app::main", "offset": "4", "inst": "movl   $0x0,(%rax)"});
+        assert!(
+            Instruction::from_mi(&insn, Some(0x555555559ff4))
+                .unwrap()
+                .current
+        );
+
+        let lldb = "General Purpose Registers:\n       rax = 0x0000000000000000\n       rip = 0x0000555555559ff4  app`app::main + 4 at main.rs:3:5\n";
+        assert_eq!(
+            parse_registers(lldb),
+            [
+                ("rax".to_string(), "0x0000000000000000".to_string()),
+                ("rip".to_string(), "0x0000555555559ff4".to_string())
+            ]
+        );
+        let gdb = "rax            0x0                 0\nrip            0x555555559ff4      0x555555559ff4 <app::main+4>\neflags         0x10246             [ IF ZF PF ]\n";
+        assert_eq!(parse_registers(gdb).len(), 3);
+    }
+
+    #[test]
+    fn panics_are_read_from_stderr_and_backtraces() {
+        let stderr = "starting\nthread 'main' (4242) panicked at src/main.rs:5:9:\nindex out of bounds: the len is 3 but the index is 10\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        assert_eq!(
+            PanicMessage::parse_stderr(stderr),
+            Some(PanicMessage {
+                thread: Some("main".to_string()),
+                message: "index out of bounds: the len is 3 but the index is 10".to_string(),
+                file: "src/main.rs".to_string(),
+                line: Some(5),
+                column: Some(9)
+            })
+        );
+        let old = PanicMessage::parse_stderr(
+            "thread '<unnamed>' panicked at 'explicit panic', src/worker.rs:12:5\n",
+        )
+        .unwrap();
+        assert_eq!(old.thread.as_deref(), Some("<unnamed>"));
+        assert_eq!(old.message, "explicit panic");
+        assert_eq!((old.file.as_str(), old.line), ("src/worker.rs", Some(12)));
+        assert_eq!(PanicMessage::parse_stderr("all good\n"), None);
+
+        let frames: Vec<FrameInfo> = [
+            "frame #0: 0x0000000000001000 app`rust_panic at panicking.rs:862:5",
+            "frame #1: 0x0000000000001100 app`std::panicking::rust_panic_with_hook at panicking.rs:826:5",
+            "frame #2: 0x0000000000001200 app`core::panicking::panic_fmt at panicking.rs:75:14",
+            "frame #3: 0x0000000000001300 app`app::parse::h0123456789abcdef at main.rs:5:9",
+            "frame #4: 0x0000000000001400 app`app::main::h0123456789abcdef at main.rs:10:5",
+            "frame #5: 0x0000000000001500 app`std::rt::lang_start::h0123456789abcdef at rt.rs:195:17",
+        ]
+        .iter()
+        .filter_map(|line| FrameInfo::parse_lldb(line))
+        .collect();
+        let user = panic_backtrace(&frames).unwrap();
+        assert_eq!(user.iter().map(|f| f.index).collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(panic_backtrace(&frames[3..]), None);
+    }
+    #[test]
+    fn sanitizer_reports_are_parsed() {
+        let stderr = "\
+=================================================================
+==4242==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000014 at pc 0x55d5c bp 0x7ffd sp 0x7ffc
+READ of size 4 at 0x602000000014 thread T0
+    #0 0x55d5c in app::main::h0123456789abcdef /src/app/src/main.rs:5:13
+    #1 0x55e00 in std::rt::lang_start::h0123456789abcdef /rustc/library/std/src/rt.rs:195:17
+
+0x602000000014 is located 0 bytes after 4-byte region [0x602000000010,0x602000000014)
+allocated by thread T0 here:
+    #0 0x4a1b2 in malloc (/src/app/target/debug/app+0x4a1b2) (BuildId: 78ff0b0ff781ae2f)
+    #1 0x55d00 in app::main::h0123456789abcdef /src/app/src/main.rs:4:17
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /src/app/src/main.rs:5:13 in app::main::h0123456789abcdef
+==4242==ABORTING
+==================
+WARNING: ThreadSanitizer: data race (pid=4243)
+  Write of size 4 at 0x5581 by thread T1:
+    #0 app::main::{{closure}} /src/app/src/main.rs:10:9 (app+0x1234)
+
+  Previous write of size 4 at 0x5581 by main thread:
+    #0 app::main /src/app/src/main.rs:7:5 (app+0x1200)
+
+SUMMARY: ThreadSanitizer: data race /src/app/src/main.rs:10:9 in app::main::{{closure}}
+";
+        let reports = SanitizerReport::parse_all(stderr);
+        assert_eq!(reports.len(), 2);
+
+        let asan = &reports[0];
+        assert_eq!(asan.sanitizer, "address");
+        assert_eq!(asan.kind, "heap-buffer-overflow");
+        assert_eq!(asan.address.as_deref(), Some("0x602000000014"));
+        assert_eq!(asan.stacks.len(), 2);
+        assert_eq!(asan.stacks[1].0, "allocated by thread T0 here:");
+        assert_eq!(asan.stacks[1].1[0].module.as_deref(), Some("app"));
+        let frame = asan.user_frame().unwrap();
+        assert_eq!(frame.file.as_deref(), Some("/src/app/src/main.rs"));
+        assert_eq!(frame.line, Some(5));
+        assert_eq!(frame.pc.as_deref(), Some("0x55d5c"));
+
+        let tsan = &reports[1];
+        assert_eq!(
+            (tsan.sanitizer.as_str(), tsan.kind.as_str()),
+            ("thread", "data race")
+        );
+        assert_eq!(
+            tsan.stacks[1].0,
+            "Previous write of size 4 at 0x5581 by main thread:"
+        );
+        assert_eq!(
+            tsan.user_frame().unwrap().function.as_deref(),
+            Some("app::main::{{closure}}")
+        );
+        assert!(SanitizerReport::parse_all("==1==ABORTING\n#0 not a report\n").is_empty());
+    }
+    #[test]
+    fn miri_diagnostics_are_parsed() {
+        let stderr = "\
+warning: unused variable: `y`
+ --> src/main.rs:9:9
+  |
+9 |     let y = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_y`
+
+error: Undefined Behavior: memory access failed: attempting to access 4 bytes, but got alloc1234+0xc which is at or beyond the end of the allocation of size 12 bytes
+ --> src/main.rs:4:22
+  |
+4 |     let x = unsafe { *p.add(3) };
+  |                      ^^^^^^^^^ Undefined Behavior occurred here
+  |
+  = help: this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior
+help: alloc1234 was allocated here:
+ --> src/main.rs:2:13
+  |
+2 |     let v = vec![1u32, 2, 3];
+  |             ^^^^^^^^^^^^^^^^
+  = note: BACKTRACE (of the first span):
+  = note: inside `read` at src/main.rs:4:22: 4:31
+note: inside `main`
+ --> src/main.rs:8:5
+  |
+8 |     read(&v);
+  |     ^^^^^^^^
+
+note: some details are omitted, run with `MIRIFLAGS=-Zmiri-backtrace=full` for a verbose backtrace
+
+error: aborting due to 1 previous error; 1 warning emitted
+
+error: process didn't exit successfully: `cargo-miri runner target/miri/debug/app` (exit status: 1)
+";
+        let diagnostics = MiriDiagnostic::parse_all(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        let ub = &diagnostics[0];
+        assert_eq!(ub.kind.as_deref(), Some("Undefined Behavior"));
+        assert!(ub.message.starts_with("memory access failed"));
+        assert_eq!(
+            ub.span,
+            Some(SourceSpan {
+                file: "src/main.rs".to_string(),
+                line: 4,
+                column: 22
+            })
+        );
+        assert_eq!(ub.help.len(), 1);
+        assert_eq!(ub.related[0].0, "alloc1234 was allocated here");
+        assert_eq!(ub.related[0].1.line, 2);
+        let backtrace: Vec<_> = ub
+            .backtrace
+            .iter()
+            .map(|(function, span)| (function.as_str(), span.as_ref().map(|s| s.line)))
+            .collect();
+        assert_eq!(backtrace, [("read", Some(4)), ("main", Some(8))]);
+        assert_eq!(ub.notes.len(), 1);
+
+        let numbered = "error: Undefined Behavior: using uninitialized data\n --> src/lib.rs:3:5\n  = note: stack backtrace:\n          0: app::f\n              at src/lib.rs:3:5: 3:9\n          1: main\n              at src/main.rs:2:5: 2:8\n";
+        let diagnostic = &MiriDiagnostic::parse_all(numbered)[0];
+        assert_eq!(diagnostic.backtrace.len(), 2);
+        assert_eq!(diagnostic.backtrace[0].0, "app::f");
+    }
+    #[test]
+    fn heap_logs_add_up_to_live_allocations() {
+        let log = "\
++ 2a 1000 400 55d0 55e0
++ 2a 2000 10 55d0 55f0
++ 2a 3000 400 55d0 55e0
+- 2a 2000
++ 2b 9000 ffff 1
++ 2a 4000 20 55d0 55f0
+- 2a 5000
+";
+        let profile = HeapProfile::parse(log, Some(0x2a));
+        assert_eq!((profile.allocations, profile.frees), (4, 1));
+        assert_eq!(
+            profile.sites,
+            [
+                AllocationSite {
+                    stack: vec![0x55d0, 0x55e0],
+                    count: 2,
+                    bytes: 0x800
+                },
+                AllocationSite {
+                    stack: vec![0x55d0, 0x55f0],
+                    count: 1,
+                    bytes: 0x20
+                }
+            ]
+        );
+
+        let frame = FrameInfo::from_lldb_lookup(
+            0,
+            "0x55d0",
+            "      Address: app[0x55d0] (app.PT_LOAD[1]..text + 16)\n      Summary: app`app::leak::h0123456789abcdef + 20 at main.rs:2:5\n",
+        );
+        assert_eq!(
+            frame.function.as_deref(),
+            Some("app::leak::h0123456789abcdef")
+        );
+        assert_eq!(
+            (frame.file.as_deref(), frame.line),
+            (Some("main.rs"), Some(2))
+        );
+        let frame = FrameInfo::from_gdb_info_line(
+            1,
+            "0x55e0",
+            "Line 8 of \"src/main.rs\" starts at address 0x55dc <app::main+28> and ends at 0x55e4 <app::main+36>.",
+        );
+        assert_eq!(frame.function.as_deref(), Some("app::main"));
+        assert_eq!(
+            (frame.file.as_deref(), frame.line),
+            (Some("src/main.rs"), Some(8))
+        );
+    }
+
+    #[test]
+    fn cpu_samples_fold_into_stacks() {
+        let perf = "             app  4242 12345.678901:   1010101 cpu-clock:u: \n\
+                    \t    55d4a1b2c3d4 app::hot+0x14 (/work/target/debug/app)\n\
+                    \t    55d4a1b2c400 app::main+0x20 (/work/target/debug/app)\n\
+                    \n\
+                    \x20            app  4242 12345.679911:   1010101 cpu-clock:u: \n\
+                    \t    55d4a1b2c3d8 app::hot+0x18 (/work/target/debug/app)\n\
+                    \t    55d4a1b2c400 app::main+0x20 (/work/target/debug/app)\n\
+                    \n\
+                    \x20            app  4242 12345.680921:   1010101 cpu-clock:u: \n\
+                    \t    7f0000001234 [unknown] ([unknown])\n\
+                    \t    55d4a1b2c400 app::main+0x20 (/work/target/debug/app)\n";
+        let profile = CpuProfile::parse_perf_script(perf);
+        assert_eq!(profile.samples(), 3);
+        assert_eq!(
+            profile.folded(),
+            vec!["app::main;app::hot 2", "app::main;0x7f0000001234 1"]
+        );
+        let hottest = profile.hottest();
+        assert_eq!(hottest[0].function, "app::hot");
+        assert_eq!((hottest[0].self_samples, hottest[0].total_samples), (2, 2));
+        let main = hottest.iter().find(|f| f.function == "app::main").unwrap();
+        assert_eq!((main.self_samples, main.total_samples), (0, 3));
+
+        let sample = "Analysis of sampling app (pid 4242) every 1 millisecond\n\
+                      Call graph:\n\
+                      \x20   10 Thread_1   DispatchQueue_1: com.apple.main-thread  (serial)\n\
+                      \x20   + 10 start  (in dyld) + 1  [0x7fff0001]\n\
+                      \x20   +   10 app::main  (in app) + 22  [0x100003f6a]  main.rs:5\n\
+                      \x20   +     7 app::hot  (in app) + 10  [0x100003f00]  main.rs:10\n\
+                      \x20   +     2 app::cold  (in app) + 4  [0x100003e00]  main.rs:14\n\
+                      \n\
+                      Total number in stack (recursive counted multiple, when >=5):\n";
+        let profile = CpuProfile::parse_sample(sample);
+        assert_eq!(profile.samples(), 10);
+        assert_eq!(
+            profile.folded(),
+            vec![
+                "start;app::main;app::hot 7",
+                "start;app::main;app::cold 2",
+                "start;app::main 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn dtruss_lines_become_syscalls() {
+        let output = "SYSCALL(args) \t\t = return\n\
+                      open(\"/etc/hosts\\0\", 0x0, 0x1B6)\t\t = 3 0\n\
+                      read(0x3, \"a, b\\0\", 0x1000)\t\t = 5 0\n\
+                      stat64(\"/missing\\0\", 0x7FF7BFEFF1A0, 0x0)\t\t = -1 2\n";
+        let calls = Syscall::parse_dtruss(output);
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].name, "open");
+        assert_eq!(calls[0].path.as_deref(), Some("/etc/hosts"));
+        assert_eq!(calls[0].result, Some(3));
+        assert_eq!(calls[1].args, vec!["0x3", "\"a, b\"", "0x1000"]);
+        assert_eq!(calls[2].errno, Some(2));
+        assert_eq!(calls[2].to_json()["error"], "ENOENT");
+
+        let mut call = calls[0].clone();
+        call.set_return_value(-13);
+        assert_eq!((call.result, call.errno), (Some(-1), Some(13)));
+    }
+
+    #[test]
+    fn watchpoint_hits_come_from_mi_stop_records() {
+        let MiRecord::Exec { results, .. } = MiRecord::parse(
+            "*stopped,reason=\"watchpoint-trigger\",wpt={number=\"2\",exp=\"-location s.count\"},value={old=\"0\",new=\"1\"},thread-id=\"1\"",
+        ) else {
+            panic!("expected a stop record");
+        };
+        let hit = WatchpointHit::from_mi(&results).unwrap();
+        assert_eq!(hit.id, 2);
+        assert_eq!(hit.old_value.as_deref(), Some("0"));
+        assert_eq!(hit.new_value.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn lldb_stop_reasons_name_the_watchpoint() {
+        assert_eq!(
+            watchpoint_hit("* thread #1, name = 'app', stop reason = watchpoint 3\n"),
+            Some(3)
+        );
+        assert_eq!(
+            watchpoint_hit("* thread #1, name = 'app', stop reason = breakpoint 1.1\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn lldb_watchpoint_listings_give_old_and_new_values() {
+        let listing = "Watchpoint 3: addr = 0x7ffe10 size = 4 state = enabled type = w\n    \
+                       watchpoint spec = 's.count'\n    old value: 0\n    new value: 1\n";
+        let hit = WatchpointHit::parse_lldb(3, listing);
+        assert_eq!(hit.old_value.as_deref(), Some("0"));
+        assert_eq!(hit.new_value.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn side_effect_allows_reads_comparisons_and_casts() {
+        for pure in [
+            "self.items[0].name",
+            "a == b && c != d",
+            "len <= cap",
+            "sizeof(header)",
+            "*(ptr as *const u8)",
+            "(char *)(buf)",
+            "\"f(x) = 1\"",
+            "match_count",
+        ] {
+            assert_eq!(side_effect(pure), None, "{}", pure);
+        }
+    }
+
+    #[test]
+    fn side_effect_finds_function_calls() {
+        assert_eq!(side_effect("queue.len()"), Some("a function call"));
+        assert_eq!(side_effect("reset (x)"), Some("a function call"));
+        assert_eq!(
+            side_effect("\"42\".parse::<u32>()"),
+            Some("a function call")
+        );
+    }
+
+    #[test]
+    fn side_effect_finds_assignments() {
+        assert_eq!(side_effect("x = 1"), Some("an assignment"));
+        assert_eq!(side_effect("flags <<= 2"), Some("an assignment"));
+        assert_eq!(side_effect("n += 1"), Some("an assignment"));
+        assert_eq!(side_effect("i++"), Some("an assignment"));
+    }
+
+    #[test]
+    fn module_regex_matches_items_and_trait_impls_in_the_module() {
+        assert_eq!(module_regex("my_crate::db", false), "(^|<)my_crate::db::");
+        assert_eq!(module_regex("my_crate::db::", false), "(^|<)my_crate::db::");
+    }
+
+    #[test]
+    fn module_regex_uses_basic_syntax_for_gdb() {
+        assert_eq!(module_regex("my_crate", true), "\\(^\\|<\\)my_crate::");
+    }
+
+    #[test]
+    fn module_regex_keeps_regular_expressions() {
+        assert_eq!(
+            module_regex("^my_crate::db::.*::get$", false),
+            "^my_crate::db::.*::get$"
+        );
+    }
+
+    const FALLIBLE_SOURCE: &str = "use std::io;
+
+fn load(path: &str) -> Result<String, Error> {
+    if path.is_empty() {
+        return Err(Error::Empty); // not Err( again
+    }
+    let text = read(path).map_err(|e| Error::Io(e))?;
+    let brace = '{';
+    /* Err(in a comment) { */
+    match text.as_str() {
+        \"Err(\" => Ok(text),
+        _ => Err(Error::Parse(format!(\"{}\", brace))),
+    }
+}
+
+fn other() -> Result<(), Error> {
+    Err(Error::Empty)
+}
+";
+
+    #[test]
+    fn error_lines_skip_literals_and_comments() {
+        assert_eq!(error_lines(FALLIBLE_SOURCE, 3), Some((14, vec![5, 12])));
+    }
+
+    #[test]
+    fn error_lines_include_a_tail_err() {
+        assert_eq!(error_lines(FALLIBLE_SOURCE, 16), Some((18, vec![17])));
+    }
+
+    #[test]
+    fn error_lines_need_a_function_body() {
+        assert_eq!(error_lines("fn f();\n", 1), None);
+    }
+
+    #[test]
+    fn libtest_harnesses_are_told_from_other_executables() {
+        let this_test = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        assert!(libtest_harness(&this_test));
+        assert!(!libtest_harness(b"\x7fELF test_main_static"));
+    }
+
+    #[test]
+    fn notable_stderr_keeps_warnings_and_errors() {
+        for line in [
+            "warning: (x86_64) /app/target/debug/app unable to locate dSYM",
+            "error: process launch failed: 'A' packet returned an error: 8",
+            "Couldn't find debug info for libfoo.so",
+            "warning: Error disabling address space randomization: Operation not permitted",
+        ] {
+            assert!(notable_stderr(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn notable_stderr_drops_progress_chatter() {
+        for line in [
+            "Downloading separate debug info for /lib/x86_64-linux-gnu/libc.so.6...",
+            "Current executable set to '/app/target/debug/app' (x86_64).",
+            "",
+        ] {
+            assert!(!notable_stderr(line), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,15438 @@
+//! The MCP server: JSON-RPC request handling and the debugging tools.
+//!
+//! [`DebugServer`] owns at most one debugging session and exposes it through MCP
+//! tools, resources, and prompts. Embed it with
+//! [`DebugServer::handle_request`], or serve it with [`DebugServer::run`],
+//! [`crate::http::serve`], or [`crate::dap::serve`].
+
+use crate::backend::{
+    evaluated_expression, forward_debugger_output, forward_debugger_stderr, is_sentinel,
+    lldb_quote, lldb_sentinel, matching_command, mi_console_command, mi_quote, sandbox_command,
+    single_line, DebuggerKind, HOST_COMMANDS, RAW_COMMANDS_DENIED, SANDBOX_CPU_SECONDS,
+    SANDBOX_MEMORY_KIB,
+};
+use crate::config::{ProjectConfig, ResourceLimits};
+use crate::error::{ErrorKind, ToolError};
+use crate::parse::{
+    async_backtrace, blocking_call, breakpoint_hit, cargo_build_progress, demangle, demangle_text,
+    error_lines, format_hex_dump, format_mi_frame, format_pieces, libtest_harness,
+    mi_frame_location, module_regex, panic_backtrace, parse_hex_bytes, parse_integer,
+    parse_lldb_memory, parse_registers, printable_ascii, side_effect, stop_event, tidy_gdb_value,
+    watchpoint_hit, AsyncTask, CargoArtifact, CheckpointInfo, CompilerDiagnostic, CpuProfile,
+    DebuggerEvent, ExitStatus, FrameInfo, HeapProfile, InferiorInfo, Instruction, MemoryType,
+    MiOutput, MiRecord, MiriDiagnostic, ModuleInfo, PanicMessage, SanitizerReport, StopSignal,
+    SymbolInfo, SymbolKind, Syscall, ThreadInfo, Variable, WatchpointHit, SYSCALL_PATH_ARGUMENTS,
+    TIMEOUT_NOTE,
+};
+use crate::session::{
+    enforce_resource_limits, truncate_json, Breakpoint, BreakpointAction, BuildConfig,
+    DebugSession, DebugState, EvalLanguage, EvalOptions, FollowFork, History, LaunchConfig,
+    OutputPages, ProgramOutput, Sanitizer, ValueRegion, WatchMode, Watchpoint, HEAP_LOG_VARIABLE,
+};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::ChildStdin;
+use tokio::sync::{broadcast, mpsc, Mutex, Notify};
+use tracing::Instrument;
+
+/// Tools that run or modify the program, which a post-mortem session cannot do.
+const EXECUTION_TOOLS: [&str; 17] = [
+    "debug_continue",
+    "debug_run_to",
+    "debug_return",
+    "debug_jump",
+    "debug_step",
+    "debug_step_into",
+    "debug_step_out",
+    "debug_finish",
+    "debug_until",
+    "debug_step_instruction",
+    "debug_wait_for_stop",
+    "debug_poll",
+    "debug_pause",
+    "debug_restart",
+    "debug_detach",
+    "debug_memory_write",
+    "debug_stdin_write",
+];
+
+/// Tools whose results report where the program stopped, and so also carry the
+/// values of watched expressions.
+const STOP_REPORT_TOOLS: [&str; 25] = [
+    "debug_run",
+    "debug_test",
+    "debug_launch_command",
+    "debug_attach",
+    "debug_open_core",
+    "debug_connect_target",
+    "debug_replay",
+    "debug_continue",
+    "debug_run_to",
+    "debug_return",
+    "debug_jump",
+    "debug_step",
+    "debug_step_into",
+    "debug_step_out",
+    "debug_finish",
+    "debug_until",
+    "debug_step_instruction",
+    "debug_wait_for_stop",
+    "debug_poll",
+    "debug_pause",
+    "debug_restart",
+    "debug_reverse_continue",
+    "debug_reverse_step",
+    "debug_reverse_step_into",
+    "debug_restore_checkpoint",
+];
+
+/// Tools that do not take the session's tool lock: they send no commands, or, like
+/// `debug_pause`, must reach a program that a call holding the lock waits on.
+const CONCURRENT_TOOLS: [&str; 4] = [
+    "debug_pause",
+    "debug_state",
+    "debug_sessions",
+    "debug_more_output",
+];
+
+/// Functions that `debug_run` breaks on by default so panics stop the program.
+///
+/// `rust_panic` is reached after the panic hook has printed its message, while
+/// `panic_fmt` stops before; `abort` catches `panic = "abort"` builds and double panics.
+const PANIC_BREAKPOINTS: [&str; 3] = ["rust_panic", "core::panicking::panic_fmt", "abort"];
+
+/// Where unwinding begins, for panics and for C++ exceptions thrown through FFI alike;
+/// no frame has been unwound yet when it is reached.
+const UNWIND_BREAKPOINTS: [&str; 1] = ["_Unwind_RaiseException"];
+
+/// Commands that, ending a breakpoint's `on_hit_commands`, continue past the hit.
+const CONTINUE_COMMANDS: [&str; 4] = ["continue", "c", "process continue", "-exec-continue"];
+
+/// Most elements `debug_eval_slice` reads.
+const MAX_SLICE_ELEMENTS: u64 = 1000;
+
+/// Frames of the crashing thread `debug_triage_crash` reports.
+const TRIAGE_FRAMES: u32 = 32;
+
+/// Instructions `debug_triage_crash` shows on either side of the program counter.
+const TRIAGE_INSTRUCTIONS: usize = 8;
+
+/// Allocation sites `debug_heap_profile` reports unless asked for another number.
+const HEAP_PROFILE_SITES: u64 = 10;
+
+/// Frames of each allocation site's stack `debug_heap_profile` reports.
+const HEAP_PROFILE_FRAMES: usize = 16;
+
+/// Frames of the writing stack in a `field_change` stop report.
+const FIELD_CHANGE_FRAMES: u32 = 16;
+
+/// Source of the allocation tracker `debug_heap_profile` builds and preloads.
+const HEAP_TRACKER_SOURCE: &str = include_str!("../preload/heap_tracker.rs");
+
+/// Functions `debug_profile` reports, those with the most samples first.
+const PROFILE_HOT_FUNCTIONS: usize = 20;
+
+/// Call stacks `debug_profile` reports in folded form, those with the most samples first.
+const PROFILE_FOLDED_STACKS: usize = 200;
+
+/// Syscalls `debug_syscalls` records before it stops tracing.
+const SYSCALL_TRACE_LIMIT: usize = 1000;
+
+/// How long `dtruss` gets to start tracing before the program is continued.
+const DTRUSS_STARTUP: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Lines `debug_source` shows when not told which.
+const SOURCE_LINES: u32 = 20;
+
+/// Most lines `debug_source` shows at once.
+const MAX_SOURCE_LINES: u32 = 500;
+
+/// Most fields or elements listed per node of a followed smart pointer's value.
+const SMART_POINTER_CHILDREN: u32 = 20;
+
+/// MCP resource with the selected thread's backtrace.
+const BACKTRACE_RESOURCE: &str = "ferroscope://session/backtrace";
+/// MCP resource listing breakpoints and watchpoints.
+const BREAKPOINTS_RESOURCE: &str = "ferroscope://session/breakpoints";
+/// MCP resource with the debugger commands sent and the output received.
+const LOG_RESOURCE: &str = "ferroscope://session/log";
+
+/// MCP protocol revisions the server speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// MCP log levels from least to most severe, as used by `logging/setLevel`.
+const LOG_LEVELS: [&str; 8] = [
+    "debug",
+    "info",
+    "notice",
+    "warning",
+    "error",
+    "critical",
+    "alert",
+    "emergency",
+];
+
+/// A canned debugging workflow offered through MCP prompts.
+struct PromptTemplate {
+    name: &'static str,
+    description: &'static str,
+    /// Argument names, descriptions, and whether each is required
+    arguments: &'static [(&'static str, &'static str, bool)],
+}
+
+const PROMPTS: [PromptTemplate; 3] = [
+    PromptTemplate {
+        name: "diagnose_panic",
+        description:
+            "Run a program until it panics and explain the cause from its stack and locals",
+        arguments: &[
+            (
+                "binary_path",
+                "Rust project directory or binary that panics",
+                true,
+            ),
+            (
+                "args",
+                "Command-line arguments that trigger the panic, space separated",
+                false,
+            ),
+        ],
+    },
+    PromptTemplate {
+        name: "inspect_variable",
+        description: "Find out why a variable holds the wrong value at a given line",
+        arguments: &[
+            ("binary_path", "Rust project directory or binary", true),
+            (
+                "location",
+                "Line where the value is wrong, as file:line",
+                true,
+            ),
+            (
+                "variable",
+                "Variable or expression with the wrong value",
+                true,
+            ),
+            ("expected", "The value it should have", false),
+        ],
+    },
+    PromptTemplate {
+        name: "bisect_failing_test",
+        description: "Narrow a failing test down to the first statement where its state goes wrong",
+        arguments: &[
+            (
+                "project_path",
+                "Rust project directory containing the test",
+                true,
+            ),
+            ("test_name", "Full name of the failing test", true),
+        ],
+    },
+];
+
+impl PromptTemplate {
+    fn to_json(&self) -> Value {
+        let arguments: Vec<Value> = self
+            .arguments
+            .iter()
+            .map(|(name, description, required)| {
+                json!({"name": name, "description": description, "required": required})
+            })
+            .collect();
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "arguments": arguments
+        })
+    }
+
+    /// Fills in the workflow's instructions; `arg` looks up an argument, giving ""
+    /// for optional ones left out.
+    fn render(&self, arg: impl Fn(&str) -> String) -> String {
+        match self.name {
+            "diagnose_panic" => format!(
+                "Diagnose the panic in {binary}{args}.\n\n\
+                 1. Call debug_run with binary_path \"{binary}\"{args_json}; panic breakpoints are set by default.\n\
+                 2. Call debug_continue and wait for the program to stop on rust_panic.\n\
+                 3. Call debug_panic_info for the panic message, its location, and the backtrace of the program's own code.\n\
+                 4. Call debug_select_frame on the innermost frame of that backtrace, then debug_locals to see the values that led to the panic.\n\
+                 5. Use debug_eval on the expressions involved in the failing operation.\n\
+                 6. Explain the root cause and propose a fix, then call debug_kill.",
+                binary = arg("binary_path"),
+                args = match arg("args") {
+                    args if args.is_empty() => String::new(),
+                    args => format!(" when run with `{}`", args),
+                },
+                args_json = match arg("args") {
+                    args if args.is_empty() => String::new(),
+                    args => format!(" and args {}", json!(args.split_whitespace().collect::<Vec<_>>())),
+                },
+            ),
+            "inspect_variable" => format!(
+                "Find out why `{variable}` is wrong at {location} in {binary}{expected}.\n\n\
+                 1. Call debug_run with binary_path \"{binary}\".\n\
+                 2. Call debug_break at \"{location}\", then debug_continue until it stops there.\n\
+                 3. Call debug_eval on \"{variable}\" and debug_locals to confirm the bad value.\n\
+                 4. Call debug_watch on \"{variable}\" and debug_restart, so the program stops each time it is written.\n\
+                 5. At every stop, check debug_backtrace and the new value until you find the write that makes it wrong.\n\
+                 6. Explain which code produced the wrong value and why, then call debug_kill.",
+                variable = arg("variable"),
+                location = arg("location"),
+                binary = arg("binary_path"),
+                expected = match arg("expected") {
+                    expected if expected.is_empty() => String::new(),
+                    expected => format!(" (expected {})", expected),
+                },
+            ),
+            _ => format!(
+                "Bisect the failing test `{test}` in {project}.\n\n\
+                 1. Run `cargo test --no-run` in {project} and note the test binary it reports under target/debug/deps.\n\
+                 2. Call debug_run with that binary and args [\"{test}\", \"--exact\", \"--nocapture\", \"--test-threads=1\"].\n\
+                 3. Set a breakpoint with debug_break halfway through the test body and debug_continue to it.\n\
+                 4. Use debug_locals and debug_eval to decide whether the state is still correct there.\n\
+                 5. If it is, move the breakpoint halfway toward the failing assertion; if not, halfway back toward the start. Use debug_restart to rerun with the new breakpoint.\n\
+                 6. Repeat until two adjacent statements separate good state from bad, step into the second with debug_step_into, and explain the bug. Finish with debug_kill.",
+                test = arg("test_name"),
+                project = arg("project_path"),
+            ),
+        }
+    }
+}
+
+/// JSON Schema of a tool's result, declared as its `outputSchema` in `tools/list`.
+///
+/// Every result may carry `success`, `state`, `output`, and `error`; tools add their
+/// own fields, and results may hold more than the schema lists.
+fn tool_output_schema(tool: &str) -> Value {
+    let typed =
+        |types: Value, description: &str| json!({"type": types, "description": description});
+    let stop_report = || {
+        vec![
+            (
+                "location",
+                typed(
+                    json!(["string", "null"]),
+                    "file:line where the program stopped",
+                ),
+            ),
+            (
+                "signal",
+                typed(
+                    json!(["object", "null"]),
+                    "Signal that stopped the program, with name, description, and fault_address",
+                ),
+            ),
+            (
+                "exit_code",
+                typed(
+                    json!(["integer", "null"]),
+                    "Exit code once the program has exited",
+                ),
+            ),
+            (
+                "terminated_by_signal",
+                typed(json!(["string", "null"]), "Signal that killed the program"),
+            ),
+            (
+                "program_stdout",
+                typed(
+                    json!("string"),
+                    "Program stdout captured since the last call",
+                ),
+            ),
+            (
+                "program_stderr",
+                typed(
+                    json!("string"),
+                    "Program stderr captured since the last call",
+                ),
+            ),
+            (
+                "watches",
+                typed(
+                    json!("array"),
+                    "Values of the debug_watch_expr expressions at this stop",
+                ),
+            ),
+            (
+                "source",
+                typed(
+                    json!("object"),
+                    "Source lines around the stop, with path, line, and text",
+                ),
+            ),
+            (
+                "new_inferiors",
+                typed(
+                    json!("array"),
+                    "Forked processes the debugger picked up since the last report, with pid and inferior",
+                ),
+            ),
+            (
+                "sanitizer_findings",
+                typed(
+                    json!("array"),
+                    "Sanitizer reports in the new stderr, with sanitizer, kind, address, summary, user_frame, and stacks",
+                ),
+            ),
+            (
+                "field_change",
+                typed(
+                    json!("object"),
+                    "Write that hit a debug_watch_field watchpoint, with watchpoint_id, field, old_value, new_value, user_frame, and backtrace",
+                ),
+            ),
+        ]
+    };
+
+    let mut properties = serde_json::Map::new();
+    properties.insert(
+        "success".into(),
+        typed(json!("boolean"), "Whether the tool did what was asked"),
+    );
+    properties.insert(
+        "state".into(),
+        typed(
+            json!("string"),
+            "Session state afterwards, such as loaded, running, or stopped",
+        ),
+    );
+    properties.insert(
+        "output".into(),
+        typed(json!("string"), "Raw debugger output"),
+    );
+    properties.insert(
+        "truncated".into(),
+        typed(
+            json!("boolean"),
+            "Set when the result was larger than max_output_bytes and its longest strings and arrays were cut",
+        ),
+    );
+    properties.insert(
+        "total_bytes".into(),
+        typed(
+            json!("integer"),
+            "Size of the complete result, in bytes of JSON",
+        ),
+    );
+    properties.insert(
+        "cursor".into(),
+        typed(
+            json!("string"),
+            "Pass to debug_more_output to page through the complete result",
+        ),
+    );
+    properties.insert(
+        "debugger_stderr".into(),
+        typed(
+            json!("array"),
+            "Warnings and errors the debugger printed to stderr since the last tool call, such as missing debug info",
+        ),
+    );
+    properties.insert(
+        "error".into(),
+        typed(
+            json!("string"),
+            "Why the tool failed, when success is false",
+        ),
+    );
+    properties.insert(
+        "error_kind".into(),
+        json!({
+            "type": "string",
+            "enum": ErrorKind::ALL.map(ErrorKind::name),
+            "description": "Machine-readable reason, when success is false"
+        }),
+    );
+
+    let extra = match tool {
+        "debug_run"
+        | "debug_attach"
+        | "debug_open_core"
+        | "debug_connect_target"
+        | "debug_replay" => {
+            let mut extra = stop_report();
+            extra.extend([
+                (
+                    "binary_path",
+                    typed(json!("string"), "Binary being debugged"),
+                ),
+                (
+                    "debugger",
+                    typed(json!("string"), "Debugger driving the session"),
+                ),
+                (
+                    "launch",
+                    typed(
+                        json!("object"),
+                        "Arguments, environment, working directory, and stdin for launches",
+                    ),
+                ),
+                (
+                    "panic_breakpoints",
+                    typed(json!("array"), "Breakpoints set to stop on panics"),
+                ),
+                (
+                    "sanitizer_breakpoints",
+                    typed(
+                        json!("array"),
+                        "Breakpoints set on the sanitizers' report functions",
+                    ),
+                ),
+                (
+                    "config",
+                    typed(
+                        json!("object"),
+                        "Settings read from ferroscope.toml: files, debugger_path, timeout_ms, \
+                         breakpoints, limits, and source_map",
+                    ),
+                ),
+                (
+                    "config_breakpoints",
+                    typed(json!("array"), "Breakpoints ferroscope.toml asked for"),
+                ),
+                (
+                    "limits",
+                    typed(
+                        json!(["object", "null"]),
+                        "The program's cpu_seconds, memory_mb, open_files, and output_mb limits, when any is set",
+                    ),
+                ),
+                (
+                    "sandbox",
+                    typed(
+                        json!(["object", "null"]),
+                        "With --sandbox, the program's working_directory, cpu_seconds and memory_kib limits, and whether it has network access",
+                    ),
+                ),
+                (
+                    "restored_breakpoints",
+                    typed(
+                        json!("array"),
+                        "Breakpoints loaded from the project's .ferroscope/breakpoints.json, with location, id, old_id, and resolved_locations",
+                    ),
+                ),
+                (
+                    "build",
+                    typed(
+                        json!("object"),
+                        "Package, bin, example or bench, features, and profile built, for source directories",
+                    ),
+                ),
+                (
+                    "warnings",
+                    typed(
+                        json!("array"),
+                        "Why debugging the build may be unreliable, e.g. optimization",
+                    ),
+                ),
+                (
+                    "debug_info",
+                    typed(
+                        json!("object"),
+                        "Whether the binary has debug info, and the dwp, dsym, dwo_files, \
+                         and debuginfod_urls it was looked up in",
+                    ),
+                ),
+            ]);
+            extra
+        }
+        "debug_miri" => vec![
+            ("command", typed(json!("string"), "The cargo command run")),
+            (
+                "passed",
+                typed(json!("boolean"), "Whether the program or tests ran without error"),
+            ),
+            (
+                "exit_code",
+                typed(json!(["integer", "null"]), "Exit code of cargo miri"),
+            ),
+            (
+                "undefined_behavior",
+                typed(json!("boolean"), "Whether Miri found undefined behavior"),
+            ),
+            (
+                "findings",
+                typed(
+                    json!("array"),
+                    "Errors Miri reported, with kind, message, span, source, help, notes, related, and backtrace",
+                ),
+            ),
+            ("summary", typed(json!("string"), "The first finding, in words")),
+            ("stdout", typed(json!("string"), "What the program printed to stdout")),
+            (
+                "stderr",
+                typed(json!("string"), "What cargo, Miri, and the program printed to stderr"),
+            ),
+        ],
+        "debug_test" => {
+            let mut extra = stop_report();
+            extra.extend([
+                ("test", typed(json!("string"), "Full path of the test being run")),
+                (
+                    "binary_path",
+                    typed(json!("string"), "Test binary being debugged"),
+                ),
+                (
+                    "breakpoint",
+                    typed(json!("object"), "Breakpoint set on the test function"),
+                ),
+                (
+                    "candidates",
+                    typed(
+                        json!("array"),
+                        "Tests test_name matched, when it matched several",
+                    ),
+                ),
+            ]);
+            extra
+        }
+        "debug_launch_command" => {
+            let mut extra = stop_report();
+            extra.extend([
+                (
+                    "program",
+                    typed(
+                        json!("string"),
+                        "Executable the debugger runs: the command, or a script's interpreter",
+                    ),
+                ),
+                ("debugger", typed(json!("string"), "Debugger in use")),
+                (
+                    "launch",
+                    typed(
+                        json!("object"),
+                        "Arguments, environment, working directory, and stdin of the launch",
+                    ),
+                ),
+                (
+                    "debug_info",
+                    typed(json!("object"), "Whether the program has debug info"),
+                ),
+            ]);
+            extra
+        }
+        "debug_continue" => {
+            let mut extra = stop_report();
+            extra.push((
+                "continued_hits",
+                typed(
+                    json!("integer"),
+                    "Hits of log_only or auto-continuing breakpoints continued past",
+                ),
+            ));
+            extra
+        }
+        "debug_run_to"
+        | "debug_step"
+        | "debug_step_into"
+        | "debug_step_out"
+        | "debug_until"
+        | "debug_step_instruction"
+        | "debug_wait_for_stop"
+        | "debug_poll"
+        | "debug_pause"
+        | "debug_restart"
+        | "debug_reverse_continue"
+        | "debug_reverse_step"
+        | "debug_reverse_step_into"
+        | "debug_monitor" => stop_report(),
+        "debug_return" | "debug_jump" => {
+            let mut extra = stop_report();
+            extra.push((
+                "frame",
+                typed(
+                    json!(["object", "null"]),
+                    "The frame now executing, with function, file, line, and pc",
+                ),
+            ));
+            extra
+        }
+        "debug_rebuild" => vec![
+            (
+                "changed",
+                typed(
+                    json!("boolean"),
+                    "Whether the binary changed and was reloaded",
+                ),
+            ),
+            (
+                "binary_path",
+                typed(json!("string"), "Binary being debugged"),
+            ),
+            (
+                "breakpoints",
+                typed(
+                    json!("array"),
+                    "Breakpoints set again in the reloaded binary, with their new and old IDs",
+                ),
+            ),
+            (
+                "dropped_watchpoints",
+                typed(json!("array"), "Watchpoints that could not carry over"),
+            ),
+        ],
+        "debug_recover" => vec![
+            (
+                "recovered_from",
+                typed(
+                    json!(["string", "null"]),
+                    "How the previous debugger exited",
+                ),
+            ),
+            (
+                "binary_path",
+                typed(json!("string"), "Binary loaded into the new debugger"),
+            ),
+            (
+                "breakpoints",
+                typed(
+                    json!("array"),
+                    "Breakpoints set again in the new debugger, with their new and old IDs",
+                ),
+            ),
+            (
+                "dropped_watchpoints",
+                typed(json!("array"), "Watchpoints that could not carry over"),
+            ),
+        ],
+        "debug_finish" => {
+            let mut extra = stop_report();
+            extra.push((
+                "return_value",
+                typed(
+                    json!(["object", "null"]),
+                    "Value the function returned, with type and value",
+                ),
+            ));
+            extra
+        }
+        "debug_record" => vec![(
+            "trace_dir",
+            typed(json!("string"), "Directory of the rr trace to replay"),
+        )],
+        "debug_break" => vec![
+            ("id", typed(json!(["integer", "null"]), "Breakpoint ID")),
+            ("location", typed(json!("string"), "Requested location")),
+            (
+                "resolved_locations",
+                typed(
+                    json!("integer"),
+                    "Code locations the breakpoint resolved to",
+                ),
+            ),
+            (
+                "condition",
+                typed(
+                    json!(["string", "null"]),
+                    "Condition the breakpoint stops on",
+                ),
+            ),
+            (
+                "ignore_count",
+                typed(json!(["integer", "null"]), "Hits ignored before stopping"),
+            ),
+            (
+                "log_only",
+                typed(
+                    json!("boolean"),
+                    "Whether hits are logged for debug_trace_log instead of stopping",
+                ),
+            ),
+            (
+                "on_hit_commands",
+                typed(
+                    json!("array"),
+                    "Debugger commands run on each hit, without a final continue",
+                ),
+            ),
+            (
+                "auto_continue",
+                typed(
+                    json!("boolean"),
+                    "Whether on_hit_commands ended in continue, so hits do not stop",
+                ),
+            ),
+        ],
+        "debug_trace_log" => vec![
+            (
+                "entries",
+                typed(
+                    json!("array"),
+                    "Hits with breakpoint_id, hit, timestamp_ms, location, thread_id, and values, plus function and args for debug_trace_calls",
+                ),
+            ),
+            (
+                "dropped",
+                typed(
+                    json!("integer"),
+                    "Oldest entries dropped because the log was full",
+                ),
+            ),
+        ],
+        "debug_watch" => vec![
+            ("id", typed(json!(["integer", "null"]), "Watchpoint ID")),
+            (
+                "target",
+                typed(json!("string"), "Watched variable or address"),
+            ),
+            ("mode", typed(json!("string"), "write, read, or read_write")),
+        ],
+        "debug_watch_field" => vec![
+            ("id", typed(json!(["integer", "null"]), "Watchpoint ID")),
+            ("field", typed(json!("string"), "The watched object_expr.field")),
+            (
+                "address",
+                typed(json!(["string", "null"]), "Address of the field"),
+            ),
+            (
+                "size",
+                typed(json!(["integer", "null"]), "Bytes watched, the field's size"),
+            ),
+            (
+                "value",
+                typed(json!(["string", "null"]), "The field's value now"),
+            ),
+        ],
+        "debug_modules" => vec![
+            (
+                "modules",
+                typed(
+                    json!("array"),
+                    "Loaded images with path, load_address, uuid, symbol_file, and has_debug_info",
+                ),
+            ),
+            (
+                "missing_debug_info",
+                typed(json!("array"), "Paths of images found to have no debug info"),
+            ),
+        ],
+        "debug_record_values" => vec![
+            ("expression", typed(json!("string"), "The expression recorded")),
+            ("steps", typed(json!("integer"), "Lines stepped through")),
+            (
+                "values",
+                typed(json!("array"), "The value at each step, with step and location"),
+            ),
+            (
+                "changes",
+                typed(
+                    json!("array"),
+                    "Steps where the value changed, with step, location, from, and to",
+                ),
+            ),
+            (
+                "ended",
+                typed(
+                    json!("string"),
+                    "Why recording ended: left_region, max_steps, stopped_in_callee, signal, or program_ended",
+                ),
+            ),
+            (
+                "location",
+                typed(json!(["string", "null"]), "Where the program is stopped now"),
+            ),
+            ("summary", typed(json!("string"), "How many values over how many steps")),
+        ],
+        "debug_break_on_error" => vec![
+            ("function", typed(json!("string"), "Function whose errors are watched")),
+            ("file", typed(json!("string"), "Source file of the function")),
+            ("lines", typed(json!("array"), "First and last line of the function")),
+            (
+                "error_breakpoints",
+                typed(
+                    json!("array"),
+                    "Breakpoints on the lines constructing Err, with location, id, success, and the line's text",
+                ),
+            ),
+            (
+                "unwind_breakpoints",
+                typed(json!("array"), "Breakpoints where unwinding begins, with location and id"),
+            ),
+            ("message", typed(json!("string"), "Why no error breakpoints were set")),
+        ],
+        "debug_break_module" => vec![
+            ("pattern", typed(json!("string"), "Requested module path or regex")),
+            (
+                "regex",
+                typed(json!("string"), "Regular expression function names were matched with"),
+            ),
+            ("ids", typed(json!("array"), "IDs of the breakpoints that were set")),
+            (
+                "resolved_locations",
+                typed(json!("integer"), "Functions the breakpoints resolved to"),
+            ),
+        ],
+        "debug_trace_calls" => vec![
+            ("pattern", typed(json!("string"), "Regular expression functions were matched with")),
+            ("count", typed(json!("integer"), "Functions being traced")),
+            (
+                "functions",
+                typed(
+                    json!("array"),
+                    "Traced functions with function, the breakpoint id, and resolved_locations",
+                ),
+            ),
+            (
+                "skipped",
+                typed(json!("array"), "Matching functions no breakpoint could be set on"),
+            ),
+            (
+                "truncated",
+                typed(json!("boolean"), "Whether more functions matched than max_functions"),
+            ),
+            ("args", typed(json!("boolean"), "Whether arguments are recorded with each call")),
+            ("message", typed(json!("string"), "How to record and read the calls")),
+        ],
+        "debug_symbols" => vec![
+            ("pattern", typed(json!("string"), "Regular expression searched for")),
+            ("kind", typed(json!("string"), "functions, types, or variables")),
+            (
+                "symbols",
+                typed(
+                    json!("array"),
+                    "Matches with name, location, module, and address",
+                ),
+            ),
+            (
+                "truncated",
+                typed(json!("boolean"), "Whether there were more matches than returned"),
+            ),
+        ],
+        "debug_source" => vec![
+            ("path", typed(json!("string"), "Source file that was read")),
+            ("start_line", typed(json!("integer"), "First line shown")),
+            ("end_line", typed(json!("integer"), "Last line shown")),
+            (
+                "text",
+                typed(
+                    json!("string"),
+                    "Numbered lines, the current line marked with >",
+                ),
+            ),
+        ],
+        "debug_set_source_map" => vec![
+            (
+                "source_map",
+                typed(json!("array"), "Every mapping now in effect, as from and to"),
+            ),
+            (
+                "applied",
+                typed(
+                    json!("boolean"),
+                    "Whether the active session's debugger took the mapping",
+                ),
+            ),
+        ],
+        "debug_watch_expr" | "debug_unwatch_expr" => vec![
+            (
+                "watch_expressions",
+                typed(json!("array"), "Expressions now watched"),
+            ),
+            (
+                "watches",
+                typed(
+                    json!("array"),
+                    "Current values of the watched expressions, when stopped",
+                ),
+            ),
+        ],
+        "debug_list_breakpoints" => vec![
+            (
+                "breakpoints",
+                typed(
+                    json!("array"),
+                    "Breakpoints with IDs, locations, and hit counts",
+                ),
+            ),
+            ("watchpoints", typed(json!("array"), "Watchpoints")),
+        ],
+        "debug_eval" => vec![
+            ("expression", typed(json!("string"), "Evaluated expression")),
+            (
+                "value",
+                typed(json!(["object", "string", "null"]), "Parsed value"),
+            ),
+            (
+                "type",
+                typed(json!(["string", "null"]), "Static type of the value, e.g. &str"),
+            ),
+            (
+                "display",
+                typed(
+                    json!(["string", "null"]),
+                    "Value as Rust's {:?} prints it, for Option, Result, strings, collections, and smart pointers",
+                ),
+            ),
+            (
+                "pointee",
+                typed(
+                    json!(["object", "null"]),
+                    "What a Box, Rc, or Arc points to, as a tree of name, type, value, and children",
+                ),
+            ),
+        ],
+        "debug_eval_many" => vec![
+            (
+                "results",
+                typed(
+                    json!("array"),
+                    "One entry per expression, in order, with expression, success, value, type, and error",
+                ),
+            ),
+            ("failed", typed(json!("integer"), "Expressions that could not be evaluated")),
+        ],
+        "debug_set_var" => vec![
+            ("name", typed(json!("string"), "The variable that was set")),
+            (
+                "old_value",
+                typed(json!(["string", "null"]), "Value before the assignment"),
+            ),
+            (
+                "value",
+                typed(json!(["string", "null"]), "Value read back after the assignment"),
+            ),
+            ("type", typed(json!(["string", "null"]), "The variable's type")),
+        ],
+        "debug_backtrace" => vec![
+            (
+                "frames",
+                typed(
+                    json!("array"),
+                    "Frames of the selected thread, innermost first",
+                ),
+            ),
+            (
+                "threads",
+                typed(json!("array"), "Frames of every thread, with all_threads"),
+            ),
+            (
+                "task",
+                typed(
+                    json!(["string", "null"]),
+                    "Root future of the task being polled, with async",
+                ),
+            ),
+            (
+                "executor_frames",
+                typed(
+                    json!("integer"),
+                    "Runtime frames below the task left out, with async",
+                ),
+            ),
+        ],
+        "debug_locals" => vec![(
+            "variables",
+            typed(
+                json!("array"),
+                "Arguments and locals with names, types, values, and children",
+            ),
+        )],
+        "debug_eval_slice" => vec![
+            ("expression", typed(json!("string"), "Pointer expression")),
+            ("len", typed(json!("integer"), "Value of len_expr")),
+            (
+                "truncated",
+                typed(json!("boolean"), "Whether only the first elements were read"),
+            ),
+            (
+                "display",
+                typed(json!(["string", "null"]), "The slice as Rust's {:?} prints it"),
+            ),
+            (
+                "elements",
+                typed(json!("array"), "Elements with names, types, values, and children"),
+            ),
+        ],
+        "debug_inspect" => vec![
+            ("expression", typed(json!("string"), "Inspected expression")),
+            (
+                "variable",
+                typed(
+                    json!("object"),
+                    "Tree of name, type, value, and children; nodes may be marked truncated or cycle",
+                ),
+            ),
+        ],
+        "debug_threads" => vec![(
+            "threads",
+            typed(
+                json!("array"),
+                "Threads with names, stop reasons, and top frames",
+            ),
+        )],
+        "debug_inferiors" => vec![(
+            "inferiors",
+            typed(
+                json!("array"),
+                "Processes with id, pid, executable, state, and selected",
+            ),
+        )],
+        "debug_async_tasks" => vec![
+            (
+                "tasks",
+                typed(
+                    json!("array"),
+                    "Spawned futures with poll_function, location, and polling_threads",
+                ),
+            ),
+            (
+                "breakpoint",
+                typed(
+                    json!(["object", "null"]),
+                    "Breakpoint set on the poll function of break_on",
+                ),
+            ),
+        ],
+        "debug_analyze_deadlock" => vec![
+            (
+                "paused",
+                typed(json!("boolean"), "Whether the running program was paused first"),
+            ),
+            (
+                "blocked",
+                typed(
+                    json!("array"),
+                    "Blocked threads with what they wait for, the lock's address, its holders, and the calling frame",
+                ),
+            ),
+            (
+                "held_locks",
+                typed(
+                    json!("object"),
+                    "Threads holding each lock, by address, as found from guards in their frames",
+                ),
+            ),
+            ("deadlock", typed(json!("boolean"), "Whether threads wait on each other in a cycle")),
+            (
+                "cycle",
+                typed(
+                    json!("array"),
+                    "Threads in the cycle, each waiting for a lock the next one holds",
+                ),
+            ),
+            ("summary", typed(json!("string"), "Who is waiting on what, in words")),
+        ],
+        "debug_triage_crash" => vec![
+            (
+                "signal",
+                typed(
+                    json!(["object", "null"]),
+                    "Fatal signal with name, description, and fault_address",
+                ),
+            ),
+            (
+                "fault_address",
+                typed(json!(["string", "null"]), "Address whose access faulted"),
+            ),
+            (
+                "thread_id",
+                typed(json!(["integer", "null"]), "Thread that crashed"),
+            ),
+            (
+                "backtrace",
+                typed(json!("array"), "Frames of the crashing thread, innermost first"),
+            ),
+            (
+                "user_frame",
+                typed(
+                    json!(["object", "null"]),
+                    "Innermost frame in the program's own code",
+                ),
+            ),
+            (
+                "locals",
+                typed(json!("array"), "Arguments and locals of user_frame"),
+            ),
+            (
+                "disassembly",
+                typed(
+                    json!("array"),
+                    "Instructions around the program counter, the faulting one marked current",
+                ),
+            ),
+            (
+                "registers",
+                typed(json!("object"), "General-purpose registers by name"),
+            ),
+            ("summary", typed(json!("string"), "What crashed where, in words")),
+        ],
+        "debug_panic_info" => vec![
+            ("message", typed(json!(["string", "null"]), "The panic message")),
+            (
+                "message_complete",
+                typed(
+                    json!("boolean"),
+                    "False when the message holds only the format string's literal parts, read before the panic hook ran",
+                ),
+            ),
+            (
+                "location",
+                typed(json!("object"), "file, line, and column the panic was raised at"),
+            ),
+            (
+                "thread",
+                typed(json!(["string", "null"]), "Name of the panicking thread"),
+            ),
+            (
+                "thread_id",
+                typed(json!(["integer", "null"]), "Thread that panicked"),
+            ),
+            (
+                "backtrace",
+                typed(
+                    json!("array"),
+                    "Frames of the program's own code, from where the panic was raised outwards",
+                ),
+            ),
+            (
+                "panic_frames",
+                typed(json!("integer"), "Frames of the panic machinery left out above"),
+            ),
+            ("summary", typed(json!("string"), "What panicked where, in words")),
+        ],
+        "debug_heap_profile" => vec![
+            (
+                "tracking",
+                typed(json!("boolean"), "Whether allocations are being tracked"),
+            ),
+            ("allocations", typed(json!("integer"), "Allocations made in total")),
+            ("frees", typed(json!("integer"), "Allocations freed")),
+            ("live_bytes", typed(json!("integer"), "Bytes still allocated")),
+            (
+                "live_allocations",
+                typed(json!("integer"), "Allocations not freed"),
+            ),
+            (
+                "site_count",
+                typed(json!("integer"), "Distinct call stacks holding live allocations"),
+            ),
+            (
+                "sites",
+                typed(
+                    json!("array"),
+                    "Call stacks holding the most live bytes, with bytes, count, backtrace, and user_frame",
+                ),
+            ),
+            (
+                "symbolized",
+                typed(
+                    json!("boolean"),
+                    "Whether backtraces carry functions and lines, which needs the program stopped",
+                ),
+            ),
+            ("summary", typed(json!("string"), "How much is live and where from, in words")),
+        ],
+        "debug_syscalls" => vec![
+            ("tracer", typed(json!("string"), "What traced the calls: gdb or dtruss")),
+            ("count", typed(json!("integer"), "Syscalls recorded")),
+            ("failed", typed(json!("integer"), "Syscalls that returned an error")),
+            (
+                "truncated",
+                typed(json!("boolean"), "Whether tracing stopped at the limit of 1000 calls"),
+            ),
+            (
+                "stop_reason",
+                typed(
+                    json!(["string", "null"]),
+                    "Why tracing ended before the time was up, e.g. breakpoint-hit",
+                ),
+            ),
+            (
+                "syscalls",
+                typed(
+                    json!("array"),
+                    "Calls in order, with name, args, path, result, errno, error (e.g. ENOENT), and thread",
+                ),
+            ),
+            ("summary", typed(json!("string"), "Counts and the most frequent calls, in words")),
+        ],
+        "debug_profile" => vec![
+            (
+                "sampler",
+                typed(json!("string"), "The profiler used: perf or sample"),
+            ),
+            ("duration_ms", typed(json!("integer"), "How long the program was sampled")),
+            (
+                "resumed",
+                typed(
+                    json!("boolean"),
+                    "Whether the program was stopped and continued to be sampled",
+                ),
+            ),
+            ("samples", typed(json!("integer"), "Samples taken")),
+            (
+                "hottest",
+                typed(
+                    json!("array"),
+                    "Functions with the most samples in their own code, with self and total samples and percentages",
+                ),
+            ),
+            (
+                "stack_count",
+                typed(json!("integer"), "Distinct call stacks sampled"),
+            ),
+            (
+                "folded",
+                typed(
+                    json!("array"),
+                    "Call stacks as `outer;inner count` lines for flame graph tools, most samples first",
+                ),
+            ),
+            ("summary", typed(json!("string"), "Where the time went, in words")),
+        ],
+        "debug_checkpoint" => vec![
+            ("id", typed(json!("integer"), "Number of the new checkpoint")),
+            ("pid", typed(json!("integer"), "Process ID of the copy")),
+            (
+                "location",
+                typed(json!(["string", "null"]), "file:line the checkpoint was taken at"),
+            ),
+            (
+                "checkpoints",
+                typed(
+                    json!("array"),
+                    "Every checkpoint with id, pid, location, and current",
+                ),
+            ),
+        ],
+        "debug_restore_checkpoint" => {
+            let mut extra = stop_report();
+            extra.extend([
+                (
+                    "checkpoint",
+                    typed(json!(["object", "null"]), "The checkpoint switched to"),
+                ),
+                (
+                    "checkpoints",
+                    typed(
+                        json!("array"),
+                        "Every checkpoint with id, pid, location, and current",
+                    ),
+                ),
+            ]);
+            extra
+        }
+        "debug_select_inferior" => vec![
+            (
+                "inferior",
+                typed(json!(["object", "null"]), "Selected inferior"),
+            ),
+            (
+                "frame",
+                typed(json!(["string", "null"]), "Top frame of the selected inferior"),
+            ),
+        ],
+        "debug_select_thread" | "debug_select_frame" | "debug_frame_info" => vec![
+            (
+                "thread_id",
+                typed(json!(["integer", "null"]), "Selected thread"),
+            ),
+            ("frame", typed(json!(["object", "null"]), "Selected frame")),
+        ],
+        "debug_memory_read" => vec![
+            ("address", typed(json!(["string", "null"]), "Start address")),
+            ("count", typed(json!("integer"), "Bytes read")),
+            ("hex", typed(json!("string"), "Bytes as hex")),
+            ("ascii", typed(json!("string"), "Bytes as ASCII")),
+            (
+                "values",
+                typed(json!("array"), "Bytes decoded as the requested type"),
+            ),
+        ],
+        "debug_memory_write" => vec![("count", typed(json!("integer"), "Bytes written"))],
+        "debug_program_output" => vec![
+            (
+                "stdout",
+                typed(json!("string"), "Everything the program wrote to stdout"),
+            ),
+            (
+                "stderr",
+                typed(json!("string"), "Everything the program wrote to stderr"),
+            ),
+            (
+                "sanitizer_findings",
+                typed(json!("array"), "Every sanitizer report in stderr"),
+            ),
+        ],
+        "debug_stdin_write" => vec![
+            (
+                "bytes_written",
+                typed(json!("integer"), "Bytes written to the program's stdin"),
+            ),
+            (
+                "closed",
+                typed(json!("boolean"), "Whether stdin is now closed"),
+            ),
+        ],
+        "debug_save_breakpoints" => vec![
+            ("path", typed(json!("string"), "File the breakpoints were saved to")),
+            ("saved", typed(json!("integer"), "Breakpoints saved")),
+        ],
+        "debug_load_breakpoints" => vec![
+            ("path", typed(json!("string"), "File the breakpoints were loaded from")),
+            (
+                "breakpoints",
+                typed(
+                    json!("array"),
+                    "Breakpoints set, with location, id, old_id, resolved_locations, and enabled",
+                ),
+            ),
+        ],
+        "debug_export_session" => vec![
+            (
+                "format",
+                typed(json!("string"), "json or markdown"),
+            ),
+            (
+                "events",
+                typed(json!("integer"), "Events in the document"),
+            ),
+            (
+                "dropped",
+                typed(
+                    json!("integer"),
+                    "Oldest events no longer kept, beyond the 10000 most recent",
+                ),
+            ),
+            (
+                "document",
+                typed(
+                    json!(["object", "string", "null"]),
+                    "The history, unless written to path",
+                ),
+            ),
+            (
+                "path",
+                typed(json!(["string", "null"]), "File the document was written to"),
+            ),
+        ],
+        "debug_doctor" => vec![
+            (
+                "healthy",
+                typed(
+                    json!("boolean"),
+                    "Whether every required check passed",
+                ),
+            ),
+            (
+                "checks",
+                typed(
+                    json!("array"),
+                    "Checks with name, ok, required, detail, and fix",
+                ),
+            ),
+            (
+                "failed",
+                typed(json!("array"), "Names of the required checks that failed"),
+            ),
+        ],
+        "debug_more_output" => vec![
+            (
+                "page",
+                typed(json!("string"), "The next part of the complete result's JSON text"),
+            ),
+            (
+                "offset",
+                typed(json!("integer"), "Where the page starts in the complete result, in bytes"),
+            ),
+            (
+                "next_cursor",
+                typed(
+                    json!(["string", "null"]),
+                    "Cursor of the following page, or null after the last",
+                ),
+            ),
+        ],
+        "debug_sessions" => vec![(
+            "sessions",
+            typed(
+                json!("array"),
+                "Active sessions with binary_path, debugger, debugger_pid, inferior_pid, attached, state, age_secs, idle_secs, and idle_timeout_secs",
+            ),
+        ),
+        (
+            "idle_timeout_secs",
+            typed(
+                json!(["integer", "null"]),
+                "How long a session may go without a tool call before it is shut down, or null for never",
+            ),
+        )],
+        "debug_raw_command" => vec![
+            ("command", typed(json!("string"), "The command sent")),
+            (
+                "raw_output",
+                typed(
+                    json!("string"),
+                    "What the debugger printed, unparsed and in its own format",
+                ),
+            ),
+            (
+                "parsed",
+                typed(
+                    json!("boolean"),
+                    "Always false: nothing in raw_output was interpreted, and the session's state, location, and breakpoints were not updated",
+                ),
+            ),
+            ("debugger", typed(json!("string"), "The debugger that ran it")),
+        ],
+        "debug_state" => vec![
+            (
+                "watch_expressions",
+                typed(json!("array"), "Expressions added with debug_watch_expr"),
+            ),
+            (
+                "busy",
+                typed(json!("boolean"), "Whether a debugger command is in flight"),
+            ),
+            (
+                "location",
+                typed(json!(["string", "null"]), "Current file:line"),
+            ),
+            (
+                "binary_path",
+                typed(json!(["string", "null"]), "Binary being debugged"),
+            ),
+            (
+                "debugger",
+                typed(json!(["string", "null"]), "Debugger driving the session"),
+            ),
+            (
+                "selected_thread",
+                typed(json!(["integer", "null"]), "Thread stepping applies to"),
+            ),
+            (
+                "selected_frame",
+                typed(json!(["integer", "null"]), "Frame evaluation applies to"),
+            ),
+            (
+                "exit_code",
+                typed(
+                    json!(["integer", "null"]),
+                    "Exit code once the program has exited",
+                ),
+            ),
+            (
+                "terminated_by_signal",
+                typed(json!(["string", "null"]), "Signal that killed the program"),
+            ),
+            (
+                "core_path",
+                typed(
+                    json!(["string", "null"]),
+                    "Core file of a post-mortem session",
+                ),
+            ),
+            (
+                "replay_trace",
+                typed(json!(["string", "null"]), "rr trace being replayed"),
+            ),
+            (
+                "debugger_exit",
+                typed(
+                    json!(["string", "null"]),
+                    "How the debugger exited, once the state is debuggerdied",
+                ),
+            ),
+        ],
+        _ => Vec::new(),
+    };
+    for (name, schema) in extra {
+        properties.insert(name.into(), schema);
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties
+    })
+}
+
+/// The main MCP server that handles debugging requests from AI assistants.
+///
+/// `DebugServer` implements the Model Context Protocol, accepting JSON-RPC commands
+/// over stdin/stdout and managing debugging sessions through LLDB or GDB.
+///
+/// ## Thread Safety
+///
+/// Requests are handled concurrently. The session lock is only held briefly; the
+/// debugger's stdin and output each have their own lock, so one command runs at a
+/// time while state queries are answered from the session without waiting for it.
+pub struct DebugServer {
+    /// The current debugging session, if any
+    pub(crate) session: Arc<Mutex<Option<DebugSession>>>,
+    /// Behaviour selected by command-line flags
+    options: ServerOptions,
+    /// MCP notifications for the connected clients, printed on stdio or streamed over SSE
+    pub(crate) notifications: broadcast::Sender<Value>,
+    /// Protocol revision agreed in `initialize`, one of [`PROTOCOL_VERSIONS`]
+    protocol_version: std::sync::Mutex<&'static str>,
+    /// Set by the client's `notifications/initialized`; requests before it are refused
+    initialized: AtomicBool,
+    /// Index into [`LOG_LEVELS`] of the least severe log message sent to clients;
+    /// past the end, so nothing is sent, until the client calls `logging/setLevel`
+    log_level: AtomicUsize,
+    /// Session state last announced in a log message, to log only transitions
+    logged_state: std::sync::Mutex<DebugState>,
+    /// Resource URIs clients subscribed to with `resources/subscribe`
+    subscriptions: std::sync::Mutex<std::collections::HashSet<String>>,
+    /// Requests still being handled, keyed by their serialized JSON-RPC id, so a
+    /// cancellation notification can abort them
+    in_flight: std::sync::Mutex<std::collections::HashMap<String, tokio::task::AbortHandle>>,
+    /// Woken by the session's reader task whenever the debugger prints a line
+    output_ready: Arc<Notify>,
+    /// Source path prefixes to rewrite, from `--source-map` and `debug_set_source_map`;
+    /// applied to every session the server starts
+    source_map: std::sync::Mutex<Vec<(String, String)>>,
+    /// Tool calls, debugger traffic, and state transitions since the server started,
+    /// for `debug_export_session`
+    history: std::sync::Mutex<History>,
+    /// Complete results of truncated tool calls, for `debug_more_output`
+    output_pages: std::sync::Mutex<OutputPages>,
+    /// Settings from the `ferroscope.toml` files the last `debug_run` read; their
+    /// debugger path and timeout apply until the next one
+    config: std::sync::Mutex<ProjectConfig>,
+}
+
+/// Kills the debugger of a session that is no longer the active one, and the program
+/// it launched, which [`DebugServer::shutdown`] cannot reach through it.
+async fn kill_session(mut session: DebugSession) {
+    let launched =
+        !session.attached && session.remote_target.is_none() && session.replay_trace.is_none();
+    if let Some(pid) = session.inferior_pid.filter(|_| launched) {
+        let _ = tokio::process::Command::new("kill")
+            .args(["-KILL", &pid.to_string()])
+            .status()
+            .await;
+    }
+    let _ = session.process.kill().await;
+    if let Some(log) = session.launch.heap_log() {
+        let _ = std::fs::remove_file(log);
+    }
+}
+
+/// The result of a step tool whose command, which printed `response`, did not finish
+/// within the timeout; the program may still be running toward the next stop.
+fn step_timed_out(state: &DebugState, response: &str) -> Value {
+    json!({
+        "success": false,
+        "error": "The step did not finish within the timeout; the program may still be running. Use debug_wait_for_stop or debug_pause",
+        "error_kind": ErrorKind::Timeout.name(),
+        "state": format!("{:?}", state).to_lowercase(),
+        "output": response.replace(TIMEOUT_NOTE, "").trim()
+    })
+}
+
+/// The error for a debugger that exited under the session, `exit` saying how if known,
+/// with the last `output` it printed.
+fn debugger_died(exit: Option<&str>, output: &str) -> anyhow::Error {
+    let mut message = match exit {
+        Some(exit) => format!("The debugger exited ({})", exit),
+        None => "The debugger exited".to_string(),
+    };
+    message
+        .push_str("; call debug_recover to start it again with the same program and breakpoints");
+    if !output.trim().is_empty() {
+        message.push_str(&format!(". Its last output: {}", output.trim()));
+    }
+    ErrorKind::DebuggerCrashed.error(message)
+}
+
+/// Why `debug_eval` refuses `expression` under `options`: it calls a function or assigns
+/// while `allow_side_effects` is unset.
+fn side_effect_refusal(expression: &str, options: &EvalOptions) -> Option<String> {
+    if options.allow_side_effects {
+        return None;
+    }
+    side_effect(expression).map(|effect| {
+        format!(
+            "{} contains {}, which could change the program; pass allow_side_effects: true to evaluate it",
+            expression, effect
+        )
+    })
+}
+
+/// Reads the tool argument `key` as an array of strings, if it was given.
+fn string_array(arguments: &Value, key: &str) -> Result<Option<Vec<String>>> {
+    match arguments.get(key) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(Some)
+            .ok_or_else(|| {
+                ErrorKind::InvalidArgument.error(format!("{} must be an array of strings", key))
+            }),
+        Some(Value::Null) | None => Ok(None),
+        Some(_) => {
+            Err(ErrorKind::InvalidArgument.error(format!("{} must be an array of strings", key)))
+        }
+    }
+}
+
+/// The sanitizer reports in `stderr`, as JSON.
+fn sanitizer_findings(stderr: &str) -> Vec<Value> {
+    SanitizerReport::parse_all(stderr)
+        .iter()
+        .map(SanitizerReport::to_json)
+        .collect()
+}
+
+/// How often [`DebugServer::reap_idle_session`] checks how long the session was idle.
+const IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long ending a session waits for the debugger to kill or detach from the program.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How often a command waiting for the debugger's reply checks that it is still running.
+const HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// How long a debugger whose output closed is given to exit before it is killed.
+const EXIT_GRACE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// How long a new LLDB session waits for its first sentinel marker before falling
+/// back to prompt detection.
+const SENTINEL_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Bytes of a truncated result left for the `truncated`, `total_bytes`, and `cursor`
+/// fields that say so.
+const TRUNCATION_NOTE_BYTES: usize = 128;
+
+/// Debugger stderr lines one tool result reports at most.
+const DEBUGGER_STDERR_LINES: usize = 20;
+
+/// How long unsolicited debugger output may pause before the block is taken as complete.
+const OUTPUT_SETTLE_TIME: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// The active debugger's stdin and output, each behind its own lock.
+type DebuggerPipes = (
+    Arc<Mutex<ChildStdin>>,
+    Arc<Mutex<mpsc::UnboundedReceiver<String>>>,
+);
+
+tokio::task_local! {
+    /// Debugger command timeout of the tool call running on this task (`timeout_ms`)
+    static COMMAND_TIMEOUT: std::time::Duration;
+    /// Progress token of the tool call running on this task, if its request had one
+    static PROGRESS: ProgressReport;
+}
+
+/// Where a tool call's `notifications/progress` go, and how far it has got.
+struct ProgressReport {
+    /// The request's `_meta.progressToken`
+    token: Value,
+    /// Last progress value sent; MCP requires each notification to increase it
+    last: std::sync::Mutex<Option<u64>>,
+}
+
+/// Server behaviour selected by command-line flags.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Debugger for tool calls and `ferroscope.toml` files that name none, instead of
+    /// LLDB (`--debugger`)
+    pub debugger: Option<DebuggerKind>,
+    /// Configuration file read instead of `~/.config/ferroscope/config.toml` (`--config`)
+    pub config: Option<std::path::PathBuf>,
+    /// Allow `debug_memory_write` to modify the debuggee's memory (`--allow-memory-write`)
+    pub allow_memory_write: bool,
+    /// Allow `debug_return` and `debug_jump` to change where the program executes
+    /// (`--allow-control-flow`)
+    pub allow_control_flow: bool,
+    /// How long a debugger command may take when the tool call sets no `timeout_ms`
+    /// (`--timeout-ms`)
+    pub command_timeout: std::time::Duration,
+    /// Serve the Debug Adapter Protocol on this address instead of MCP on stdio (`--dap`)
+    pub dap: Option<String>,
+    /// Serve MCP over Streamable HTTP on this address instead of stdio (`--http`)
+    pub http: Option<String>,
+    /// Lines of source shown on each side of the stop line in stop reports, 0 for
+    /// none (`--source-context`)
+    pub source_context: u32,
+    /// Source path prefixes rewritten before looking up files, e.g. a CI build
+    /// directory mapped to the local checkout (`--source-map <from>=<to>`, repeatable)
+    pub source_map: Vec<(String, String)>,
+    /// Run debuggers, and so the programs they launch, with CPU and memory limits, a
+    /// cleaned environment, no network, and a scratch working directory, and refuse
+    /// breakpoint commands that reach the host (`--sandbox`)
+    pub sandbox: bool,
+    /// Allow `debug_raw_command` to pass commands to the debugger
+    /// (`--allow-raw-commands`)
+    pub allow_raw_commands: bool,
+    /// Commands `debug_raw_command` runs although they are denied by default, e.g.
+    /// `break` (`--raw-command-allow`, repeatable)
+    pub raw_command_allow: Vec<String>,
+    /// Commands `debug_raw_command` refuses besides the default ones, e.g. `memory write`
+    /// (`--raw-command-deny`, repeatable)
+    pub raw_command_deny: Vec<String>,
+    /// Caps on the program's CPU time, memory, open files, and output, for those
+    /// `ferroscope.toml` does not set (`--limit-cpu-seconds`, `--limit-memory-mb`,
+    /// `--limit-open-files`, `--limit-output-mb`)
+    pub limits: ResourceLimits,
+    /// How long a session may go without a tool call before its program and debugger
+    /// are shut down, unless `ferroscope.toml` says otherwise; `None` never
+    /// (`--idle-timeout-secs`, 0 for never)
+    pub idle_timeout: Option<std::time::Duration>,
+    /// Largest tool result, in bytes of JSON, returned whole when the call sets no
+    /// `max_output_bytes`; larger ones are truncated and paged with `debug_more_output`.
+    /// `None` never truncates (`--max-output-bytes`, 0 for no limit)
+    pub max_output_bytes: Option<usize>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            debugger: None,
+            config: None,
+            allow_memory_write: false,
+            allow_control_flow: false,
+            command_timeout: std::time::Duration::from_secs(10),
+            dap: None,
+            http: None,
+            source_context: 3,
+            source_map: Vec::new(),
+            sandbox: false,
+            allow_raw_commands: false,
+            raw_command_allow: Vec::new(),
+            raw_command_deny: Vec::new(),
+            limits: ResourceLimits::default(),
+            idle_timeout: Some(std::time::Duration::from_secs(3600)),
+            max_output_bytes: Some(64 * 1024),
+        }
+    }
+}
+
+impl DebugServer {
+    /// Creates a new debug server instance.
+    ///
+    /// The server starts with no active debugging session. Sessions are created
+    /// when the `debug_run` tool is called with a binary path.
+    pub fn new(options: ServerOptions) -> Self {
+        Self {
+            session: Arc::new(Mutex::new(None)),
+            notifications: broadcast::channel(64).0,
+            in_flight: Default::default(),
+            protocol_version: std::sync::Mutex::new(PROTOCOL_VERSIONS[0]),
+            initialized: AtomicBool::new(false),
+            log_level: AtomicUsize::new(LOG_LEVELS.len()),
+            logged_state: std::sync::Mutex::new(DebugState::NotLoaded),
+            subscriptions: Default::default(),
+            output_ready: Arc::new(Notify::new()),
+            source_map: std::sync::Mutex::new(options.source_map.clone()),
+            history: Default::default(),
+            output_pages: Default::default(),
+            config: Default::default(),
+            options,
+        }
+    }
+
+    /// Applies debugger output as soon as it arrives while no command is waiting for it.
+    ///
+    /// Stops and exits of a running program update the session state and log right
+    /// away instead of at the next tool call; the output itself is kept for the next
+    /// `debug_poll` or `debug_wait_for_stop`. The transports run this next to request
+    /// handling and it never returns, so embedders wanting the same should spawn it.
+    pub async fn pump_debugger_output(&self) {
+        loop {
+            self.output_ready.notified().await;
+            let Ok((_, output)) = self.debugger_pipes().await else {
+                continue;
+            };
+            // A command reads its own replies; whatever it leaves is handled afterwards
+            let mut output = output.lock().await;
+
+            // A stop is followed by its thread and frame lines, so take the whole block
+            let mut pending = String::new();
+            loop {
+                while let Ok(line) = output.try_recv() {
+                    pending.push_str(&line);
+                }
+                let last = pending.lines().last().map(str::trim);
+                if pending.is_empty()
+                    || matches!(last, Some("(lldb)" | "(gdb)"))
+                    || last.is_some_and(is_sentinel)
+                {
+                    break;
+                }
+                match tokio::time::timeout(OUTPUT_SETTLE_TIME, output.recv()).await {
+                    Ok(Some(line)) => pending.push_str(&line),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            if pending.is_empty() {
+                continue;
+            }
+
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                let text = self.apply_debugger_output(session, &pending).await;
+                session.unread_output.push_str(&text);
+            }
+        }
+    }
+
+    /// Subscribes to the MCP notifications the server sends: stops of a running
+    /// program, progress, log messages, and resource updates.
+    pub fn subscribe(&self) -> broadcast::Receiver<Value> {
+        self.notifications.subscribe()
+    }
+
+    /// Sends a command to the active debugger process and returns the response.
+    ///
+    /// This method handles communication with the underlying LLDB or GDB process,
+    /// including timeout handling and response parsing.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The debugger command to execute (e.g., "breakpoint set", "continue")
+    ///
+    /// # Returns
+    ///
+    /// Returns the debugger's response as a string, or an error if no session is active
+    /// or if the command fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The command spans several lines ([`ErrorKind::InvalidArgument`])
+    /// - No debugging session is currently active
+    /// - The debugger process has terminated ([`ErrorKind::DebuggerCrashed`])
+    /// - Communication with the debugger fails
+    /// - The command times out (after the tool call's `timeout_ms`, 10 seconds by default)
+    async fn send_debugger_command(&self, command: &str) -> Result<String> {
+        self.send_debugger_command_with(command, true).await
+    }
+
+    /// Like [`Self::send_debugger_command`], but with `wait_for_stop` unset, execution
+    /// commands return as soon as the process is resumed or launched.
+    ///
+    /// Each command is followed by one printing a numbered sentinel marker, and its
+    /// reply is everything before that marker; a command that resumed the program
+    /// reads on to the stop. Without Python, LLDB cannot print the marker and replies
+    /// are framed by [`Self::is_response_complete`] instead.
+    async fn send_debugger_command_with(
+        &self,
+        command: &str,
+        wait_for_stop: bool,
+    ) -> Result<String> {
+        single_line(command)?;
+        self.send_debugger_lines(command, wait_for_stop).await
+    }
+
+    /// Sends `command` as [`Self::send_debugger_command_with`] does, without refusing
+    /// line breaks, for the few commands LLDB reads further lines for.
+    async fn send_debugger_lines(&self, command: &str, wait_for_stop: bool) -> Result<String> {
+        let (stdin, output) = self.debugger_pipes().await?;
+        let mut output = output.lock().await;
+
+        let sentinel = {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            self.apply_pending_output(session, &mut output).await;
+            session.sentinels.then(|| {
+                session.next_token += 1;
+                lldb_sentinel(session.next_token)
+            })
+        };
+
+        {
+            // Send command to debugger
+            let mut stdin = stdin.lock().await;
+            let line = match &sentinel {
+                Some((_, print_marker)) => format!("{}\n{}\n", command, print_marker),
+                None => format!("{}\n", command),
+            };
+            if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                drop(stdin);
+                return Err(self.debugger_exited("").await);
+            }
+        }
+        self.log("debug", "ferroscope.debugger", json!({ "sent": command }));
+
+        {
+            // Read response with intelligent parsing
+            let mut response = String::new();
+            let mut exited = false;
+            // Whether our marker came, and whether the program resumed and stopped
+            let (mut framed, mut resumed, mut stopped) = (false, false, false);
+
+            let timeout_duration = self.command_timeout();
+            let start_time = std::time::Instant::now();
+
+            loop {
+                let remaining = timeout_duration.saturating_sub(start_time.elapsed());
+                match self.recv_output(&mut output, remaining).await {
+                    Ok(Some(line)) => {
+                        if let Some((marker, _)) = &sentinel {
+                            // Markers of earlier commands that gave up waiting are stale
+                            if line.trim() == marker {
+                                framed = true;
+                            } else if !is_sentinel(&line) {
+                                response.push_str(&line);
+                            }
+                            match DebuggerEvent::parse_lldb(&line) {
+                                Some(DebuggerEvent::Resumed) => resumed = true,
+                                Some(
+                                    DebuggerEvent::Stopped { .. } | DebuggerEvent::Exited { .. },
+                                ) => stopped = true,
+                                _ => {}
+                            }
+                            if framed && !(wait_for_stop && resumed && !stopped) {
+                                break;
+                            }
+                        } else {
+                            response.push_str(&line);
+
+                            // Intelligent response detection based on command type
+                            if self.is_response_complete(&line, command, &response) {
+                                break;
+                            }
+                        }
+                        if !wait_for_stop
+                            && matches!(
+                                DebuggerEvent::parse_lldb(&line),
+                                Some(DebuggerEvent::Launched { .. } | DebuggerEvent::Resumed)
+                            )
+                        {
+                            break;
+                        }
+                    }
+                    Ok(None) => {
+                        exited = true;
+                        break;
+                    }
+                    Err(_) => {
+                        response.push_str(TIMEOUT_NOTE);
+                        break;
+                    }
+                }
+            }
+
+            // Update session state based on response
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.log(format!("(lldb) {}\n{}", command, response.trim_end()));
+                self.log(
+                    "debug",
+                    "ferroscope.debugger",
+                    json!({ "command": command, "response": response }),
+                );
+                self.update_session_state(&response, session);
+                self.log_state(&session.state);
+                session
+                    .notify_on_stop
+                    .store(session.state == DebugState::Running, Ordering::SeqCst);
+            }
+
+            if exited {
+                drop(session_guard);
+                return Err(self.debugger_exited(&response).await);
+            }
+            Ok(response)
+        }
+    }
+
+    /// Sends a GDB/MI command to the active debugger and collects its output records.
+    ///
+    /// Each command is prefixed with a unique token so its result record can be told
+    /// apart from stale output. Execution commands answer `^running` first; for those
+    /// the method keeps reading until the matching `*stopped` record arrives.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the command spans several lines, if no
+    /// debugging session is active, or if communication with the debugger fails.
+    /// Timeouts are reported through [`MiOutput::timed_out`] rather than as an error.
+    async fn send_mi_command(&self, command: &str) -> Result<MiOutput> {
+        self.send_mi_command_with(command, true).await
+    }
+
+    /// Like [`Self::send_mi_command`], but with `wait_for_stop` unset, execution
+    /// commands return at `^running` instead of waiting for `*stopped`.
+    async fn send_mi_command_with(&self, command: &str, wait_for_stop: bool) -> Result<MiOutput> {
+        single_line(command)?;
+        let (stdin, output_lines) = self.debugger_pipes().await?;
+        let mut output_lines = output_lines.lock().await;
+
+        let token = {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            self.apply_pending_output(session, &mut output_lines).await;
+            session.next_token += 1;
+            session.next_token
+        };
+
+        {
+            let mut stdin = stdin.lock().await;
+            let line = format!("{}{}\n", token, command);
+            if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                drop(stdin);
+                return Err(self.debugger_exited("").await);
+            }
+        }
+        self.log(
+            "debug",
+            "ferroscope.debugger",
+            json!({ "sent": format!("{}{}", token, command) }),
+        );
+
+        {
+            let mut output = MiOutput::default();
+            let mut running = false;
+            let mut exited = false;
+
+            let timeout_duration = self.command_timeout();
+            let start_time = std::time::Instant::now();
+
+            loop {
+                let remaining = timeout_duration.saturating_sub(start_time.elapsed());
+                match self.recv_output(&mut output_lines, remaining).await {
+                    Ok(Some(line)) => {
+                        let record = MiRecord::parse(&line);
+
+                        let finished = match &record {
+                            MiRecord::Result {
+                                token: Some(t),
+                                class,
+                                ..
+                            } if *t == token => {
+                                running = class == "running";
+                                !running || !wait_for_stop
+                            }
+                            MiRecord::Exec { class, .. } => running && class == "stopped",
+                            _ => false,
+                        };
+
+                        output.records.push(record);
+                        if finished {
+                            break;
+                        }
+                    }
+                    Ok(None) => {
+                        exited = true;
+                        break;
+                    }
+                    Err(_) => {
+                        output.timed_out = true;
+                        break;
+                    }
+                }
+            }
+
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.log(format!("(gdb) {}\n{}", command, output.text().trim_end()));
+                self.log(
+                    "debug",
+                    "ferroscope.debugger",
+                    json!({ "command": command, "response": output.text() }),
+                );
+                self.update_session_state_from_mi(&output, session);
+                self.log_state(&session.state);
+                session
+                    .notify_on_stop
+                    .store(session.state == DebugState::Running, Ordering::SeqCst);
+            }
+
+            if exited {
+                drop(session_guard);
+                return Err(self.debugger_exited(&output.text()).await);
+            }
+            Ok(output)
+        }
+    }
+
+    /// Sends several GDB/MI commands in one write and collects the output of each, for
+    /// commands that do not resume the program.
+    ///
+    /// GDB answers commands in order, so the records up to a command's result record
+    /// belong to it. Commands left unanswered when the timeout expires get outputs with
+    /// [`MiOutput::timed_out`] set.
+    async fn send_mi_batch(&self, commands: &[String]) -> Result<Vec<MiOutput>> {
+        for command in commands {
+            single_line(command)?;
+        }
+        let (stdin, output_lines) = self.debugger_pipes().await?;
+        let mut output_lines = output_lines.lock().await;
+
+        let first_token = {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            self.apply_pending_output(session, &mut output_lines).await;
+            let first_token = session.next_token + 1;
+            session.next_token += commands.len() as u64;
+            first_token
+        };
+
+        let lines: Vec<String> = commands
+            .iter()
+            .zip(first_token..)
+            .map(|(command, token)| format!("{}{}", token, command))
+            .collect();
+        {
+            let mut stdin = stdin.lock().await;
+            let batch = format!("{}\n", lines.join("\n"));
+            if stdin.write_all(batch.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                drop(stdin);
+                return Err(self.debugger_exited("").await);
+            }
+        }
+        self.log("debug", "ferroscope.debugger", json!({ "sent": lines }));
+
+        let mut outputs = Vec::new();
+        let mut output = MiOutput::default();
+        let mut exited = false;
+        let timeout_duration = self.command_timeout();
+        let start_time = std::time::Instant::now();
+        while outputs.len() < commands.len() {
+            let remaining = timeout_duration.saturating_sub(start_time.elapsed());
+            match self.recv_output(&mut output_lines, remaining).await {
+                Ok(Some(line)) => {
+                    let record = MiRecord::parse(&line);
+                    let token = first_token + outputs.len() as u64;
+                    let finished = matches!(
+                        &record,
+                        MiRecord::Result { token: Some(t), .. } if *t == token
+                    );
+                    output.records.push(record);
+                    if finished {
+                        outputs.push(std::mem::take(&mut output));
+                    }
+                }
+                Ok(None) => {
+                    exited = true;
+                    break;
+                }
+                Err(_) => {
+                    output.timed_out = true;
+                    break;
+                }
+            }
+        }
+        if outputs.len() < commands.len() {
+            outputs.push(output);
+        }
+        while outputs.len() < commands.len() {
+            outputs.push(MiOutput {
+                timed_out: true,
+                ..MiOutput::default()
+            });
+        }
+
+        let mut session_guard = self.session.lock().await;
+        if let Some(session) = session_guard.as_mut() {
+            for (command, output) in commands.iter().zip(&outputs) {
+                session.log(format!("(gdb) {}\n{}", command, output.text().trim_end()));
+                self.log(
+                    "debug",
+                    "ferroscope.debugger",
+                    json!({ "command": command, "response": output.text() }),
+                );
+                self.update_session_state_from_mi(output, session);
+            }
+        }
+
+        if exited {
+            drop(session_guard);
+            let text: String = outputs.iter().map(MiOutput::text).collect();
+            return Err(self.debugger_exited(&text).await);
+        }
+        Ok(outputs)
+    }
+
+    /// Sends a command in the dialect of the active debugger and returns its text output.
+    ///
+    /// `lldb` is used for the LLDB command interpreter and `mi` for GDB/MI debuggers.
+    async fn send_command(&self, lldb: &str, mi: &str) -> Result<String> {
+        if self.uses_mi().await {
+            Ok(self.send_mi_command(mi).await?.text())
+        } else {
+            self.send_debugger_command(lldb).await
+        }
+    }
+
+    /// How long the current tool call lets a debugger command take.
+    fn command_timeout(&self) -> std::time::Duration {
+        COMMAND_TIMEOUT
+            .try_with(|timeout| *timeout)
+            .unwrap_or_else(|_| self.default_timeout())
+    }
+
+    /// How long the session may be idle: as `ferroscope.toml` says, or
+    /// `--idle-timeout-secs`; `None` when sessions are never ended for idleness.
+    fn idle_timeout(&self) -> Option<std::time::Duration> {
+        let configured = self.config.lock().unwrap().idle_timeout;
+        configured
+            .or(self.options.idle_timeout)
+            .filter(|timeout| !timeout.is_zero())
+    }
+
+    /// Caps on the program's resources: as `ferroscope.toml` sets them, or the
+    /// `--limit-*` flags.
+    fn resource_limits(&self) -> ResourceLimits {
+        self.config.lock().unwrap().limits.or(self.options.limits)
+    }
+
+    /// How long debugger commands may take when a tool call sets no `timeout_ms`: as
+    /// `ferroscope.toml` says, or `--timeout-ms`.
+    fn default_timeout(&self) -> std::time::Duration {
+        self.config
+            .lock()
+            .unwrap()
+            .timeout
+            .unwrap_or(self.options.command_timeout)
+    }
+
+    /// Returns the active session's debugger stdin and output, or an error without one
+    /// or once the debugger has died.
+    async fn debugger_pipes(&self) -> Result<DebuggerPipes> {
+        let mut session_guard = self.session.lock().await;
+        let session = session_guard
+            .as_mut()
+            .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+        self.check_debugger(session)?;
+        Ok((session.stdin.clone(), session.output.clone()))
+    }
+
+    /// Checks that the session's debugger is still running, logging its death the
+    /// first time it is found dead.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ErrorKind::DebuggerCrashed`] error pointing at `debug_recover` once
+    /// the debugger has died.
+    fn check_debugger(&self, session: &mut DebugSession) -> Result<()> {
+        let known = session.state == DebugState::DebuggerDied;
+        if !session.debugger_died() {
+            return Ok(());
+        }
+        if !known {
+            self.log_debugger_death(session);
+        }
+        let stderr = session.take_stderr().join("\n");
+        Err(debugger_died(session.debugger_exit.as_deref(), &stderr))
+    }
+
+    /// Logs that the session's debugger died and how.
+    fn log_debugger_death(&self, session: &DebugSession) {
+        self.log(
+            "error",
+            "ferroscope.session",
+            json!({ "debugger_exited": session.debugger_exit }),
+        );
+        self.log_state(&session.state);
+    }
+
+    /// Marks the session's debugger dead after its output closed or it took no more
+    /// input, and returns the error for it with the last `output` it printed.
+    async fn debugger_exited(&self, output: &str) -> anyhow::Error {
+        let mut session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_mut() else {
+            return debugger_died(None, output);
+        };
+        if self.check_debugger(session).is_ok() {
+            // The output closes just before the process can be reaped
+            let exit = match tokio::time::timeout(EXIT_GRACE, session.process.wait()).await {
+                Ok(Ok(status)) => status.to_string(),
+                _ => {
+                    let _ = session.process.start_kill();
+                    "closed its output and was killed".to_string()
+                }
+            };
+            session.state = DebugState::DebuggerDied;
+            session.debugger_exit = Some(exit);
+            self.log_debugger_death(session);
+        }
+        let mut output = output.trim_end().to_string();
+        for line in session.take_stderr() {
+            output.push('\n');
+            output.push_str(&line);
+        }
+        debugger_died(session.debugger_exit.as_deref(), &output)
+    }
+
+    /// Checks whether a new LLDB session can print sentinel markers, which needs its
+    /// Python support, and frames the session's replies with them if so. Output that
+    /// arrives before the marker is applied as if nobody had been waiting for it.
+    async fn probe_sentinels(&self) {
+        let Ok((stdin, output)) = self.debugger_pipes().await else {
+            return;
+        };
+        let mut output = output.lock().await;
+        let (marker, print_marker) = lldb_sentinel(0);
+        {
+            let mut stdin = stdin.lock().await;
+            let line = format!("{}\n", print_marker);
+            if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                return;
+            }
+        }
+
+        let deadline = std::time::Instant::now() + SENTINEL_PROBE_TIMEOUT;
+        let mut earlier = String::new();
+        let supported = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.recv_output(&mut output, remaining).await {
+                Ok(Some(line)) if line.trim() == marker => break true,
+                Ok(Some(line)) => {
+                    let failed = line.contains("error:");
+                    earlier.push_str(&line);
+                    if failed {
+                        break false;
+                    }
+                }
+                Ok(None) | Err(_) => break false,
+            }
+        };
+
+        let mut session_guard = self.session.lock().await;
+        if let Some(session) = session_guard.as_mut() {
+            session.sentinels = supported;
+            self.apply_debugger_output(session, &earlier).await;
+        }
+        self.log(
+            "info",
+            "ferroscope.debugger",
+            json!({ "sentinel_framing": supported }),
+        );
+    }
+
+    /// The tool lock of the active session, which tool `name` holds while it runs,
+    /// unless it is one of the [`CONCURRENT_TOOLS`].
+    async fn tool_lock(&self, name: &str) -> Option<Arc<Mutex<()>>> {
+        if CONCURRENT_TOOLS.contains(&name) {
+            return None;
+        }
+        let session_guard = self.session.lock().await;
+        session_guard
+            .as_ref()
+            .map(|session| session.tool_lock.clone())
+    }
+
+    /// Waits up to `timeout` for the debugger's next line of output, like
+    /// [`tokio::time::timeout`] on `output`, but checks every [`HEALTH_CHECK_INTERVAL`]
+    /// that the debugger still runs and reports a dead one as closed output.
+    async fn recv_output(
+        &self,
+        output: &mut mpsc::UnboundedReceiver<String>,
+        timeout: std::time::Duration,
+    ) -> std::result::Result<Option<String>, tokio::time::error::Elapsed> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let wait = deadline.saturating_duration_since(std::time::Instant::now());
+            let last = wait <= HEALTH_CHECK_INTERVAL;
+            match tokio::time::timeout(wait.min(HEALTH_CHECK_INTERVAL), output.recv()).await {
+                Err(_) if !last => {
+                    let mut session_guard = self.session.lock().await;
+                    if let Some(session) = session_guard.as_mut() {
+                        if self.check_debugger(session).is_err() {
+                            return Ok(None);
+                        }
+                    }
+                }
+                received => return received,
+            }
+        }
+    }
+
+    /// Applies debugger output that arrived while no command was waiting for it.
+    ///
+    /// Returns the output as text (rendered from MI records for MI debuggers), after
+    /// any that [`Self::pump_debugger_output`] already applied but nobody reported.
+    async fn apply_pending_output(
+        &self,
+        session: &mut DebugSession,
+        output: &mut mpsc::UnboundedReceiver<String>,
+    ) -> String {
+        session.notify_on_stop.store(false, Ordering::SeqCst);
+
+        let mut pending = String::new();
+        while let Ok(line) = output.try_recv() {
+            pending.push_str(&line);
+        }
+        let mut text = std::mem::take(&mut session.unread_output);
+        if !pending.is_empty() {
+            text.push_str(&self.apply_debugger_output(session, &pending).await);
+        }
+        text
+    }
+
+    /// Updates the session state from raw debugger output and returns it as text.
+    async fn apply_debugger_output(&self, session: &mut DebugSession, raw: &str) -> String {
+        if !raw.trim().is_empty() {
+            session.log(raw.trim_end().to_string());
+            self.log("debug", "ferroscope.debugger", json!({ "output": raw }));
+        }
+        let text = if session.kind.uses_mi() {
+            let output = MiOutput {
+                records: raw.lines().map(MiRecord::parse).collect(),
+                timed_out: false,
+            };
+            self.update_session_state_from_mi(&output, session);
+            output.text()
+        } else {
+            // Markers of commands that gave up waiting arrive here
+            let text: String = raw
+                .split_inclusive('\n')
+                .filter(|line| !is_sentinel(line))
+                .collect();
+            self.update_session_state(&text, session);
+            text
+        };
+        self.log_state(&session.state);
+        text
+    }
+
+    /// Returns true if the active session talks GDB/MI.
+    async fn uses_mi(&self) -> bool {
+        self.debugger_kind()
+            .await
+            .map(|kind| kind.uses_mi())
+            .unwrap_or(false)
+    }
+
+    /// Returns the state of the active session, `DebuggerDied` once its debugger has
+    /// exited, or `NotLoaded` when there is none.
+    async fn current_state(&self) -> DebugState {
+        let mut session_guard = self.session.lock().await;
+        session_guard
+            .as_mut()
+            .map(|s| {
+                let _ = self.check_debugger(s);
+                s.state.clone()
+            })
+            .unwrap_or(DebugState::NotLoaded)
+    }
+
+    /// Returns the debugger driven by the active session, if any.
+    async fn debugger_kind(&self) -> Option<DebuggerKind> {
+        let session_guard = self.session.lock().await;
+        session_guard.as_ref().map(|s| s.kind)
+    }
+
+    /// Guesses whether `line` ends the reply to `command` from LLDB's prompt and the
+    /// command's usual last line, for an LLDB that cannot print sentinel markers.
+    fn is_response_complete(&self, line: &str, command: &str, response: &str) -> bool {
+        // LLDB prompt detection
+        if line.trim() == "(lldb)" {
+            return true;
+        }
+
+        // Command-specific completion detection
+        let event = DebuggerEvent::parse_lldb(line);
+        if command.starts_with("process launch")
+            && matches!(
+                event,
+                Some(DebuggerEvent::Launched { .. } | DebuggerEvent::Stopped { .. })
+            )
+        {
+            return true;
+        }
+
+        if command.starts_with("process attach")
+            && (matches!(event, Some(DebuggerEvent::Stopped { .. })) || line.contains("error:"))
+        {
+            return true;
+        }
+
+        if command.starts_with("process detach") && line.contains("detached") {
+            return true;
+        }
+
+        if command.starts_with("process kill")
+            && matches!(event, Some(DebuggerEvent::Exited { .. }))
+        {
+            return true;
+        }
+
+        if command.starts_with("breakpoint ")
+            && !command.starts_with("breakpoint set")
+            && !command.starts_with("breakpoint list")
+            && (line.contains("breakpoints ") || line.contains("error:"))
+        {
+            return true;
+        }
+
+        if command.starts_with("watchpoint set") && line.contains("error:") {
+            return true;
+        }
+
+        if command.starts_with("frame info") && line.trim_start().starts_with("frame #") {
+            return true;
+        }
+
+        if command.starts_with("process continue")
+            && matches!(
+                event,
+                Some(DebuggerEvent::Stopped { .. } | DebuggerEvent::Exited { .. })
+            )
+        {
+            return true;
+        }
+
+        if command.starts_with("breakpoint set")
+            && line.contains("Breakpoint")
+            && line.contains(":")
+        {
+            return true;
+        }
+
+        // A value that opens a `{` block of children ends at its matching `}`
+        if (command.starts_with("expression") || command.starts_with("frame variable"))
+            && (line.contains("error:")
+                || ((line.contains("=") || line.trim() == "}") && Self::blocks_closed(response)))
+        {
+            return true;
+        }
+
+        false
+    }
+
+    /// Whether every `{` block opened by a line of LLDB value output has been closed.
+    fn blocks_closed(response: &str) -> bool {
+        let depth = response.lines().fold(0i32, |depth, line| {
+            let line = line.trim_end();
+            if line.trim_start() == "}" {
+                depth - 1
+            } else if line.ends_with('{') {
+                depth + 1
+            } else {
+                depth
+            }
+        });
+        depth <= 0
+    }
+
+    /// Moves the session along the transition `event` causes. Events that cannot happen
+    /// in the current state are logged and ignored; returns whether `event` applied.
+    fn apply_event(&self, session: &mut DebugSession, event: &DebuggerEvent) -> bool {
+        let Some(state) = session.state.on_event(event) else {
+            self.log(
+                "debug",
+                "ferroscope.session",
+                json!({
+                    "ignored_event": format!("{:?}", event),
+                    "state": format!("{:?}", session.state).to_lowercase()
+                }),
+            );
+            return false;
+        };
+
+        if let DebuggerEvent::Launched { pid: Some(pid) }
+        | DebuggerEvent::Stopped { pid: Some(pid) } = event
+        {
+            // LLDB reports a followed child's stops under the child's PID
+            if matches!(event, DebuggerEvent::Stopped { .. })
+                && session.inferior_pid.is_some_and(|old| old != *pid)
+            {
+                self.add_inferior(session, *pid, None);
+            }
+            session.inferior_pid = Some(*pid);
+        }
+        if state == DebugState::Running {
+            session.exit_status = None;
+        }
+        session.state = state;
+        true
+    }
+
+    /// Feeds the `Process <pid> ...` lines of LLDB output to the session's state machine.
+    fn update_session_state(&self, response: &str, session: &mut DebugSession) {
+        let mut stopped = false;
+        for line in response.lines() {
+            let Some(event) = DebuggerEvent::parse_lldb(line) else {
+                continue;
+            };
+            if !self.apply_event(session, &event) {
+                continue;
+            }
+            match event {
+                DebuggerEvent::Stopped { .. } => stopped = true,
+                DebuggerEvent::Exited { .. } => session.exit_status = ExitStatus::parse_lldb(line),
+                _ => {}
+            }
+        }
+
+        // The thread and frame lines following a stop tell where the program is
+        if stopped {
+            if let Some(location) = self.extract_location_from_response(response) {
+                session.current_location = Some(location);
+            }
+
+            // The debugger selects the thread that stopped and its innermost frame
+            if let Some(thread) = response
+                .lines()
+                .find_map(|line| Self::parse_lldb_thread_line(line).filter(|t| t.selected))
+            {
+                session.selected_thread = Some(thread.id);
+            }
+            session.selected_frame = 0;
+            session.stop_signal = StopSignal::parse_lldb(response);
+        }
+    }
+
+    /// Records a process the program forked that the debugger now controls, for the
+    /// next tool result to report.
+    fn add_inferior(&self, session: &mut DebugSession, pid: u32, id: Option<&str>) {
+        let inferior = json!({ "pid": pid, "inferior": id });
+        self.log(
+            "info",
+            "ferroscope.session",
+            json!({ "new_inferior": inferior }),
+        );
+        session.new_inferiors.push(inferior);
+    }
+
+    /// Feeds MI records to the session's state machine.
+    fn update_session_state_from_mi(&self, output: &MiOutput, session: &mut DebugSession) {
+        for record in &output.records {
+            if let MiRecord::Notify { class, results } = record {
+                if class == "thread-group-started" {
+                    if let Some(pid) = results
+                        .get("pid")
+                        .and_then(|v| v.as_str())
+                        .and_then(|pid| pid.parse().ok())
+                    {
+                        // Once the program runs, another process starting is a fork
+                        // the debugger picked up
+                        if session.inferior_pid.is_none() || session.state == DebugState::Loaded {
+                            session.inferior_pid = Some(pid);
+                        } else if session.inferior_pid != Some(pid) {
+                            let id = results.get("id").and_then(|v| v.as_str());
+                            self.add_inferior(session, pid, id);
+                            if session.launch.follow_fork == FollowFork::Child {
+                                session.inferior_pid = Some(pid);
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let Some(event) = DebuggerEvent::from_mi(record) else {
+                continue;
+            };
+            if !self.apply_event(session, &event) {
+                continue;
+            }
+            if let MiRecord::Exec { class, results } = record {
+                if class != "stopped" {
+                    continue;
+                }
+                let field = |name: &str| results.get(name).and_then(|v| v.as_str());
+                if let Some(location) = results.get("frame").and_then(mi_frame_location) {
+                    session.current_location = Some(location);
+                }
+
+                // The debugger selects the thread that stopped and its innermost frame
+                if let Some(thread) = field("thread-id").and_then(|id| id.parse().ok()) {
+                    session.selected_thread = Some(thread);
+                }
+                session.selected_frame = 0;
+                session.stop_signal = StopSignal::from_mi(results);
+                session.watchpoint_hit = WatchpointHit::from_mi(results);
+                if let Some(exit_status) = ExitStatus::from_mi(results) {
+                    session.exit_status = Some(exit_status);
+                }
+            }
+        }
+    }
+
+    fn extract_location_from_response(&self, response: &str) -> Option<String> {
+        // Look for patterns like "at main.rs:10:5"
+        for line in response.lines() {
+            if line.contains(" at ") {
+                if let Some(location_part) = line.split(" at ").nth(1) {
+                    if let Some(location) = location_part.split_whitespace().next() {
+                        return Some(location.to_string());
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Loads and prepares a Rust program for debugging.
+    ///
+    /// This is the primary tool for starting a debugging session. It can accept either
+    /// a path to a compiled binary or a path to a Rust project directory. If given a
+    /// directory, it will automatically build the project using `cargo build`.
+    ///
+    /// # Arguments
+    ///
+    /// * `binary_path` - Path to a compiled binary or Rust project directory
+    /// * `debugger` - Which debugger to drive; MI debuggers yield structured responses
+    /// * `launch` - Arguments, environment, working directory, and stdin used when the
+    ///   program is started by `debug_continue`
+    /// * `build` - Package, binary, example, or benchmark, and features to build when
+    ///   `binary_path` is a project directory
+    /// * `break_on_panic` - Stop on panics and aborts (see [`PANIC_BREAKPOINTS`]) so the
+    ///   panicking stack can be inspected before the process dies
+    ///
+    /// With `sanitizers` in `build`, a project is built instrumented, a breakpoint is set on
+    /// each sanitizer's report function, and the sanitizer's runtime options make reports
+    /// abort; a prebuilt binary must have been built with the same sanitizers.
+    ///
+    /// # Returns
+    ///
+    /// Returns a JSON response indicating success or failure of loading the program.
+    ///
+    /// # Examples
+    ///
+    /// Loading a Rust project directory:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./my_rust_project"}}
+    /// ```
+    ///
+    /// Loading a compiled binary:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./target/debug/my_program"}}
+    /// ```
+    ///
+    /// Debugging through GDB's machine interface:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./my_rust_project", "debugger": "gdb"}}
+    /// ```
+    ///
+    /// Passing arguments and environment to the program:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./my_cli", "args": ["--input", "data.txt"], "env": {"RUST_LOG": "debug"}}}
+    /// ```
+    ///
+    /// Building one binary of a workspace member, like `cargo run -p server --bin worker --features tls`:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./my_workspace", "package": "server", "bin": "worker", "features": ["tls"]}}
+    /// ```
+    ///
+    /// Debugging an example:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"project_path": ".", "example": "demo"}}
+    /// ```
+    ///
+    /// Catching memory errors in unsafe code with AddressSanitizer:
+    /// ```json
+    /// {"name": "debug_run", "arguments": {"binary_path": "./my_rust_project", "sanitizers": ["address"]}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The binary path does not exist
+    /// - Building the Rust project fails (for directory paths)
+    /// - Starting the debugger process fails
+    /// - The debugger cannot load the binary
+    pub(crate) async fn debug_run(
+        &self,
+        binary_path: &str,
+        debugger: DebuggerKind,
+        mut launch: LaunchConfig,
+        build: BuildConfig,
+        break_on_panic: bool,
+    ) -> Result<Value> {
+        // Clean up any existing session
+        self.end_session().await;
+
+        // Benchmarks only run their benchmarks when told to, as `cargo bench` does
+        if build.bench.is_some() && launch.args.is_empty() {
+            launch.args.push("--bench".to_string());
+        }
+        for (name, value) in build.sanitizers.iter().filter_map(|s| s.options_env()) {
+            if !launch.env.iter().any(|(key, _)| key == name) {
+                launch.env.push((name.to_string(), value.to_string()));
+            }
+        }
+        let sanitizers = build.sanitizers.clone();
+
+        // Check if the path is a directory (source code) or binary
+        let path = std::path::Path::new(binary_path);
+        let (binary_to_debug, artifact) = if path.is_dir() {
+            // It's a source directory, try to build it
+            let artifact = self.build_rust_project(binary_path, &build).await?;
+            (artifact.executable.clone(), Some(artifact))
+        } else if path.exists() {
+            // It's an existing binary
+            (binary_path.to_string(), None)
+        } else {
+            return Err(
+                ErrorKind::InvalidArgument.error(format!("Path does not exist: {}", binary_path))
+            );
+        };
+
+        // Start debugger with the binary
+        self.report_progress(None, None, &format!("Starting {}", debugger.name()));
+        let mut result = self
+            .start_debugger_session(&binary_to_debug, debugger, launch)
+            .await?;
+        self.report_progress(None, None, &format!("Loaded {}", binary_to_debug));
+
+        if let Some(artifact) = artifact {
+            result["build"] = build.to_json();
+            let mut warnings = Vec::new();
+            if artifact.opt_level != "0" {
+                warnings.push(format!(
+                    "Built with opt-level {}: stepping may skip or revisit lines, and variables may be optimized out",
+                    artifact.opt_level
+                ));
+            }
+            if !artifact.debuginfo {
+                warnings.push(
+                    "Built without debug info, so line breakpoints and variables will not work; pass \"debuginfo\": true"
+                        .to_string(),
+                );
+            }
+            if !warnings.is_empty() {
+                result["warnings"] = json!(warnings);
+            }
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.source_dir = Some(binary_path.to_string());
+                session.build = build;
+            }
+        }
+
+        if break_on_panic {
+            result["panic_breakpoints"] = json!(self.set_panic_breakpoints().await?);
+        }
+        if !sanitizers.is_empty() {
+            result["sanitizer_breakpoints"] =
+                json!(self.set_sanitizer_breakpoints(&sanitizers).await?);
+        }
+        Ok(result)
+    }
+
+    /// Builds the tests of the project in `project_path` and runs the one named
+    /// `test_name` under the debugger, stopped at the start of the test function.
+    ///
+    /// `test_name` is the test's path as `cargo test -- --list` shows it, e.g.
+    /// `parser::tests::empty_input`, or any `::`-separated suffix of it that names a
+    /// single test; targets with `harness = false` cannot list their tests and are
+    /// skipped. The test binary holding it runs only that test, with
+    /// `--exact --nocapture`, from the package directory like `cargo test` does. Panic
+    /// breakpoints are set as by `debug_run`, so a failing assertion stops the program
+    /// with the test's stack intact.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_test", "arguments": {"project_path": "./my_rust_project", "test_name": "parses_empty_input"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - `project_path` is not a directory
+    /// - Building the tests fails
+    /// - Starting the debugger process fails
+    async fn debug_test(
+        &self,
+        project_path: &str,
+        test_name: &str,
+        debugger: DebuggerKind,
+    ) -> Result<Value> {
+        if !std::path::Path::new(project_path).is_dir() {
+            return Err(ErrorKind::InvalidArgument
+                .error(format!("Not a project directory: {}", project_path)));
+        }
+        self.end_session().await;
+
+        let messages = self
+            .run_cargo(
+                project_path,
+                &["test", "--no-run", "--message-format=json"],
+                &[],
+            )
+            .await?;
+        let binaries: Vec<CargoArtifact> = messages
+            .lines()
+            .filter_map(CargoArtifact::parse)
+            .filter(|artifact| artifact.test)
+            .collect();
+
+        // Each test binary lists its tests by full path. Listing runs the binary, so it
+        // is sandboxed and timed like the debugger, and skipped if not a libtest harness.
+        let suffix = format!("::{}", test_name);
+        let mut matches = Vec::new();
+        let mut skipped = Vec::new();
+        for binary in &binaries {
+            let harness = tokio::fs::read(&binary.executable)
+                .await
+                .is_ok_and(|bytes| libtest_harness(&bytes));
+            if !harness {
+                skipped
+                    .push(json!({"binary": binary.executable, "reason": "not a libtest harness"}));
+                continue;
+            }
+            let mut cmd = tokio::process::Command::new(&binary.executable);
+            cmd.args(["--list", "--format", "terse"]);
+            if self.options.sandbox {
+                cmd = sandbox_command(&cmd);
+            }
+            cmd.stdin(Stdio::null()).kill_on_drop(true);
+            let output = match tokio::time::timeout(self.command_timeout(), cmd.output()).await {
+                Ok(Ok(output)) => output,
+                Ok(Err(_)) => continue,
+                Err(_) => {
+                    skipped.push(json!({"binary": binary.executable, "reason": "listing its tests timed out"}));
+                    continue;
+                }
+            };
+            for name in String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.strip_suffix(": test"))
+            {
+                if name == test_name || name.ends_with(&suffix) {
+                    matches.push((binary, name.to_string()));
+                }
+            }
+        }
+        if matches.iter().any(|(_, name)| name == test_name) {
+            matches.retain(|(_, name)| name == test_name);
+        }
+
+        let (binary, test) = match matches.as_slice() {
+            [(binary, test)] => (*binary, test.clone()),
+            [] => {
+                return Ok(json!({
+                    "success": false,
+                    "error": format!("No test named {} in {}", test_name, project_path),
+                    "error_kind": ErrorKind::LocationNotFound.name(),
+                    "state": "not_loaded",
+                    "test_binaries": binaries
+                        .iter()
+                        .map(|binary| binary.executable.as_str())
+                        .collect::<Vec<_>>(),
+                    "skipped_binaries": skipped
+                }));
+            }
+            _ => {
+                return Ok(json!({
+                    "success": false,
+                    "error": format!("{} matches several tests; pass one of their full names", test_name),
+                    "error_kind": ErrorKind::InvalidArgument.name(),
+                    "state": "not_loaded",
+                    "candidates": matches
+                        .iter()
+                        .map(|(binary, test)| json!({"test": test, "binary": binary.executable}))
+                        .collect::<Vec<_>>()
+                }));
+            }
+        };
+
+        let package_dir = std::path::Path::new(&binary.manifest_path)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_else(|| project_path.to_string());
+        let launch = LaunchConfig {
+            args: vec![
+                test.clone(),
+                "--exact".to_string(),
+                "--nocapture".to_string(),
+            ],
+            env: vec![("CARGO_MANIFEST_DIR".to_string(), package_dir.clone())],
+            cwd: Some(package_dir),
+            stdin_file: None,
+            stop_at_entry: false,
+            follow_fork: FollowFork::Parent,
+        };
+        let loaded = self
+            .debug_run(
+                &binary.executable,
+                debugger,
+                launch,
+                BuildConfig::default(),
+                true,
+            )
+            .await?;
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.source_dir = Some(project_path.to_string());
+                session.test_name = Some(test.clone());
+            }
+        }
+
+        // The test function is named by its path within the test crate
+        let crate_name = binary.target_name.replace('-', "_");
+        let breakpoint = self
+            .debug_break(&format!("{}::{}", crate_name, test), None, None)
+            .await?;
+
+        let mut result = self.debug_continue(None, true).await?;
+        result["test"] = json!(test);
+        result["binary_path"] = json!(binary.executable);
+        result["debugger"] = loaded["debugger"].clone();
+        result["launch"] = loaded["launch"].clone();
+        result["debug_info"] = loaded["debug_info"].clone();
+        result["panic_breakpoints"] = loaded["panic_breakpoints"].clone();
+        result["breakpoint"] = json!({
+            "id": breakpoint["id"],
+            "location": breakpoint["location"],
+            "resolved_locations": breakpoint["resolved_locations"]
+        });
+        Ok(result)
+    }
+
+    /// Runs the project in `project_path` under Miri, the interpreter that checks each
+    /// operation against Rust's rules, and reports the undefined behavior and other
+    /// errors it found.
+    ///
+    /// Without `test_name` the project's binary runs with `cargo miri run`; with it, the
+    /// tests matching `test_name` run with `cargo miri test`. Each finding carries Miri's
+    /// `kind` (e.g. `Undefined Behavior`), its message, the `span` it occurred at with the
+    /// `source` around it, the `help` explaining which rule was broken, `related` places
+    /// such as where the memory was allocated, and the `backtrace`. This complements the
+    /// debugger: Miri catches bugs that corrupt memory silently, but runs far slower and
+    /// cannot call most foreign functions.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_miri", "arguments": {"project_path": "./my_rust_project", "test_name": "parses_empty_input"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `project_path` is not a directory or
+    /// cargo cannot be started.
+    async fn debug_miri(&self, project_path: &str, test_name: Option<&str>) -> Result<Value> {
+        if !std::path::Path::new(project_path).is_dir() {
+            return Err(ErrorKind::InvalidArgument
+                .error(format!("Not a project directory: {}", project_path)));
+        }
+        let mut args = vec!["miri"];
+        match test_name {
+            Some(test_name) => args.extend(["test", test_name]),
+            None => args.push("run"),
+        }
+        let command_line = format!("cargo {}", args.join(" "));
+
+        self.report_progress(None, None, &format!("Running {}", command_line));
+        let mut command = tokio::process::Command::new("cargo");
+        command
+            .args(&args)
+            .current_dir(project_path)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        // Miri ships with nightly toolchains only
+        if std::env::var_os("RUSTUP_TOOLCHAIN").is_none() {
+            command.env("RUSTUP_TOOLCHAIN", "nightly");
+        }
+        let output = command.output().await?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no such command: `miri`")
+            || stderr.contains("'cargo-miri' is not installed")
+        {
+            return Ok(json!({
+                "success": false,
+                "error": format!(
+                    "Miri is not installed; add it with `rustup +nightly component add miri`: {}",
+                    stderr.lines().next().unwrap_or_default().trim()
+                ),
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+
+        let diagnostics = MiriDiagnostic::parse_all(&stderr);
+        let mut findings = Vec::new();
+        for diagnostic in &diagnostics {
+            let mut finding = diagnostic.to_json();
+            if let Some(span) = &diagnostic.span {
+                // Miri names files relative to the workspace root
+                let local = std::path::Path::new(project_path).join(&span.file);
+                let path = match local.is_file() {
+                    true => Some(local),
+                    false => self.resolve_source_path(&span.file).await,
+                };
+                let line = u32::try_from(span.line).ok();
+                if let (Some(path), Some(line)) = (path, line) {
+                    if let Some(source) = self.source_around(&path, line) {
+                        finding["source"] = source;
+                    }
+                }
+            }
+            findings.push(finding);
+        }
+
+        let summary = match diagnostics.first() {
+            Some(diagnostic) => {
+                let mut summary = diagnostic.kind.clone().unwrap_or("error".to_string());
+                if let Some(span) = &diagnostic.span {
+                    summary.push_str(&format!(" at {}:{}:{}", span.file, span.line, span.column));
+                }
+                format!("{}: {}", summary, diagnostic.message)
+            }
+            None if output.status.success() => "Miri found no undefined behavior".to_string(),
+            None => format!("{} failed without a Miri error", command_line),
+        };
+        Ok(json!({
+            "success": true,
+            "command": command_line,
+            "passed": output.status.success(),
+            "exit_code": output.status.code(),
+            "undefined_behavior": diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.kind.as_deref() == Some("Undefined Behavior")),
+            "findings": findings,
+            "summary": summary,
+            "stdout": stdout,
+            "stderr": stderr
+        }))
+    }
+
+    /// Launches `command` under the debugger, stopped at its first instruction.
+    ///
+    /// For programs that need a launcher: the command is looked up on `PATH` unless it
+    /// is a path, and a `#!` script is debugged as its interpreter running the script.
+    /// Breakpoints can be set before `debug_continue` lets it run; `debug_restart`
+    /// relaunches it stopped at entry again.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_launch_command", "arguments": {"command": "./scripts/run-server.sh", "args": ["--port", "8080"]}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - `command` is not found
+    /// - Starting the debugger process fails
+    /// - The debugger cannot load the program
+    async fn debug_launch_command(
+        &self,
+        command: &str,
+        debugger: DebuggerKind,
+        mut launch: LaunchConfig,
+    ) -> Result<Value> {
+        self.end_session().await;
+
+        let (program, leading_args) = Self::resolve_command(command)?;
+        launch.args.splice(0..0, leading_args);
+        launch.stop_at_entry = true;
+
+        self.report_progress(None, None, &format!("Starting {}", debugger.name()));
+        let loaded = self
+            .start_debugger_session(&program, debugger, launch)
+            .await?;
+
+        let mut result = self.debug_continue(None, true).await?;
+        result["program"] = json!(program);
+        result["debugger"] = loaded["debugger"].clone();
+        result["launch"] = loaded["launch"].clone();
+        result["debug_info"] = loaded["debug_info"].clone();
+        Ok(result)
+    }
+
+    /// Finds what the debugger runs for `command`: the executable it names, searched
+    /// for on `PATH` when it has no `/`, and the arguments to put before the user's.
+    ///
+    /// A `#!` script cannot be loaded by a debugger, so its interpreter is run instead
+    /// with the interpreter's argument and the script, as the kernel would; for
+    /// `#!/usr/bin/env prog` the interpreter is `prog` on `PATH`.
+    fn resolve_command(command: &str) -> Result<(String, Vec<String>)> {
+        let find = |command: &str| {
+            let path = if command.contains('/') {
+                Some(std::path::PathBuf::from(command))
+            } else {
+                std::env::var_os("PATH").and_then(|paths| {
+                    std::env::split_paths(&paths)
+                        .map(|dir| dir.join(command))
+                        .find(|path| path.is_file())
+                })
+            };
+            path.filter(|path| path.is_file())
+                .map(|path| path.to_string_lossy().to_string())
+                .ok_or_else(|| {
+                    ErrorKind::InvalidArgument.error(format!("Command not found: {}", command))
+                })
+        };
+        let path = find(command)?;
+
+        let mut head = Vec::new();
+        std::io::Read::read_to_end(
+            &mut std::io::Read::take(std::fs::File::open(&path)?, 256),
+            &mut head,
+        )?;
+        let Some(shebang) = head.strip_prefix(b"#!") else {
+            return Ok((path, Vec::new()));
+        };
+        let line = String::from_utf8_lossy(shebang);
+        let line = line.lines().next().unwrap_or("").trim();
+        let (interpreter, argument) = match line.split_once(char::is_whitespace) {
+            Some((interpreter, argument)) => (interpreter, Some(argument.trim())),
+            None => (line, None),
+        };
+        if interpreter.is_empty() {
+            return Err(
+                ErrorKind::InvalidArgument.error(format!("{} has no interpreter after #!", path))
+            );
+        }
+
+        match argument {
+            Some(program) if interpreter.ends_with("/env") && !program.starts_with('-') => {
+                Ok((find(program)?, vec![path]))
+            }
+            Some(argument) => Ok((find(interpreter)?, vec![argument.to_string(), path])),
+            None => Ok((find(interpreter)?, vec![path])),
+        }
+    }
+
+    /// Sets a breakpoint on each of [`PANIC_BREAKPOINTS`], returning what was set.
+    async fn set_panic_breakpoints(&self) -> Result<Vec<Value>> {
+        self.set_function_breakpoints(&PANIC_BREAKPOINTS).await
+    }
+
+    /// Sets a breakpoint on the report function of each of `sanitizers`, returning what
+    /// was set.
+    async fn set_sanitizer_breakpoints(&self, sanitizers: &[Sanitizer]) -> Result<Vec<Value>> {
+        let locations: Vec<&str> = sanitizers.iter().map(|s| s.report_function()).collect();
+        self.set_function_breakpoints(&locations).await
+    }
+
+    /// Sets a breakpoint on each function in `locations`, resolved when the library
+    /// defining it loads, and returns what was set.
+    ///
+    /// The breakpoints are tracked like user breakpoints, so they show up in
+    /// `debug_list_breakpoints` and can be removed with `debug_break_delete`.
+    async fn set_function_breakpoints(&self, locations: &[&str]) -> Result<Vec<Value>> {
+        let uses_mi = self.uses_mi().await;
+        let mut set = Vec::new();
+
+        for &location in locations {
+            // Symbols in libc only resolve once the program has loaded its libraries
+            let id = if uses_mi {
+                let output = self
+                    .send_mi_command(&format!("-break-insert -f {}", location))
+                    .await?;
+                output
+                    .result_field("bkpt")
+                    .and_then(|bkpt| bkpt.get("number"))
+                    .and_then(|v| v.as_str())
+                    .and_then(|number| number.parse().ok())
+            } else {
+                let response = self
+                    .send_debugger_command(&format!("breakpoint set --name {}", location))
+                    .await?;
+                Self::parse_breakpoint_id(&response)
+            };
+
+            self.record_breakpoint(id, location, None, None).await;
+            set.push(json!({"location": location, "id": id}));
+        }
+        Ok(set)
+    }
+
+    /// Builds the targets `build` selects in `source_dir` with `cargo build` and returns
+    /// the path of the binary (or example) to debug.
+    ///
+    /// The binary is the executable cargo reports building, so workspaces, `[[bin]]`
+    /// targets, and a custom `target-dir` are found too. When several were built, the
+    /// ones of the package in `source_dir` itself are preferred; if that still leaves
+    /// more than one, the caller has to pick one with `bin` or by path.
+    async fn build_rust_project(
+        &self,
+        source_dir: &str,
+        build: &BuildConfig,
+    ) -> Result<CargoArtifact> {
+        let mut args = vec!["build".to_string(), "--message-format=json".to_string()];
+        args.extend(build.cargo_args());
+        if !build.sanitizers.is_empty() {
+            // With an explicit target, build scripts and proc macros are not instrumented
+            args.push("--target".to_string());
+            args.push(self.host_target(source_dir, &build.cargo_env()).await?);
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let messages = self
+            .run_cargo(source_dir, &args, &build.cargo_env())
+            .await?;
+        let (kind, name) = match (&build.example, &build.bench) {
+            (Some(example), _) => ("example", Some(example)),
+            (_, Some(bench)) => ("bench", Some(bench)),
+            _ => ("bin", build.bin.as_ref()),
+        };
+        let binaries: Vec<CargoArtifact> = messages
+            .lines()
+            .filter_map(CargoArtifact::parse)
+            .filter(|artifact| artifact.kinds.iter().any(|k| k == kind))
+            .filter(|artifact| name.is_none_or(|name| &artifact.target_name == name))
+            .collect();
+
+        let manifest = std::fs::canonicalize(source_dir)?.join("Cargo.toml");
+        let own: Vec<&CargoArtifact> = binaries
+            .iter()
+            .filter(|binary| std::path::Path::new(&binary.manifest_path) == manifest)
+            .collect();
+        let candidates = if own.is_empty() {
+            binaries.iter().collect()
+        } else {
+            own
+        };
+        match candidates.as_slice() {
+            [binary] => Ok((*binary).clone()),
+            [] => {
+                Err(ErrorKind::BuildFailed
+                    .error(format!("Building {} produced no binary", source_dir)))
+            }
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Building {} produced several binaries; choose one with package and bin, or pass its path: {}",
+                source_dir,
+                candidates
+                    .iter()
+                    .map(|binary| binary.executable.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    /// The target triple of the host, as the toolchain selected by `env` in `source_dir`
+    /// reports it.
+    async fn host_target(&self, source_dir: &str, env: &[(String, String)]) -> Result<String> {
+        let output = tokio::process::Command::new("rustc")
+            .arg("-vV")
+            .envs(env.iter().cloned())
+            .current_dir(source_dir)
+            .output()
+            .await?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                ErrorKind::BuildFailed.error(format!(
+                    "Could not find the host target with rustc -vV: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            })
+    }
+
+    /// Runs `cargo` with `args` and the extra environment `env` in `source_dir`, and
+    /// returns what it printed on stdout.
+    ///
+    /// Cargo's progress bar is forced on so each crate compiled can be reported as
+    /// progress on the tool call.
+    async fn run_cargo(
+        &self,
+        source_dir: &str,
+        args: &[&str],
+        env: &[(String, String)],
+    ) -> Result<String> {
+        self.report_progress(Some(0), None, &format!("Building {}", source_dir));
+
+        let mut child = tokio::process::Command::new("cargo")
+            .args(args)
+            .envs(env.iter().cloned())
+            .current_dir(source_dir)
+            .env("CARGO_TERM_PROGRESS_WHEN", "always")
+            .env("CARGO_TERM_PROGRESS_WIDTH", "100")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get cargo's stdout"))?;
+        let stdout = tokio::spawn(async move {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).await.map(|_| output)
+        });
+
+        let mut stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get cargo's stderr"))?;
+        let mut messages = String::new();
+        let mut pending = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let read = stderr.read(&mut chunk).await?;
+            pending.extend_from_slice(&chunk[..read]);
+            // The progress bar is redrawn after a carriage return rather than a newline
+            while let Some(end) = pending.iter().position(|&b| b == b'\r' || b == b'\n') {
+                let segment: Vec<u8> = pending.drain(..=end).collect();
+                let segment = String::from_utf8_lossy(&segment);
+                match cargo_build_progress(&segment) {
+                    Some((done, total, units)) => self.report_progress(
+                        Some(done),
+                        Some(total),
+                        &format!("Compiling {}/{}: {}", done, total, units),
+                    ),
+                    None if !segment.trim().is_empty() => {
+                        messages.push_str(segment.trim_end());
+                        messages.push('\n');
+                    }
+                    None => {}
+                }
+            }
+            if read == 0 {
+                messages.push_str(&String::from_utf8_lossy(&pending));
+                break;
+            }
+        }
+
+        let stdout = stdout.await??;
+        if !child.wait().await?.success() {
+            // With `--message-format=json` the compiler's errors are on stdout
+            let diagnostics: Vec<CompilerDiagnostic> = stdout
+                .lines()
+                .filter_map(CompilerDiagnostic::parse)
+                .collect();
+            let rendered: String = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.level.starts_with("error"))
+                .filter_map(|diagnostic| diagnostic.rendered.as_deref())
+                .collect();
+            return Err(ErrorKind::BuildFailed.error_with_details(
+                format!("Build failed: {}{}", rendered, messages),
+                json!({
+                    "diagnostics": diagnostics
+                        .iter()
+                        .map(CompilerDiagnostic::to_json)
+                        .collect::<Vec<_>>()
+                }),
+            ));
+        }
+        Ok(stdout)
+    }
+
+    /// Ends the session as a departing client should: the debugger kills a launched
+    /// program or detaches from an attached one, and then is killed itself. A launched
+    /// program is killed directly if the debugger does not answer in time. Called when
+    /// the client sends `shutdown` or closes stdin, and when the session is idle too long.
+    pub async fn shutdown(&self) {
+        let (live, died, attached, pid, kind) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return;
+            };
+            (
+                matches!(session.state, DebugState::Running | DebugState::Stopped)
+                    && session.core_path.is_none(),
+                session.state == DebugState::DebuggerDied,
+                session.attached,
+                session
+                    .inferior_pid
+                    .filter(|_| session.remote_target.is_none() && session.replay_trace.is_none()),
+                session.kind,
+            )
+        };
+
+        if live {
+            let (lldb_command, mi_command) = if attached {
+                ("process detach", "-target-detach".to_string())
+            } else if kind == DebuggerKind::Gdb {
+                ("process kill", mi_console_command("kill"))
+            } else {
+                ("process kill", "-exec-abort".to_string())
+            };
+            let answered = COMMAND_TIMEOUT
+                .scope(
+                    SHUTDOWN_TIMEOUT,
+                    self.send_command(lldb_command, &mi_command),
+                )
+                .await
+                .is_ok();
+            if let (false, false, Some(pid)) = (answered, attached, pid) {
+                let _ = tokio::process::Command::new("kill")
+                    .args(["-KILL", &pid.to_string()])
+                    .status()
+                    .await;
+            }
+        } else if let (true, false, Some(pid)) = (died, attached, pid) {
+            // A program the dead debugger launched may have outlived it
+            let _ = tokio::process::Command::new("kill")
+                .args(["-KILL", &pid.to_string()])
+                .status()
+                .await;
+        }
+        self.end_session().await;
+    }
+
+    /// Shuts the session down once no tool call has used it for the idle timeout, so a
+    /// client that went away without a word does not leave a debugger and its program
+    /// behind. Like [`Self::pump_debugger_output`], the transports run this next to
+    /// request handling and it never returns.
+    pub async fn reap_idle_session(&self) {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            let Some(timeout) = self.idle_timeout() else {
+                continue;
+            };
+            // A long debug_wait_for_stop is not idleness
+            if !self.in_flight.lock().unwrap().is_empty() {
+                continue;
+            }
+            let idle = {
+                let session_guard = self.session.lock().await;
+                session_guard
+                    .as_ref()
+                    .map(|session| session.last_used.elapsed())
+            };
+            if idle.is_some_and(|idle| idle >= timeout) {
+                self.log(
+                    "info",
+                    "ferroscope.session",
+                    json!({ "idle_timeout_secs": timeout.as_secs(), "ended": "idle" }),
+                );
+                self.shutdown().await;
+            }
+        }
+    }
+
+    /// Restarts the idle timeout of [`Self::reap_idle_session`], after a tool call or a
+    /// DAP request.
+    pub(crate) async fn mark_session_used(&self) {
+        if let Some(session) = self.session.lock().await.as_mut() {
+            session.last_used = std::time::Instant::now();
+        }
+    }
+
+    /// Lists the active debugging sessions, with how long ago each started and was last
+    /// used. A server runs one session at a time, shared by its clients, so there is at
+    /// most one.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_sessions", "arguments": {}}
+    /// ```
+    async fn debug_sessions(&self) -> Result<Value> {
+        let idle_timeout = self.idle_timeout().map(|timeout| timeout.as_secs());
+        let session_guard = self.session.lock().await;
+        let sessions: Vec<Value> = session_guard
+            .iter()
+            .map(|session| {
+                json!({
+                    "binary_path": session.binary_path,
+                    "debugger": session.kind.name(),
+                    "debugger_pid": session.process.id(),
+                    "inferior_pid": session.inferior_pid,
+                    "attached": session.attached,
+                    "state": format!("{:?}", session.state).to_lowercase(),
+                    "age_secs": session.started.elapsed().as_secs(),
+                    "idle_secs": session.last_used.elapsed().as_secs(),
+                    "idle_timeout_secs": idle_timeout
+                })
+            })
+            .collect();
+        Ok(json!({
+            "success": true,
+            "sessions": sessions,
+            "idle_timeout_secs": idle_timeout
+        }))
+    }
+
+    /// Returns the page of a truncated tool result at `cursor`, at most `max_bytes` of
+    /// its JSON text, with the cursor of the next page. The newest 16 truncated
+    /// results are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_more_output", "arguments": {"cursor": "3:0"}}
+    /// ```
+    fn debug_more_output(&self, cursor: &str, max_bytes: Option<usize>) -> Result<Value> {
+        let mut result = self
+            .output_pages
+            .lock()
+            .unwrap()
+            .page(cursor, max_bytes.unwrap_or(usize::MAX))?;
+        result["success"] = json!(true);
+        Ok(result)
+    }
+
+    /// How large the result of the tool call with `arguments` may be: its
+    /// `max_output_bytes`, or `--max-output-bytes`.
+    fn max_output_bytes(&self, arguments: &Value) -> Result<Option<usize>> {
+        match arguments.get("max_output_bytes") {
+            Some(max) => max
+                .as_u64()
+                .filter(|&max| max > 0)
+                .map(|max| Some(max as usize))
+                .ok_or_else(|| {
+                    ErrorKind::InvalidArgument.error("max_output_bytes must be a positive integer")
+                }),
+            None => Ok(self.options.max_output_bytes),
+        }
+    }
+
+    /// Kills the active debugger process, if any, and forgets the session.
+    pub(crate) async fn end_session(&self) {
+        let mut session_guard = self.session.lock().await;
+        if let Some(mut old_session) = session_guard.take() {
+            let _ = old_session.process.kill().await;
+            if let Some(log) = old_session.launch.heap_log() {
+                let _ = std::fs::remove_file(log);
+            }
+        }
+    }
+
+    /// Spawns a debugger process and installs it as the active session.
+    ///
+    /// `binary_path` is recorded for `debug_state`; loading a target is left to the caller.
+    /// The debugger executable is the `debugger_path` of `ferroscope.toml`, if set.
+    async fn spawn_debugger(&self, binary_path: &str, debugger: DebuggerKind) -> Result<()> {
+        let path = self.config.lock().unwrap().debugger_path.clone();
+        let cmd = match path {
+            Some(path) => debugger.command_at(&path),
+            None => debugger.command(),
+        };
+        self.spawn_debugger_with(binary_path, debugger, cmd).await
+    }
+
+    /// Like [`DebugServer::spawn_debugger`], but runs `cmd`, which must start a debugger
+    /// speaking `debugger`'s protocol (e.g. GDB/MI launched by `rr replay`).
+    async fn spawn_debugger_with(
+        &self,
+        binary_path: &str,
+        debugger: DebuggerKind,
+        cmd: tokio::process::Command,
+    ) -> Result<()> {
+        let mut cmd = if self.options.sandbox {
+            sandbox_command(&cmd)
+        } else {
+            cmd
+        };
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd.spawn()?;
+
+        // Get stdin/stdout handles
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdin"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdout"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stderr"))?;
+        let (stderr_tx, stderr_lines) = mpsc::unbounded_channel();
+        tokio::spawn(forward_debugger_stderr(stderr, stderr_tx));
+        let (output_tx, output) = mpsc::unbounded_channel();
+        let notify_on_stop = Arc::new(AtomicBool::new(false));
+        tokio::spawn(forward_debugger_output(
+            stdout,
+            output_tx,
+            notify_on_stop.clone(),
+            Some(self.notifications.clone()).filter(|_| self.options.dap.is_none()),
+            self.output_ready.clone(),
+        ));
+
+        // Create session
+        let session = DebugSession {
+            process: child,
+            stdin: Arc::new(Mutex::new(stdin)),
+            output: Arc::new(Mutex::new(output)),
+            notify_on_stop,
+            unread_output: String::new(),
+            state: DebugState::NotLoaded,
+            binary_path: binary_path.to_string(),
+            current_location: None,
+            kind: debugger,
+            next_token: 0,
+            sentinels: false,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            watch_expressions: Vec::new(),
+            launch: LaunchConfig::default(),
+            program_output: None,
+            selected_thread: None,
+            selected_frame: 0,
+            stop_signal: None,
+            watchpoint_hit: None,
+            exit_status: None,
+            source_dir: None,
+            build: BuildConfig::default(),
+            test_name: None,
+            inferior_pid: None,
+            new_inferiors: Vec::new(),
+            core_path: None,
+            replay_trace: None,
+            remote_target: None,
+            log: std::collections::VecDeque::new(),
+            trace_log: std::collections::VecDeque::new(),
+            trace_dropped: 0,
+            saved_breakpoints: None,
+            limit_exceeded: None,
+            attached: false,
+            started: std::time::Instant::now(),
+            last_used: std::time::Instant::now(),
+            debugger_exit: None,
+            stderr: stderr_lines,
+            tool_lock: Default::default(),
+        };
+
+        let debugger_pid = session.process.id();
+
+        // Store the session; a concurrent debug_run or debug_attach may have stored its
+        // own since the caller ended the last one
+        let replaced = {
+            let mut session_guard = self.session.lock().await;
+            session_guard.replace(session)
+        };
+        if let Some(replaced) = replaced {
+            self.log(
+                "warning",
+                "ferroscope.session",
+                json!({ "replaced_session": replaced.binary_path }),
+            );
+            kill_session(replaced).await;
+        }
+        let limits = self.resource_limits();
+        if !limits.is_empty() {
+            tokio::spawn(enforce_resource_limits(
+                self.session.clone(),
+                debugger_pid,
+                limits,
+            ));
+        }
+
+        // Wait for the debugger to start
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        if !debugger.uses_mi() {
+            self.probe_sentinels().await;
+        }
+        self.load_rust_formatters(debugger).await;
+        let source_map = self.source_map.lock().unwrap().clone();
+        if !source_map.is_empty() {
+            let _ = self.send_source_map(debugger, &source_map).await;
+        }
+        Ok(())
+    }
+
+    /// Hands the source map to the debugger (`target.source-map` in LLDB,
+    /// `substitute-path` in GDB), so line breakpoints and its own source lookups use
+    /// the rewritten paths. Returns whether the debugger accepted it, and its output.
+    async fn send_source_map(
+        &self,
+        debugger: DebuggerKind,
+        source_map: &[(String, String)],
+    ) -> Result<(bool, String)> {
+        if debugger.uses_mi() {
+            let mut accepted = true;
+            let mut text = String::new();
+            for (from, to) in source_map {
+                let command = format!("set substitute-path {} {}", from, to);
+                let output = self.send_mi_command(&mi_console_command(&command)).await?;
+                accepted &= !output.is_error();
+                text.push_str(&output.text());
+            }
+            Ok((accepted, text))
+        } else {
+            let mut command = "settings set target.source-map".to_string();
+            for (from, to) in source_map {
+                command.push_str(&format!(" {} {}", lldb_quote(from), lldb_quote(to)));
+            }
+            let response = self.send_debugger_command(&command).await?;
+            Ok((!response.contains("error:"), response))
+        }
+    }
+
+    /// Loads the formatters shipped with the Rust toolchain (the ones `rust-lldb` and
+    /// `rust-gdb` use), so `Option`, `Vec`, `String`, and maps print as summaries
+    /// instead of raw layouts. Skipped silently when `rustc` or the scripts are missing.
+    async fn load_rust_formatters(&self, debugger: DebuggerKind) {
+        let Some(sysroot) = Self::rust_sysroot().await else {
+            return;
+        };
+        let etc = std::path::Path::new(&sysroot).join("lib/rustlib/etc");
+
+        if debugger.uses_mi() {
+            let script = etc.join("gdb_load_rust_pretty_printers.py");
+            if !script.exists() {
+                return;
+            }
+            let _ = self.send_mi_command("-enable-pretty-printing").await;
+            let _ = self
+                .send_mi_command(&mi_console_command(&format!("source {}", script.display())))
+                .await;
+        } else {
+            let script = etc.join("lldb_lookup.py");
+            let commands = etc.join("lldb_commands");
+            if !script.exists() || !commands.exists() {
+                return;
+            }
+            let _ = self
+                .send_debugger_command(&format!("command script import \"{}\"", script.display()))
+                .await;
+            let _ = self
+                .send_debugger_command(&format!(
+                    "command source -s true \"{}\"",
+                    commands.display()
+                ))
+                .await;
+        }
+    }
+
+    /// The active toolchain's sysroot, as printed by `rustc --print sysroot`.
+    pub(crate) async fn rust_sysroot() -> Option<String> {
+        let output = tokio::process::Command::new("rustc")
+            .args(["--print", "sysroot"])
+            .output()
+            .await
+            .ok()?;
+        let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !sysroot.is_empty()).then_some(sysroot)
+    }
+
+    async fn start_debugger_session(
+        &self,
+        binary_path: &str,
+        debugger: DebuggerKind,
+        mut launch: LaunchConfig,
+    ) -> Result<Value> {
+        if launch.follow_fork == FollowFork::Both && debugger != DebuggerKind::Gdb {
+            return Err(ErrorKind::Unsupported.error(
+                "follow_fork \"both\" needs gdb; LLDB follows either the parent or the child",
+            ));
+        }
+
+        // Before the debugger loads the binary, so a generated dSYM is picked up
+        let mut debug_info = self.locate_debug_info(binary_path).await;
+
+        self.spawn_debugger(binary_path, debugger).await?;
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                let output = ProgramOutput::create()?;
+                if self.options.sandbox {
+                    launch.sandbox(&output.sandbox_dir()?);
+                }
+                session.program_output = Some(output);
+            }
+        }
+
+        // GDB only queries debuginfod servers when told to, as it cannot ask over MI
+        if debugger == DebuggerKind::Gdb && debug_info["debuginfod_urls"].is_string() {
+            let output = self
+                .send_mi_command(&mi_console_command("set debuginfod enabled on"))
+                .await?;
+            debug_info["debuginfod_enabled"] = json!(!output.is_error());
+        }
+
+        // Load the binary
+        let load_response = if debugger.uses_mi() {
+            let output = self
+                .send_mi_command(&format!("-file-exec-and-symbols {}", mi_quote(binary_path)))
+                .await?;
+            if output.is_error() {
+                return Err(ErrorKind::CommandFailed.error(format!(
+                    "Debugger could not load {}: {}",
+                    binary_path,
+                    output.text().trim()
+                )));
+            }
+            output.text()
+        } else {
+            self.send_debugger_command(&format!("target create \"{}\"", binary_path))
+                .await?
+        };
+
+        // Update state
+        let launch_json = launch.to_json();
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.state = DebugState::Loaded;
+                session.launch = launch;
+            }
+        }
+
+        debug_info["has_debug_info"] = if debugger.uses_mi() {
+            json!(!load_response.contains("No debugging symbols found"))
+        } else {
+            // The binary is the first module LLDB lists
+            self.debug_modules().await?["modules"][0]["has_debug_info"].clone()
+        };
+
+        let sandbox = self.options.sandbox.then(|| {
+            json!({
+                "working_directory": launch_json["cwd"],
+                "cpu_seconds": SANDBOX_CPU_SECONDS,
+                "memory_kib": SANDBOX_MEMORY_KIB,
+                "network": !cfg!(target_os = "linux")
+            })
+        });
+        let limits = self.resource_limits();
+        Ok(json!({
+            "success": true,
+            "state": "loaded",
+            "output": load_response.trim(),
+            "binary_path": binary_path,
+            "debugger": debugger.name(),
+            "launch": launch_json,
+            "debug_info": debug_info,
+            "sandbox": sandbox,
+            "limits": (!limits.is_empty()).then(|| limits.to_json())
+        }))
+    }
+
+    /// Looks for debug info kept outside `binary_path`, as `-C split-debuginfo` leaves
+    /// it: a DWARF package (`<binary>.dwp`), a `.dSYM` bundle, and split DWARF objects
+    /// (`.dwo`) next to the binary or in its `deps` directory. On macOS a missing `.dSYM`
+    /// is generated with `dsymutil`.
+    async fn locate_debug_info(&self, binary_path: &str) -> Value {
+        let binary = std::path::Path::new(binary_path);
+        let sibling = |extension: &str| {
+            let mut path = binary.as_os_str().to_os_string();
+            path.push(extension);
+            std::path::PathBuf::from(path)
+        };
+        let dwp = sibling(".dwp");
+        let dsym = sibling(".dSYM");
+
+        let mut dsym_generated = false;
+        if cfg!(target_os = "macos") && !dsym.is_dir() {
+            self.report_progress(None, None, &format!("Running dsymutil on {}", binary_path));
+            let status = tokio::process::Command::new("dsymutil")
+                .arg(binary)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await;
+            dsym_generated = status.is_ok_and(|status| status.success()) && dsym.is_dir();
+        }
+
+        let dir = binary
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+        let dwo_files = [dir.to_path_buf(), dir.join("deps")]
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "dwo"))
+            .count();
+
+        json!({
+            "dwp": dwp.is_file().then_some(dwp),
+            "dsym": dsym.is_dir().then_some(dsym),
+            "dsym_generated": dsym_generated,
+            "dwo_files": dwo_files,
+            "debuginfod_urls": std::env::var("DEBUGINFOD_URLS")
+                .ok()
+                .filter(|urls| !urls.trim().is_empty())
+        })
+    }
+
+    /// Attaches the debugger to an already running process.
+    ///
+    /// Spawns a fresh debugger, attaches to the process identified by `pid` or by
+    /// `process_name`, and leaves it stopped so it can be inspected. Any existing
+    /// session is torn down first.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - Process ID to attach to
+    /// * `process_name` - Executable name to attach to when no PID is given
+    /// * `debugger` - Which debugger to drive
+    ///
+    /// # Returns
+    ///
+    /// Returns a JSON response with the attach output. When the operating system refuses
+    /// the attach, the response carries `success: false` and a `hint` explaining how to
+    /// grant the debugger permission.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_attach", "arguments": {"pid": 4242}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_attach", "arguments": {"process_name": "my_service"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Neither `pid` nor `process_name` is given
+    /// - No process with the given name is running (MI debuggers)
+    /// - Starting the debugger process fails
+    pub(crate) async fn debug_attach(
+        &self,
+        pid: Option<u32>,
+        process_name: Option<&str>,
+        debugger: DebuggerKind,
+    ) -> Result<Value> {
+        self.end_session().await;
+
+        let target = match (pid, process_name) {
+            (Some(pid), _) => format!("pid {}", pid),
+            (None, Some(name)) => name.to_string(),
+            (None, None) => {
+                return Err(ErrorKind::InvalidArgument.error("pid or process_name required"))
+            }
+        };
+
+        self.spawn_debugger(&target, debugger).await?;
+
+        let (success, output) = if debugger.uses_mi() {
+            // MI has no attach-by-name, so resolve the PID ourselves
+            let pid = match (pid, process_name) {
+                (Some(pid), _) => pid,
+                (None, Some(name)) => Self::find_pid_by_name(name).await?,
+                (None, None) => unreachable!(),
+            };
+            let output = self
+                .send_mi_command(&format!("-target-attach {}", pid))
+                .await?;
+            (!output.is_error(), output.text())
+        } else {
+            let command = match (pid, process_name) {
+                (Some(pid), _) => format!("process attach --pid {}", pid),
+                (None, Some(name)) => format!("process attach --name \"{}\"", name),
+                (None, None) => unreachable!(),
+            };
+            let response = self.send_debugger_command(&command).await?;
+            (
+                !response.contains("error:") && !response.contains(TIMEOUT_NOTE),
+                response,
+            )
+        };
+
+        if !success {
+            self.end_session().await;
+            return Ok(json!({
+                "success": false,
+                "state": "not_loaded",
+                "output": output.trim(),
+                "error": format!("Failed to attach to {}", target),
+                "error_kind": ErrorKind::CommandFailed.name(),
+                "hint": Self::attach_failure_hint(&output)
+            }));
+        }
+
+        let location = {
+            let mut session_guard = self.session.lock().await;
+            session_guard.as_mut().and_then(|session| {
+                session.state = DebugState::Stopped;
+                session.attached = true;
+                session.current_location.clone()
+            })
+        };
+
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "output": output.trim(),
+            "target": target,
+            "location": location,
+            "debugger": debugger.name()
+        }))
+    }
+
+    /// Opens a core dump for post-mortem inspection.
+    ///
+    /// Loads `binary_path` together with `core_path` into a fresh debugger. The session
+    /// starts stopped where the program died, so backtraces, frames, locals, and memory
+    /// reads work, while the tools in [`EXECUTION_TOOLS`] report a post-mortem error.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_open_core", "arguments": {"binary_path": "./target/debug/my_program", "core_path": "./core.4242"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either path does not exist or starting the
+    /// debugger process fails.
+    async fn debug_open_core(
+        &self,
+        binary_path: &str,
+        core_path: &str,
+        debugger: DebuggerKind,
+    ) -> Result<Value> {
+        self.end_session().await;
+
+        for path in [binary_path, core_path] {
+            if !std::path::Path::new(path).exists() {
+                return Err(
+                    ErrorKind::InvalidArgument.error(format!("Path does not exist: {}", path))
+                );
+            }
+        }
+
+        self.spawn_debugger(binary_path, debugger).await?;
+
+        let (success, output) = if debugger.uses_mi() {
+            let load = self
+                .send_mi_command(&format!("-file-exec-and-symbols {}", mi_quote(binary_path)))
+                .await?;
+            if load.is_error() {
+                (false, load.text())
+            } else {
+                let core = self
+                    .send_mi_command(&format!("-target-select core {}", mi_quote(core_path)))
+                    .await?;
+                (!core.is_error(), format!("{}{}", load.text(), core.text()))
+            }
+        } else {
+            let response = self
+                .send_debugger_command(&format!(
+                    "target create --core \"{}\" \"{}\"",
+                    core_path, binary_path
+                ))
+                .await?;
+            if response.contains("error:") || response.contains(TIMEOUT_NOTE) {
+                (false, response)
+            } else {
+                // The status names the thread and signal the program died on
+                let status = self.send_debugger_command("process status").await?;
+                (true, format!("{}\n{}", response.trim(), status))
+            }
+        };
+
+        if !success {
+            self.end_session().await;
+            return Ok(json!({
+                "success": false,
+                "state": "not_loaded",
+                "output": output.trim(),
+                "error": format!("Failed to open core file {}", core_path),
+                "error_kind": ErrorKind::CommandFailed.name()
+            }));
+        }
+
+        let frame_location = if debugger.uses_mi() {
+            let frame = self.send_mi_command("-stack-info-frame").await?;
+            frame.result_field("frame").and_then(mi_frame_location)
+        } else {
+            None
+        };
+
+        let location = {
+            let mut session_guard = self.session.lock().await;
+            session_guard.as_mut().and_then(|session| {
+                session.state = DebugState::Stopped;
+                session.core_path = Some(core_path.to_string());
+                if session.stop_signal.is_none() {
+                    session.stop_signal = StopSignal::parse_gdb_core(&output);
+                }
+                if frame_location.is_some() {
+                    session.current_location = frame_location;
+                }
+                session.current_location.clone()
+            })
+        };
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "post_mortem": true,
+            "output": output.trim(),
+            "binary_path": binary_path,
+            "core_path": core_path,
+            "location": location,
+            "signal": signal,
+            "debugger": debugger.name()
+        }))
+    }
+
+    /// Records a run of the program with `rr` for later replay.
+    ///
+    /// The program runs to completion under `rr record`; its output is returned along
+    /// with the trace directory to pass to `debug_replay`. Linux only.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_record", "arguments": {"binary_path": "./my_rust_project", "args": ["--seed", "7"]}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the path does not exist, building the
+    /// project fails, or `rr` cannot be started.
+    async fn debug_record(&self, binary_path: &str, launch: LaunchConfig) -> Result<Value> {
+        if !cfg!(target_os = "linux") {
+            return Ok(json!({
+                "success": false,
+                "error": "rr record and replay is only available on Linux",
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+
+        let path = std::path::Path::new(binary_path);
+        let binary = if path.is_dir() {
+            self.build_rust_project(binary_path, &BuildConfig::default())
+                .await?
+                .executable
+        } else if path.exists() {
+            binary_path.to_string()
+        } else {
+            return Err(
+                ErrorKind::InvalidArgument.error(format!("Path does not exist: {}", binary_path))
+            );
+        };
+
+        let mut cmd = tokio::process::Command::new("rr");
+        cmd.arg("record").arg(&binary).args(&launch.args);
+        if self.options.sandbox {
+            cmd = sandbox_command(&cmd);
+        }
+        cmd.envs(launch.env.iter().map(|(key, value)| (key, value)));
+        if let Some(cwd) = &launch.cwd {
+            cmd.current_dir(cwd);
+        }
+        cmd.stdin(match &launch.stdin_file {
+            Some(stdin_file) => Stdio::from(std::fs::File::open(stdin_file)?),
+            None => Stdio::null(),
+        });
+        let output = cmd.output().await.map_err(|e| {
+            ErrorKind::Unsupported.error(format!("Failed to run rr (is it installed?): {}", e))
+        })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // rr announces "rr: Saving execution to trace directory `<dir>'."
+        let trace_dir = stderr
+            .split_once("trace directory `")
+            .and_then(|(_, rest)| rest.split_once('\''))
+            .map(|(dir, _)| dir.to_string());
+
+        Ok(json!({
+            "success": trace_dir.is_some(),
+            "trace_dir": trace_dir,
+            "exit_code": output.status.code(),
+            "program_stdout": stdout,
+            "program_stderr": stderr,
+            "hint": "Open the recording with debug_replay to step backwards with debug_reverse_continue, debug_reverse_step, and debug_reverse_step_into"
+        }))
+    }
+
+    /// Opens an rr recording under `rr replay`, driving GDB/MI.
+    ///
+    /// The replay starts stopped at the beginning of the recording. Forward execution
+    /// tools work as usual and the `debug_reverse_*` tools run backwards; every replay
+    /// follows exactly the recorded execution. Without `trace_dir`, rr opens its latest
+    /// recording.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_replay", "arguments": {"trace_dir": "/home/me/.local/share/rr/my_program-0"}}
+    /// ```
+    async fn debug_replay(&self, trace_dir: Option<&str>) -> Result<Value> {
+        if !cfg!(target_os = "linux") {
+            return Ok(json!({
+                "success": false,
+                "error": "rr record and replay is only available on Linux",
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+
+        self.end_session().await;
+
+        // rr execs GDB with its own connection arguments, followed by ours
+        let mut cmd = tokio::process::Command::new("rr");
+        cmd.args(["replay", "-q"]);
+        if let Some(trace_dir) = trace_dir {
+            cmd.arg(trace_dir);
+        }
+        cmd.args(["--", "--interpreter=mi2", "--quiet"]);
+
+        let trace = trace_dir.unwrap_or("latest-trace");
+        self.spawn_debugger_with(trace, DebuggerKind::Gdb, cmd)
+            .await
+            .map_err(|e| {
+                ErrorKind::Unsupported.error(format!("Failed to run rr (is it installed?): {}", e))
+            })?;
+
+        let output = self.send_mi_command("-stack-info-frame").await?;
+        if output.is_error() {
+            self.end_session().await;
+            return Ok(json!({
+                "success": false,
+                "state": "not_loaded",
+                "output": output.text().trim(),
+                "error": format!("Failed to replay {}", trace),
+                "error_kind": ErrorKind::CommandFailed.name()
+            }));
+        }
+
+        let location = output.result_field("frame").and_then(mi_frame_location);
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.state = DebugState::Stopped;
+                session.replay_trace = Some(trace.to_string());
+                session.current_location = location.clone();
+            }
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "output": output.text().trim(),
+            "trace_dir": trace,
+            "location": location,
+            "debugger": DebuggerKind::Gdb.name()
+        }))
+    }
+
+    /// Runs a replay session backwards with `mi_command`, one of `-exec-continue`,
+    /// `-exec-next`, or `-exec-step` with `--reverse`.
+    ///
+    /// Stops at breakpoints and watchpoints as forward execution would, or at the start
+    /// of the recording, reported as `reached_start`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_reverse_continue", "arguments": {}}
+    /// ```
+    async fn debug_reverse(&self, mi_command: &str) -> Result<Value> {
+        let (current_state, replaying) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (session.state.clone(), session.replay_trace.is_some()),
+                None => {
+                    return Ok(json!({
+                        "success": false,
+                        "error": "No active debugging session",
+                        "error_kind": ErrorKind::NoSession.name(),
+                        "state": "not_loaded"
+                    }))
+                }
+            }
+        };
+
+        if !replaying {
+            return Ok(json!({
+                "success": false,
+                "error": "Reverse execution needs an rr replay; record with debug_record and open it with debug_replay",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to execute in reverse",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let output = self.send_mi_command(mi_command).await?;
+        let reached_start = output.records.iter().any(|record| {
+            matches!(record, MiRecord::Exec { class, results }
+                if class == "stopped"
+                    && results.get("reason").and_then(|r| r.as_str()) == Some("no-history"))
+        });
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": !output.is_error(),
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": output.text().trim(),
+            "location": location,
+            "signal": signal,
+            "reached_start": reached_start
+        }))
+    }
+
+    /// Connects to an embedded target through a GDB remote stub such as OpenOCD,
+    /// J-Link, or `probe-rs gdb`.
+    ///
+    /// Loads symbols from the firmware ELF, connects to `remote`, and optionally halts
+    /// the core with `monitor reset halt` and flashes the firmware. The target is left
+    /// halted; `debug_continue` resumes it rather than launching a process, and
+    /// `debug_monitor` passes commands through to the stub.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_connect_target", "arguments": {"binary_path": "./target/thumbv7em-none-eabihf/debug/firmware", "remote": "localhost:3333", "flash": true}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the firmware does not exist or starting the
+    /// debugger process fails.
+    async fn debug_connect_target(
+        &self,
+        binary_path: &str,
+        remote: &str,
+        debugger: DebuggerKind,
+        reset: bool,
+        flash: bool,
+    ) -> Result<Value> {
+        self.end_session().await;
+
+        if !std::path::Path::new(binary_path).exists() {
+            return Err(
+                ErrorKind::InvalidArgument.error(format!("Path does not exist: {}", binary_path))
+            );
+        }
+
+        self.spawn_debugger(binary_path, debugger).await?;
+
+        let mut output = String::new();
+        let connected = if debugger.uses_mi() {
+            let load = self
+                .send_mi_command(&format!("-file-exec-and-symbols {}", mi_quote(binary_path)))
+                .await?;
+            output.push_str(&load.text());
+            if load.is_error() {
+                false
+            } else {
+                let connect = self
+                    .send_mi_command(&format!("-target-select extended-remote {}", remote))
+                    .await?;
+                output.push_str(&connect.text());
+                !connect.is_error()
+            }
+        } else {
+            let load = self
+                .send_debugger_command(&format!("target create \"{}\"", binary_path))
+                .await?;
+            let connect = self
+                .send_debugger_command(&format!("gdb-remote {}", remote))
+                .await?;
+            output.push_str(&load);
+            output.push_str(&connect);
+            !output.contains("error:") && !output.contains(TIMEOUT_NOTE)
+        };
+
+        if !connected {
+            self.end_session().await;
+            return Ok(json!({
+                "success": false,
+                "state": "not_loaded",
+                "output": output.trim(),
+                "error": format!("Failed to connect to {}", remote),
+                "error_kind": ErrorKind::CommandFailed.name(),
+                "hint": "Check that the GDB server (OpenOCD, J-Link, probe-rs) is running and listening on this address"
+            }));
+        }
+
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.state = DebugState::Stopped;
+                session.remote_target = Some(remote.to_string());
+                session.inferior_pid = None;
+            }
+        }
+
+        let mut success = true;
+        if reset {
+            let response = self.monitor_command("reset halt").await?;
+            success &= !response.contains("error");
+            output.push_str(&response);
+        }
+        if flash {
+            // Loading also points the PC at the firmware's entry
+            let response = self
+                .send_command(
+                    "target modules load --load --set-pc-from-entry --slide 0",
+                    "-target-download",
+                )
+                .await?;
+            success &= !response.contains("error");
+            output.push_str(&response);
+        }
+
+        let frame_location = if debugger.uses_mi() {
+            let frame = self.send_mi_command("-stack-info-frame").await?;
+            frame.result_field("frame").and_then(mi_frame_location)
+        } else {
+            None
+        };
+        let location = {
+            let mut session_guard = self.session.lock().await;
+            session_guard.as_mut().and_then(|session| {
+                if frame_location.is_some() {
+                    session.current_location = frame_location;
+                }
+                session.current_location.clone()
+            })
+        };
+
+        Ok(json!({
+            "success": success,
+            "state": "stopped",
+            "output": output.trim(),
+            "binary_path": binary_path,
+            "remote": remote,
+            "location": location,
+            "reset": reset,
+            "flashed": flash,
+            "debugger": debugger.name(),
+            "hint": "The target is halted. Set breakpoints, then debug_continue to run it; use debug_monitor for stub commands such as \"reset halt\""
+        }))
+    }
+
+    /// Sends a `monitor` command to the GDB remote stub and returns its reply.
+    async fn monitor_command(&self, command: &str) -> Result<String> {
+        self.send_command(
+            &format!("process plugin packet monitor {}", command),
+            &mi_console_command(&format!("monitor {}", command)),
+        )
+        .await
+    }
+
+    /// Passes a command through to the GDB remote stub of an embedded target.
+    ///
+    /// The command is stub specific, e.g. `reset halt` or `flash info 0` for OpenOCD.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_monitor", "arguments": {"command": "reset halt"}}
+    /// ```
+    async fn debug_monitor(&self, command: &str) -> Result<Value> {
+        let (current_state, remote) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (session.state.clone(), session.remote_target.clone()),
+                None => {
+                    return Ok(json!({
+                        "success": false,
+                        "error": "No active debugging session",
+                        "error_kind": ErrorKind::NoSession.name(),
+                        "state": "not_loaded"
+                    }))
+                }
+            }
+        };
+
+        if remote.is_none() {
+            return Ok(json!({
+                "success": false,
+                "error": "Monitor commands need a remote target; connect with debug_connect_target",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+        if current_state == DebugState::Running {
+            return Ok(json!({
+                "success": false,
+                "error": "Target must be halted to send monitor commands; use debug_pause first",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": "running"
+            }));
+        }
+
+        let response = self.monitor_command(command).await?;
+        // A reset moves the core, so whatever location we had is stale
+        if command.starts_with("reset") {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.current_location = None;
+                session.selected_frame = 0;
+            }
+        }
+
+        Ok(json!({
+            "success": !response.contains("error"),
+            "command": command,
+            "output": response.trim()
+        }))
+    }
+
+    /// Why `debug_raw_command` refuses `command`, if it does. `--raw-command-deny`
+    /// wins over `--raw-command-allow`, which lifts the default denials of
+    /// [`HOST_COMMANDS`] and [`RAW_COMMANDS_DENIED`], except for host commands under
+    /// `--sandbox`.
+    fn raw_command_refusal(&self, command: &str) -> Option<String> {
+        if !self.options.allow_raw_commands {
+            return Some(
+                "Raw debugger commands are disabled; restart ferroscope with --allow-raw-commands to enable them"
+                    .to_string(),
+            );
+        }
+        let options = &self.options;
+        if let Some(denied) =
+            matching_command(command, options.raw_command_deny.iter().map(String::as_str))
+        {
+            return Some(format!(
+                "`{}` commands are denied by --raw-command-deny",
+                denied
+            ));
+        }
+        let host = matching_command(command, HOST_COMMANDS);
+        if let Some(host) = host.filter(|_| options.sandbox) {
+            return Some(format!(
+                "`{}` commands are not allowed under --sandbox",
+                host
+            ));
+        }
+        if matching_command(
+            command,
+            options.raw_command_allow.iter().map(String::as_str),
+        )
+        .is_some()
+        {
+            return None;
+        }
+        if let Some(host) = host {
+            return Some(format!(
+                "`{}` commands reach the host; start ferroscope with --raw-command-allow \"{}\" to allow them",
+                host, host
+            ));
+        }
+        if let Some((evaluates, expression)) = evaluated_expression(command) {
+            if let Some(effect) = side_effect(expression) {
+                return Some(format!(
+                    "{} contains {}, which could change the program; use debug_eval with allow_side_effects: true, or start ferroscope with --raw-command-allow \"{}\"",
+                    expression, effect, evaluates
+                ));
+            }
+        }
+        matching_command(command, RAW_COMMANDS_DENIED).map(|denied| {
+            format!(
+                "`{}` commands change the program or its breakpoints behind the tools that track them; use those tools, or start ferroscope with --raw-command-allow \"{}\"",
+                denied, denied
+            )
+        })
+    }
+
+    /// Passes one command line to the debugger and returns what it printed, unparsed.
+    ///
+    /// For what no tool covers, e.g. LLDB's `image lookup` or GDB's `info frame`. The
+    /// server's policy decides which commands run: none without `--allow-raw-commands`,
+    /// and by default none that reach the host or run the program.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_raw_command", "arguments": {"command": "info frame"}}
+    /// ```
+    async fn debug_raw_command(&self, command: &str) -> Result<Value> {
+        if let Some(refusal) = self.raw_command_refusal(command) {
+            return Ok(json!({
+                "success": false,
+                "error": refusal,
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+
+        let (current_state, debugger) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (session.state.clone(), session.kind),
+                None => {
+                    return Ok(json!({
+                        "success": false,
+                        "error": "No active debugging session",
+                        "error_kind": ErrorKind::NoSession.name(),
+                        "state": "not_loaded"
+                    }))
+                }
+            }
+        };
+        if current_state == DebugState::Running {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to send raw commands; use debug_pause first",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": "running"
+            }));
+        }
+
+        let (success, output) = if debugger.uses_mi() {
+            let output = self.send_mi_command(&mi_console_command(command)).await?;
+            (!output.is_error(), output.text())
+        } else {
+            let response = self.send_debugger_command(command).await?;
+            (!response.contains("error:"), response)
+        };
+
+        Ok(json!({
+            "success": success,
+            "command": command,
+            "raw_output": output.trim(),
+            "parsed": false,
+            "debugger": debugger.name(),
+            "state": format!("{:?}", current_state).to_lowercase()
+        }))
+    }
+
+    /// Looks up the PID of a running process by its exact executable name.
+    async fn find_pid_by_name(name: &str) -> Result<u32> {
+        let output = tokio::process::Command::new("pgrep")
+            .arg("-x")
+            .arg(name)
+            .output()
+            .await?;
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().parse().ok())
+            .ok_or_else(|| {
+                ErrorKind::LocationNotFound.error(format!("No running process named {}", name))
+            })
+    }
+
+    /// Explains how to fix an attach that the operating system refused.
+    fn attach_failure_hint(output: &str) -> Option<&'static str> {
+        let lower = output.to_lowercase();
+        let denied = lower.contains("not allowed")
+            || lower.contains("not permitted")
+            || lower.contains("permission")
+            || lower.contains("ptrace");
+
+        if !denied {
+            None
+        } else if cfg!(target_os = "macos") {
+            Some("macOS refused the attach. Enable Developer Mode (`sudo DevToolsSecurity -enable`) and make sure the target was not built with the hardened runtime, or that it has the com.apple.security.get-task-allow entitlement.")
+        } else {
+            Some("The kernel refused the attach. Allow ptrace with `echo 0 | sudo tee /proc/sys/kernel/yama/ptrace_scope`, or run the debugger with CAP_SYS_PTRACE.")
+        }
+    }
+
+    /// Sets a breakpoint at the specified function or line.
+    ///
+    /// Breakpoints pause program execution when reached, allowing inspection
+    /// of variables and program state at that point. Locations of the form
+    /// `file:line` become line breakpoints; anything else is treated as a function name.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - Function name (e.g., "main") or file:line (e.g., "src/main.rs:10")
+    /// * `condition` - Optional expression; the breakpoint only stops when it is true
+    /// * `ignore_count` - Optional number of hits to skip before the breakpoint stops
+    ///
+    /// # Returns
+    ///
+    /// Returns a JSON response indicating whether the breakpoint was successfully set,
+    /// including how many code locations it resolved to.
+    ///
+    /// # Examples
+    ///
+    /// Setting a breakpoint on the main function:
+    /// ```json
+    /// {"name": "debug_break", "arguments": {"location": "main"}}
+    /// ```
+    ///
+    /// Setting a breakpoint at a specific line:
+    /// ```json
+    /// {"name": "debug_break", "arguments": {"location": "src/main.rs:25"}}
+    /// ```
+    ///
+    /// Stopping only on a particular iteration:
+    /// ```json
+    /// {"name": "debug_break", "arguments": {"location": "process_item", "condition": "i == 42"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - No debugging session is active
+    /// - The debugger communication fails
+    /// - The specified location cannot be resolved
+    pub(crate) async fn debug_break(
+        &self,
+        location: &str,
+        condition: Option<&str>,
+        ignore_count: Option<u64>,
+    ) -> Result<Value> {
+        if self.uses_mi().await {
+            let mut command = String::from("-break-insert");
+            if let Some(condition) = condition {
+                command.push_str(&format!(" -c {}", mi_quote(condition)));
+            }
+            if let Some(count) = ignore_count {
+                command.push_str(&format!(" -i {}", count));
+            }
+            command.push_str(&format!(" {}", mi_quote(location)));
+
+            let output = self.send_mi_command(&command).await?;
+            let bkpt = output.result_field("bkpt");
+            let id = bkpt
+                .and_then(|bkpt| bkpt.get("number"))
+                .and_then(|v| v.as_str())
+                .and_then(|number| number.parse().ok());
+            // The parser nests MI2's trailing location tuples under `locations` too
+            let resolved_locations = bkpt.map(|bkpt| {
+                match (
+                    bkpt.get("locations"),
+                    bkpt.get("addr").and_then(|v| v.as_str()),
+                ) {
+                    (Some(Value::Array(locations)), _) => locations.len(),
+                    (_, Some("<PENDING>")) => 0,
+                    _ => 1,
+                }
+            });
+            self.record_breakpoint(id, location, condition, ignore_count)
+                .await;
+
+            return Ok(json!({
+                "success": !output.is_error(),
+                "output": output.text().trim(),
+                "location": location,
+                "id": id,
+                "resolved_locations": resolved_locations.unwrap_or(0),
+                "condition": condition,
+                "ignore_count": ignore_count,
+                "breakpoint": bkpt
+            }));
+        }
+
+        let mut command = match Self::parse_file_line(location) {
+            Some((file, line)) => {
+                format!("breakpoint set --file {} --line {}", lldb_quote(file), line)
+            }
+            None => format!("breakpoint set --name {}", lldb_quote(location)),
+        };
+        if let Some(condition) = condition {
+            command.push_str(&format!(" --condition {}", lldb_quote(condition)));
+        }
+        if let Some(count) = ignore_count {
+            command.push_str(&format!(" --ignore-count {}", count));
+        }
+        let response = self.send_debugger_command(&command).await?;
+
+        let success = !response.contains("no locations") && !response.contains("error:");
+
+        // LLDB keeps unresolved breakpoints around, so track them whenever an ID came back
+        let id = Self::parse_breakpoint_id(&response);
+        self.record_breakpoint(id, location, condition, ignore_count)
+            .await;
+
+        let mut result = json!({
+            "success": success,
+            "output": response.trim(),
+            "location": location,
+            "id": id,
+            "resolved_locations": Self::parse_resolved_locations(&response),
+            "condition": condition,
+            "ignore_count": ignore_count
+        });
+        if response.contains("no locations") {
+            result["error_kind"] = json!(ErrorKind::LocationNotFound.name());
+            result["hint"] = json!(
+                "Look up the exact function name with debug_symbols, e.g. {\"pattern\": \"name$\"}, and check with debug_modules that its library is loaded and has debug info"
+            );
+        }
+        Ok(result)
+    }
+
+    /// Splits a `file:line` breakpoint location, e.g. `src/main.rs:25`.
+    ///
+    /// Function paths such as `my_crate::parse` are not mistaken for file:line because
+    /// the part after the last colon must be a line number.
+    fn parse_file_line(location: &str) -> Option<(&str, u32)> {
+        let (file, line) = location.rsplit_once(':')?;
+        let line = line.parse().ok()?;
+        if file.is_empty() || file.ends_with(':') {
+            return None;
+        }
+        Some((file, line))
+    }
+
+    /// Sets breakpoints on every function of a crate or module, e.g. `my_crate::db`, so
+    /// the program stops whenever any of it runs.
+    ///
+    /// A plain path also matches submodules and the methods of the module's types,
+    /// trait impls included; any other `pattern` is a regular expression matched against
+    /// function names. LLDB sets one breakpoint with a location per function (`breakpoint
+    /// set -r`), GDB one breakpoint per function (`rbreak`); `ids` lists them for
+    /// `debug_break_delete`, and `resolved_locations` counts the functions.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_break_module", "arguments": {"pattern": "my_crate::db"}}
+    /// ```
+    async fn debug_break_module(&self, pattern: &str) -> Result<Value> {
+        let current_state = self.current_state().await;
+        let state = format!("{:?}", current_state).to_lowercase();
+        match current_state {
+            DebugState::NotLoaded => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": state
+                }))
+            }
+            DebugState::Running => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Pause the program before setting breakpoints",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": state
+                }))
+            }
+            _ => {}
+        }
+
+        let kind = self.debugger_kind().await;
+        let regex = module_regex(pattern, kind == Some(DebuggerKind::Gdb));
+        let (output, breakpoints, resolved_locations) = if kind == Some(DebuggerKind::Gdb) {
+            // rbreak announces each breakpoint it creates
+            let output = self
+                .send_mi_command(&mi_console_command(&format!("rbreak {}", regex)))
+                .await?;
+            let breakpoints: Vec<(u32, String)> = output
+                .records
+                .iter()
+                .filter_map(|record| match record {
+                    MiRecord::Notify { class, results } if class == "breakpoint-created" => {
+                        let bkpt = results.get("bkpt")?;
+                        let id = bkpt.get("number")?.as_str()?.parse().ok()?;
+                        let function = bkpt
+                            .get("func")
+                            .and_then(|v| v.as_str())
+                            .map_or_else(|| regex.clone(), demangle);
+                        Some((id, function))
+                    }
+                    _ => None,
+                })
+                .collect();
+            let count = breakpoints.len();
+            (output.text(), breakpoints, count)
+        } else {
+            let command = format!("breakpoint set --func-regex {}", lldb_quote(&regex));
+            let response = if kind == Some(DebuggerKind::LldbMi) {
+                self.send_mi_command(&mi_console_command(&command))
+                    .await?
+                    .text()
+            } else {
+                self.send_debugger_command(&command).await?
+            };
+            let id = Self::parse_breakpoint_id(&response);
+            let locations = Self::parse_resolved_locations(&response);
+            let breakpoints = match id {
+                Some(id) if locations > 0 => vec![(id, regex.clone())],
+                Some(id) => {
+                    // LLDB keeps breakpoints without locations; this one would only clutter
+                    self.debug_break_modify(BreakpointAction::Delete, Some(id), None)
+                        .await?;
+                    Vec::new()
+                }
+                None => Vec::new(),
+            };
+            (response, breakpoints, locations)
+        };
+
+        if breakpoints.is_empty() {
+            return Ok(json!({
+                "success": false,
+                "error": format!("No functions match {}", regex),
+                "error_kind": ErrorKind::LocationNotFound.name(),
+                "state": state,
+                "pattern": pattern,
+                "regex": regex,
+                "output": demangle_text(output.trim()),
+                "hint": "Look up the module's function names with debug_symbols, e.g. {\"pattern\": \"db::\"}"
+            }));
+        }
+
+        for (id, location) in &breakpoints {
+            self.record_breakpoint(Some(*id), location, None, None)
+                .await;
+        }
+        Ok(json!({
+            "success": true,
+            "state": state,
+            "pattern": pattern,
+            "regex": regex,
+            "ids": breakpoints.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            "resolved_locations": resolved_locations
+        }))
+    }
+
+    /// Stops where errors originate rather than where they are reported: on each line
+    /// of `function` that constructs an `Err(..)`, and with `unwind` whenever unwinding
+    /// begins, for a panic or a foreign exception.
+    ///
+    /// The lines are found in the function's source. An error that `?` passes on was
+    /// constructed in the callee, so run the tool on that function next.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_break_on_error", "arguments": {"function": "my_crate::config::load"}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_break_on_error", "arguments": {"unwind": true}}
+    /// ```
+    async fn debug_break_on_error(&self, function: Option<&str>, unwind: bool) -> Result<Value> {
+        let current_state = self.current_state().await;
+        let state = format!("{:?}", current_state).to_lowercase();
+        match current_state {
+            DebugState::NotLoaded => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": state
+                }))
+            }
+            DebugState::Running => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Pause the program before setting breakpoints",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": state
+                }))
+            }
+            _ => {}
+        }
+
+        let mut result = json!({ "success": true, "state": state });
+        if let Some(function) = function {
+            let failure = |error: String| {
+                json!({
+                    "success": false,
+                    "error": error,
+                    "error_kind": ErrorKind::LocationNotFound.name(),
+                    "state": state,
+                    "function": function
+                })
+            };
+            let (_, _, symbols) = self
+                .lookup_symbols(function, SymbolKind::Function, 200)
+                .await?;
+            let suffix = format!("::{}", function);
+            let declaration = symbols.iter().find_map(|symbol| {
+                let name = demangle(&symbol.name);
+                (name == function || name.ends_with(&suffix))
+                    .then_some(symbol.location.as_deref())
+                    .flatten()
+                    .and_then(Self::parse_stop_location)
+            });
+            let Some((file, start)) = declaration else {
+                return Ok(failure(format!(
+                    "Found no debug info declaring {}; look up its name with debug_symbols",
+                    function
+                )));
+            };
+            let source = match self.resolve_source_path(file).await {
+                Some(path) => std::fs::read_to_string(path).ok(),
+                None => None,
+            };
+            let Some(source) = source else {
+                return Ok(failure(format!(
+                    "Source file {} of {} not found; map it with debug_set_source_map",
+                    file, function
+                )));
+            };
+            let Some((end, lines)) = error_lines(&source, start) else {
+                return Ok(failure(format!(
+                    "No body of {} found at {}:{}",
+                    function, file, start
+                )));
+            };
+
+            let texts: Vec<&str> = source.lines().collect();
+            let mut breakpoints = Vec::new();
+            for line in lines {
+                let location = format!("{}:{}", file, line);
+                let set = self.debug_break(&location, None, None).await?;
+                breakpoints.push(json!({
+                    "location": location,
+                    "id": set["id"],
+                    "success": set["success"],
+                    "text": texts.get(line as usize - 1).map(|text| text.trim())
+                }));
+            }
+            result["function"] = json!(function);
+            result["file"] = json!(file);
+            result["lines"] = json!([start, end]);
+            if breakpoints.is_empty() {
+                result["message"] = json!(format!(
+                    "{} constructs no Err(..) itself; its errors come from callees through ?",
+                    function
+                ));
+            }
+            result["error_breakpoints"] = json!(breakpoints);
+        }
+        if unwind {
+            result["unwind_breakpoints"] =
+                json!(self.set_function_breakpoints(&UNWIND_BREAKPOINTS).await?);
+        }
+        Ok(result)
+    }
+
+    /// Searches the program's debug info for functions, types, or global variables
+    /// whose names match the regular expression `pattern`.
+    ///
+    /// Use it to find the exact name to break on when a guessed one resolves to no
+    /// locations, e.g. a method inside an `impl` block or a generic instantiation.
+    /// At most `max_results` matches are returned; `truncated` reports whether there
+    /// were more.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_symbols", "arguments": {"pattern": "parse_.*"}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_symbols", "arguments": {"pattern": "Config$", "kind": "types"}}
+    /// ```
+    async fn debug_symbols(
+        &self,
+        pattern: &str,
+        kind: SymbolKind,
+        max_results: usize,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state == DebugState::NotLoaded {
+            return Ok(json!({
+                "success": false,
+                "error": "No program loaded. Use debug_run first.",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        }
+
+        let (success, output, symbols) = self.lookup_symbols(pattern, kind, max_results).await?;
+        let truncated = symbols.len() > max_results;
+        let symbols: Vec<Value> = symbols
+            .iter()
+            .take(max_results)
+            .map(SymbolInfo::to_json)
+            .collect();
+        let mut result = json!({
+            "success": success,
+            "pattern": pattern,
+            "kind": kind.name(),
+            "symbols": symbols,
+            "truncated": truncated
+        });
+        // Large outputs only repeat what `symbols` holds
+        if symbols.is_empty() {
+            result["output"] = json!(demangle_text(output.trim()));
+        }
+        Ok(result)
+    }
+
+    /// Looks up the symbols of `kind` matching `pattern`, returning whether the lookup
+    /// succeeded, the debugger's output, and the symbols: at most one more than
+    /// `max_results` with GDB, which shows whether there were more.
+    async fn lookup_symbols(
+        &self,
+        pattern: &str,
+        kind: SymbolKind,
+        max_results: usize,
+    ) -> Result<(bool, String, Vec<SymbolInfo>)> {
+        Ok(if self.uses_mi().await {
+            let command = match kind {
+                SymbolKind::Function => "-symbol-info-functions",
+                SymbolKind::Type => "-symbol-info-types",
+                SymbolKind::Variable => "-symbol-info-variables",
+            };
+            // One extra result shows whether there were more
+            let output = self
+                .send_mi_command(&format!(
+                    "{} --name {} --max-results {}",
+                    command,
+                    mi_quote(pattern),
+                    max_results + 1
+                ))
+                .await?;
+            let symbols = output
+                .result_field("symbols")
+                .map(SymbolInfo::from_mi)
+                .unwrap_or_default();
+            (!output.is_error(), output.text(), symbols)
+        } else {
+            // Global variables have no lookup of their own; they are data symbols
+            let option = match kind {
+                SymbolKind::Function => "-n",
+                SymbolKind::Type => "-t",
+                SymbolKind::Variable => "-s",
+            };
+            let response = self
+                .send_debugger_command(&format!(
+                    "image lookup -r {} {}",
+                    option,
+                    lldb_quote(pattern)
+                ))
+                .await?;
+            let symbols = SymbolInfo::parse_lldb(&response);
+            (!response.contains("error:"), response, symbols)
+        })
+    }
+
+    /// Traces calls of every function whose name matches the regular expression
+    /// `pattern`, e.g. `my_crate::parser::`, without stopping the program.
+    ///
+    /// A `log_only` breakpoint is set on each matching function, at most
+    /// `max_functions` of them. While `debug_continue` waits, each call is recorded in
+    /// the trace log with the `function`, thread, and a timestamp, and with `args` the
+    /// function's arguments, then the program continues. Read the calls with
+    /// `debug_trace_log`, which keeps the most recent entries, and remove the
+    /// breakpoints with `debug_break_delete` once done.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_trace_calls", "arguments": {"pattern": "^my_crate::parser::", "args": true}}
+    /// ```
+    async fn debug_trace_calls(
+        &self,
+        pattern: &str,
+        log_arguments: bool,
+        max_functions: usize,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+        let state = format!("{:?}", current_state).to_lowercase();
+        match current_state {
+            DebugState::NotLoaded => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": state
+                }))
+            }
+            DebugState::Running => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Pause the program before setting breakpoints",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": state
+                }))
+            }
+            _ => {}
+        }
+
+        let (_, output, symbols) = self
+            .lookup_symbols(pattern, SymbolKind::Function, max_functions)
+            .await?;
+        let mut names: Vec<String> = Vec::new();
+        for symbol in symbols {
+            let name = demangle(&symbol.name);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let truncated = names.len() > max_functions;
+        names.truncate(max_functions);
+        if names.is_empty() {
+            return Ok(json!({
+                "success": false,
+                "error": format!("No functions match {}", pattern),
+                "error_kind": ErrorKind::LocationNotFound.name(),
+                "state": state,
+                "output": demangle_text(output.trim())
+            }));
+        }
+
+        let mut functions = Vec::new();
+        let mut skipped = Vec::new();
+        for name in names {
+            let set = self.debug_break(&name, None, None).await?;
+            let id = set["id"].as_u64().and_then(|id| u32::try_from(id).ok());
+            match id.filter(|_| set["success"] == true) {
+                Some(id) => {
+                    self.set_call_trace(id, log_arguments).await;
+                    functions.push(json!({
+                        "function": name,
+                        "id": id,
+                        "resolved_locations": set["resolved_locations"]
+                    }));
+                }
+                None => {
+                    // LLDB keeps breakpoints without locations; they would only clutter
+                    if let Some(id) = id {
+                        self.debug_break_modify(BreakpointAction::Delete, Some(id), None)
+                            .await?;
+                    }
+                    skipped.push(name);
+                }
+            }
+        }
+
+        Ok(json!({
+            "success": !functions.is_empty(),
+            "state": state,
+            "pattern": pattern,
+            "count": functions.len(),
+            "functions": functions,
+            "skipped": skipped,
+            "truncated": truncated,
+            "args": log_arguments,
+            "message": "Run the program with debug_continue to record calls, then read them with debug_trace_log"
+        }))
+    }
+
+    /// Lists the binary and shared libraries loaded into the program, with load
+    /// addresses and whether debug info was found for each.
+    ///
+    /// A breakpoint with no locations in a library usually means the library was not
+    /// loaded yet or was built without debug info; this shows which.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_modules", "arguments": {}}
+    /// ```
+    async fn debug_modules(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state == DebugState::NotLoaded {
+            return Ok(json!({
+                "success": false,
+                "error": "No program loaded. Use debug_run first.",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        }
+
+        let (success, output, modules) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&mi_console_command("info sharedlibrary"))
+                .await?;
+            let text = output.text();
+            // The executable itself is not a shared library
+            let mut modules = vec![ModuleInfo {
+                path: self.binary_path().await.unwrap_or_default(),
+                load_address: None,
+                uuid: None,
+                symbol_file: None,
+                has_debug_info: None,
+            }];
+            modules.extend(ModuleInfo::parse_gdb(&text));
+            (!output.is_error(), text, modules)
+        } else {
+            let response = self.send_debugger_command("image list").await?;
+            let mut modules = ModuleInfo::parse_lldb(&response);
+
+            // LLDB's statistics know how much DWARF each module has
+            let statistics = self.send_debugger_command("statistics dump").await?;
+            let sizes = statistics
+                .find('{')
+                .zip(statistics.rfind('}'))
+                .and_then(|(start, end)| {
+                    serde_json::from_str::<Value>(&statistics[start..=end]).ok()
+                });
+            for module in &mut modules {
+                let size = sizes
+                    .as_ref()
+                    .and_then(|sizes| sizes.get("modules"))
+                    .and_then(|v| v.as_array())
+                    .and_then(|stats| {
+                        stats
+                            .iter()
+                            .find(|stat| stat["path"].as_str() == Some(module.path.as_str()))
+                    })
+                    .and_then(|stat| stat["debugInfoByteSize"].as_u64());
+                if let Some(size) = size {
+                    module.has_debug_info = Some(size > 0 || module.symbol_file.is_some());
+                }
+            }
+            (!response.contains("error:"), response, modules)
+        };
+
+        let missing: Vec<&str> = modules
+            .iter()
+            .filter(|module| module.has_debug_info == Some(false))
+            .map(|module| module.path.as_str())
+            .collect();
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "modules": modules.iter().map(ModuleInfo::to_json).collect::<Vec<_>>(),
+            "missing_debug_info": missing
+        }))
+    }
+
+    /// Splits a stop location such as `main.rs:42:5` or `src/main.rs:42` into its file
+    /// and line, dropping the column.
+    fn parse_stop_location(location: &str) -> Option<(&str, u32)> {
+        let (file, last) = Self::parse_file_line(location)?;
+        Some(Self::parse_file_line(file).unwrap_or((file, last)))
+    }
+
+    /// Counts the locations LLDB resolved a new breakpoint to.
+    ///
+    /// LLDB reports `where = ...` for a single location, `N locations.` for several,
+    /// and `no locations (pending).` when nothing matched.
+    fn parse_resolved_locations(response: &str) -> usize {
+        response
+            .lines()
+            .find_map(|line| {
+                let (_, rest) = line.trim().strip_prefix("Breakpoint ")?.split_once(": ")?;
+                if rest.starts_with("no locations") {
+                    Some(0)
+                } else if let Some(count) = rest.strip_suffix(" locations.") {
+                    count.parse().ok()
+                } else {
+                    Some(1)
+                }
+            })
+            .unwrap_or(0)
+    }
+
+    /// Extracts the breakpoint number from LLDB's `Breakpoint N: ...` output.
+    fn parse_breakpoint_id(response: &str) -> Option<u32> {
+        response.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix("Breakpoint ")?
+                .split(':')
+                .next()?
+                .parse()
+                .ok()
+        })
+    }
+
+    async fn record_breakpoint(
+        &self,
+        id: Option<u32>,
+        location: &str,
+        condition: Option<&str>,
+        ignore_count: Option<u64>,
+    ) {
+        let Some(id) = id else { return };
+        let mut session_guard = self.session.lock().await;
+        if let Some(session) = session_guard.as_mut() {
+            session.breakpoints.push(Breakpoint {
+                id,
+                location: location.to_string(),
+                condition: condition.map(str::to_string),
+                ignore_count,
+                enabled: true,
+                log_expressions: None,
+                log_hits: 0,
+                on_hit_commands: Vec::new(),
+                auto_continue: false,
+                call_trace: false,
+                log_arguments: false,
+            });
+        }
+    }
+
+    /// Turns the tracked breakpoint `id` into a `log_only` one: `debug_continue` records
+    /// `expressions` in the trace log on each hit and continues instead of stopping.
+    async fn set_log_expressions(&self, id: u32, expressions: Vec<String>) {
+        let mut session_guard = self.session.lock().await;
+        let breakpoint = session_guard
+            .as_mut()
+            .and_then(|session| session.breakpoints.iter_mut().rfind(|bp| bp.id == id));
+        if let Some(breakpoint) = breakpoint {
+            breakpoint.log_expressions = Some(expressions);
+        }
+    }
+
+    /// Turns the tracked breakpoint `id` on a function into a call tracepoint for
+    /// `debug_trace_calls`, logging the function's arguments too with `log_arguments`.
+    async fn set_call_trace(&self, id: u32, log_arguments: bool) {
+        let mut session_guard = self.session.lock().await;
+        let breakpoint = session_guard
+            .as_mut()
+            .and_then(|session| session.breakpoints.iter_mut().rfind(|bp| bp.id == id));
+        if let Some(breakpoint) = breakpoint {
+            breakpoint.log_expressions = Some(Vec::new());
+            breakpoint.call_trace = true;
+            breakpoint.log_arguments = log_arguments;
+        }
+    }
+
+    /// Attaches `commands` to breakpoint `id`, which the debugger runs each time it is
+    /// hit (LLDB `breakpoint command add`, GDB `commands`).
+    ///
+    /// A final `continue` is not passed on: `debug_continue` continues past the hit
+    /// itself, so it can still tell whether the program is running.
+    async fn set_on_hit_commands(&self, id: u32, mut commands: Vec<String>) -> Result<Value> {
+        let auto_continue = commands
+            .last()
+            .is_some_and(|command| CONTINUE_COMMANDS.contains(&command.trim()));
+        if auto_continue {
+            commands.pop();
+        }
+        for command in &commands {
+            single_line(command)?;
+        }
+        let denied = commands
+            .iter()
+            .find_map(|command| matching_command(command, HOST_COMMANDS))
+            .filter(|_| self.options.sandbox);
+
+        let (success, output) = if let Some(denied) = denied {
+            (
+                false,
+                format!("`{}` commands are not allowed under --sandbox", denied),
+            )
+        } else if commands.is_empty() {
+            (true, String::new())
+        } else if self.uses_mi().await {
+            let quoted: Vec<String> = commands.iter().map(|command| mi_quote(command)).collect();
+            let output = self
+                .send_mi_command(&format!("-break-commands {} {}", id, quoted.join(" ")))
+                .await?;
+            (!output.is_error(), output.text())
+        } else {
+            // LLDB reads the commands that follow up to a line saying DONE
+            let response = self
+                .send_debugger_lines(
+                    &format!(
+                        "breakpoint command add {}\n{}\nDONE",
+                        id,
+                        commands.join("\n")
+                    ),
+                    true,
+                )
+                .await?;
+            (!response.contains("error:"), response)
+        };
+
+        if success {
+            let mut session_guard = self.session.lock().await;
+            let breakpoint = session_guard
+                .as_mut()
+                .and_then(|session| session.breakpoints.iter_mut().rfind(|bp| bp.id == id));
+            if let Some(breakpoint) = breakpoint {
+                breakpoint.on_hit_commands = commands.clone();
+                breakpoint.auto_continue = auto_continue;
+            }
+        }
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "on_hit_commands": commands,
+            "auto_continue": auto_continue
+        }))
+    }
+
+    /// Whether the program is stopped and has breakpoints to continue past.
+    async fn stopped_with_continuing_breakpoints(&self) -> bool {
+        self.session.lock().await.as_ref().is_some_and(|session| {
+            session.state == DebugState::Stopped
+                && session.breakpoints.iter().any(Breakpoint::continues)
+        })
+    }
+
+    /// Handles a stop at breakpoint `id`, returning whether the program should continue
+    /// past it. Hits of `log_only` breakpoints are recorded in the trace log first.
+    async fn continue_past_hit(&self, id: u32) -> Result<bool> {
+        let (expressions, log_arguments) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(false);
+            };
+            if session.state != DebugState::Stopped {
+                return Ok(false);
+            }
+            match session.breakpoints.iter().rfind(|bp| bp.id == id) {
+                Some(Breakpoint {
+                    log_expressions: Some(expressions),
+                    log_arguments,
+                    ..
+                }) => (expressions.clone(), *log_arguments),
+                Some(breakpoint) => return Ok(breakpoint.auto_continue),
+                None => return Ok(false),
+            }
+        };
+
+        let values = self.evaluate_expressions(expressions).await?;
+        let arguments = if log_arguments {
+            Some(self.frame_arguments().await?)
+        } else {
+            None
+        };
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let mut session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_mut() else {
+            return Ok(false);
+        };
+        let Some(breakpoint) = session.breakpoints.iter_mut().rfind(|bp| bp.id == id) else {
+            return Ok(false);
+        };
+        breakpoint.log_hits += 1;
+        let mut entry = json!({
+            "breakpoint_id": id,
+            "hit": breakpoint.log_hits,
+            "timestamp_ms": timestamp_ms,
+            "location": session.current_location,
+            "thread_id": session.selected_thread,
+            "values": values
+        });
+        if breakpoint.call_trace {
+            entry["function"] = json!(breakpoint.location);
+        }
+        if let Some(arguments) = arguments {
+            entry["args"] = json!(arguments);
+        }
+        session.trace(entry);
+        Ok(true)
+    }
+
+    /// Returns the hits of `log_only` breakpoints recorded so far, oldest first.
+    ///
+    /// Each entry holds the `breakpoint_id`, its `hit` number, a `timestamp_ms` (Unix
+    /// time in milliseconds), the `location` and `thread_id`, and the `values` of the
+    /// breakpoint's expressions; calls traced by `debug_trace_calls` also carry the
+    /// `function` and, if asked for, its `args`. `breakpoint_id` keeps only one breakpoint's hits, and
+    /// `clear` removes the returned entries from the log.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_trace_log", "arguments": {"breakpoint_id": 3, "clear": true}}
+    /// ```
+    async fn debug_trace_log(&self, breakpoint_id: Option<u32>, clear: bool) -> Result<Value> {
+        let mut session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_mut() else {
+            return Ok(json!({
+                "success": false,
+                "error": "No program loaded. Use debug_run first.",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        };
+
+        let selected =
+            |entry: &Value| breakpoint_id.is_none_or(|id| entry["breakpoint_id"] == json!(id));
+        let entries: Vec<Value> = session
+            .trace_log
+            .iter()
+            .filter(|entry| selected(entry))
+            .cloned()
+            .collect();
+        let dropped = session.trace_dropped;
+        if clear {
+            session.trace_log.retain(|entry| !selected(entry));
+            if breakpoint_id.is_none() {
+                session.trace_dropped = 0;
+            }
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", session.state).to_lowercase(),
+            "output": format!("{} trace entries", entries.len()),
+            "entries": entries,
+            "dropped": dropped
+        }))
+    }
+
+    /// Deletes, disables, or enables breakpoints selected by ID or by location.
+    ///
+    /// When `location` is given, every tracked breakpoint set at that exact location
+    /// is affected, so repeated `debug_break` calls on one spot are handled together.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_break_disable", "arguments": {"id": 2}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_break_delete", "arguments": {"location": "src/main.rs:25"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if neither `id` nor `location` is given or if
+    /// communication with the debugger fails.
+    pub(crate) async fn debug_break_modify(
+        &self,
+        action: BreakpointAction,
+        id: Option<u32>,
+        location: Option<&str>,
+    ) -> Result<Value> {
+        let ids: Vec<u32> = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            match (id, location) {
+                (Some(id), _) => vec![id],
+                (None, Some(location)) => session
+                    .breakpoints
+                    .iter()
+                    .filter(|bp| bp.location == location)
+                    .map(|bp| bp.id)
+                    .collect(),
+                (None, None) => {
+                    return Err(ErrorKind::InvalidArgument.error("id or location required"))
+                }
+            }
+        };
+
+        if ids.is_empty() {
+            return Ok(json!({
+                "success": false,
+                "error": format!("No breakpoint set at {}", location.unwrap_or_default()),
+                "error_kind": ErrorKind::LocationNotFound.name()
+            }));
+        }
+
+        let id_list = ids.iter().map(u32::to_string).collect::<Vec<_>>().join(" ");
+        let verb = action.verb();
+        let (success, output) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&format!("-break-{} {}", verb, id_list))
+                .await?;
+            (!output.is_error(), output.text())
+        } else {
+            let response = self
+                .send_debugger_command(&format!("breakpoint {} {}", verb, id_list))
+                .await?;
+            (!response.contains("error:"), response)
+        };
+
+        if success {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                match action {
+                    BreakpointAction::Delete => {
+                        session.breakpoints.retain(|bp| !ids.contains(&bp.id))
+                    }
+                    BreakpointAction::Disable | BreakpointAction::Enable => session
+                        .breakpoints
+                        .iter_mut()
+                        .filter(|bp| ids.contains(&bp.id))
+                        .for_each(|bp| bp.enabled = action == BreakpointAction::Enable),
+                }
+            }
+        }
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "ids": ids
+        }))
+    }
+
+    /// Sets a watchpoint that stops the program when memory is accessed.
+    ///
+    /// Unlike breakpoints, watchpoints trigger on data rather than code: the program
+    /// stops whenever the watched variable or address is written (or read, depending
+    /// on `mode`). The program must be stopped so the variable can be resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `variable` - Variable in the current frame to watch
+    /// * `address` - Raw address to watch when no variable is given
+    /// * `size` - Number of bytes to watch at `address` (default 8)
+    /// * `mode` - Which accesses trigger the watchpoint
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_watch", "arguments": {"variable": "counter"}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_watch", "arguments": {"address": "0x7ffee3b8", "size": 4, "mode": "read_write"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if neither `variable` nor `address` is given
+    /// or if communication with the debugger fails.
+    async fn debug_watch(
+        &self,
+        variable: Option<&str>,
+        address: Option<&str>,
+        size: u64,
+        mode: WatchMode,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to set a watchpoint",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let target = match (variable, address) {
+            (Some(variable), _) => variable.to_string(),
+            (None, Some(address)) => address.to_string(),
+            (None, None) => {
+                return Err(ErrorKind::InvalidArgument.error("variable or address required"))
+            }
+        };
+
+        let (id, success, output) = if self.uses_mi().await {
+            let expression = match variable {
+                Some(variable) => variable.to_string(),
+                None => format!("*(unsigned char (*)[{}]) {}", size, target),
+            };
+            let output = self
+                .send_mi_command(&format!(
+                    "-break-watch {}{}",
+                    mode.mi_flag(),
+                    mi_quote(&expression)
+                ))
+                .await?;
+            let id = ["wpt", "hw-rwpt", "hw-awpt"]
+                .iter()
+                .find_map(|field| output.result_field(field))
+                .and_then(|wpt| wpt.get("number"))
+                .and_then(|v| v.as_str())
+                .and_then(|number| number.parse().ok());
+            (id, !output.is_error(), output.text())
+        } else {
+            let command = match variable {
+                Some(variable) => {
+                    format!("watchpoint set variable -w {} {}", mode.name(), variable)
+                }
+                None => format!(
+                    "watchpoint set expression -w {} -s {} -- {}",
+                    mode.name(),
+                    size,
+                    target
+                ),
+            };
+            let response = self.send_debugger_command(&command).await?;
+            let id = response
+                .split("Watchpoint created: Watchpoint ")
+                .nth(1)
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|number| number.parse().ok());
+            (id, !response.contains("error:"), response)
+        };
+
+        if let Some(id) = id {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.watchpoints.push(Watchpoint {
+                    id,
+                    target: target.clone(),
+                    mode,
+                    field: None,
+                    last_value: None,
+                });
+            }
+        }
+
+        Ok(json!({
+            "success": success && id.is_some(),
+            "output": output.trim(),
+            "id": id,
+            "target": target,
+            "mode": mode.name()
+        }))
+    }
+
+    /// Sets a write watchpoint on one field of a struct.
+    ///
+    /// The address of `object_expr.field` is resolved in the selected frame and exactly
+    /// the field's bytes are watched, so writes are caught through any alias and after
+    /// the frame has returned. Every stop at the watchpoint then reports `field_change`:
+    /// the old and new value and the backtrace of the code that wrote it.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_watch_field", "arguments": {"object_expr": "self.stats", "field": "hits"}}
+    /// ```
+    async fn debug_watch_field(&self, object_expr: &str, field: &str) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to set a watchpoint",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let expression = format!("{}.{}", object_expr, field);
+        let (id, address, size, value, output) = if self.uses_mi().await {
+            let resolved = self
+                .send_mi_command(&format!(
+                    "-data-evaluate-expression {}",
+                    mi_quote(&format!("&{}", expression))
+                ))
+                .await?;
+            // GDB prints Rust references with their type, `(*mut i32) 0x7ffe...`
+            let Some(address) = resolved
+                .result_field("value")
+                .and_then(|v| v.as_str())
+                .and_then(|value| value.split_whitespace().find(|t| t.starts_with("0x")))
+                .map(str::to_string)
+            else {
+                return Ok(json!({
+                    "success": false,
+                    "error": format!("Cannot resolve the address of {}", expression),
+                    "output": resolved.text().trim(),
+                    "state": "stopped"
+                }));
+            };
+            let size: Option<u64> = self
+                .send_mi_command(&format!(
+                    "-data-evaluate-expression {}",
+                    mi_quote(&format!("sizeof({})", expression))
+                ))
+                .await?
+                .result_field("value")
+                .and_then(|v| v.as_str())
+                .and_then(|size| size.parse().ok());
+            let value = self
+                .send_mi_command(&format!(
+                    "-data-evaluate-expression {}",
+                    mi_quote(&expression)
+                ))
+                .await?
+                .result_field("value")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            // -location watches the address rather than the expression, which would go
+            // out of scope with the frame
+            let output = self
+                .send_mi_command(&mi_console_command(&format!(
+                    "watch -location {}",
+                    expression
+                )))
+                .await?;
+            let text = output.text();
+            let id = text
+                .split("atchpoint ")
+                .nth(1)
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|number| number.parse().ok());
+            (id, Some(address), size, value, text)
+        } else {
+            // LLDB resolves the variable path to its address and size itself
+            let response = self
+                .send_debugger_command(&format!("watchpoint set variable -w write {}", expression))
+                .await?;
+            let id = response
+                .split("Watchpoint created: Watchpoint ")
+                .nth(1)
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|number| number.parse().ok());
+            let address = response
+                .split("addr = ")
+                .nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .map(str::to_string);
+            let size = response
+                .split("size = ")
+                .nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|size| size.parse().ok());
+            let value = WatchpointHit::parse_lldb(id.unwrap_or(0), &response).new_value;
+            (id, address, size, value, response)
+        };
+
+        if let Some(id) = id {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.watchpoints.push(Watchpoint {
+                    id,
+                    target: address.clone().unwrap_or_else(|| expression.clone()),
+                    mode: WatchMode::Write,
+                    field: Some(expression.clone()),
+                    last_value: value.clone(),
+                });
+            }
+        }
+
+        let mut result = json!({
+            "success": id.is_some(),
+            "output": output.trim(),
+            "id": id,
+            "field": expression,
+            "address": address,
+            "size": size,
+            "value": value
+        });
+        if id.is_none() {
+            result["error"] = json!(format!("Could not watch {}", expression));
+        }
+        Ok(result)
+    }
+
+    /// Describes the write behind a stop at a `debug_watch_field` watchpoint, for the
+    /// stop report's `field_change`, and remembers the new value for the next hit.
+    async fn field_change(&self) -> Result<Option<Value>> {
+        let hit = match self.session.lock().await.as_ref() {
+            Some(session) if session.watchpoints.iter().any(|w| w.field.is_some()) => {
+                session.watchpoint_hit.clone()
+            }
+            _ => return Ok(None),
+        };
+        let hit = match hit {
+            Some(hit) => hit,
+            None if !self.uses_mi().await => {
+                let info = self.send_debugger_command("thread info").await?;
+                let Some(id) = watchpoint_hit(&info) else {
+                    return Ok(None);
+                };
+                let listing = self
+                    .send_debugger_command(&format!("watchpoint list {}", id))
+                    .await?;
+                WatchpointHit::parse_lldb(id, &listing)
+            }
+            None => return Ok(None),
+        };
+
+        let (field, old_value) = {
+            let mut session_guard = self.session.lock().await;
+            let watchpoint = session_guard
+                .as_mut()
+                .and_then(|session| session.watchpoints.iter_mut().find(|w| w.id == hit.id));
+            let Some(watchpoint) = watchpoint.filter(|w| w.field.is_some()) else {
+                return Ok(None);
+            };
+            let old_value = hit.old_value.clone().or(watchpoint.last_value.clone());
+            if hit.new_value.is_some() {
+                watchpoint.last_value = hit.new_value.clone();
+            }
+            (watchpoint.field.clone(), old_value)
+        };
+
+        let (_, _, threads) = self
+            .thread_backtraces(Some(FIELD_CHANGE_FRAMES), false)
+            .await?;
+        let frames = threads
+            .into_iter()
+            .next()
+            .map(|(_, frames)| frames)
+            .unwrap_or_default();
+        Ok(Some(json!({
+            "watchpoint_id": hit.id,
+            "field": field,
+            "old_value": old_value,
+            "new_value": hit.new_value,
+            "user_frame": frames.iter().find(|f| f.is_user_frame()).map(FrameInfo::to_json),
+            "backtrace": frames.iter().map(FrameInfo::to_json).collect::<Vec<_>>()
+        })))
+    }
+
+    /// Adds `expression` to the expressions re-evaluated at every stop.
+    ///
+    /// Step, continue, and other execution tools then report the values under
+    /// `watches`, so a value can be followed across stops without a `debug_eval` each
+    /// time. Unlike `debug_watch`, this does not stop the program when the value changes.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_watch_expr", "arguments": {"expression": "self.queue.len()"}}
+    /// ```
+    async fn debug_watch_expr(&self, expression: &str) -> Result<Value> {
+        let (state, watch_expressions) = {
+            let mut session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_mut() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            if !session.watch_expressions.iter().any(|e| e == expression) {
+                session.watch_expressions.push(expression.to_string());
+            }
+            (session.state.clone(), session.watch_expressions.clone())
+        };
+
+        let mut result = json!({
+            "success": true,
+            "state": format!("{:?}", state).to_lowercase(),
+            "watch_expressions": watch_expressions
+        });
+        if state == DebugState::Stopped {
+            if let Some(watches) = self.evaluate_watches().await? {
+                result["watches"] = watches;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Stops re-evaluating `expression` at every stop.
+    async fn debug_unwatch_expr(&self, expression: &str) -> Result<Value> {
+        let mut session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_mut() else {
+            return Ok(json!({
+                "success": false,
+                "error": "No active debugging session",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        };
+
+        let watched = session.watch_expressions.len();
+        session.watch_expressions.retain(|e| e != expression);
+        if session.watch_expressions.len() == watched {
+            return Ok(json!({
+                "success": false,
+                "error": format!("{} is not watched", expression),
+                "error_kind": ErrorKind::InvalidArgument.name(),
+                "watch_expressions": session.watch_expressions
+            }));
+        }
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", session.state).to_lowercase(),
+            "watch_expressions": session.watch_expressions
+        }))
+    }
+
+    /// Evaluates every watched expression, or returns `None` when nothing is watched.
+    ///
+    /// Each entry carries the `expression`, its `value` and `display`, and an `error`
+    /// when it could not be evaluated at this stop, e.g. because it is out of scope.
+    async fn evaluate_watches(&self) -> Result<Option<Value>> {
+        let expressions = match self.session.lock().await.as_ref() {
+            Some(session) if !session.watch_expressions.is_empty() => {
+                session.watch_expressions.clone()
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(json!(self.evaluate_expressions(expressions).await?)))
+    }
+
+    /// Evaluates `expressions` at the current stop, in the form of
+    /// [`Self::evaluate_watches`] entries.
+    async fn evaluate_expressions(&self, expressions: Vec<String>) -> Result<Vec<Value>> {
+        let mut watches = Vec::new();
+        for expression in expressions {
+            // Watched expressions were chosen ahead of time and may call methods
+            let options = EvalOptions {
+                allow_side_effects: true,
+                ..EvalOptions::default()
+            };
+            let result = self.debug_eval(&expression, &options).await?;
+            let output = result["output"].as_str().unwrap_or("");
+            // LLDB answers `(i32) $0 = 5`, MI with the bare value
+            let value = match result["value"].as_str() {
+                Some(value) => Some(value.to_string()),
+                None => Variable::parse_lldb_tree(output)
+                    .into_iter()
+                    .next()
+                    .and_then(|variable| variable.value),
+            };
+            let mut watch = json!({
+                "expression": expression,
+                "value": value,
+                "display": result["display"]
+            });
+            if result["success"] != true {
+                watch["error"] = json!(output);
+            }
+            watches.push(watch);
+        }
+        Ok(watches)
+    }
+
+    /// Launches the program (first call) or continues it until the next stop.
+    ///
+    /// When the program has not been started yet, `launch` replaces the launch
+    /// configuration given to `debug_run`; it is ignored once the program is running.
+    /// With `wait` unset the call returns as soon as the program runs, leaving the
+    /// stop to `debug_poll` or `debug_wait_for_stop`. While waiting, hits of `log_only`
+    /// breakpoints and of breakpoints whose `on_hit_commands` end in `continue` are
+    /// continued past; `continued_hits` counts them.
+    pub(crate) async fn debug_continue(
+        &self,
+        launch: Option<LaunchConfig>,
+        wait: bool,
+    ) -> Result<Value> {
+        // Check current state
+        let current_state = self.current_state().await;
+
+        let (command, mi_command) = match current_state {
+            DebugState::Loaded => {
+                // First time - need to launch the program
+                let (kind, lldb_command, mi_setup, launch_at_entry, fork_setup) = {
+                    let mut session_guard = self.session.lock().await;
+                    let session = session_guard
+                        .as_mut()
+                        .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+                    if let Some(launch) = launch {
+                        // The debugger was checked against follow_fork when it started
+                        session.launch = LaunchConfig {
+                            follow_fork: session.launch.follow_fork,
+                            ..launch
+                        };
+                        if let Some(output) = session.program_output.as_ref() {
+                            if self.options.sandbox {
+                                session.launch.sandbox(&output.sandbox_dir()?);
+                            }
+                        }
+                    }
+                    let output = session.program_output.as_ref();
+                    (
+                        session.kind,
+                        session.launch.lldb_launch_command(output),
+                        session.launch.mi_setup_commands(session.kind, output),
+                        session.launch.stop_at_entry,
+                        session.launch.follow_fork_commands(session.kind),
+                    )
+                };
+                if !kind.uses_mi() {
+                    for setup in fork_setup {
+                        let output = self.send_debugger_command(&setup).await?;
+                        if output.contains("error:") {
+                            return Err(ErrorKind::Unsupported.error(format!(
+                                "This LLDB cannot follow forks ({}): {}",
+                                setup,
+                                output.trim()
+                            )));
+                        }
+                    }
+                }
+                if kind.uses_mi() {
+                    for setup in mi_setup {
+                        let output = self.send_mi_command(&setup).await?;
+                        if output.is_error() {
+                            return Err(ErrorKind::CommandFailed.error(format!(
+                                "Failed to configure launch ({}): {}",
+                                setup,
+                                output.text().trim()
+                            )));
+                        }
+                    }
+                }
+                let mi_command = match (launch_at_entry, kind) {
+                    (false, _) => "-exec-run".to_string(),
+                    // GDB's -exec-run --start stops at main rather than the first instruction
+                    (true, DebuggerKind::Gdb) => mi_console_command("starti"),
+                    (true, _) => "-exec-run --start".to_string(),
+                };
+                (lldb_command, mi_command)
+            }
+            DebugState::Stopped => {
+                // Program is stopped at breakpoint - continue execution
+                ("process continue".to_string(), "-exec-continue".to_string())
+            }
+            DebugState::Running => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Program is already running",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": "running"
+                }));
+            }
+            DebugState::Completed | DebugState::Crashed => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Program has finished execution",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": format!("{:?}", current_state).to_lowercase()
+                }));
+            }
+            DebugState::NotLoaded => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            }
+            DebugState::DebuggerDied => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "The debugger exited; call debug_recover to start it again",
+                    "error_kind": ErrorKind::DebuggerCrashed.name(),
+                    "state": "debuggerdied"
+                }));
+            }
+        };
+
+        let uses_mi = self.uses_mi().await;
+        let (mut command, mut mi_command) = (command, mi_command);
+        let mut continued_hits = 0;
+        let mut response = String::new();
+        let stopped_at = loop {
+            // Output of commands run at hits that were continued past is kept
+            let hit = if uses_mi {
+                let output = self.send_mi_command_with(&mi_command, wait).await?;
+                response.push_str(&output.text());
+                output.breakpoint_hit()
+            } else {
+                let output = self.send_debugger_command_with(&command, wait).await?;
+                response.push_str(&output);
+                match breakpoint_hit(&output) {
+                    // The stop reason usually follows the `Process N stopped` the reply ends at
+                    None if wait && self.stopped_with_continuing_breakpoints().await => {
+                        breakpoint_hit(&self.send_debugger_command("thread info").await?)
+                    }
+                    hit => hit,
+                }
+            };
+            match hit {
+                Some(id) if wait && self.continue_past_hit(id).await? => {
+                    continued_hits += 1;
+                    command = "process continue".to_string();
+                    mi_command = "-exec-continue".to_string();
+                }
+                hit => break hit,
+            }
+        };
+
+        // Get updated state
+        let (new_state, location, exit_status) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (
+                    session.state.clone(),
+                    session.current_location.clone(),
+                    session.exit_status.clone(),
+                )
+            } else {
+                (DebugState::NotLoaded, None, None)
+            }
+        };
+
+        if let Some(id) = stopped_at {
+            self.history
+                .lock()
+                .unwrap()
+                .push("breakpoint_hit", json!({"id": id, "location": location}));
+        }
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "signal": signal,
+            "exit_code": exit_status.as_ref().and_then(|exit| exit.code),
+            "terminated_by_signal": exit_status.and_then(|exit| exit.signal),
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr,
+            "continued_hits": continued_hits
+        }))
+    }
+
+    /// Runs the program until it reaches `location` ("run to cursor").
+    ///
+    /// Sets a temporary breakpoint at the function or `file:line`, continues (or
+    /// launches) the program, and removes the breakpoint again if execution stopped
+    /// somewhere else first. `reached` reports whether the location was hit.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_run_to", "arguments": {"location": "src/main.rs:42"}}
+    /// ```
+    async fn debug_run_to(&self, location: &str) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if !matches!(current_state, DebugState::Loaded | DebugState::Stopped) {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be loaded or stopped to run to a location",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        // The temporary breakpoint is deleted by the debugger when hit and is not tracked
+        let uses_mi = self.uses_mi().await;
+        let (id, output) = if uses_mi {
+            let output = self
+                .send_mi_command(&format!("-break-insert -t {}", mi_quote(location)))
+                .await?;
+            let id = output
+                .result_field("bkpt")
+                .and_then(|bkpt| bkpt.get("number"))
+                .and_then(|v| v.as_str())
+                .and_then(|number| number.parse::<u32>().ok());
+            (id, output.text())
+        } else {
+            let command = match Self::parse_file_line(location) {
+                Some((file, line)) => format!(
+                    "breakpoint set --one-shot true --file {} --line {}",
+                    lldb_quote(file),
+                    line
+                ),
+                None => format!(
+                    "breakpoint set --one-shot true --name {}",
+                    lldb_quote(location)
+                ),
+            };
+            let response = self.send_debugger_command(&command).await?;
+            let id = Self::parse_breakpoint_id(&response)
+                .filter(|_| Self::parse_resolved_locations(&response) > 0);
+            (id, response)
+        };
+
+        let Some(id) = id else {
+            return Ok(json!({
+                "success": false,
+                "error": format!("Could not set a breakpoint at {}", location),
+                "error_kind": ErrorKind::LocationNotFound.name(),
+                "output": output.trim(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        };
+
+        let mut result = self.debug_continue(None, true).await?;
+
+        // If the breakpoint is already gone the debugger removed it on hit
+        let reached = match self.current_state().await {
+            DebugState::Running => false,
+            state => {
+                let deleted = if uses_mi {
+                    !self
+                        .send_mi_command(&format!("-break-delete {}", id))
+                        .await?
+                        .is_error()
+                } else {
+                    !self
+                        .send_debugger_command(&format!("breakpoint delete {}", id))
+                        .await?
+                        .contains("error:")
+                };
+                state == DebugState::Stopped && !deleted
+            }
+        };
+
+        result["target"] = json!(location);
+        result["reached"] = json!(reached);
+        Ok(result)
+    }
+
+    /// Returns from the selected frame at once, without running the rest of the
+    /// function, optionally with `value` as its return value.
+    ///
+    /// The program stays stopped in the caller, so an experiment such as "what if this
+    /// lookup had returned `None`?" needs no rebuild. Disabled unless the server was
+    /// started with `--allow-control-flow`: skipped code leaves the program in a state
+    /// it could never reach on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_return", "arguments": {"value": "0"}}
+    /// ```
+    async fn debug_return(&self, value: Option<&str>) -> Result<Value> {
+        if let Some(refusal) = self.control_flow_refusal("debug_return").await {
+            return Ok(refusal);
+        }
+
+        let (success, output) = if self.uses_mi().await {
+            let command = match value {
+                Some(value) => format!("-exec-return {}", mi_quote(value)),
+                None => "-exec-return".to_string(),
+            };
+            let output = self.send_mi_command(&command).await?;
+            let text = output
+                .error_message()
+                .map(str::to_string)
+                .unwrap_or_else(|| output.text());
+            (!output.is_error(), text)
+        } else {
+            let command = match value {
+                Some(value) => format!("thread return {}", value),
+                None => "thread return".to_string(),
+            };
+            let response = self.send_debugger_command(&command).await?;
+            (!response.contains("error:"), response)
+        };
+
+        let mut result = self.frame_moved(success, &output).await?;
+        result["value"] = json!(value);
+        Ok(result)
+    }
+
+    /// Moves execution of the selected thread to `location` without running the code
+    /// in between, to skip a suspect statement or run one again.
+    ///
+    /// `location` is a line of the current file, a `file:line`, or an address. The
+    /// program stays stopped there; jumping out of the current function is refused by
+    /// LLDB and undefined with GDB. Like `debug_return`, it requires the server flag
+    /// `--allow-control-flow`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_jump", "arguments": {"location": "src/cache.rs:88"}}
+    /// ```
+    async fn debug_jump(&self, location: &str) -> Result<Value> {
+        if let Some(refusal) = self.control_flow_refusal("debug_jump").await {
+            return Ok(refusal);
+        }
+
+        let (success, output) = if self.uses_mi().await {
+            // GDB resumes at the target, so a temporary breakpoint there stops it again
+            let location = if location.starts_with("0x") {
+                format!("*{}", location)
+            } else {
+                location.to_string()
+            };
+            let breakpoint = self
+                .send_mi_command(&format!("-break-insert -t {}", mi_quote(&location)))
+                .await?;
+            if breakpoint.is_error() {
+                (false, breakpoint.text())
+            } else {
+                let output = self
+                    .send_mi_command_with(&format!("-exec-jump {}", mi_quote(&location)), true)
+                    .await?;
+                let text = output
+                    .error_message()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| output.text());
+                (!output.is_error(), text)
+            }
+        } else {
+            let target = match Self::parse_file_line(location) {
+                Some((file, line)) => format!("--file {} --line {}", lldb_quote(file), line),
+                None if location.parse::<u32>().is_ok() => format!("--line {}", location),
+                None => format!("--address {}", location),
+            };
+            let response = self
+                .send_debugger_command(&format!("thread jump {}", target))
+                .await?;
+            (!response.contains("error:"), response)
+        };
+
+        let mut result = self.frame_moved(success, &output).await?;
+        result["target"] = json!(location);
+        Ok(result)
+    }
+
+    /// Why `tool` may not change the program's control flow now, as a failed result:
+    /// the server flag is missing or the program is not stopped.
+    async fn control_flow_refusal(&self, tool: &str) -> Option<Value> {
+        if !self.options.allow_control_flow {
+            return Some(json!({
+                "success": false,
+                "error": format!(
+                    "{} is disabled; restart ferroscope with --allow-control-flow to enable it",
+                    tool
+                ),
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+        let current_state = self.current_state().await;
+        if current_state != DebugState::Stopped {
+            return Some(json!({
+                "success": false,
+                "error": "Program must be stopped to change where it executes",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+        None
+    }
+
+    /// Reports where the program is after `debug_return` or `debug_jump` moved it, and
+    /// records the new location, which no stop event announced.
+    async fn frame_moved(&self, success: bool, output: &str) -> Result<Value> {
+        let (_, _, threads) = self.thread_backtraces(Some(1), false).await?;
+        let frame = threads
+            .into_iter()
+            .next()
+            .and_then(|(_, frames)| frames.into_iter().next());
+        let location = frame
+            .as_ref()
+            .and_then(|frame| match (&frame.file, frame.line) {
+                (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+                _ => frame.function.as_deref().map(demangle),
+            });
+        let state = {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            if success && location.is_some() {
+                session.current_location = location.clone();
+                session.selected_frame = 0;
+            }
+            session.state.clone()
+        };
+
+        let mut result = json!({
+            "success": success,
+            "state": format!("{:?}", state).to_lowercase(),
+            "output": output.trim(),
+            "location": location,
+            "frame": frame.as_ref().map(FrameInfo::to_json)
+        });
+        if !success {
+            result["error"] = json!(output.trim());
+        }
+        Ok(result)
+    }
+
+    /// Steps through a region of code and records the value `expression` takes at each
+    /// line, to answer questions like "when does `x` become negative?" in one call.
+    ///
+    /// The region is a `function`, left when it returns, or the lines `start_line` to
+    /// `end_line` of `file`, left when execution moves elsewhere in the same frame. If
+    /// the program is not already in the region it runs there first. Each step goes
+    /// over calls; recording ends on leaving the region, after `max_steps` steps, or on
+    /// any other stop, such as a breakpoint in a called function. `changes` lists only
+    /// the steps where the value differed from the one before.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_record_values", "arguments": {"expression": "balance", "function": "bank::withdraw"}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_record_values", "arguments": {"expression": "x", "file": "src/main.rs", "start_line": 10, "end_line": 24}}
+    /// ```
+    async fn debug_record_values(
+        &self,
+        expression: &str,
+        region: &ValueRegion,
+        max_steps: usize,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+        if !matches!(current_state, DebugState::Loaded | DebugState::Stopped) {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be loaded or stopped to record values",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+        let in_region = |frame: &FrameInfo| match region {
+            ValueRegion::Function(name) => frame.function.as_deref().is_some_and(|function| {
+                let function = demangle(function);
+                let function = function.split('(').next().unwrap_or("");
+                function == name || function.ends_with(&format!("::{}", name))
+            }),
+            ValueRegion::Lines { file, start, end } => {
+                let in_file = frame.file.as_deref().is_some_and(|path| {
+                    std::path::Path::new(path).ends_with(file)
+                        || std::path::Path::new(file).ends_with(path)
+                });
+                in_file
+                    && frame
+                        .line
+                        .is_some_and(|line| (*start..=*end).contains(&line))
+            }
+        };
+
+        let mut position = match current_state {
+            DebugState::Stopped => self.stack_position().await?,
+            _ => None,
+        };
+        if !position.as_ref().is_some_and(|(frame, _)| in_region(frame)) {
+            let target = match region {
+                ValueRegion::Function(name) => name.clone(),
+                ValueRegion::Lines { file, start, .. } => format!("{}:{}", file, start),
+            };
+            let result = self.debug_run_to(&target).await?;
+            if result["reached"] != true {
+                let mut result = result;
+                result["success"] = json!(false);
+                result["error"] = json!(format!("Execution did not reach {}", target));
+                if result.get("error_kind").is_none() {
+                    result["error_kind"] = json!(ErrorKind::WrongState.name());
+                }
+                return Ok(result);
+            }
+            position = self.stack_position().await?;
+        }
+        let Some((_, entry_depth)) = position else {
+            return Ok(json!({
+                "success": false,
+                "error": "Could not read the stack",
+                "error_kind": ErrorKind::CommandFailed.name(),
+                "state": format!("{:?}", self.current_state().await).to_lowercase()
+            }));
+        };
+
+        let mut values: Vec<Value> = Vec::new();
+        let mut changes: Vec<Value> = Vec::new();
+        let mut previous: Option<Value> = None;
+        let ended = loop {
+            let Some((frame, depth)) = position.take() else {
+                break "stack_unreadable";
+            };
+            if depth < entry_depth || (depth == entry_depth && !in_region(&frame)) {
+                break "left_region";
+            }
+            if depth > entry_depth {
+                break "stopped_in_callee";
+            }
+            let evaluated = self
+                .evaluate_expressions(vec![expression.to_string()])
+                .await?
+                .into_iter()
+                .next()
+                .unwrap_or_default();
+            let value = match &evaluated["display"] {
+                Value::Null => evaluated["value"].clone(),
+                display => display.clone(),
+            };
+            let location = match (&frame.file, frame.line) {
+                (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+                _ => None,
+            };
+            let step = values.len();
+            if previous.as_ref() != Some(&value) {
+                changes.push(json!({
+                    "step": step,
+                    "location": location,
+                    "from": previous,
+                    "to": value
+                }));
+            }
+            let mut entry = json!({ "step": step, "location": location, "value": value });
+            if let Some(error) = evaluated.get("error") {
+                entry["error"] = error.clone();
+            }
+            values.push(entry);
+            previous = Some(value);
+
+            if values.len() >= max_steps {
+                break "max_steps";
+            }
+            self.send_command("thread step-over", "-exec-next").await?;
+            if self.current_state().await != DebugState::Stopped {
+                break "program_ended";
+            }
+            if self.stop_signal().await?.is_some() {
+                break "signal";
+            }
+            position = self.stack_position().await?;
+        };
+
+        let state = self.current_state().await;
+        let location = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .and_then(|session| session.current_location.clone())
+        };
+        // The first entry in `changes` is the starting value
+        let changed = changes.len().saturating_sub(1);
+        let mut summary = format!(
+            "{} changed {} time{} over {} step{}",
+            expression,
+            changed,
+            if changed == 1 { "" } else { "s" },
+            values.len(),
+            if values.len() == 1 { "" } else { "s" }
+        );
+        if let Some(last) = previous.as_ref().and_then(|value| value.as_str()) {
+            summary.push_str(&format!(", ending as {}", last));
+        }
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", state).to_lowercase(),
+            "expression": expression,
+            "steps": values.len(),
+            "values": values,
+            "changes": changes,
+            "ended": ended,
+            "location": location,
+            "summary": summary
+        }))
+    }
+
+    /// The selected thread's innermost frame and the number of frames on its stack.
+    async fn stack_position(&self) -> Result<Option<(FrameInfo, usize)>> {
+        if self.uses_mi().await {
+            let output = self.send_mi_command("-stack-info-depth").await?;
+            let depth = output
+                .result_field("depth")
+                .and_then(|v| v.as_str())
+                .and_then(|depth| depth.parse().ok());
+            let (_, _, threads) = self.thread_backtraces(Some(1), false).await?;
+            let frame = threads
+                .into_iter()
+                .next()
+                .and_then(|(_, frames)| frames.into_iter().next());
+            Ok(frame.zip(depth))
+        } else {
+            let (_, _, threads) = self.thread_backtraces(None, false).await?;
+            Ok(threads.into_iter().next().and_then(|(_, frames)| {
+                let depth = frames.len();
+                frames.into_iter().next().map(|frame| (frame, depth))
+            }))
+        }
+    }
+
+    pub(crate) async fn debug_step(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let response = self.send_command("thread step-over", "-exec-next").await?;
+        let timed_out = response.contains(TIMEOUT_NOTE);
+
+        // Get updated state and location
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        if timed_out {
+            return Ok(step_timed_out(&new_state, &response));
+        }
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    /// Steps a single machine instruction, over calls when `over` is set.
+    ///
+    /// Useful where line stepping is too coarse: optimized builds, inlined code, or
+    /// inline assembly. The response carries the new frame including its `pc`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_step_instruction", "arguments": {"over": true}}
+    /// ```
+    async fn debug_step_instruction(&self, over: bool) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (response, frame, timed_out) = if self.uses_mi().await {
+            let command = if over {
+                "-exec-next-instruction"
+            } else {
+                "-exec-step-instruction"
+            };
+            let output = self.send_mi_command(command).await?;
+            let frame = output.records.iter().rev().find_map(|record| match record {
+                MiRecord::Exec { class, results } if class == "stopped" => {
+                    results.get("frame").map(FrameInfo::from_mi)
+                }
+                _ => None,
+            });
+            (output.text(), frame, output.timed_out)
+        } else {
+            let command = if over {
+                "thread step-inst-over"
+            } else {
+                "thread step-inst"
+            };
+            let response = self.send_debugger_command(command).await?;
+            let frame = response.lines().find_map(FrameInfo::parse_lldb);
+            let timed_out = response.contains(TIMEOUT_NOTE);
+            (response, frame, timed_out)
+        };
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        if timed_out {
+            return Ok(step_timed_out(&new_state, &response));
+        }
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "frame": frame.map(|frame| frame.to_json()),
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    pub(crate) async fn debug_step_into(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let response = self.send_command("thread step-in", "-exec-step").await?;
+        let timed_out = response.contains(TIMEOUT_NOTE);
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        if timed_out {
+            return Ok(step_timed_out(&new_state, &response));
+        }
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    pub(crate) async fn debug_step_out(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let response = self.send_command("thread step-out", "-exec-finish").await?;
+        let timed_out = response.contains(TIMEOUT_NOTE);
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        if timed_out {
+            return Ok(step_timed_out(&new_state, &response));
+        }
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    /// Runs until the current function returns and reports its return value.
+    ///
+    /// Like `debug_step_out`, but extracts the value the debugger prints on return
+    /// (`Return value:` in LLDB, `return-value` in GDB/MI) into `return_value`.
+    /// Functions returning `()` or returning via a hidden pointer may report none.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_finish", "arguments": {}}
+    /// ```
+    async fn debug_finish(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (response, return_value, timed_out) = if self.uses_mi().await {
+            let output = self.send_mi_command("-exec-finish").await?;
+            let return_value = output.records.iter().rev().find_map(|record| match record {
+                MiRecord::Exec { class, results } if class == "stopped" => {
+                    results.get("return-value").map(|value| {
+                        json!({
+                            "name": results.get("gdb-result-var"),
+                            "type": null,
+                            "value": value
+                        })
+                    })
+                }
+                _ => None,
+            });
+            (output.text(), return_value, output.timed_out)
+        } else {
+            let response = self.send_debugger_command("thread step-out").await?;
+            let return_value = response
+                .split_once("Return value: ")
+                .and_then(|(_, rest)| Variable::parse_lldb_tree(rest).into_iter().next())
+                .map(|value| value.to_json());
+            let timed_out = response.contains(TIMEOUT_NOTE);
+            (response, return_value, timed_out)
+        };
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+        if timed_out {
+            return Ok(step_timed_out(&new_state, &response));
+        }
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "return_value": return_value,
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    /// Runs until a later line of the current frame is reached, or the frame returns.
+    ///
+    /// No breakpoint is left behind, which makes this the quick way to skip past a
+    /// loop without stepping through every iteration.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_until", "arguments": {"line": 57}}
+    /// ```
+    async fn debug_until(&self, line: u32) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped at a breakpoint to step",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let response = self
+            .send_command(
+                &format!("thread until {}", line),
+                &format!("-exec-until {}", line),
+            )
+            .await?;
+
+        let (new_state, location) = {
+            let session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_ref() {
+                (session.state.clone(), session.current_location.clone())
+            } else {
+                (DebugState::NotLoaded, None)
+            }
+        };
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": !response.contains("error:"),
+            "state": format!("{:?}", new_state).to_lowercase(),
+            "output": response.trim(),
+            "location": location,
+            "signal": signal,
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    /// Evaluates an expression in the current debugging context.
+    ///
+    /// This tool allows inspection of variables, calling functions, and evaluating
+    /// arbitrary expressions at the current program state. The program must be
+    /// stopped (e.g., at a breakpoint) for evaluation to work.
+    ///
+    /// # Arguments
+    ///
+    /// * `expression` - The expression to evaluate (variable name, function call, etc.)
+    /// * `options` - Integer format, language, and the frame and thread to evaluate in;
+    ///   a frame or thread other than the selected one is selected only for the call.
+    ///   Unless `allow_side_effects` is set, expressions that call functions or assign
+    ///   are refused, and LLDB reads variable paths first and may not JIT code
+    ///
+    /// # Returns
+    ///
+    /// Returns a JSON response with the evaluation result, the value's static `type`,
+    /// or an error message.
+    ///
+    /// # Examples
+    ///
+    /// Inspecting a variable:
+    /// ```json
+    /// {"name": "debug_eval", "arguments": {"expression": "my_variable"}}
+    /// ```
+    ///
+    /// Evaluating a complex expression:
+    /// ```json
+    /// {"name": "debug_eval", "arguments": {"expression": "my_struct.field + 42"}}
+    /// ```
+    ///
+    /// Calling a method, which must be allowed:
+    /// ```json
+    /// {"name": "debug_eval", "arguments": {"expression": "queue.len()", "allow_side_effects": true}}
+    /// ```
+    ///
+    /// Reading a C caller's flags in hex:
+    /// ```json
+    /// {"name": "debug_eval", "arguments": {"expression": "ctx->flags", "language": "c", "format": "hex", "in_frame": 2}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - No debugging session is active
+    /// - The program is not currently stopped at a breakpoint
+    /// - The expression cannot be evaluated in the current context
+    /// - The debugger communication fails
+    ///
+    /// # Security Warning
+    ///
+    /// ⚠️ With `allow_side_effects`, this function can execute arbitrary code through the
+    /// expression evaluator. Only use with trusted expressions and in secure environments.
+    pub(crate) async fn debug_eval(
+        &self,
+        expression: &str,
+        options: &EvalOptions,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped (at breakpoint) to evaluate expressions",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        if let Some(refusal) = side_effect_refusal(expression, options) {
+            return Ok(json!({
+                "success": false,
+                "error": refusal,
+                "error_kind": ErrorKind::InvalidArgument.name(),
+                "state": "stopped"
+            }));
+        }
+        if options.thread.is_none() && options.frame.is_none() {
+            return self.evaluate(expression, options).await;
+        }
+        let thread = self.current_thread_id().await?;
+        let frame = self
+            .session
+            .lock()
+            .await
+            .as_ref()
+            .map_or(0, |session| session.selected_frame);
+        if let Some(error) = self.select_quietly(options.thread, options.frame).await? {
+            self.select_quietly(thread, Some(frame)).await?;
+            return Ok(json!({
+                "success": false,
+                "error": error,
+                "error_kind": ErrorKind::InvalidArgument.name(),
+                "state": "stopped"
+            }));
+        }
+        let result = self.evaluate(expression, options).await;
+        self.select_quietly(thread, Some(frame)).await?;
+        result
+    }
+
+    /// Evaluates `expression` in the selected frame, for [`Self::debug_eval`].
+    async fn evaluate(&self, expression: &str, options: &EvalOptions) -> Result<Value> {
+        if self.uses_mi().await {
+            // GDB switches languages globally, so the previous setting is put back
+            let previous_language = match options.language {
+                Some(language) => {
+                    let previous = self
+                        .send_mi_command("-gdb-show language")
+                        .await?
+                        .result_field("value")
+                        .and_then(|v| v.as_str())
+                        .map(|value| value.split(';').next().unwrap_or(value).to_string());
+                    self.send_mi_command(&format!("-gdb-set language {}", language.name()))
+                        .await?;
+                    previous
+                }
+                None => None,
+            };
+            // Calls the syntactic check missed, e.g. of operator impls, fail instead
+            if !options.allow_side_effects {
+                self.send_mi_command("-gdb-set may-call-functions off")
+                    .await?;
+            }
+
+            let (success, value, text) = match options.format {
+                Some(format) => {
+                    let output = self
+                        .send_mi_command(&mi_console_command(&format!(
+                            "print /{} {}",
+                            format.gdb_letter(),
+                            expression
+                        )))
+                        .await?;
+                    let text = output.text();
+                    // `$1 = 0x2a`
+                    let value = text
+                        .split_once(" = ")
+                        .map(|(_, value)| value.trim().to_string());
+                    (!output.is_error(), value, text)
+                }
+                None => {
+                    let output = self
+                        .send_mi_command(&format!(
+                            "-data-evaluate-expression {}",
+                            mi_quote(expression)
+                        ))
+                        .await?;
+                    let value = output
+                        .result_field("value")
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string);
+                    (!output.is_error(), value, output.text())
+                }
+            };
+            let variable = self.mi_variable_tree(expression, 0, None).await?;
+            let pointee = match &variable {
+                Some(variable) if variable.is_smart_pointer() => {
+                    self.smart_pointer_target(expression).await?
+                }
+                _ => None,
+            };
+            if let Some(previous) = previous_language {
+                self.send_mi_command(&format!("-gdb-set language {}", previous))
+                    .await?;
+            }
+            if !options.allow_side_effects {
+                self.send_mi_command("-gdb-set may-call-functions on")
+                    .await?;
+            }
+
+            return Ok(json!({
+                "success": success,
+                "expression": expression,
+                "output": value.clone().unwrap_or_else(|| text.trim().to_string()),
+                "value": value,
+                "type": variable.and_then(|variable| variable.type_name),
+                "display": value.as_deref().filter(|_| options.format.is_none()).map(tidy_gdb_value),
+                "pointee": pointee,
+                "method": if options.format.is_some() { "print" } else { "data_evaluate_expression" }
+            }));
+        }
+
+        // LLDB has no Rust expression parser, so only C changes the language
+        let format = options
+            .format
+            .map(|format| format!(" --format {}", format.lldb_format()))
+            .unwrap_or_default();
+        let language = match options.language {
+            Some(EvalLanguage::C) => " --language c",
+            _ => "",
+        };
+
+        let jit = if options.allow_side_effects {
+            ""
+        } else {
+            " --allow-jit false"
+        };
+        let expression_command =
+            format!("expression{}{}{} -- {}", format, language, jit, expression);
+        let variable_command = format!("frame variable{} {}", format, expression);
+
+        // Try expression first, then frame variable as a fallback; without side effects
+        // the other way round, as `frame variable` only reads memory
+        let attempts = if options.allow_side_effects {
+            [
+                ("expression", expression_command),
+                ("frame_variable", variable_command),
+            ]
+        } else {
+            [
+                ("frame_variable", variable_command),
+                ("expression", expression_command),
+            ]
+        };
+        let mut method = "";
+        let mut response = String::new();
+        for (attempt, command) in attempts {
+            method = attempt;
+            response = self.send_debugger_command(&command).await?;
+            if !response.contains("error:") && !response.contains("undeclared identifier") {
+                break;
+            }
+        }
+
+        let variable = Variable::parse_lldb_tree(&response).into_iter().next();
+        let pointee = match &variable {
+            Some(variable) if variable.is_smart_pointer() => {
+                self.smart_pointer_target(expression).await?
+            }
+            _ => None,
+        };
+        Ok(json!({
+            "success": !response.contains("error:"),
+            "expression": expression,
+            "output": response.trim(),
+            "value": variable.as_ref().and_then(|variable| variable.value.clone()),
+            "type": variable.as_ref().and_then(|variable| variable.type_name.clone()),
+            "display": variable
+                .as_ref()
+                .filter(|_| options.format.is_none())
+                .and_then(Variable::rust_display),
+            "pointee": pointee,
+            "method": method
+        }))
+    }
+
+    /// Evaluates several expressions in the selected frame in one call, returning the
+    /// `value` and static `type` of each in order.
+    ///
+    /// GDB receives all the commands in a single write; LLDB, which reads one command
+    /// at a time, evaluates them one after the other. An expression that fails, or that
+    /// calls a function or assigns without `allow_side_effects`, carries its `error`
+    /// without failing the others.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_eval_many", "arguments": {"expressions": ["self.len", "self.cap", "items[0]"]}}
+    /// ```
+    async fn debug_eval_many(
+        &self,
+        expressions: &[String],
+        allow_side_effects: bool,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped (at breakpoint) to evaluate expressions",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let options = EvalOptions {
+            allow_side_effects,
+            ..EvalOptions::default()
+        };
+        let refusals: Vec<Option<String>> = expressions
+            .iter()
+            .map(|expression| side_effect_refusal(expression, &options))
+            .collect();
+        let refused = |expression: &String, refusal: &String| {
+            json!({
+                "expression": expression,
+                "success": false,
+                "value": null,
+                "type": null,
+                "error": refusal
+            })
+        };
+
+        let mut results = Vec::new();
+        if self.uses_mi().await {
+            let mut commands: Vec<String> = expressions
+                .iter()
+                .zip(&refusals)
+                .filter(|(_, refusal)| refusal.is_none())
+                .flat_map(|(expression, _)| {
+                    [
+                        format!("-data-evaluate-expression {}", mi_quote(expression)),
+                        mi_console_command(&format!("whatis {}", expression)),
+                    ]
+                })
+                .collect();
+            if !allow_side_effects && !commands.is_empty() {
+                commands.insert(0, "-gdb-set may-call-functions off".to_string());
+                commands.push("-gdb-set may-call-functions on".to_string());
+            }
+            let outputs = if commands.is_empty() {
+                Vec::new()
+            } else {
+                self.send_mi_batch(&commands).await?
+            };
+            let first = usize::from(!allow_side_effects).min(outputs.len());
+            let mut evaluated = outputs[first..].chunks_exact(2);
+            for (expression, refusal) in expressions.iter().zip(&refusals) {
+                if let Some(refusal) = refusal {
+                    results.push(refused(expression, refusal));
+                    continue;
+                }
+                let Some([value, type_output]) = evaluated.next() else {
+                    continue;
+                };
+                let mut result = json!({
+                    "expression": expression,
+                    "success": !value.is_error() && !value.timed_out,
+                    "value": value.result_field("value").and_then(|v| v.as_str()),
+                    // `type = i32`
+                    "type": type_output
+                        .text()
+                        .trim()
+                        .strip_prefix("type = ")
+                        .map(str::to_string)
+                });
+                if let Some(error) = value.error_message() {
+                    result["error"] = json!(error);
+                }
+                results.push(result);
+            }
+        } else {
+            for (expression, refusal) in expressions.iter().zip(&refusals) {
+                if let Some(refusal) = refusal {
+                    results.push(refused(expression, refusal));
+                    continue;
+                }
+                let evaluated = self.evaluate(expression, &options).await?;
+                let mut result = json!({
+                    "expression": expression,
+                    "success": evaluated["success"],
+                    "value": evaluated["value"],
+                    "type": evaluated["type"]
+                });
+                if evaluated["success"] != true {
+                    result["error"] = evaluated["output"].clone();
+                }
+                results.push(result);
+            }
+        }
+
+        let failed = results.iter().filter(|r| r["success"] != true).count();
+        Ok(json!({
+            "success": true,
+            "results": results,
+            "failed": failed
+        }))
+    }
+
+    /// Assigns `value` to the variable `name` in the selected frame, to try out a fix
+    /// without rebuilding: "what if `len` were 0 here?".
+    ///
+    /// `name` may be any place the debugger can assign to, such as `self.len` or
+    /// `buf[3]`; `value` is an expression in the debugger's syntax. The variable is read
+    /// before and after, and both values are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_set_var", "arguments": {"name": "self.len", "value": "0"}}
+    /// ```
+    async fn debug_set_var(&self, name: &str, value: &str) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to set a variable",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+        if let Some(effect) = side_effect(name) {
+            return Err(ErrorKind::InvalidArgument.error(format!(
+                "name must be a variable, but {} contains {}",
+                name, effect
+            )));
+        }
+
+        let before = self.evaluate(name, &EvalOptions::default()).await?;
+        let (success, output) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&mi_console_command(&format!(
+                    "set var {} = {}",
+                    name, value
+                )))
+                .await?;
+            let text = output
+                .error_message()
+                .map(str::to_string)
+                .unwrap_or_else(|| output.text());
+            (!output.is_error(), text)
+        } else {
+            let response = self
+                .send_debugger_command(&format!("expression -- {} = {}", name, value))
+                .await?;
+            (!response.contains("error:"), response)
+        };
+        if !success {
+            return Ok(json!({
+                "success": false,
+                "error": format!("Could not set {}: {}", name, output.trim()),
+                "name": name,
+                "value": before["value"],
+                "state": "stopped"
+            }));
+        }
+
+        let after = self.evaluate(name, &EvalOptions::default()).await?;
+        Ok(json!({
+            "success": true,
+            "output": output.trim(),
+            "name": name,
+            "old_value": before["value"],
+            "value": after["value"],
+            "type": after["type"]
+        }))
+    }
+
+    /// The thread the debugger has selected, which stepping and evaluation act on.
+    async fn current_thread_id(&self) -> Result<Option<u32>> {
+        if self.uses_mi().await {
+            let output = self.send_mi_command("-thread-list-ids").await?;
+            Ok(output
+                .result_field("current-thread-id")
+                .and_then(|v| v.as_str())
+                .and_then(|id| id.parse().ok()))
+        } else {
+            let response = self.send_debugger_command("thread info").await?;
+            Ok(response
+                .lines()
+                .find_map(Self::parse_lldb_thread_line)
+                .map(|thread| thread.id))
+        }
+    }
+
+    /// Selects `thread`, then `frame`, in the debugger without recording the selection
+    /// in the session, returning the debugger's error if either does not exist.
+    async fn select_quietly(
+        &self,
+        thread: Option<u32>,
+        frame: Option<u32>,
+    ) -> Result<Option<String>> {
+        let uses_mi = self.uses_mi().await;
+        let commands = thread
+            .map(|thread| ("thread select", "-thread-select", thread))
+            .into_iter()
+            .chain(frame.map(|frame| ("frame select", "-stack-select-frame", frame)));
+        for (lldb_command, mi_command, index) in commands {
+            let error = if uses_mi {
+                let output = self
+                    .send_mi_command(&format!("{} {}", mi_command, index))
+                    .await?;
+                output.error_message().map(str::to_string)
+            } else {
+                let response = self
+                    .send_debugger_command(&format!("{} {}", lldb_command, index))
+                    .await?;
+                response
+                    .contains("error:")
+                    .then(|| response.trim().to_string())
+            };
+            if error.is_some() {
+                return Ok(error);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Expands `expression` `depth` levels deep, following pointers as far, with LLDB.
+    ///
+    /// `frame variable` reads variable paths without running code; anything else goes
+    /// to the expression evaluator. Returns the raw output and the parsed value.
+    async fn lldb_value_tree(
+        &self,
+        expression: &str,
+        depth: u32,
+    ) -> Result<(String, Option<Variable>)> {
+        let options = format!("-T --depth {} --ptr-depth {}", depth, depth);
+        let mut response = self
+            .send_debugger_command(&format!("frame variable {} -- {}", options, expression))
+            .await?;
+        if response.contains("error:") {
+            response = self
+                .send_debugger_command(&format!("expression {} -- {}", options, expression))
+                .await?;
+        }
+        let variable = Variable::parse_lldb_tree(&response).into_iter().next();
+        Ok((response, variable))
+    }
+
+    /// What the `Box`, `Rc`, or `Arc` that `expression` evaluates to points at, so one
+    /// `debug_eval` shows the value rather than an address or reference counts.
+    async fn smart_pointer_target(&self, expression: &str) -> Result<Option<Value>> {
+        let variable = if self.uses_mi().await {
+            self.mi_variable_tree(expression, 3, Some(SMART_POINTER_CHILDREN))
+                .await?
+        } else {
+            self.lldb_value_tree(expression, 3).await?.1
+        };
+        Ok(variable
+            .as_ref()
+            .and_then(Variable::pointee)
+            .map(|pointee| pointee.to_json_limited(SMART_POINTER_CHILDREN as usize)))
+    }
+
+    /// Renders `len` elements of type `element_type` starting at a raw pointer, the
+    /// view of a `*const T` plus length that `debug_eval` cannot show.
+    ///
+    /// `len_expr` is evaluated first, so it may be a variable or field such as
+    /// `self.len`. At most [`MAX_SLICE_ELEMENTS`] elements are read; `truncated` reports
+    /// whether there were more.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_eval_slice", "arguments": {"ptr_expr": "self.buf", "len_expr": "self.len"}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_eval_slice", "arguments": {"ptr_expr": "data", "len_expr": "4", "type": "u32"}}
+    /// ```
+    async fn debug_eval_slice(
+        &self,
+        ptr_expr: &str,
+        len_expr: &str,
+        element_type: Option<&str>,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to evaluate expressions",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let uses_mi = self.uses_mi().await;
+        let len_value = if uses_mi {
+            self.mi_variable_tree(len_expr, 0, None).await?
+        } else {
+            self.lldb_value_tree(len_expr, 0).await?.1
+        }
+        .and_then(|variable| variable.value);
+        let Some(len) = len_value.as_deref().and_then(parse_integer) else {
+            return Ok(json!({
+                "success": false,
+                "error": format!(
+                    "len_expr {} did not evaluate to an integer: {}",
+                    len_expr,
+                    len_value.as_deref().unwrap_or("no value")
+                ),
+                "error_kind": ErrorKind::InvalidArgument.name()
+            }));
+        };
+        let shown = len.min(MAX_SLICE_ELEMENTS);
+
+        let (output, elements) = if shown == 0 {
+            (String::new(), Vec::new())
+        } else if uses_mi {
+            // GDB's `@` repeats the pointee into an array
+            let expression = match element_type {
+                Some(element_type) => {
+                    format!("*({} as *const {})@{}", ptr_expr, element_type, shown)
+                }
+                None => format!("*({})@{}", ptr_expr, shown),
+            };
+            let elements = self
+                .mi_variable_tree(&expression, 2, Some(shown as u32))
+                .await?
+                .map(|array| array.children)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|mut element| {
+                    element.name = format!("[{}]", element.name);
+                    element
+                })
+                .collect();
+            (String::new(), elements)
+        } else {
+            // LLDB's expression evaluator speaks C casts; `-Z` prints the pointee as an array
+            let pointer = match element_type {
+                Some(element_type) => format!("({} *)({})", element_type, ptr_expr),
+                None => ptr_expr.to_string(),
+            };
+            let response = self
+                .send_debugger_command(&format!("expression -T -Z {} -- {}", shown, pointer))
+                .await?;
+            let elements = Variable::parse_lldb_tree(&response)
+                .into_iter()
+                .next()
+                .map(|pointer| pointer.children)
+                .unwrap_or_default();
+            (response, elements)
+        };
+
+        if shown > 0 && elements.is_empty() {
+            return Ok(json!({
+                "success": false,
+                "error": format!("Could not read {} elements at {}", shown, ptr_expr),
+                "output": output.trim()
+            }));
+        }
+
+        let slice = Variable {
+            name: ptr_expr.to_string(),
+            type_name: Some(format!("[{}]", element_type.unwrap_or("_"))),
+            value: Some(format!("size={}", len)),
+            children: elements,
+        };
+        Ok(json!({
+            "success": true,
+            "expression": ptr_expr,
+            "len": len,
+            "truncated": len > shown,
+            "output": output.trim(),
+            "display": slice.rust_display(),
+            "elements": slice.children.iter().map(Variable::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Reads `count` bytes of the debuggee's memory at an address or pointer expression.
+    ///
+    /// The bytes are returned as a hex string, printable ASCII, and a hex dump, and
+    /// optionally decoded as little-endian values of `value_type` — the view needed
+    /// when chasing unsafe or FFI bugs that `debug_eval` cannot show.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_memory_read", "arguments": {"address": "buf.as_ptr()", "count": 32, "type": "u32"}}
+    /// ```
+    async fn debug_memory_read(
+        &self,
+        address: &str,
+        count: u64,
+        value_type: Option<MemoryType>,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to read memory",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, start, bytes) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&format!(
+                    "-data-read-memory-bytes {} {}",
+                    mi_quote(address),
+                    count
+                ))
+                .await?;
+            let block = output
+                .result_field("memory")
+                .and_then(|v| v.as_array())
+                .and_then(|blocks| blocks.first());
+            let field = |name: &str| block.and_then(|b| b.get(name)).and_then(|v| v.as_str());
+            let start = field("begin")
+                .and_then(|begin| begin.strip_prefix("0x"))
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok());
+            let bytes = match field("contents") {
+                Some(contents) => parse_hex_bytes(contents)?,
+                None => Vec::new(),
+            };
+            (!output.is_error(), output.text(), start, bytes)
+        } else {
+            let response = self
+                .send_debugger_command(&format!(
+                    "memory read --size 1 --format x --count {} -- {}",
+                    count,
+                    lldb_quote(address)
+                ))
+                .await?;
+            let (start, bytes) = parse_lldb_memory(&response);
+            (!response.contains("error:"), response, start, bytes)
+        };
+
+        let mut result = json!({
+            "success": success && start.is_some(),
+            "address": start.map(|a| format!("{:#x}", a)),
+            "expression": address,
+            "count": bytes.len(),
+            "hex": bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "ascii": printable_ascii(&bytes),
+            "dump": format_hex_dump(start.unwrap_or(0), &bytes),
+            "output": output.trim()
+        });
+        if let Some(value_type) = value_type {
+            result["values"] = Value::Array(value_type.decode(&bytes));
+        }
+        Ok(result)
+    }
+
+    /// Writes raw bytes into the debuggee's memory at an address or pointer expression.
+    ///
+    /// Disabled unless the server was started with `--allow-memory-write`, because a
+    /// bad write silently corrupts the program under test.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_memory_write", "arguments": {"address": "&flag", "bytes": "01"}}
+    /// ```
+    async fn debug_memory_write(&self, address: &str, bytes: &[u8]) -> Result<Value> {
+        if !self.options.allow_memory_write {
+            return Ok(json!({
+                "success": false,
+                "error": "Memory writes are disabled; restart ferroscope with --allow-memory-write to enable them",
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        }
+
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to write memory",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let (success, output) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&format!(
+                    "-data-write-memory-bytes {} {}",
+                    mi_quote(address),
+                    hex
+                ))
+                .await?;
+            (!output.is_error(), output.text())
+        } else {
+            let values: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+            let response = self
+                .send_debugger_command(&format!(
+                    "memory write --size 1 -- {} {}",
+                    lldb_quote(address),
+                    values.join(" ")
+                ))
+                .await?;
+            (!response.contains("error:"), response)
+        };
+
+        Ok(json!({
+            "success": success,
+            "expression": address,
+            "count": bytes.len(),
+            "hex": hex,
+            "output": output.trim()
+        }))
+    }
+
+    /// Shows or changes how the debugger handles a signal.
+    ///
+    /// `stop` decides whether the signal stops the program, `pass` whether the
+    /// program receives it, and `notify` whether the debugger reports it. Options
+    /// left out keep their current setting, so calling with only `signal` reports
+    /// the current policy. Typical uses are ignoring `SIGPIPE` in network code or
+    /// letting `SIGINT` through to a program that handles it.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_signals", "arguments": {"signal": "SIGPIPE", "stop": false, "notify": false}}
+    /// ```
+    async fn debug_signals(
+        &self,
+        signal: &str,
+        stop: Option<bool>,
+        pass: Option<bool>,
+        notify: Option<bool>,
+    ) -> Result<Value> {
+        if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(
+                ErrorKind::InvalidArgument.error(format!("Invalid signal name: {}", signal))
+            );
+        }
+
+        let kind = match self.debugger_kind().await {
+            Some(kind) => kind,
+            None => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            }
+        };
+
+        let (success, output, policy) = if kind.uses_mi() {
+            let mut command = format!("handle {}", signal);
+            for (setting, yes, no) in [
+                (stop, "stop", "nostop"),
+                (notify, "print", "noprint"),
+                (pass, "pass", "nopass"),
+            ] {
+                if let Some(setting) = setting {
+                    command.push(' ');
+                    command.push_str(if setting { yes } else { no });
+                }
+            }
+            let output = self.send_mi_command(&mi_console_command(&command)).await?;
+            let text = output.text();
+            // Signal  Stop  Print  Pass to program  Description
+            let policy = text.lines().find_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() < 4 || columns[0] != signal {
+                    return None;
+                }
+                let yes = |column: &str| column == "Yes";
+                Some(json!({
+                    "stop": yes(columns[1]),
+                    "notify": yes(columns[2]),
+                    "pass": yes(columns[3])
+                }))
+            });
+            (!output.is_error(), text, policy)
+        } else {
+            let mut command = format!("process handle {}", signal);
+            for (setting, flag) in [(stop, "-s"), (pass, "-p"), (notify, "-n")] {
+                if let Some(setting) = setting {
+                    command.push_str(&format!(" {} {}", flag, setting));
+                }
+            }
+            let response = self.send_debugger_command(&command).await?;
+            // NAME  PASS  STOP  NOTIFY
+            let policy = response.lines().find_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() < 4 || columns[0] != signal {
+                    return None;
+                }
+                let yes = |column: &str| column == "true";
+                Some(json!({
+                    "stop": yes(columns[2]),
+                    "notify": yes(columns[3]),
+                    "pass": yes(columns[1])
+                }))
+            });
+            (!response.contains("error:"), response, policy)
+        };
+
+        Ok(json!({
+            "success": success && policy.is_some(),
+            "signal": signal,
+            "policy": policy,
+            "output": output.trim()
+        }))
+    }
+
+    /// Lists all threads of the stopped program.
+    ///
+    /// Each thread is reported with its debugger ID, OS thread ID, name, stop reason,
+    /// and top frame, so the agent can pick the thread worth inspecting in a
+    /// multithreaded program and switch to it with `debug_select_thread`.
+    pub(crate) async fn debug_threads(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to list threads",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, threads) = if self.uses_mi().await {
+            let output = self.send_mi_command("-thread-info").await?;
+            let current = output
+                .result_field("current-thread-id")
+                .and_then(|v| v.as_str());
+            let threads: Vec<ThreadInfo> = output
+                .result_field("threads")
+                .and_then(|v| v.as_array())
+                .map(|threads| {
+                    threads
+                        .iter()
+                        .filter_map(|thread| {
+                            let field = |name: &str| {
+                                thread
+                                    .get(name)
+                                    .and_then(|v| v.as_str())
+                                    .map(str::to_string)
+                            };
+                            let id = field("id")?;
+                            Some(ThreadInfo {
+                                id: id.parse().ok()?,
+                                tid: field("target-id"),
+                                name: field("name"),
+                                stop_reason: None,
+                                frame: thread.get("frame").map(format_mi_frame),
+                                selected: current == Some(id.as_str()),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            (!output.is_error(), output.text(), threads)
+        } else {
+            let response = self.send_debugger_command("thread list").await?;
+            let threads = response
+                .lines()
+                .filter_map(Self::parse_lldb_thread_line)
+                .collect();
+            (!response.contains("error:"), response, threads)
+        };
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "threads": threads.iter().map(ThreadInfo::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Parses one line of LLDB's `thread list` output.
+    ///
+    /// Lines look like
+    /// `* thread #1: tid = 0x1c0e, 0x0000000100003f94 app`main at main.rs:3:5, name = 'main', stop reason = breakpoint 1.1`
+    /// where the leading `*` marks the selected thread. The shorter headers printed by
+    /// `thread backtrace` (`* thread #1, name = 'main', stop reason = ...`) parse too.
+    fn parse_lldb_thread_line(line: &str) -> Option<ThreadInfo> {
+        let trimmed = line.trim_start();
+        let (selected, rest) = match trimmed.strip_prefix("* ") {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let rest = rest.strip_prefix("thread #")?;
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let id = rest[..digits].parse().ok()?;
+        // `thread list` puts a colon after the ID, backtrace headers go straight to `, name = `
+        let rest = &rest[digits..];
+        let rest = match rest.strip_prefix(':') {
+            Some(rest) => rest,
+            None if rest.is_empty() || rest.starts_with(", ") => rest,
+            None => return None,
+        };
+
+        // Everything after the tid is the frame followed by optional `key = value` pairs.
+        // Frames may contain ", " (generic parameters), so split at the known keys instead.
+        let rest = rest.trim_start();
+        let (tid, rest) = match rest.strip_prefix("tid = ") {
+            Some(tid_rest) => match tid_rest.split_once(", ") {
+                Some((tid, rest)) => (Some(tid.to_string()), rest),
+                None => (Some(tid_rest.to_string()), ""),
+            },
+            None => (None, rest),
+        };
+
+        let key_start = |key: &str| rest.find(key);
+        let frame_end = [", name = ", ", queue = ", ", stop reason = "]
+            .iter()
+            .filter_map(|key| key_start(key))
+            .min()
+            .unwrap_or(rest.len());
+        let frame = Some(rest[..frame_end].trim().to_string()).filter(|f| !f.is_empty());
+
+        let name = key_start(", name = ").map(|start| {
+            let value = &rest[start + ", name = ".len()..];
+            let value = value.split(", queue = ").next().unwrap_or(value);
+            let value = value.split(", stop reason = ").next().unwrap_or(value);
+            value.trim_matches('\'').to_string()
+        });
+        let stop_reason = key_start(", stop reason = ")
+            .map(|start| rest[start + ", stop reason = ".len()..].trim().to_string());
+
+        Some(ThreadInfo {
+            id,
+            tid,
+            name,
+            stop_reason,
+            frame,
+            selected,
+        })
+    }
+
+    /// Makes `thread_id` the thread that stepping, backtraces, and evaluation apply to.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_select_thread", "arguments": {"thread_id": 2}}
+    /// ```
+    pub(crate) async fn debug_select_thread(&self, thread_id: u32) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to select a thread",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, frame) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&format!("-thread-select {}", thread_id))
+                .await?;
+            let frame = output.result_field("frame").map(format_mi_frame);
+            (!output.is_error(), output.text(), frame)
+        } else {
+            let response = self
+                .send_debugger_command(&format!("thread select {}", thread_id))
+                .await?;
+            let frame = response
+                .lines()
+                .find(|line| line.trim_start().starts_with("frame #"))
+                .map(|line| line.trim().to_string());
+            (!response.contains("error:"), response, frame)
+        };
+
+        if success {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.selected_thread = Some(thread_id);
+                session.selected_frame = 0;
+            }
+        }
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "thread_id": thread_id,
+            "frame": frame
+        }))
+    }
+
+    /// Lists the processes of the session: GDB inferiors or LLDB targets.
+    ///
+    /// There is one until the debugger picks up a forked child, e.g. with `follow_fork`
+    /// set to `"both"`, which keeps the parent and child side by side; switch between
+    /// them with `debug_select_inferior`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_inferiors", "arguments": {}}
+    /// ```
+    async fn debug_inferiors(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if matches!(current_state, DebugState::NotLoaded | DebugState::Running) {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be loaded or stopped to list inferiors",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, inferiors) = self.list_inferiors().await?;
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "inferiors": inferiors.iter().map(InferiorInfo::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Asks the debugger for its inferiors (GDB) or targets (LLDB).
+    async fn list_inferiors(&self) -> Result<(bool, String, Vec<InferiorInfo>)> {
+        match self.debugger_kind().await {
+            Some(DebuggerKind::Gdb) => {
+                // MI does not say which inferior is selected, but `inferior` does
+                let current = self
+                    .send_mi_command(&mi_console_command("inferior"))
+                    .await?
+                    .text();
+                let current = current
+                    .split("Current inferior is ")
+                    .nth(1)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|id| id.parse().ok());
+                let output = self.send_mi_command("-list-thread-groups").await?;
+                let inferiors = output
+                    .result_field("groups")
+                    .and_then(|v| v.as_array())
+                    .map(|groups| {
+                        groups
+                            .iter()
+                            .filter_map(|group| InferiorInfo::from_mi(group, current))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok((!output.is_error(), output.text(), inferiors))
+            }
+            Some(DebuggerKind::LldbMi) => {
+                let output = self
+                    .send_mi_command(&mi_console_command("target list"))
+                    .await?;
+                let text = output.text();
+                let inferiors = text.lines().filter_map(InferiorInfo::parse_lldb).collect();
+                Ok((!output.is_error(), text, inferiors))
+            }
+            _ => {
+                let response = self.send_debugger_command("target list").await?;
+                let inferiors = response
+                    .lines()
+                    .filter_map(InferiorInfo::parse_lldb)
+                    .collect();
+                Ok((!response.contains("error:"), response, inferiors))
+            }
+        }
+    }
+
+    /// Makes inferior `id` the process that execution control and inspection apply to.
+    ///
+    /// `id` is the number `debug_inferiors` reports: GDB's inferior number or LLDB's
+    /// target index. The thread and frame selections start over in the new process.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_select_inferior", "arguments": {"id": 2}}
+    /// ```
+    async fn debug_select_inferior(&self, id: u32) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if matches!(current_state, DebugState::NotLoaded | DebugState::Running) {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be loaded or stopped to select an inferior",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output) = match self.debugger_kind().await {
+            Some(DebuggerKind::Gdb) => {
+                let output = self
+                    .send_mi_command(&mi_console_command(&format!("inferior {}", id)))
+                    .await?;
+                (!output.is_error(), output.text())
+            }
+            Some(DebuggerKind::LldbMi) => {
+                let output = self
+                    .send_mi_command(&mi_console_command(&format!("target select {}", id)))
+                    .await?;
+                (!output.is_error(), output.text())
+            }
+            _ => {
+                let response = self
+                    .send_debugger_command(&format!("target select {}", id))
+                    .await?;
+                (!response.contains("error:"), response)
+            }
+        };
+        if !success {
+            return Ok(json!({
+                "success": false,
+                "error": format!("No inferior {}; see debug_inferiors", id),
+                "error_kind": ErrorKind::InvalidArgument.name(),
+                "output": output.trim()
+            }));
+        }
+
+        let (_, _, inferiors) = self.list_inferiors().await?;
+        let inferior = inferiors.into_iter().find(|inferior| inferior.id == id);
+        let pid = inferior.as_ref().and_then(|inferior| inferior.pid);
+
+        // An inferior without a process has nothing to show
+        let frame = match pid {
+            None => None,
+            Some(_) if self.uses_mi().await => {
+                let info = self.send_mi_command("-stack-info-frame").await?;
+                info.result_field("frame").map(format_mi_frame)
+            }
+            Some(_) => {
+                let response = self.send_debugger_command("frame info").await?;
+                response
+                    .lines()
+                    .find(|line| line.trim_start().starts_with("frame #"))
+                    .map(|line| line.trim().to_string())
+            }
+        };
+
+        let state = {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            session.inferior_pid = pid;
+            session.selected_thread = None;
+            session.selected_frame = 0;
+            session.current_location = frame
+                .as_deref()
+                .and_then(|frame| self.extract_location_from_response(frame));
+            session.state = if pid.is_some() {
+                DebugState::Stopped
+            } else {
+                DebugState::Loaded
+            };
+            format!("{:?}", session.state).to_lowercase()
+        };
+
+        Ok(json!({
+            "success": true,
+            "state": state,
+            "output": output.trim(),
+            "inferior": inferior.as_ref().map(InferiorInfo::to_json),
+            "frame": frame
+        }))
+    }
+
+    /// Why checkpoints cannot be taken or restored now, as a failed tool result.
+    ///
+    /// They need GDB on Linux and a stopped process the debugger can fork.
+    async fn checkpoints_unavailable(&self) -> Option<Value> {
+        let session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_ref() else {
+            return Some(json!({
+                "success": false,
+                "error": "No program loaded. Use debug_run first.",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        };
+        let state = format!("{:?}", session.state).to_lowercase();
+
+        let unsupported = if !cfg!(target_os = "linux") {
+            Some("Checkpoints are only available on Linux")
+        } else if session.kind != DebuggerKind::Gdb {
+            Some("Checkpoints need GDB; start the session with \"debugger\": \"gdb\"")
+        } else if session.core_path.is_some() || session.remote_target.is_some() {
+            Some("Checkpoints need a local process to fork")
+        } else {
+            None
+        };
+        if let Some(error) = unsupported {
+            return Some(json!({
+                "success": false,
+                "error": error,
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": state
+            }));
+        }
+        if session.state != DebugState::Stopped {
+            return Some(json!({
+                "success": false,
+                "error": "Program must be stopped to take or restore a checkpoint",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": state
+            }));
+        }
+        None
+    }
+
+    /// Lists GDB's checkpoints, from `info checkpoints`.
+    async fn list_checkpoints(&self) -> Result<Vec<CheckpointInfo>> {
+        let output = self
+            .send_mi_command(&mi_console_command("info checkpoints"))
+            .await?;
+        Ok(output
+            .text()
+            .lines()
+            .filter_map(CheckpointInfo::parse_gdb)
+            .collect())
+    }
+
+    /// Snapshots the stopped program with GDB's `checkpoint`, which forks a copy of it
+    /// that `debug_restore_checkpoint` can switch to later.
+    ///
+    /// This rewinds cheaply to just before a suspicious call without recording the run
+    /// with rr. Only the process is copied: output already written and changes to files
+    /// stay as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_checkpoint", "arguments": {}}
+    /// ```
+    async fn debug_checkpoint(&self) -> Result<Value> {
+        if let Some(error) = self.checkpoints_unavailable().await {
+            return Ok(error);
+        }
+
+        let output = self
+            .send_mi_command(&mi_console_command("checkpoint"))
+            .await?;
+        let text = output.text();
+        let Some((id, pid)) = CheckpointInfo::parse_gdb_created(&text) else {
+            return Ok(json!({
+                "success": false,
+                "error": format!("GDB could not take a checkpoint: {}", text.trim()),
+                "error_kind": ErrorKind::CommandFailed.name(),
+                "state": "stopped",
+                "output": text.trim()
+            }));
+        };
+
+        let checkpoints = self.list_checkpoints().await?;
+        let location = checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+            .and_then(|checkpoint| checkpoint.location.clone());
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "output": text.trim(),
+            "id": id,
+            "pid": pid,
+            "location": location,
+            "checkpoints": checkpoints.iter().map(CheckpointInfo::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Switches to checkpoint `id` with GDB's `restart`, rewinding the program to where
+    /// the checkpoint was taken.
+    ///
+    /// Breakpoints and watched expressions stay as they are. The checkpoint's process
+    /// becomes the one being debugged, so take another checkpoint to come back to the
+    /// same point again.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_restore_checkpoint", "arguments": {"id": 1}}
+    /// ```
+    async fn debug_restore_checkpoint(&self, id: u32) -> Result<Value> {
+        if let Some(error) = self.checkpoints_unavailable().await {
+            return Ok(error);
+        }
+
+        let output = self
+            .send_mi_command(&mi_console_command(&format!("restart {}", id)))
+            .await?;
+        if output.is_error() {
+            return Ok(json!({
+                "success": false,
+                "error": format!("No checkpoint {}; see debug_checkpoint", id),
+                "error_kind": ErrorKind::InvalidArgument.name(),
+                "state": "stopped",
+                "output": output.text().trim()
+            }));
+        }
+
+        let checkpoints = self.list_checkpoints().await?;
+        let checkpoint = checkpoints.iter().find(|checkpoint| checkpoint.current);
+        let frame = self.send_mi_command("-stack-info-frame").await?;
+        let location = frame.result_field("frame").and_then(mi_frame_location);
+        {
+            let mut session_guard = self.session.lock().await;
+            let session = session_guard
+                .as_mut()
+                .ok_or_else(|| ErrorKind::NoSession.error("No active debugger session"))?;
+            session.state = DebugState::Stopped;
+            session.current_location = location.clone();
+            session.selected_thread = None;
+            session.selected_frame = 0;
+            session.stop_signal = None;
+            session.watchpoint_hit = None;
+            if let Some(pid) = checkpoint.and_then(|checkpoint| checkpoint.pid) {
+                session.inferior_pid = Some(pid);
+            }
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "output": output.text().trim(),
+            "location": location,
+            "checkpoint": checkpoint.map(CheckpointInfo::to_json),
+            "checkpoints": checkpoints.iter().map(CheckpointInfo::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Lists the tokio tasks the program spawns, and can break where one is polled.
+    ///
+    /// Tasks are found by the future types tokio allocates task cells for, so each entry
+    /// is a kind of task, e.g. every connection handler spawned from `serve`, with the
+    /// function that polls it and where that is defined. When the program is stopped,
+    /// `polling_threads` lists the threads currently polling it; tasks waiting to be
+    /// woken are not on any stack. `break_on` names a task's `future` or
+    /// `poll_function` to set a breakpoint that stops the next time it is polled, which
+    /// steps into async code without wading through the executor.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_async_tasks", "arguments": {}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_async_tasks", "arguments": {"break_on": "app::serve::{async_fn#0}"}}
+    /// ```
+    async fn debug_async_tasks(&self, break_on: Option<&str>) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if matches!(current_state, DebugState::NotLoaded | DebugState::Running) {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be loaded or stopped to list tasks",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let cells = self
+            .debug_symbols("tokio::runtime::task::core::Cell<", SymbolKind::Type, 1000)
+            .await?;
+        let mut tasks: Vec<AsyncTask> = Vec::new();
+        for cell in cells["symbols"].as_array().into_iter().flatten() {
+            let task = cell["raw_name"]
+                .as_str()
+                .and_then(AsyncTask::from_cell_type);
+            if let Some(task) = task.filter(|task| !tasks.contains(task)) {
+                tasks.push(task);
+            }
+        }
+        if tasks.is_empty() {
+            return Ok(json!({
+                "success": false,
+                "error": "No tokio tasks found: the program spawns none, or was built without debug info",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let threads = if current_state == DebugState::Stopped {
+            self.thread_backtraces(None, true).await?.2
+        } else {
+            Vec::new()
+        };
+
+        let mut listed = Vec::new();
+        for task in &tasks {
+            // Names hold braces and angle brackets, which GDB and LLDB regexes read differently
+            let pattern = format!(
+                "^{}$",
+                task.poll_function
+                    .replace(|c: char| "^$.|?*+()[]{}\\".contains(c), ".")
+            );
+            let symbols = self
+                .debug_symbols(&pattern, SymbolKind::Function, 1)
+                .await?;
+            let polling_threads: Vec<Option<u32>> = threads
+                .iter()
+                .filter(|(_, frames)| {
+                    frames.iter().any(|frame| {
+                        frame
+                            .function
+                            .as_deref()
+                            .is_some_and(|function| task.is_polled_by(&demangle(function)))
+                    })
+                })
+                .map(|(id, _)| *id)
+                .collect();
+            listed.push(json!({
+                "future": task.future,
+                "poll_function": task.poll_function,
+                "location": symbols["symbols"][0]["location"],
+                "polling_threads": polling_threads
+            }));
+        }
+
+        let breakpoint = match break_on {
+            Some(name) => {
+                let Some(task) = tasks
+                    .iter()
+                    .find(|task| task.future == name || task.poll_function == name)
+                else {
+                    return Ok(json!({
+                        "success": false,
+                        "error": format!("No task future or poll function named {}", name),
+                        "error_kind": ErrorKind::InvalidArgument.name(),
+                        "tasks": listed
+                    }));
+                };
+                Some(self.debug_break(&task.poll_function, None, None).await?)
+            }
+            None => None,
+        };
+
+        Ok(json!({
+            "success": breakpoint.as_ref().is_none_or(|b| b["success"] != false),
+            "tasks": listed,
+            "breakpoint": breakpoint
+        }))
+    }
+
+    /// Selects a frame of the current thread, so `debug_eval` sees that frame's variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Frame number from `debug_backtrace`; 0 is the innermost frame and
+    ///   higher numbers walk out towards the callers
+    ///
+    /// # Examples
+    ///
+    /// Inspecting the caller of the current function:
+    /// ```json
+    /// {"name": "debug_select_frame", "arguments": {"index": 1}}
+    /// ```
+    pub(crate) async fn debug_select_frame(&self, index: u32) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to select a frame",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, frame) = if self.uses_mi().await {
+            let output = self
+                .send_mi_command(&format!("-stack-select-frame {}", index))
+                .await?;
+            if output.is_error() {
+                (false, output.text(), None)
+            } else {
+                let info = self.send_mi_command("-stack-info-frame").await?;
+                let frame = info.result_field("frame").map(FrameInfo::from_mi);
+                (!info.is_error(), info.text(), frame)
+            }
+        } else {
+            let response = self
+                .send_debugger_command(&format!("frame select {}", index))
+                .await?;
+            let frame = response.lines().find_map(FrameInfo::parse_lldb);
+            (!response.contains("error:"), response, frame)
+        };
+
+        if success {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.selected_frame = index;
+            }
+        }
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "frame": frame.as_ref().map(FrameInfo::to_json)
+        }))
+    }
+
+    /// Describes the currently selected frame: function, source location, and PC.
+    async fn debug_frame_info(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to inspect frames",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, frame) = if self.uses_mi().await {
+            let output = self.send_mi_command("-stack-info-frame").await?;
+            let frame = output.result_field("frame").map(FrameInfo::from_mi);
+            (!output.is_error(), output.text(), frame)
+        } else {
+            let response = self.send_debugger_command("frame info").await?;
+            let frame = response.lines().find_map(FrameInfo::parse_lldb);
+            (!response.contains("error:"), response, frame)
+        };
+
+        let thread = {
+            let session_guard = self.session.lock().await;
+            session_guard.as_ref().and_then(|s| s.selected_thread)
+        };
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "thread_id": thread,
+            "frame": frame.as_ref().map(FrameInfo::to_json)
+        }))
+    }
+
+    /// Shows the call stack as a list of structured frames.
+    ///
+    /// Each frame carries `index`, `function`, `module`, `file`, `line`, `pc`, and
+    /// `is_rust_frame`, so the agent can skip runtime and libc frames without
+    /// re-parsing debugger text.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_frames` - Only report the innermost `max_frames` frames
+    /// * `all_threads` - Report the stacks of every thread instead of the selected one
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_backtrace", "arguments": {"max_frames": 10}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_backtrace", "arguments": {"all_threads": true}}
+    /// ```
+    /// Returns all arguments and local variables of the selected frame as structured JSON.
+    ///
+    /// Aggregates (structs, enums, collections) are expanded into `children` up to
+    /// `depth` levels, so one call shows the whole frame instead of one `debug_eval`
+    /// per variable.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_locals", "arguments": {"depth": 2}}
+    /// ```
+    pub(crate) async fn debug_locals(&self, depth: u32) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to list local variables",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, variables) = self.frame_variables(depth).await?;
+
+        Ok(json!({
+            "success": success,
+            "output": output.trim(),
+            "variables": variables.iter().map(Variable::to_json).collect::<Vec<_>>()
+        }))
+    }
+
+    /// Reads the arguments of the selected frame, without expanding them.
+    async fn frame_arguments(&self) -> Result<Vec<Value>> {
+        if self.uses_mi().await {
+            let output = self
+                .send_mi_command("-stack-list-variables --simple-values")
+                .await?;
+            let variables = output
+                .result_field("variables")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            Ok(variables
+                .iter()
+                .filter(|variable| variable.get("arg").is_some())
+                .map(|variable| {
+                    let field = |name: &str| variable.get(name).and_then(|v| v.as_str());
+                    Variable {
+                        name: field("name").unwrap_or_default().to_string(),
+                        type_name: field("type").map(str::to_string),
+                        value: field("value").map(tidy_gdb_value),
+                        children: Vec::new(),
+                    }
+                    .to_json()
+                })
+                .collect())
+        } else {
+            let response = self
+                .send_debugger_command("frame variable --no-locals -T --depth 0")
+                .await?;
+            Ok(Variable::parse_lldb_tree(&response)
+                .iter()
+                .map(Variable::to_json)
+                .collect())
+        }
+    }
+
+    /// Reads the arguments and locals of the selected frame, expanded `depth` levels.
+    async fn frame_variables(&self, depth: u32) -> Result<(bool, String, Vec<Variable>)> {
+        Ok(if self.uses_mi().await {
+            let output = self
+                .send_mi_command("-stack-list-variables --simple-values")
+                .await?;
+            if output.is_error() {
+                (false, output.text(), Vec::new())
+            } else {
+                let names: Vec<String> = output
+                    .result_field("variables")
+                    .and_then(|v| v.as_array())
+                    .map(|vars| {
+                        vars.iter()
+                            .filter_map(|var| var.get("name").and_then(|v| v.as_str()))
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                let mut variables = Vec::new();
+                for name in names {
+                    if let Some(variable) = self.mi_variable_tree(&name, depth, None).await? {
+                        variables.push(variable);
+                    }
+                }
+                (true, output.text(), variables)
+            }
+        } else {
+            let response = self
+                .send_debugger_command(&format!("frame variable -T --depth {}", depth))
+                .await?;
+            let variables = Variable::parse_lldb_tree(&response);
+            (!response.contains("error:"), response, variables)
+        })
+    }
+
+    /// Expands an expression into a nested tree of fields and elements.
+    ///
+    /// Where `debug_eval` returns one string, this returns `{name, type, value, children}`
+    /// nodes `depth` levels deep. Each node keeps at most `max_children` children and is
+    /// marked `truncated` if it had more, and a pointer back to an enclosing node is
+    /// marked `cycle` instead of being expanded again, so linked structures stay small
+    /// enough to read.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_inspect", "arguments": {"expression": "self.nodes", "depth": 4, "max_children": 10}}
+    /// ```
+    pub(crate) async fn debug_inspect(
+        &self,
+        expression: &str,
+        depth: u32,
+        max_children: u32,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to inspect values",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (output, variable) = if self.uses_mi().await {
+            // One extra child shows whether there were more
+            let variable = self
+                .mi_variable_tree(expression, depth, Some(max_children + 1))
+                .await?;
+            (String::new(), variable)
+        } else {
+            self.lldb_value_tree(expression, depth).await?
+        };
+
+        let Some(variable) = variable else {
+            return Ok(json!({
+                "success": false,
+                "expression": expression,
+                "error": format!("Could not evaluate {}", expression),
+                "output": output.trim()
+            }));
+        };
+        Ok(json!({
+            "success": true,
+            "expression": expression,
+            "output": output.trim(),
+            "variable": variable.to_json_limited(max_children as usize)
+        }))
+    }
+
+    /// Evaluates `expression` into a variable tree using MI variable objects, listing
+    /// at most `max_children` children of each node.
+    ///
+    /// The variable object is deleted again once its children have been read.
+    /// Returns `None` if the expression cannot be evaluated in the current frame.
+    async fn mi_variable_tree(
+        &self,
+        expression: &str,
+        depth: u32,
+        max_children: Option<u32>,
+    ) -> Result<Option<Variable>> {
+        let created = self
+            .send_mi_command(&format!("-var-create - * {}", mi_quote(expression)))
+            .await?;
+        if created.is_error() {
+            return Ok(None);
+        }
+
+        let field = |name: &str| {
+            created
+                .result_field(name)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let Some(varobj) = field("name") else {
+            return Ok(None);
+        };
+        let has_children = field("numchild").is_some_and(|n| n != "0");
+
+        let children = if depth > 0 && has_children {
+            self.mi_variable_children(&varobj, depth - 1, max_children)
+                .await?
+        } else {
+            Vec::new()
+        };
+        self.send_mi_command(&format!("-var-delete {}", varobj))
+            .await?;
+
+        Ok(Some(Variable {
+            name: expression.to_string(),
+            type_name: field("type"),
+            value: field("value"),
+            children,
+        }))
+    }
+
+    /// Lists the children of an MI variable object, recursing `depth` more levels.
+    fn mi_variable_children<'a>(
+        &'a self,
+        varobj: &'a str,
+        depth: u32,
+        max_children: Option<u32>,
+    ) -> futures::future::BoxFuture<'a, Result<Vec<Variable>>> {
+        Box::pin(async move {
+            let range = max_children.map_or(String::new(), |max| format!(" 0 {}", max));
+            let output = self
+                .send_mi_command(&format!(
+                    "-var-list-children --all-values {}{}",
+                    varobj, range
+                ))
+                .await?;
+            let children: Vec<Value> = output
+                .result_field("children")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+
+            let mut variables = Vec::new();
+            for child in children {
+                let field =
+                    |name: &str| child.get(name).and_then(|v| v.as_str()).map(str::to_string);
+                let grandchildren = match field("name") {
+                    Some(child_varobj)
+                        if depth > 0 && field("numchild").is_some_and(|n| n != "0") =>
+                    {
+                        self.mi_variable_children(&child_varobj, depth - 1, max_children)
+                            .await?
+                    }
+                    _ => Vec::new(),
+                };
+                variables.push(Variable {
+                    name: field("exp").unwrap_or_default(),
+                    type_name: field("type"),
+                    value: field("value"),
+                    children: grandchildren,
+                });
+            }
+            Ok(variables)
+        })
+    }
+
+    pub(crate) async fn debug_backtrace(
+        &self,
+        max_frames: Option<u32>,
+        all_threads: bool,
+    ) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to show backtrace",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, threads) = self.thread_backtraces(max_frames, all_threads).await?;
+
+        let frames_json =
+            |frames: &[FrameInfo]| frames.iter().map(FrameInfo::to_json).collect::<Vec<_>>();
+
+        let output = demangle_text(output.trim());
+        if all_threads {
+            let threads: Vec<Value> = threads
+                .iter()
+                .map(|(id, frames)| json!({"thread_id": id, "frames": frames_json(frames)}))
+                .collect();
+            Ok(json!({
+                "success": success,
+                "output": output,
+                "threads": threads
+            }))
+        } else {
+            let frames = threads
+                .first()
+                .map(|(_, frames)| frames_json(frames))
+                .unwrap_or_default();
+            Ok(json!({
+                "success": success,
+                "output": output,
+                "frames": frames
+            }))
+        }
+    }
+
+    /// Shows the logical async backtrace of the task the selected thread is polling, or
+    /// of every thread that polls one: the chain of `async fn`s awaiting each other,
+    /// innermost first, without the executor frames the physical stack is mostly made of.
+    ///
+    /// See [`async_backtrace`] for how the chain is read from the stack. A task waiting
+    /// to be woken is not on any stack; break where it is polled with
+    /// `debug_async_tasks` to catch it running.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_backtrace", "arguments": {"async": true}}
+    /// ```
+    async fn debug_async_backtrace(&self, all_threads: bool) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be stopped to show backtrace",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, threads) = self.thread_backtraces(None, all_threads).await?;
+        if !success {
+            return Ok(json!({
+                "success": false,
+                "output": demangle_text(output.trim())
+            }));
+        }
+
+        let tasks: Vec<Value> = threads
+            .iter()
+            .filter_map(|(id, frames)| {
+                let (logical, root) = async_backtrace(frames)?;
+                Some(json!({
+                    "thread_id": id,
+                    "task": frames[root].function.as_deref().map(demangle),
+                    "frames": logical,
+                    "executor_frames": frames.len() - root - 1
+                }))
+            })
+            .collect();
+
+        if all_threads {
+            return Ok(json!({
+                "success": true,
+                "threads": tasks
+            }));
+        }
+        match tasks.into_iter().next() {
+            Some(mut task) => {
+                task["success"] = json!(true);
+                Ok(task)
+            }
+            None => Ok(json!({
+                "success": false,
+                "error": "The selected thread is not polling an async task; try all_threads, or debug_async_tasks with break_on",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": "stopped"
+            })),
+        }
+    }
+
+    /// Diagnoses a hang: pauses the program if it is running, finds the threads blocked
+    /// in a lock, wait, join, or channel receive, and reports who waits on what.
+    ///
+    /// A blocked thread's `lock` is the address of the `Mutex` or `RwLock` it is
+    /// locking (`std` or `lock_api`/parking_lot). Holders are found from the lock guards
+    /// alive in each thread's own frames, so a lock held without a guard in a local
+    /// variable, e.g. one leaked with `mem::forget`, has none. When threads wait for
+    /// locks held by each other, `deadlock` is set and `cycle` lists them. The thread
+    /// and frame selections are restored afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_analyze_deadlock", "arguments": {}}
+    /// ```
+    async fn debug_analyze_deadlock(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+        let paused = current_state == DebugState::Running;
+        if paused {
+            self.debug_pause().await?;
+        }
+        let current_state = self.current_state().await;
+        if current_state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be running or stopped to analyze deadlocks",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let (success, output, threads) = self.thread_backtraces(None, true).await?;
+        if !success {
+            return Ok(json!({
+                "success": false,
+                "paused": paused,
+                "output": demangle_text(output.trim())
+            }));
+        }
+        let (selected_thread, selected_frame) = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .map_or((None, 0), |s| (s.selected_thread, s.selected_frame))
+        };
+
+        let mut blocked = Vec::new();
+        let mut holders: std::collections::BTreeMap<String, Vec<u32>> = Default::default();
+        for (id, frames) in &threads {
+            let Some(id) = *id else { continue };
+            self.debug_select_thread(id).await?;
+
+            // Guards in the thread's own frames tell which locks it holds
+            for frame in frames.iter().filter(|frame| frame.is_user_frame()) {
+                self.debug_select_frame(frame.index).await?;
+                let (_, _, variables) = self.frame_variables(1).await?;
+                for lock in variables.iter().flat_map(Variable::held_locks) {
+                    let threads = holders.entry(lock).or_default();
+                    if !threads.contains(&id) {
+                        threads.push(id);
+                    }
+                }
+            }
+
+            let Some((index, waits_for)) = blocking_call(frames) else {
+                continue;
+            };
+            self.debug_select_frame(frames[index].index).await?;
+            let (_, _, variables) = self.frame_variables(0).await?;
+            let lock = variables
+                .iter()
+                .find(|variable| variable.name == "self")
+                .and_then(Variable::address)
+                .map(str::to_string);
+            let caller = frames[index..].iter().find(|frame| frame.is_user_frame());
+            blocked.push((id, waits_for, lock, frames[index].clone(), caller.cloned()));
+        }
+
+        if let Some(thread) = selected_thread {
+            self.debug_select_thread(thread).await?;
+            self.debug_select_frame(selected_frame).await?;
+        }
+
+        // Each blocked thread waits for the holders of its lock, other than itself
+        let waits_on = |lock: &Option<String>, id: u32| -> Vec<u32> {
+            lock.as_ref()
+                .and_then(|lock| holders.get(lock))
+                .map(|threads| threads.iter().copied().filter(|&t| t != id).collect())
+                .unwrap_or_default()
+        };
+        let mut cycle = Vec::new();
+        for (start, ..) in &blocked {
+            let mut path = vec![*start];
+            let mut current = *start;
+            while let Some((_, _, lock, ..)) = blocked.iter().find(|(id, ..)| *id == current) {
+                let Some(&next) = waits_on(lock, current).first() else {
+                    break;
+                };
+                if let Some(position) = path.iter().position(|&id| id == next) {
+                    cycle = path.split_off(position);
+                    break;
+                }
+                path.push(next);
+                current = next;
+            }
+            if !cycle.is_empty() {
+                break;
+            }
+        }
+
+        let mut summary: Vec<String> = blocked
+            .iter()
+            .map(|(id, waits_for, lock, _, caller)| {
+                let mut line = format!("Thread {} waits for {}", id, waits_for.replace('_', " "));
+                if let Some(lock) = lock {
+                    line.push_str(&format!(" {}", lock));
+                }
+                let holders = waits_on(lock, *id);
+                if !holders.is_empty() {
+                    let holders: Vec<String> = holders.iter().map(u32::to_string).collect();
+                    line.push_str(&format!(" held by thread {}", holders.join(", ")));
+                }
+                if let Some(function) = caller.as_ref().and_then(|c| c.function.as_deref()) {
+                    line.push_str(&format!(" in {}", demangle(function)));
+                }
+                line
+            })
+            .collect();
+        if blocked.is_empty() {
+            summary.push("No thread is blocked in a lock, wait, join, or receive".to_string());
+        }
+        if !cycle.is_empty() {
+            let threads: Vec<String> = cycle.iter().map(u32::to_string).collect();
+            summary.push(format!("Deadlock between threads {}", threads.join(", ")));
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": "stopped",
+            "paused": paused,
+            "threads": threads.len(),
+            "blocked": blocked
+                .iter()
+                .map(|(id, waits_for, lock, call, caller)| json!({
+                    "thread_id": id,
+                    "waits_for": waits_for,
+                    "lock": lock,
+                    "held_by": waits_on(lock, *id),
+                    "call": call.to_json(),
+                    "caller": caller.as_ref().map(FrameInfo::to_json)
+                }))
+                .collect::<Vec<_>>(),
+            "held_locks": holders,
+            "deadlock": !cycle.is_empty(),
+            "cycle": cycle,
+            "summary": summary.join("; ")
+        }))
+    }
+
+    /// Gathers what a crash needs looking at into one report: the fatal signal and fault
+    /// address, the crashing thread's backtrace, the instructions around the program
+    /// counter, the general-purpose registers, and the locals of the innermost frame in
+    /// the program's own code.
+    ///
+    /// The program must be `crashed`, or stopped on a signal as LLDB reports crashes,
+    /// with its process still there to inspect.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_triage_crash", "arguments": {}}
+    /// ```
+    async fn debug_triage_crash(&self) -> Result<Value> {
+        let (state, signalled, terminated, thread_id, selected_frame) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            (
+                session.state.clone(),
+                session.stop_signal.is_some(),
+                session.exit_status.is_some(),
+                session.selected_thread,
+                session.selected_frame,
+            )
+        };
+        let state_name = format!("{:?}", state).to_lowercase();
+        if terminated {
+            return Ok(json!({
+                "success": false,
+                "error": "The program has terminated, so there is no process left to inspect",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": state_name
+            }));
+        }
+        if !(state == DebugState::Crashed || (state == DebugState::Stopped && signalled)) {
+            return Ok(json!({
+                "success": false,
+                "error": "The program has not crashed; it must be stopped on a fatal signal",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": state_name
+            }));
+        }
+
+        let signal = self.stop_signal().await?;
+        let (_, _, threads) = self.thread_backtraces(Some(TRIAGE_FRAMES), false).await?;
+        let (backtrace_thread, frames) = threads.into_iter().next().unwrap_or_default();
+
+        self.select_frame_quietly(0).await?;
+        let disassembly = self.disassemble_around_pc().await?;
+        let registers = if self.uses_mi().await {
+            self.send_mi_command(&mi_console_command("info registers"))
+                .await?
+                .text()
+        } else {
+            self.send_debugger_command("register read").await?
+        };
+        let registers: serde_json::Map<String, Value> = parse_registers(&registers)
+            .into_iter()
+            .map(|(name, value)| (name, json!(value)))
+            .collect();
+
+        let user_frame = frames.iter().find(|frame| frame.is_user_frame());
+        let locals = match user_frame {
+            Some(frame) => {
+                self.select_frame_quietly(frame.index).await?;
+                let (_, _, variables) = self.frame_variables(1).await?;
+                variables
+            }
+            None => Vec::new(),
+        };
+        self.select_frame_quietly(selected_frame).await?;
+
+        let fault_address = signal
+            .as_ref()
+            .and_then(|signal| signal["fault_address"].as_str())
+            .map(str::to_string);
+        let mut summary = signal
+            .as_ref()
+            .and_then(|signal| signal["name"].as_str())
+            .unwrap_or("Crash")
+            .to_string();
+        if let Some(address) = &fault_address {
+            summary.push_str(&format!(" accessing {}", address));
+        }
+        if let Some(frame) = frames.first() {
+            let function = frame.function.as_deref().map(demangle);
+            summary.push_str(&format!(" in {}", function.as_deref().unwrap_or("??")));
+        }
+        if let Some(frame) = user_frame {
+            if frame.index > 0 {
+                let function = frame.function.as_deref().map(demangle);
+                summary.push_str(&format!(
+                    ", called from {}",
+                    function.as_deref().unwrap_or("??")
+                ));
+            }
+            if let (Some(file), Some(line)) = (&frame.file, frame.line) {
+                summary.push_str(&format!(" at {}:{}", file, line));
+            }
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": state_name,
+            "signal": signal,
+            "fault_address": fault_address,
+            "thread_id": thread_id.or(backtrace_thread),
+            "backtrace": frames.iter().map(FrameInfo::to_json).collect::<Vec<_>>(),
+            "user_frame": user_frame.map(FrameInfo::to_json),
+            "locals": locals.iter().map(Variable::to_json).collect::<Vec<_>>(),
+            "disassembly": disassembly.iter().map(Instruction::to_json).collect::<Vec<_>>(),
+            "registers": registers,
+            "summary": summary
+        }))
+    }
+
+    /// Explains the panic the program is stopped in: the message, where it was raised,
+    /// and the backtrace of the program's own code with the panic machinery left out.
+    ///
+    /// The message and location come from what the panic hook printed to stderr. When
+    /// the program stops on `core::panicking::panic_fmt`, before the hook has run, the
+    /// message is pieced together from the format string's literal parts, marked with
+    /// `message_complete: false`, and the location is that of the innermost user frame.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_panic_info", "arguments": {}}
+    /// ```
+    async fn debug_panic_info(&self) -> Result<Value> {
+        let (state, stderr, selected_frame) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            let stderr = session
+                .program_output
+                .as_ref()
+                .map(|output| output.read_all().1);
+            (session.state.clone(), stderr, session.selected_frame)
+        };
+        let state_name = format!("{:?}", state).to_lowercase();
+        if !matches!(state, DebugState::Stopped | DebugState::Crashed) {
+            return Ok(json!({
+                "success": false,
+                "error": "The program must be stopped in a panic to inspect it",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": state_name
+            }));
+        }
+
+        let (_, _, threads) = self.thread_backtraces(None, false).await?;
+        let (thread_id, frames) = threads.into_iter().next().unwrap_or_default();
+        let Some(backtrace) = panic_backtrace(&frames) else {
+            return Ok(json!({
+                "success": false,
+                "error": "The selected thread is not panicking; stop on a panic breakpoint first",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": state_name
+            }));
+        };
+        let panic_frames = &frames[..backtrace[0].index as usize];
+
+        // The hook prints the message before calling rust_panic, or abort for
+        // `panic = "abort"` builds, so output from an earlier panic is not mistaken
+        // for this one's
+        let hook_ran = panic_frames.iter().any(|frame| {
+            let function = frame.function.as_deref().map(demangle).unwrap_or_default();
+            let function = function.trim_start_matches("__rustc::");
+            function == "rust_panic" || function.ends_with("abort")
+        });
+        let printed = stderr
+            .filter(|_| hook_ran)
+            .and_then(|stderr| PanicMessage::parse_stderr(&stderr));
+
+        let user_frame = &backtrace[0];
+        let (message, message_complete, location, thread) = match printed {
+            Some(panic) => (
+                Some(panic.message),
+                true,
+                json!({"file": panic.file, "line": panic.line, "column": panic.column}),
+                panic.thread,
+            ),
+            None => {
+                let pieces = match panic_frames.iter().find(|frame| {
+                    frame.function.as_deref().map(demangle).as_deref()
+                        == Some("core::panicking::panic_fmt")
+                }) {
+                    Some(frame) => {
+                        self.select_frame_quietly(frame.index).await?;
+                        let (_, _, variables) = self.frame_variables(4).await?;
+                        self.select_frame_quietly(selected_frame).await?;
+                        format_pieces(&variables)
+                    }
+                    None => Vec::new(),
+                };
+                (
+                    (!pieces.is_empty()).then(|| pieces.concat()),
+                    false,
+                    json!({"file": user_frame.file, "line": user_frame.line, "column": null}),
+                    None,
+                )
+            }
+        };
+
+        let function = user_frame.function.as_deref().map(demangle);
+        let mut summary = format!("Panicked in {}", function.as_deref().unwrap_or("??"));
+        if let (Some(file), Some(line)) = (location["file"].as_str(), location["line"].as_u64()) {
+            summary.push_str(&format!(" at {}:{}", file, line));
+        }
+        if let Some(message) = &message {
+            summary.push_str(&format!(": {}", message));
+        }
+
+        Ok(json!({
+            "success": true,
+            "state": state_name,
+            "message": message,
+            "message_complete": message_complete,
+            "location": location,
+            "thread": thread,
+            "thread_id": thread_id,
+            "backtrace": backtrace.iter().map(FrameInfo::to_json).collect::<Vec<_>>(),
+            "panic_frames": panic_frames.len(),
+            "summary": summary
+        }))
+    }
+
+    /// Tracks the program's heap allocations and reports the memory still allocated,
+    /// grouped by the call stack that allocated it, to chase leaks and unbounded growth.
+    ///
+    /// The first call turns tracking on: an allocator wrapper (`preload/heap_tracker.rs`)
+    /// is built with `rustc` and preloaded into the program, and logs each allocation and
+    /// free. Tracking starts when `debug_continue` launches the program, so a program
+    /// already running must be relaunched with `debug_restart`. Later calls add up the
+    /// log and return the `top` call stacks holding the most bytes, symbolized while the
+    /// program is stopped. Linux only, for programs started by `debug_run`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_heap_profile", "arguments": {"top": 5}}
+    /// ```
+    async fn debug_heap_profile(&self, top: usize) -> Result<Value> {
+        let (state, log_path, pid, launched) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            let state = format!("{:?}", session.state).to_lowercase();
+            let unsupported = if !cfg!(target_os = "linux") {
+                Some("Heap profiling is only available on Linux")
+            } else if session.program_output.is_none() || session.remote_target.is_some() {
+                Some("Heap profiling needs a program launched by debug_run")
+            } else {
+                None
+            };
+            if let Some(error) = unsupported {
+                return Ok(json!({
+                    "success": false,
+                    "error": error,
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": state
+                }));
+            }
+            (
+                session.state.clone(),
+                session.launch.heap_log().map(str::to_string),
+                session.inferior_pid,
+                session.state != DebugState::Loaded,
+            )
+        };
+        let state_name = format!("{:?}", state).to_lowercase();
+
+        let Some(log_path) = log_path else {
+            let library = self.heap_tracker_library().await?;
+            static NEXT_LOG: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+            let log_path = std::env::temp_dir().join(format!(
+                "ferroscope-{}-heap-{}.log",
+                std::process::id(),
+                NEXT_LOG.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            ));
+            std::fs::File::create(&log_path)?;
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                let env = &mut session.launch.env;
+                match env.iter_mut().find(|(key, _)| key == "LD_PRELOAD") {
+                    Some((_, preload)) => *preload = format!("{}:{}", library, preload),
+                    None => env.push(("LD_PRELOAD".to_string(), library)),
+                }
+                env.push((
+                    HEAP_LOG_VARIABLE.to_string(),
+                    log_path.to_string_lossy().to_string(),
+                ));
+            }
+            return Ok(json!({
+                "success": true,
+                "state": state_name,
+                "tracking": true,
+                "message": if launched {
+                    "Allocation tracking is on; relaunch the program with debug_restart to track it from the start"
+                } else {
+                    "Allocation tracking is on and starts when debug_continue launches the program"
+                }
+            }));
+        };
+
+        let log = std::fs::read_to_string(&log_path).unwrap_or_default();
+        let profile = HeapProfile::parse(&log, pid);
+        let live_bytes: u64 = profile.sites.iter().map(|site| site.bytes).sum();
+        let live_allocations: u64 = profile.sites.iter().map(|site| site.count).sum();
+
+        // Return addresses point after the call; looking up the byte before finds its line
+        let symbolize = state == DebugState::Stopped;
+        let uses_mi = self.uses_mi().await;
+        let mut symbols: std::collections::HashMap<u64, FrameInfo> =
+            std::collections::HashMap::new();
+        let mut sites = Vec::new();
+        for site in profile.sites.iter().take(top) {
+            let mut frames = Vec::new();
+            for (index, &address) in site.stack.iter().take(HEAP_PROFILE_FRAMES).enumerate() {
+                let pc = format!("{:#x}", address);
+                let index = index as u32;
+                let frame = match symbols.get(&address) {
+                    Some(frame) => FrameInfo {
+                        index,
+                        ..frame.clone()
+                    },
+                    None if symbolize => {
+                        let lookup = address.saturating_sub(1);
+                        let frame = if uses_mi {
+                            let output = self
+                                .send_mi_command(&mi_console_command(&format!(
+                                    "info line *{:#x}",
+                                    lookup
+                                )))
+                                .await?
+                                .text();
+                            FrameInfo::from_gdb_info_line(index, &pc, &output)
+                        } else {
+                            let output = self
+                                .send_debugger_command(&format!("image lookup -a {:#x}", lookup))
+                                .await?;
+                            FrameInfo::from_lldb_lookup(index, &pc, &output)
+                        };
+                        symbols.insert(address, frame.clone());
+                        frame
+                    }
+                    None => FrameInfo {
+                        index,
+                        function: None,
+                        module: None,
+                        file: None,
+                        line: None,
+                        pc: Some(pc),
+                    },
+                };
+                frames.push(frame);
+            }
+            let user_frame = frames.iter().find(|frame| frame.is_user_frame());
+            sites.push(json!({
+                "bytes": site.bytes,
+                "count": site.count,
+                "user_frame": user_frame.map(FrameInfo::to_json),
+                "backtrace": frames.iter().map(FrameInfo::to_json).collect::<Vec<_>>()
+            }));
+        }
+
+        let mut summary = format!(
+            "{} bytes in {} allocations still live ({} made, {} freed)",
+            live_bytes, live_allocations, profile.allocations, profile.frees
+        );
+        if let Some(site) = sites.first() {
+            let frame = &site["user_frame"];
+            if let Some(function) = frame["function"].as_str() {
+                summary.push_str(&format!(
+                    "; the most, {} bytes, from {}",
+                    site["bytes"], function
+                ));
+                if let (Some(file), Some(line)) = (frame["file"].as_str(), frame["line"].as_u64()) {
+                    summary.push_str(&format!(" at {}:{}", file, line));
+                }
+            }
+        }
+        Ok(json!({
+            "success": true,
+            "state": state_name,
+            "tracking": true,
+            "allocations": profile.allocations,
+            "frees": profile.frees,
+            "live_bytes": live_bytes,
+            "live_allocations": live_allocations,
+            "site_count": profile.sites.len(),
+            "sites": sites,
+            "symbolized": symbolize,
+            "summary": summary
+        }))
+    }
+
+    /// Samples where the program spends its CPU time for `duration_ms` and reports the
+    /// hottest functions and the sampled call stacks.
+    ///
+    /// The process is sampled with `perf record -g` on Linux or `sample` on macOS. A
+    /// stopped program is continued while it is sampled and paused again afterwards,
+    /// unless it stops at a breakpoint first. `folded` holds the stacks in the format
+    /// flame graph tools read, e.g. `main;app::run;app::hot 42`, most samples first.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_profile", "arguments": {"duration_ms": 2000}}
+    /// ```
+    async fn debug_profile(&self, duration_ms: u64) -> Result<Value> {
+        let (state, pid) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            if session.core_path.is_some() || session.remote_target.is_some() {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Profiling needs a process running on this machine",
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": format!("{:?}", session.state).to_lowercase()
+                }));
+            }
+            (session.state.clone(), session.inferior_pid)
+        };
+        let Some(pid) = pid.filter(|_| matches!(state, DebugState::Running | DebugState::Stopped))
+        else {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be running or stopped to profile it",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", state).to_lowercase()
+            }));
+        };
+
+        let resumed = state == DebugState::Stopped;
+        if resumed {
+            let result = self.debug_continue(None, false).await?;
+            if result["success"] != true {
+                return Ok(result);
+            }
+        }
+        self.report_progress(None, None, &format!("Sampling process {}", pid));
+        let sampled = self
+            .sample_process(pid, std::time::Duration::from_millis(duration_ms))
+            .await;
+        if resumed && self.current_state().await == DebugState::Running {
+            self.debug_pause().await?;
+        }
+        let state = format!("{:?}", self.current_state().await).to_lowercase();
+        let (sampler, profile) = match sampled {
+            Ok(sampled) => sampled,
+            Err(e) => {
+                return Ok(json!({
+                    "success": false,
+                    "error": e.to_string(),
+                    "error_kind": ErrorKind::of(&e).name(),
+                    "state": state
+                }))
+            }
+        };
+
+        let samples = profile.samples();
+        let percent = |count: u64| (count * 1000 / samples.max(1)) as f64 / 10.0;
+        let hottest: Vec<Value> = profile
+            .hottest()
+            .into_iter()
+            .take(PROFILE_HOT_FUNCTIONS)
+            .map(|hot| {
+                json!({
+                    "function": hot.function,
+                    "self_samples": hot.self_samples,
+                    "self_percent": percent(hot.self_samples),
+                    "total_samples": hot.total_samples,
+                    "total_percent": percent(hot.total_samples)
+                })
+            })
+            .collect();
+        let folded = profile.folded();
+        let summary = match hottest.first() {
+            Some(hot) => format!(
+                "{} samples in {} ms; most time in {} ({}% of samples)",
+                samples,
+                duration_ms,
+                hot["function"].as_str().unwrap_or(""),
+                hot["self_percent"]
+            ),
+            None => format!(
+                "No samples in {} ms: the program did not use the CPU while it was sampled",
+                duration_ms
+            ),
+        };
+        Ok(json!({
+            "success": true,
+            "state": state,
+            "sampler": sampler,
+            "duration_ms": duration_ms,
+            "resumed": resumed,
+            "samples": samples,
+            "hottest": hottest,
+            "stack_count": folded.len(),
+            "folded": folded.into_iter().take(PROFILE_FOLDED_STACKS).collect::<Vec<_>>(),
+            "summary": summary
+        }))
+    }
+
+    /// Samples the call stacks of process `pid` for `duration` with the platform's
+    /// profiler, returning the profiler's name and what it recorded.
+    async fn sample_process(
+        &self,
+        pid: u32,
+        duration: std::time::Duration,
+    ) -> Result<(&'static str, CpuProfile)> {
+        let missing = |sampler: &str, e: std::io::Error| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ErrorKind::Unsupported.error(format!("{} is not installed", sampler))
+            } else {
+                e.into()
+            }
+        };
+        let failed = |sampler: &str, output: &std::process::Output| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            ErrorKind::CommandFailed.error(format!("{} failed: {}", sampler, stderr.trim()))
+        };
+        let data =
+            std::env::temp_dir().join(format!("ferroscope-{}-profile-{}", std::process::id(), pid));
+
+        if cfg!(target_os = "macos") {
+            let seconds = duration.as_millis().div_ceil(1000).max(1).to_string();
+            let output = tokio::process::Command::new("sample")
+                .args([&pid.to_string(), &seconds, "1", "-mayDie", "-file"])
+                .arg(&data)
+                .kill_on_drop(true)
+                .output()
+                .await
+                .map_err(|e| missing("sample", e))?;
+            let report = std::fs::read_to_string(&data).unwrap_or_default();
+            let _ = std::fs::remove_file(&data);
+            if !output.status.success() && report.is_empty() {
+                return Err(failed("sample", &output));
+            }
+            return Ok(("sample", CpuProfile::parse_sample(&report)));
+        }
+        if !cfg!(target_os = "linux") {
+            return Err(
+                ErrorKind::Unsupported.error("Profiling is only available on Linux and macOS")
+            );
+        }
+
+        let output = tokio::process::Command::new("perf")
+            .args(["record", "-F", "997", "-g", "-p", &pid.to_string(), "-o"])
+            .arg(&data)
+            .args(["--", "sleep", &format!("{:.3}", duration.as_secs_f64())])
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| missing("perf", e))?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&data);
+            return Err(failed("perf record", &output));
+        }
+        let script = tokio::process::Command::new("perf")
+            .args(["script", "-i"])
+            .arg(&data)
+            .kill_on_drop(true)
+            .output()
+            .await;
+        let _ = std::fs::remove_file(&data);
+        let script = script.map_err(|e| missing("perf", e))?;
+        if !script.status.success() {
+            return Err(failed("perf script", &script));
+        }
+        Ok((
+            "perf",
+            CpuProfile::parse_perf_script(&String::from_utf8_lossy(&script.stdout)),
+        ))
+    }
+
+    /// Traces the system calls the program makes, with their arguments and results, to
+    /// debug file and network misbehavior without breakpoints.
+    ///
+    /// The program runs while traced, for `duration` or until it stops at a breakpoint,
+    /// on a signal, or exits, and is paused again afterwards if still running. The debugger is already the
+    /// program's tracer, so on Linux GDB catches each call itself (`catch syscall`);
+    /// on macOS `dtruss` traces it, which needs root. `syscalls` limits the trace to the
+    /// named calls.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_syscalls", "arguments": {"until_stop": true, "syscalls": ["openat", "connect"]}}
+    /// ```
+    async fn debug_syscalls(
+        &self,
+        duration: std::time::Duration,
+        syscalls: &[String],
+    ) -> Result<Value> {
+        let (pid, uses_mi) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No program loaded. Use debug_run first.",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            let state = format!("{:?}", session.state).to_lowercase();
+            let unsupported = if session.core_path.is_some() || session.remote_target.is_some() {
+                Some("Syscall tracing needs a process running on this machine")
+            } else if !cfg!(target_os = "macos") && !session.kind.uses_mi() {
+                Some("Syscall tracing on Linux needs GDB; start the session with \"debugger\": \"gdb\"")
+            } else {
+                None
+            };
+            if let Some(error) = unsupported {
+                return Ok(json!({
+                    "success": false,
+                    "error": error,
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": state
+                }));
+            }
+            (session.inferior_pid, session.kind.uses_mi())
+        };
+        if self.current_state().await == DebugState::Running {
+            self.debug_pause().await?;
+        }
+        let state = self.current_state().await;
+        if state != DebugState::Stopped {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be running or stopped to trace its syscalls",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", state).to_lowercase()
+            }));
+        };
+
+        let traced = if cfg!(target_os = "macos") {
+            match pid {
+                Some(pid) => self.trace_syscalls_dtruss(pid, duration, syscalls).await,
+                None => Err(ErrorKind::Unsupported.error("The program's process ID is unknown")),
+            }
+        } else if uses_mi {
+            self.trace_syscalls_gdb(duration, syscalls).await
+        } else {
+            Err(ErrorKind::Unsupported.error("Syscall tracing is not available here"))
+        };
+        if self.current_state().await == DebugState::Running {
+            self.debug_pause().await?;
+        }
+        let state = format!("{:?}", self.current_state().await).to_lowercase();
+        let (tracer, mut calls, stop_reason) = match traced {
+            Ok(traced) => traced,
+            Err(e) => {
+                return Ok(json!({
+                    "success": false,
+                    "error": e.to_string(),
+                    "error_kind": ErrorKind::of(&e).name(),
+                    "state": state
+                }))
+            }
+        };
+
+        let truncated = calls.len() > SYSCALL_TRACE_LIMIT;
+        calls.truncate(SYSCALL_TRACE_LIMIT);
+        let failed = calls.iter().filter(|call| call.errno.is_some()).count();
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for call in &calls {
+            match counts.iter_mut().find(|(name, _)| *name == call.name) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((&call.name, 1)),
+            }
+        }
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let mut summary = format!("{} syscalls, {} failed", calls.len(), failed);
+        if !counts.is_empty() {
+            let most: Vec<String> = counts
+                .iter()
+                .take(3)
+                .map(|(name, count)| format!("{} ({})", name, count))
+                .collect();
+            summary.push_str(&format!("; most frequent: {}", most.join(", ")));
+        }
+        if let Some(reason) = &stop_reason {
+            summary.push_str(&format!("; tracing ended on {}", reason));
+        }
+        Ok(json!({
+            "success": true,
+            "state": state,
+            "tracer": tracer,
+            "count": calls.len(),
+            "failed": failed,
+            "truncated": truncated,
+            "stop_reason": stop_reason,
+            "syscalls": calls.iter().map(Syscall::to_json).collect::<Vec<_>>(),
+            "summary": summary
+        }))
+    }
+
+    /// Traces syscalls with a GDB catchpoint, continuing past each entry and return until
+    /// `duration` has passed or the program stops for another reason.
+    async fn trace_syscalls_gdb(
+        &self,
+        duration: std::time::Duration,
+        syscalls: &[String],
+    ) -> Result<(&'static str, Vec<Syscall>, Option<String>)> {
+        let output = self
+            .send_mi_command(&mi_console_command(&format!(
+                "catch syscall {}",
+                syscalls.join(" ")
+            )))
+            .await?;
+        if output.is_error() {
+            return Err(ErrorKind::InvalidArgument.error(format!(
+                "Cannot catch these syscalls: {}",
+                output.error_message().unwrap_or("").trim()
+            )));
+        }
+        let text = output.text();
+        let catchpoint = text
+            .split("Catchpoint ")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .map(str::to_string);
+
+        let (arguments, returned) = if cfg!(target_arch = "aarch64") {
+            ("x0 x1 x2 x3 x4 x5", "x0")
+        } else {
+            ("rdi rsi rdx r10 r8 r9", "rax")
+        };
+        let mut calls: Vec<Syscall> = Vec::new();
+        let mut stop_reason = None;
+        let start = std::time::Instant::now();
+        while calls.len() <= SYSCALL_TRACE_LIMIT {
+            let remaining = duration.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            let output = COMMAND_TIMEOUT
+                .scope(remaining, self.send_mi_command("-exec-continue"))
+                .await?;
+            let stop = output.records.iter().rev().find_map(|record| match record {
+                MiRecord::Exec { class, results } if class == "stopped" => Some(results),
+                _ => None,
+            });
+            let Some(stop) = stop else {
+                // Still running: no syscall, nor any other stop, before the time ran out
+                break;
+            };
+            let field = |name: &str| stop.get(name).and_then(|v| v.as_str());
+            let name = field("syscall-name").unwrap_or("").to_string();
+            let thread = field("thread-id").and_then(|id| id.parse().ok());
+            match field("reason").unwrap_or("") {
+                "syscall-entry" => {
+                    let registers = self
+                        .send_mi_command(&mi_console_command(&format!(
+                            "info registers {}",
+                            arguments
+                        )))
+                        .await?
+                        .text();
+                    let args: Vec<String> = parse_registers(&registers)
+                        .into_iter()
+                        .map(|(_, value)| value)
+                        .collect();
+                    let path_argument = SYSCALL_PATH_ARGUMENTS
+                        .iter()
+                        .find(|(syscall, _)| *syscall == name)
+                        .and_then(|(_, index)| args.get(*index));
+                    let path = match path_argument {
+                        Some(address) => {
+                            let text = self
+                                .send_mi_command(&mi_console_command(&format!("x/s {}", address)))
+                                .await?
+                                .text();
+                            text.split_once('"')
+                                .and_then(|(_, rest)| rest.rsplit_once('"'))
+                                .map(|(path, _)| path.to_string())
+                        }
+                        None => None,
+                    };
+                    calls.push(Syscall {
+                        name,
+                        args,
+                        path,
+                        result: None,
+                        errno: None,
+                        thread,
+                    });
+                }
+                "syscall-return" => {
+                    let registers = self
+                        .send_mi_command(&mi_console_command(&format!(
+                            "info registers {}",
+                            returned
+                        )))
+                        .await?
+                        .text();
+                    let value = parse_registers(&registers)
+                        .first()
+                        .and_then(|(_, value)| parse_integer(value))
+                        .map(|value| value as i64);
+                    let pending = calls.iter_mut().rev().find(|call| {
+                        call.result.is_none() && call.name == name && call.thread == thread
+                    });
+                    // A call already under way when tracing began only shows its return
+                    let call = match pending {
+                        Some(call) => call,
+                        None => {
+                            calls.push(Syscall {
+                                name,
+                                args: Vec::new(),
+                                path: None,
+                                result: None,
+                                errno: None,
+                                thread,
+                            });
+                            calls.last_mut().expect("just pushed")
+                        }
+                    };
+                    if let Some(value) = value {
+                        call.set_return_value(value);
+                    }
+                }
+                reason => {
+                    stop_reason = Some(reason.to_string());
+                    break;
+                }
+            }
+        }
+
+        if self.current_state().await == DebugState::Running {
+            self.debug_pause().await?;
+        }
+        if let Some(catchpoint) = catchpoint {
+            self.send_mi_command(&format!("-break-delete {}", catchpoint))
+                .await?;
+        }
+        Ok(("gdb", calls, stop_reason))
+    }
+
+    /// Traces syscalls with macOS's `dtruss` while the program runs, for `duration` or
+    /// until it stops.
+    async fn trace_syscalls_dtruss(
+        &self,
+        pid: u32,
+        duration: std::time::Duration,
+        syscalls: &[String],
+    ) -> Result<(&'static str, Vec<Syscall>, Option<String>)> {
+        let mut command = tokio::process::Command::new("dtruss");
+        command.args(["-p", &pid.to_string()]);
+        if let [syscall] = syscalls {
+            command.args(["-t", syscall]);
+        }
+        let mut child = command
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ErrorKind::Unsupported.error("dtruss is not installed")
+                } else {
+                    e.into()
+                }
+            })?;
+        // DTrace compiles its probes before tracing; the program waits, stopped, meanwhile
+        tokio::time::sleep(DTRUSS_STARTUP).await;
+        if child.try_wait()?.is_some() {
+            let output = child.wait_with_output().await?;
+            return Err(ErrorKind::CommandFailed.error(format!(
+                "dtruss failed (it needs root): {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let result = self.debug_continue(None, false).await?;
+        let start = std::time::Instant::now();
+        if result["success"] == true {
+            while start.elapsed() < duration && self.current_state().await == DebugState::Running {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        }
+        let state = self.current_state().await;
+        let stop_reason =
+            (state != DebugState::Running).then(|| format!("{:?}", state).to_lowercase());
+        let _ = child.start_kill();
+        let output = child.wait_with_output().await?;
+        let mut calls = Syscall::parse_dtruss(&String::from_utf8_lossy(&output.stdout));
+        calls.extend(Syscall::parse_dtruss(&String::from_utf8_lossy(
+            &output.stderr,
+        )));
+        if syscalls.len() > 1 {
+            calls.retain(|call| syscalls.contains(&call.name));
+        }
+        Ok(("dtruss", calls, stop_reason))
+    }
+
+    /// Builds the heap tracker preloaded by `debug_heap_profile`, once per version of its
+    /// source, and returns the library's path.
+    async fn heap_tracker_library(&self) -> Result<String> {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        HEAP_TRACKER_SOURCE.hash(&mut hasher);
+        let dir =
+            std::env::temp_dir().join(format!("ferroscope-heap-tracker-{:016x}", hasher.finish()));
+        let library = dir.join("libferroscope_heap_tracker.so");
+        if library.is_file() {
+            return Ok(library.to_string_lossy().to_string());
+        }
+
+        std::fs::create_dir_all(&dir)?;
+        let source = dir.join("heap_tracker.rs");
+        std::fs::write(&source, HEAP_TRACKER_SOURCE)?;
+        // Built beside the final path and renamed, so a concurrent build is never half-read
+        let partial = dir.join(format!("partial-{}.so", std::process::id()));
+        self.report_progress(None, None, "Building the heap tracker");
+        let output = tokio::process::Command::new("rustc")
+            .args(["--edition", "2021", "--crate-type", "cdylib"])
+            .args(["-C", "opt-level=2", "-C", "panic=abort", "-o"])
+            .arg(&partial)
+            .arg(&source)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(ErrorKind::BuildFailed.error(format!(
+                "Failed to build the heap tracker: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        std::fs::rename(&partial, &library)?;
+        Ok(library.to_string_lossy().to_string())
+    }
+
+    /// Selects frame `index` of the selected thread in any state, for reports on a
+    /// crashed program, which `debug_select_frame` refuses.
+    async fn select_frame_quietly(&self, index: u32) -> Result<()> {
+        if self.uses_mi().await {
+            self.send_mi_command(&format!("-stack-select-frame {}", index))
+                .await?;
+        } else {
+            self.send_debugger_command(&format!("frame select {}", index))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Disassembles the selected frame's function and keeps the instructions around its
+    /// program counter.
+    async fn disassemble_around_pc(&self) -> Result<Vec<Instruction>> {
+        let instructions = if self.uses_mi().await {
+            let frame = self.send_mi_command("-stack-info-frame").await?;
+            let pc = frame
+                .result_field("frame")
+                .and_then(|frame| frame.get("addr"))
+                .and_then(|v| v.as_str())
+                .and_then(parse_integer);
+            let mut output = self
+                .send_mi_command("-data-disassemble -a $pc -- 0")
+                .await?;
+            if output.is_error() {
+                // Without symbols there is no function to disassemble whole
+                output = self
+                    .send_mi_command("-data-disassemble -s $pc -e \"$pc + 64\" -- 0")
+                    .await?;
+            }
+            output
+                .result_field("asm_insns")
+                .and_then(|v| v.as_array())
+                .map(|insns| {
+                    insns
+                        .iter()
+                        .filter_map(|insn| Instruction::from_mi(insn, pc))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            let response = self.send_debugger_command("disassemble --frame").await?;
+            response
+                .lines()
+                .filter_map(Instruction::parse_lldb)
+                .collect()
+        };
+        Ok(Instruction::around_pc(instructions, TRIAGE_INSTRUCTIONS))
+    }
+
+    /// Collects the backtrace of the selected thread, or of every thread, with the
+    /// debugger's output.
+    async fn thread_backtraces(
+        &self,
+        max_frames: Option<u32>,
+        all_threads: bool,
+    ) -> Result<(bool, String, Vec<(Option<u32>, Vec<FrameInfo>)>)> {
+        if self.uses_mi().await {
+            return self.mi_backtrace(max_frames, all_threads).await;
+        }
+        let mut command = String::from("thread backtrace");
+        if all_threads {
+            command.push_str(" all");
+        }
+        if let Some(count) = max_frames {
+            command.push_str(&format!(" --count {}", count));
+        }
+        let response = self.send_debugger_command(&command).await?;
+        let threads = Self::parse_lldb_backtrace(&response);
+        Ok((!response.contains("error:"), response, threads))
+    }
+
+    /// Collects backtraces over MI, one `-stack-list-frames` per thread.
+    async fn mi_backtrace(
+        &self,
+        max_frames: Option<u32>,
+        all_threads: bool,
+    ) -> Result<(bool, String, Vec<(Option<u32>, Vec<FrameInfo>)>)> {
+        let thread_ids: Vec<Option<u32>> = if all_threads {
+            let info = self.send_mi_command("-thread-info").await?;
+            if info.is_error() {
+                return Ok((false, info.text(), Vec::new()));
+            }
+            info.result_field("threads")
+                .and_then(|v| v.as_array())
+                .map(|threads| {
+                    threads
+                        .iter()
+                        .filter_map(|t| t.get("id").and_then(|v| v.as_str()))
+                        .map(|id| id.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            vec![None]
+        };
+
+        let mut threads = Vec::new();
+        let mut rendered = Vec::new();
+        for thread_id in thread_ids {
+            let mut command = String::from("-stack-list-frames");
+            if let Some(id) = thread_id {
+                command.push_str(&format!(" --thread {}", id));
+                rendered.push(format!("thread #{}", id));
+            }
+            if let Some(count) = max_frames.filter(|count| *count > 0) {
+                command.push_str(&format!(" 0 {}", count - 1));
+            }
+
+            let output = self.send_mi_command(&command).await?;
+            if output.is_error() {
+                return Ok((false, output.text(), threads));
+            }
+            let frames: Vec<Value> = output
+                .result_field("stack")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            rendered.extend(frames.iter().map(format_mi_frame));
+            threads.push((thread_id, frames.iter().map(FrameInfo::from_mi).collect()));
+        }
+
+        Ok((true, rendered.join("\n"), threads))
+    }
+
+    /// Groups the frame lines of `thread backtrace [all]` output by thread.
+    fn parse_lldb_backtrace(response: &str) -> Vec<(Option<u32>, Vec<FrameInfo>)> {
+        let mut threads: Vec<(Option<u32>, Vec<FrameInfo>)> = Vec::new();
+        for line in response.lines() {
+            if let Some(thread) = Self::parse_lldb_thread_line(line) {
+                threads.push((Some(thread.id), Vec::new()));
+            } else if let Some(frame) = FrameInfo::parse_lldb(line) {
+                match threads.last_mut() {
+                    Some((_, frames)) => frames.push(frame),
+                    None => threads.push((None, vec![frame])),
+                }
+            }
+        }
+        threads
+    }
+
+    async fn debug_list_breakpoints(&self) -> Result<Value> {
+        if self.uses_mi().await {
+            let output = self.send_mi_command("-break-list").await?;
+            let mut breakpoints = output
+                .result_field("BreakpointTable")
+                .and_then(|table| table.get("body"))
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for bkpt in &mut breakpoints {
+                if let Some(func) = bkpt.get("func").and_then(|v| v.as_str()) {
+                    let raw = func.to_string();
+                    bkpt["func"] = json!(demangle(&raw));
+                    bkpt["raw_func"] = json!(raw);
+                }
+            }
+            let rendered: Vec<String> = breakpoints
+                .iter()
+                .map(|bkpt| {
+                    let field = |name: &str| bkpt.get(name).and_then(|v| v.as_str()).unwrap_or("?");
+                    format!(
+                        "Breakpoint {}: {} (enabled: {}, hits: {})",
+                        field("number"),
+                        mi_frame_location(bkpt)
+                            .unwrap_or_else(|| field("original-location").to_string()),
+                        field("enabled"),
+                        field("times")
+                    )
+                })
+                .collect();
+
+            return Ok(json!({
+                "success": !output.is_error(),
+                "output": rendered.join("\n"),
+                "breakpoints": breakpoints
+            }));
+        }
+
+        let response = self.send_debugger_command("breakpoint list").await?;
+        let (breakpoints, watchpoints): (Vec<Value>, Vec<Value>) = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .map(|s| {
+                    (
+                        s.breakpoints.iter().map(Breakpoint::to_json).collect(),
+                        s.watchpoints.iter().map(Watchpoint::to_json).collect(),
+                    )
+                })
+                .unwrap_or_default()
+        };
+
+        Ok(json!({
+            "success": true,
+            "output": demangle_text(response.trim()),
+            "breakpoints": breakpoints,
+            "watchpoints": watchpoints
+        }))
+    }
+
+    /// Returns the signal behind the most recent stop as JSON, if it was one.
+    ///
+    /// GDB's stop record carries no fault address, so for memory faults it is read
+    /// from `$_siginfo` on first request and remembered for the rest of the stop.
+    async fn stop_signal(&self) -> Result<Option<Value>> {
+        let (signal, kind) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (session.stop_signal.clone(), session.kind),
+                None => return Ok(None),
+            }
+        };
+        let Some(mut signal) = signal else {
+            return Ok(None);
+        };
+
+        if kind == DebuggerKind::Gdb && signal.is_fault() && signal.fault_address.is_none() {
+            let output = self
+                .send_mi_command("-data-evaluate-expression $_siginfo._sifields._sigfault.si_addr")
+                .await?;
+            signal.fault_address = output
+                .result_field("value")
+                .and_then(|v| v.as_str())
+                .and_then(|value| value.split_whitespace().find(|t| t.starts_with("0x")))
+                .map(str::to_string);
+
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.stop_signal = Some(signal.clone());
+            }
+        }
+        Ok(Some(signal.to_json()))
+    }
+
+    /// Returns the resource limit the program was killed at, once.
+    async fn take_limit_exceeded(&self) -> Option<Value> {
+        let mut session_guard = self.session.lock().await;
+        session_guard.as_mut()?.limit_exceeded.take()
+    }
+
+    /// Returns the notable debugger stderr lines printed since the last call, if there
+    /// are any, each once and up to [`DEBUGGER_STDERR_LINES`]; the rest are only logged.
+    async fn take_debugger_stderr(&self) -> Option<Value> {
+        let mut session_guard = self.session.lock().await;
+        let mut lines = session_guard.as_mut()?.take_stderr();
+        if lines.is_empty() {
+            return None;
+        }
+        let mut seen = std::collections::HashSet::new();
+        lines.retain(|line| seen.insert(line.clone()));
+        lines.truncate(DEBUGGER_STDERR_LINES);
+        Some(json!(lines))
+    }
+
+    /// Returns the forked processes picked up since the last call, if there are any.
+    async fn take_new_inferiors(&self) -> Option<Value> {
+        let mut session_guard = self.session.lock().await;
+        let session = session_guard.as_mut()?;
+        if session.new_inferiors.is_empty() {
+            return None;
+        }
+        Some(json!(std::mem::take(&mut session.new_inferiors)))
+    }
+
+    /// Returns the program output captured since the last call, if output is captured.
+    async fn take_program_output(&self) -> (Option<String>, Option<String>) {
+        let mut session_guard = self.session.lock().await;
+        match session_guard
+            .as_mut()
+            .and_then(|s| s.program_output.as_mut())
+        {
+            Some(output) => {
+                let (stdout, stderr) = output.take_new();
+                (Some(stdout), Some(stderr))
+            }
+            None => (None, None),
+        }
+    }
+
+    /// Returns everything the debugged program has written to stdout and stderr.
+    ///
+    /// Program output is redirected to capture files at launch so that it never mixes
+    /// with debugger output. Step and continue responses carry the output produced
+    /// during that command; this tool returns the complete history.
+    async fn debug_program_output(&self) -> Result<Value> {
+        let session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_ref() else {
+            return Ok(json!({
+                "success": false,
+                "error": "No active debugging session",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        };
+        let Some(output) = session.program_output.as_ref() else {
+            return Ok(json!({
+                "success": false,
+                "error": "Program output is not captured for attached processes",
+                "error_kind": ErrorKind::Unsupported.name()
+            }));
+        };
+
+        let (stdout, stderr) = output.read_all();
+        Ok(json!({
+            "success": true,
+            "sanitizer_findings": sanitizer_findings(&stderr),
+            "stdout": stdout,
+            "stderr": stderr
+        }))
+    }
+
+    /// Writes `data` to the program's stdin, closing it afterwards when `eof` is set.
+    ///
+    /// Data written before `debug_continue` launches the program waits in the pipe until
+    /// the program reads it.
+    async fn debug_stdin_write(&self, data: &str, eof: bool) -> Result<Value> {
+        use std::io::Write;
+
+        let writer = {
+            let mut session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_mut() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            if !matches!(
+                session.state,
+                DebugState::Loaded | DebugState::Running | DebugState::Stopped
+            ) {
+                return Ok(json!({
+                    "success": false,
+                    "error": "The program is not running",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": format!("{:?}", session.state).to_lowercase()
+                }));
+            }
+            if session.launch.stdin_file.is_some() {
+                return Ok(json!({
+                    "success": false,
+                    "error": "The program reads stdin from stdin_file",
+                    "error_kind": ErrorKind::Unsupported.name()
+                }));
+            }
+            // Closing the only open end of the FIFO discards whatever it still holds
+            if eof && session.state == DebugState::Loaded {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Stdin can only be closed after debug_continue launches the program",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": "loaded"
+                }));
+            }
+            let Some(output) = session.program_output.as_mut() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Stdin is not available for attached processes",
+                    "error_kind": ErrorKind::Unsupported.name()
+                }));
+            };
+            let Some(stdin) = output.stdin.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Stdin is closed",
+                    "error_kind": ErrorKind::WrongState.name()
+                }));
+            };
+            let writer = stdin.try_clone()?;
+            if eof {
+                output.stdin = None;
+            }
+            writer
+        };
+
+        // A full pipe blocks the write until the program reads, so write off the runtime
+        // and give up after the command timeout rather than hanging the server.
+        let bytes = data.as_bytes().to_vec();
+        let len = bytes.len();
+        let write = tokio::task::spawn_blocking(move || {
+            let mut writer = writer;
+            writer.write_all(&bytes)
+        });
+        match tokio::time::timeout(self.command_timeout(), write).await {
+            Ok(result) => result??,
+            Err(_) => {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Timed out writing to stdin; the program is not reading it",
+                    "error_kind": ErrorKind::Timeout.name()
+                }));
+            }
+        }
+
+        Ok(json!({
+            "success": true,
+            "bytes_written": len,
+            "closed": eof
+        }))
+    }
+
+    /// Detaches from the debugged process and ends the session, leaving the process running.
+    ///
+    /// Useful after `debug_attach` to let a service carry on once inspection is done.
+    /// The debugger process is shut down and the state returns to `not_loaded`.
+    async fn debug_detach(&self) -> Result<Value> {
+        let current_state = self.current_state().await;
+
+        if current_state == DebugState::NotLoaded {
+            return Ok(json!({
+                "success": false,
+                "error": "No active debugging session",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        }
+
+        let output = if matches!(current_state, DebugState::Running | DebugState::Stopped) {
+            self.send_command("process detach", "-target-detach")
+                .await?
+        } else {
+            String::new()
+        };
+
+        self.end_session().await;
+
+        Ok(json!({
+            "success": true,
+            "state": "not_loaded",
+            "output": output.trim()
+        }))
+    }
+
+    /// Waits until the running program stops or exits, for at most `timeout_secs`.
+    ///
+    /// Commands give up waiting after their timeout, leaving long-running programs in the
+    /// `running` state. This long-polls for the stop instead, and returns at once if
+    /// the program is not running. `timed_out` is set if it is still running.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_wait_for_stop", "arguments": {"timeout_secs": 120}}
+    /// ```
+    async fn debug_wait_for_stop(&self, timeout_secs: u64) -> Result<Value> {
+        let mut result = self
+            .wait_for_stop(std::time::Duration::from_secs(timeout_secs))
+            .await?;
+        if let Some(state) = result.get("state").cloned() {
+            result["timed_out"] = json!(state == "running");
+        }
+        Ok(result)
+    }
+
+    /// Reports the latest state without blocking, applying any debugger output that
+    /// arrived since the last command.
+    ///
+    /// The polling companion to `debug_continue` with `"wait": false`.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_poll", "arguments": {}}
+    /// ```
+    async fn debug_poll(&self) -> Result<Value> {
+        self.wait_for_stop(std::time::Duration::ZERO).await
+    }
+
+    /// Reads debugger output until the program stops or `timeout` passes, and
+    /// reports the resulting state, location, and program output.
+    pub(crate) async fn wait_for_stop(&self, timeout: std::time::Duration) -> Result<Value> {
+        let no_session = json!({
+            "success": false,
+            "error": "No active debugging session",
+            "error_kind": ErrorKind::NoSession.name(),
+            "state": "not_loaded"
+        });
+        let Ok((_, output_lines)) = self.debugger_pipes().await else {
+            return Ok(no_session);
+        };
+        let mut output_lines = output_lines.lock().await;
+
+        let (mut output, running) = {
+            let mut session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_mut() else {
+                return Ok(no_session);
+            };
+            let output = self.apply_pending_output(session, &mut output_lines).await;
+            (output, session.state == DebugState::Running)
+        };
+
+        if running {
+            let deadline = std::time::Instant::now() + timeout;
+            let mut raw = String::new();
+            let mut stopped = false;
+
+            loop {
+                // Once the stop is seen, only linger for the stop details that follow it
+                let wait = if stopped {
+                    std::time::Duration::from_millis(200)
+                } else {
+                    deadline.saturating_duration_since(std::time::Instant::now())
+                };
+                match self.recv_output(&mut output_lines, wait).await {
+                    Ok(Some(line)) => {
+                        stopped |= stop_event(&line).is_some();
+                        raw.push_str(&line);
+                    }
+                    Ok(None) => {
+                        drop(output_lines);
+                        return Err(self.debugger_exited(&raw).await);
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                output.push_str(&self.apply_debugger_output(session, &raw).await);
+                if session.state == DebugState::Running {
+                    session.notify_on_stop.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        drop(output_lines);
+
+        let (state, location, exit_status) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(no_session);
+            };
+            (
+                session.state.clone(),
+                session.current_location.clone(),
+                session.exit_status.clone(),
+            )
+        };
+
+        let (program_stdout, program_stderr) = self.take_program_output().await;
+        let signal = self.stop_signal().await?;
+
+        Ok(json!({
+            "success": true,
+            "state": format!("{:?}", state).to_lowercase(),
+            "output": output.trim(),
+            "location": location,
+            "signal": signal,
+            "exit_code": exit_status.as_ref().and_then(|exit| exit.code),
+            "terminated_by_signal": exit_status.and_then(|exit| exit.signal),
+            "program_stdout": program_stdout,
+            "program_stderr": program_stderr
+        }))
+    }
+
+    /// Interrupts the running program so it can be inspected.
+    ///
+    /// Sends `SIGINT` to the program, which the debugger intercepts and reports as a
+    /// stop; when the process ID is unknown the debugger's own interrupt command is
+    /// used instead. Useful to find where an apparently hung program is spinning.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_pause", "arguments": {}}
+    /// ```
+    pub(crate) async fn debug_pause(&self) -> Result<Value> {
+        let (current_state, pid, uses_mi) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (
+                    session.state.clone(),
+                    // A remote stub's "process" ID means nothing to the local kernel
+                    session
+                        .inferior_pid
+                        .filter(|_| session.remote_target.is_none()),
+                    session.kind.uses_mi(),
+                ),
+                None => (DebugState::NotLoaded, None, false),
+            }
+        };
+
+        if current_state != DebugState::Running {
+            return Ok(json!({
+                "success": false,
+                "error": "Program must be running to pause it",
+                "error_kind": ErrorKind::WrongState.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        let signalled = match pid {
+            Some(pid) => tokio::process::Command::new("kill")
+                .args(["-INT", &pid.to_string()])
+                .status()
+                .await
+                .is_ok_and(|status| status.success()),
+            None => false,
+        };
+
+        if !signalled {
+            // The reply arrives with the stop, so only write the command here
+            let command = if uses_mi {
+                "-exec-interrupt"
+            } else {
+                "process interrupt"
+            };
+            // Only stdin is locked, so this gets through while a command awaits its reply
+            let (stdin, _) = self.debugger_pipes().await?;
+            let mut stdin = stdin.lock().await;
+            stdin.write_all(command.as_bytes()).await?;
+            stdin.write_all(b"\n").await?;
+            stdin.flush().await?;
+            self.log("debug", "ferroscope.debugger", json!({ "sent": command }));
+        }
+
+        let mut result = self.wait_for_stop(self.command_timeout()).await?;
+        result["success"] = json!(result["state"] != "running");
+        if result["success"] == false {
+            result["error_kind"] = json!(ErrorKind::Timeout.name());
+        }
+        result["method"] = json!(if signalled { "sigint" } else { "interrupt" });
+        Ok(result)
+    }
+
+    /// Kills and relaunches the program, keeping breakpoints, watchpoints, and the
+    /// launch configuration.
+    ///
+    /// With `rebuild`, the project given to `debug_run` is rebuilt first; the debugger
+    /// notices the new binary on relaunch and re-resolves breakpoints against it.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_restart", "arguments": {"rebuild": true}}
+    /// ```
+    async fn debug_restart(&self, rebuild: bool) -> Result<Value> {
+        let (current_state, kind, source_dir, build, test, attached, remote) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (
+                    session.state.clone(),
+                    session.kind,
+                    session.source_dir.clone(),
+                    session.build.clone(),
+                    session.test_name.is_some(),
+                    session.program_output.is_none(),
+                    session.remote_target.is_some(),
+                ),
+                None => {
+                    return Ok(json!({
+                        "success": false,
+                        "error": "No active debugging session",
+                        "error_kind": ErrorKind::NoSession.name(),
+                        "state": "not_loaded"
+                    }))
+                }
+            }
+        };
+
+        if remote {
+            return Ok(json!({
+                "success": false,
+                "error": "Remote targets have no process to relaunch; use debug_monitor with \"reset halt\", then debug_continue",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        if attached {
+            return Ok(json!({
+                "success": false,
+                "error": "Attached processes cannot be restarted; use debug_detach and debug_attach instead",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        }
+
+        if rebuild {
+            let Some(source_dir) = source_dir else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "Nothing to rebuild: debug_run was given a binary rather than a project directory",
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": format!("{:?}", current_state).to_lowercase()
+                }));
+            };
+            if test {
+                self.run_cargo(
+                    &source_dir,
+                    &["test", "--no-run", "--message-format=json"],
+                    &[],
+                )
+                .await?;
+            } else {
+                self.build_rust_project(&source_dir, &build).await?;
+            }
+        }
+
+        if matches!(current_state, DebugState::Running | DebugState::Stopped) {
+            let mi_command = match kind {
+                DebuggerKind::Gdb => mi_console_command("kill"),
+                _ => "-exec-abort".to_string(),
+            };
+            self.send_command("process kill", &mi_command).await?;
+        }
+
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.state = DebugState::Loaded;
+                session.current_location = None;
+                session.selected_thread = None;
+                session.selected_frame = 0;
+                session.stop_signal = None;
+                session.watchpoint_hit = None;
+                session.exit_status = None;
+                session.inferior_pid = None;
+                session.program_output = Some(ProgramOutput::create()?);
+            }
+        }
+
+        let mut result = self.debug_continue(None, true).await?;
+        result["restarted"] = json!(true);
+        result["rebuilt"] = json!(rebuild);
+        Ok(result)
+    }
+
+    /// Rebuilds the project given to `debug_run` (or `debug_test`) and, when the binary
+    /// changed, reloads it in a fresh debugger with the same breakpoints.
+    ///
+    /// Breakpoints are set again by their original location, file:line or function
+    /// name, with their conditions, ignore counts, and disabled state; watched
+    /// expressions and the launch configuration carry over. Watchpoints refer to
+    /// addresses in the old program and are dropped. The program is left loaded but
+    /// not started, as after `debug_run`. When nothing changed, the session is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_rebuild", "arguments": {}}
+    /// ```
+    async fn debug_rebuild(&self) -> Result<Value> {
+        let (current_state, source_dir, binary_path) = {
+            let session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_ref() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            (
+                session.state.clone(),
+                session.source_dir.clone(),
+                session.binary_path.clone(),
+            )
+        };
+        let Some(source_dir) = source_dir else {
+            return Ok(json!({
+                "success": false,
+                "error": "Nothing to rebuild: debug_run was given a binary rather than a project directory",
+                "error_kind": ErrorKind::Unsupported.name(),
+                "state": format!("{:?}", current_state).to_lowercase()
+            }));
+        };
+
+        let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let built_before = modified(&binary_path);
+        let (test_name, build) = {
+            let session_guard = self.session.lock().await;
+            let session = session_guard.as_ref();
+            (
+                session.and_then(|session| session.test_name.clone()),
+                session
+                    .map(|session| session.build.clone())
+                    .unwrap_or_default(),
+            )
+        };
+        let binary = if test_name.is_some() {
+            // Test binaries keep their path across rebuilds
+            self.run_cargo(
+                &source_dir,
+                &["test", "--no-run", "--message-format=json"],
+                &[],
+            )
+            .await?;
+            binary_path.clone()
+        } else {
+            self.build_rust_project(&source_dir, &build)
+                .await?
+                .executable
+        };
+
+        if binary == binary_path && modified(&binary) == built_before {
+            return Ok(json!({
+                "success": true,
+                "changed": false,
+                "state": format!("{:?}", current_state).to_lowercase(),
+                "binary_path": binary
+            }));
+        }
+
+        let Some(old) = self.session.lock().await.take() else {
+            return Err(ErrorKind::NoSession.error("The session ended during the rebuild"));
+        };
+        let DebugSession {
+            mut process,
+            kind,
+            launch,
+            breakpoints,
+            watchpoints,
+            watch_expressions,
+            ..
+        } = old;
+        let _ = process.kill().await;
+
+        let sanitizers = build.sanitizers.clone();
+        self.report_progress(None, None, &format!("Reloading {}", binary));
+        let mut result = self.start_debugger_session(&binary, kind, launch).await?;
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.source_dir = Some(source_dir);
+                session.build = build;
+                session.test_name = test_name;
+                session.watch_expressions = watch_expressions;
+            }
+        }
+
+        result["changed"] = json!(true);
+        self.reinstate_breakpoints(breakpoints, &sanitizers, &mut result)
+            .await?;
+        if !watchpoints.is_empty() {
+            result["dropped_watchpoints"] = json!(watchpoints
+                .iter()
+                .map(Watchpoint::to_json)
+                .collect::<Vec<_>>());
+        }
+        Ok(result)
+    }
+
+    /// Starts a new debugger after the session's one died, loading the same program
+    /// with its launch configuration and setting its breakpoints again.
+    ///
+    /// The program died with the debugger (or is killed now), so it is not running
+    /// afterwards; `debug_continue` launches it again. Watchpoints are dropped and
+    /// reported, as after a rebuild. Sessions started by `debug_attach`,
+    /// `debug_open_core`, `debug_replay`, or `debug_connect_target` are not recovered;
+    /// calling that tool again starts a new one.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_recover", "arguments": {}}
+    /// ```
+    async fn debug_recover(&self) -> Result<Value> {
+        let old = {
+            let mut session_guard = self.session.lock().await;
+            let Some(session) = session_guard.as_mut() else {
+                return Ok(json!({
+                    "success": false,
+                    "error": "No active debugging session",
+                    "error_kind": ErrorKind::NoSession.name(),
+                    "state": "not_loaded"
+                }));
+            };
+            if self.check_debugger(session).is_ok() {
+                return Ok(json!({
+                    "success": false,
+                    "error": "The debugger is still running; debug_recover only restarts one that exited",
+                    "error_kind": ErrorKind::WrongState.name(),
+                    "state": format!("{:?}", session.state).to_lowercase()
+                }));
+            }
+            let started_by = if session.attached {
+                Some("debug_attach")
+            } else if session.core_path.is_some() {
+                Some("debug_open_core")
+            } else if session.replay_trace.is_some() {
+                Some("debug_replay")
+            } else if session.remote_target.is_some() {
+                Some("debug_connect_target")
+            } else {
+                None
+            };
+            if let Some(tool) = started_by {
+                return Ok(json!({
+                    "success": false,
+                    "error": format!("This session was started by {}; call it again to start a new one", tool),
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": "debuggerdied"
+                }));
+            }
+            (
+                session.binary_path.clone(),
+                session.kind,
+                session.launch.clone(),
+                session.breakpoints.clone(),
+                session.watchpoints.clone(),
+                session.watch_expressions.clone(),
+                session.source_dir.clone(),
+                session.build.clone(),
+                session.test_name.clone(),
+                session.debugger_exit.clone(),
+            )
+        };
+        let (
+            binary_path,
+            kind,
+            launch,
+            breakpoints,
+            watchpoints,
+            watch_expressions,
+            source_dir,
+            build,
+            test_name,
+            debugger_exit,
+        ) = old;
+        self.shutdown().await;
+
+        let sanitizers = build.sanitizers.clone();
+        self.report_progress(None, None, &format!("Reloading {}", binary_path));
+        let mut result = self
+            .start_debugger_session(&binary_path, kind, launch)
+            .await?;
+        {
+            let mut session_guard = self.session.lock().await;
+            if let Some(session) = session_guard.as_mut() {
+                session.source_dir = source_dir;
+                session.build = build;
+                session.test_name = test_name;
+                session.watch_expressions = watch_expressions;
+            }
+        }
+
+        self.reinstate_breakpoints(breakpoints, &sanitizers, &mut result)
+            .await?;
+        if !watchpoints.is_empty() {
+            result["dropped_watchpoints"] = json!(watchpoints
+                .iter()
+                .map(Watchpoint::to_json)
+                .collect::<Vec<_>>());
+        }
+        result["recovered_from"] = json!(debugger_exit);
+        Ok(result)
+    }
+
+    /// Sets a previous session's `breakpoints` in the current one, reporting them in
+    /// `result` as `breakpoints`, `panic_breakpoints`, and `sanitizer_breakpoints`.
+    async fn reinstate_breakpoints(
+        &self,
+        breakpoints: Vec<Breakpoint>,
+        sanitizers: &[Sanitizer],
+        result: &mut Value,
+    ) -> Result<()> {
+        // Panic and sanitizer breakpoints need deferred resolution, which their own
+        // setters handle
+        let (panic, user): (Vec<Breakpoint>, Vec<Breakpoint>) =
+            breakpoints.into_iter().partition(|breakpoint| {
+                let location = breakpoint.location.as_str();
+                PANIC_BREAKPOINTS.contains(&location)
+                    || sanitizers.iter().any(|s| s.report_function() == location)
+            });
+        if panic
+            .iter()
+            .any(|breakpoint| PANIC_BREAKPOINTS.contains(&breakpoint.location.as_str()))
+        {
+            result["panic_breakpoints"] = json!(self.set_panic_breakpoints().await?);
+        }
+        if !sanitizers.is_empty() {
+            result["sanitizer_breakpoints"] =
+                json!(self.set_sanitizer_breakpoints(sanitizers).await?);
+        }
+        result["breakpoints"] = json!(self.restore_breakpoints(user).await?);
+        Ok(())
+    }
+
+    /// Sets `breakpoints` again in the current session, with their conditions, trace
+    /// settings, hit commands, and enabled state, and reports each one's old and new id.
+    async fn restore_breakpoints(&self, breakpoints: Vec<Breakpoint>) -> Result<Vec<Value>> {
+        let mut restored = Vec::new();
+        for breakpoint in breakpoints {
+            let set = self
+                .debug_break(
+                    &breakpoint.location,
+                    breakpoint.condition.as_deref(),
+                    breakpoint.ignore_count,
+                )
+                .await?;
+            let id = set["id"].as_u64().and_then(|id| u32::try_from(id).ok());
+            match (id, breakpoint.log_expressions.clone()) {
+                (Some(id), _) if breakpoint.call_trace => {
+                    self.set_call_trace(id, breakpoint.log_arguments).await
+                }
+                (Some(id), Some(expressions)) => self.set_log_expressions(id, expressions).await,
+                _ => {}
+            }
+            let mut commands = breakpoint.on_hit_commands.clone();
+            if breakpoint.auto_continue {
+                commands.push("continue".to_string());
+            }
+            if let (Some(id), false) = (id, commands.is_empty()) {
+                self.set_on_hit_commands(id, commands).await?;
+            }
+            if let (Some(id), false) = (id, breakpoint.enabled) {
+                self.debug_break_modify(BreakpointAction::Disable, Some(id), None)
+                    .await?;
+            }
+            restored.push(json!({
+                "location": breakpoint.location,
+                "id": id,
+                "old_id": breakpoint.id,
+                "resolved_locations": set["resolved_locations"],
+                "enabled": breakpoint.enabled
+            }));
+        }
+        Ok(restored)
+    }
+
+    /// The `.ferroscope/breakpoints.json` of the session's project: the directory
+    /// `debug_run` built from, or the nearest one above the binary with a `Cargo.toml`.
+    async fn breakpoints_file(&self) -> Option<std::path::PathBuf> {
+        let (source_dir, binary_path) = {
+            let session_guard = self.session.lock().await;
+            let session = session_guard.as_ref()?;
+            (session.source_dir.clone(), session.binary_path.clone())
+        };
+        let project = match source_dir {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => std::fs::canonicalize(&binary_path)
+                .ok()?
+                .ancestors()
+                .find(|dir| dir.join("Cargo.toml").is_file())?
+                .to_path_buf(),
+        };
+        Some(project.join(".ferroscope").join("breakpoints.json"))
+    }
+
+    /// The session's breakpoints as `debug_save_breakpoints` writes them, leaving out
+    /// the panic and sanitizer breakpoints `debug_run` sets by itself and the unwind
+    /// breakpoints of `debug_break_on_error`.
+    async fn breakpoints_document(&self) -> Option<Value> {
+        let session_guard = self.session.lock().await;
+        let session = session_guard.as_ref()?;
+        let breakpoints: Vec<Value> = session
+            .breakpoints
+            .iter()
+            .filter(|breakpoint| {
+                let location = breakpoint.location.as_str();
+                !PANIC_BREAKPOINTS.contains(&location)
+                    && !UNWIND_BREAKPOINTS.contains(&location)
+                    && !session
+                        .build
+                        .sanitizers
+                        .iter()
+                        .any(|s| s.report_function() == location)
+            })
+            .map(Breakpoint::to_json)
+            .collect();
+        Some(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Saves the session's breakpoints to the project's breakpoints file when they
+    /// changed since last saved or loaded. No file is created for a project that
+    /// never had breakpoints.
+    async fn autosave_breakpoints(&self) -> Result<()> {
+        let Some(document) = self.breakpoints_document().await else {
+            return Ok(());
+        };
+        let Some(path) = self.breakpoints_file().await else {
+            return Ok(());
+        };
+        {
+            let session_guard = self.session.lock().await;
+            let saved = session_guard
+                .as_ref()
+                .and_then(|session| session.saved_breakpoints.as_ref());
+            let empty = document["breakpoints"].as_array().is_none_or(Vec::is_empty);
+            if saved == Some(&document) || (saved.is_none() && empty && !path.exists()) {
+                return Ok(());
+            }
+        }
+        self.write_breakpoints(&path, document).await
+    }
+
+    /// Writes `document` to `path` and remembers it as the saved breakpoints.
+    async fn write_breakpoints(&self, path: &std::path::Path, document: Value) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&document)?)?;
+        if let Some(session) = self.session.lock().await.as_mut() {
+            session.saved_breakpoints = Some(document);
+        }
+        Ok(())
+    }
+
+    /// Saves the breakpoints set in this session, with their conditions, trace
+    /// settings, and hit commands, to `path`, by default the project's
+    /// `.ferroscope/breakpoints.json`. They are also saved there whenever they change,
+    /// and `debug_run` loads that file when it opens the project again.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_save_breakpoints", "arguments": {}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written.
+    pub(crate) async fn debug_save_breakpoints(&self, path: Option<&str>) -> Result<Value> {
+        let Some(document) = self.breakpoints_document().await else {
+            return Ok(json!({
+                "success": false,
+                "error": "No active debugging session",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        };
+        let path = match path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => self.breakpoints_file().await.ok_or_else(|| {
+                ErrorKind::InvalidArgument
+                    .error("The binary is not in a Cargo project; pass a path to save to")
+            })?,
+        };
+        let count = document["breakpoints"].as_array().map_or(0, Vec::len);
+        self.write_breakpoints(&path, document).await?;
+        Ok(json!({
+            "success": true,
+            "path": path,
+            "saved": count,
+            "state": format!("{:?}", self.current_state().await).to_lowercase()
+        }))
+    }
+
+    /// Sets the breakpoints saved in `path`, by default the project's
+    /// `.ferroscope/breakpoints.json`, in the current session. File:line and function
+    /// locations are resolved afresh against the loaded binary; breakpoints already
+    /// set at the same location with the same condition are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_load_breakpoints", "arguments": {"path": "./breakpoints.json"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or does not
+    /// hold saved breakpoints.
+    pub(crate) async fn debug_load_breakpoints(&self, path: Option<&str>) -> Result<Value> {
+        let current_state = self.current_state().await;
+        if current_state == DebugState::NotLoaded {
+            return Ok(json!({
+                "success": false,
+                "error": "No program loaded. Use debug_run first.",
+                "error_kind": ErrorKind::NoSession.name(),
+                "state": "not_loaded"
+            }));
+        }
+        let path = match path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => self.breakpoints_file().await.ok_or_else(|| {
+                ErrorKind::InvalidArgument
+                    .error("The binary is not in a Cargo project; pass a path to load from")
+            })?,
+        };
+        let restored = self.load_breakpoints(&path).await?;
+        Ok(json!({
+            "success": true,
+            "path": path,
+            "breakpoints": restored,
+            "state": format!("{:?}", self.current_state().await).to_lowercase()
+        }))
+    }
+
+    /// Restores the breakpoints saved in `path` that the session does not have yet.
+    async fn load_breakpoints(&self, path: &std::path::Path) -> Result<Vec<Value>> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            ErrorKind::InvalidArgument.error(format!("Cannot read {}: {}", path.display(), e))
+        })?;
+        let document: Value = serde_json::from_str(&text)
+            .map_err(|e| ErrorKind::InvalidArgument.error(format!("{}: {}", path.display(), e)))?;
+        let saved = document["breakpoints"]
+            .as_array()
+            .ok_or_else(|| {
+                ErrorKind::InvalidArgument
+                    .error(format!("{} holds no breakpoints array", path.display()))
+            })?
+            .iter()
+            .map(Breakpoint::from_json)
+            .collect::<Result<Vec<_>>>()?;
+        let missing: Vec<Breakpoint> = {
+            let session_guard = self.session.lock().await;
+            let existing = session_guard
+                .as_ref()
+                .map(|session| session.breakpoints.as_slice())
+                .unwrap_or_default();
+            saved
+                .into_iter()
+                .filter(|breakpoint| {
+                    !existing.iter().any(|set| {
+                        set.location == breakpoint.location && set.condition == breakpoint.condition
+                    })
+                })
+                .collect()
+        };
+        let restored = self.restore_breakpoints(missing).await?;
+        let document = self.breakpoints_document().await;
+        if let Some(session) = self.session.lock().await.as_mut() {
+            // What the file holds, unless setting them changed it
+            session.saved_breakpoints = document;
+        }
+        Ok(restored)
+    }
+
+    /// Terminates the debugged process and the debugger, ending the session.
+    pub(crate) async fn debug_kill(&self) -> Result<Value> {
+        let (current_state, kind, post_mortem) = {
+            let session_guard = self.session.lock().await;
+            match session_guard.as_ref() {
+                Some(session) => (
+                    session.state.clone(),
+                    session.kind,
+                    session.core_path.is_some(),
+                ),
+                None => {
+                    return Ok(json!({
+                        "success": false,
+                        "error": "No active debugging session",
+                        "error_kind": ErrorKind::NoSession.name(),
+                        "state": "not_loaded"
+                    }))
+                }
+            }
+        };
+
+        let live = matches!(current_state, DebugState::Running | DebugState::Stopped);
+        let output = if live && !post_mortem {
+            let mi_command = match kind {
+                DebuggerKind::Gdb => mi_console_command("kill"),
+                _ => "-exec-abort".to_string(),
+            };
+            self.send_command("process kill", &mi_command).await?
+        } else {
+            String::new()
+        };
+
+        self.end_session().await;
+
+        Ok(json!({
+            "success": true,
+            "state": "not_loaded",
+            "output": output.trim()
+        }))
+    }
+
+    /// Exports the server history as JSON or as a Markdown narrative: each tool call
+    /// with its arguments and result, the debugger commands and responses, state
+    /// transitions, and breakpoint hits, across every session this server ran. With
+    /// `path` the document is written to that file instead of returned.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_export_session", "arguments": {"format": "markdown", "path": "session.md"}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file at `path` cannot be written.
+    pub(crate) async fn debug_export_session(
+        &self,
+        format: &str,
+        path: Option<&str>,
+    ) -> Result<Value> {
+        let (document, events, dropped) = {
+            let history = self.history.lock().unwrap();
+            let document = match format {
+                "markdown" => json!(history.to_markdown()),
+                _ => history.to_json(),
+            };
+            (document, history.entries.len(), history.dropped)
+        };
+        let mut result = json!({
+            "success": true,
+            "format": format,
+            "events": events,
+            "dropped": dropped,
+            "state": format!("{:?}", self.current_state().await).to_lowercase()
+        });
+        match path {
+            Some(path) => {
+                let text = match &document {
+                    Value::String(text) => text.clone(),
+                    document => serde_json::to_string_pretty(document)?,
+                };
+                std::fs::write(path, text)?;
+                result["path"] = json!(path);
+            }
+            None => result["document"] = document,
+        }
+        Ok(result)
+    }
+
+    /// Runs the checks of `ferroscope doctor` with this server's options. The probe
+    /// runs in a debugger of its own, so an open session is left as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_doctor", "arguments": {}}
+    /// ```
+    pub(crate) async fn debug_doctor(&self) -> Result<Value> {
+        let checks = crate::doctor::checks(&self.options).await;
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|check| check.failed())
+            .map(|check| check.name.as_str())
+            .collect();
+        Ok(json!({
+            "success": true,
+            "healthy": failed.is_empty(),
+            "checks": checks.iter().map(crate::doctor::Check::to_json).collect::<Vec<_>>(),
+            "failed": failed,
+            "state": format!("{:?}", self.current_state().await).to_lowercase()
+        }))
+    }
+
+    async fn get_debug_state(&self) -> Result<Value> {
+        let mut session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_mut() else {
+            return Ok(json!({
+                "state": format!("{:?}", DebugState::NotLoaded).to_lowercase(),
+                "location": null,
+                "binary_path": null,
+                "debugger": null,
+                "selected_thread": null,
+                "selected_frame": null,
+                "exit_code": null,
+                "terminated_by_signal": null,
+                "core_path": null,
+                "replay_trace": null,
+                "watch_expressions": [],
+                "debugger_exit": null,
+                "busy": false
+            }));
+        };
+
+        // Pick up a stop that happened since the last command, unless a command is in
+        // flight and will read it itself
+        let output_lines = session.output.clone();
+        let busy = match output_lines.try_lock() {
+            Ok(mut output_lines) => {
+                self.apply_pending_output(session, &mut output_lines).await;
+                if session.state == DebugState::Running {
+                    session.notify_on_stop.store(true, Ordering::SeqCst);
+                }
+                false
+            }
+            Err(_) => true,
+        };
+
+        let _ = self.check_debugger(session);
+        let exit_status = session.exit_status.as_ref();
+        Ok(json!({
+            "state": format!("{:?}", session.state).to_lowercase(),
+            "debugger_exit": session.debugger_exit,
+            "busy": busy,
+            "location": session.current_location,
+            "binary_path": session.binary_path,
+            "debugger": session.kind.name(),
+            "selected_thread": session.selected_thread,
+            "selected_frame": session.selected_frame,
+            "exit_code": exit_status.and_then(|exit| exit.code),
+            "terminated_by_signal": exit_status.and_then(|exit| exit.signal.clone()),
+            "core_path": session.core_path,
+            "replay_trace": session.replay_trace,
+            "watch_expressions": session.watch_expressions
+        }))
+    }
+
+    // MCP Protocol Implementation
+
+    /// Handles the MCP initialize request from AI assistants.
+    ///
+    /// This method implements the Model Context Protocol initialization handshake,
+    /// announcing the server's capabilities and protocol version to the AI assistant.
+    /// The client's `protocolVersion` is accepted if it is one of [`PROTOCOL_VERSIONS`];
+    /// otherwise the newest is offered and the client decides whether to continue.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Initialization parameters from the client
+    ///
+    /// # Returns
+    ///
+    /// Returns a JSON response with server capabilities and version information.
+    async fn handle_initialize(&self, params: Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+        let version = PROTOCOL_VERSIONS
+            .into_iter()
+            .find(|version| Some(*version) == requested)
+            .unwrap_or(PROTOCOL_VERSIONS[0]);
+        *self.protocol_version.lock().unwrap() = version;
+
+        json!({
+            "protocolVersion": version,
+            "capabilities": {
+                "tools": {},
+                "resources": {
+                    "subscribe": true,
+                    "listChanged": true
+                },
+                "prompts": {},
+                "logging": {}
+            },
+            "serverInfo": {
+                "name": "ferroscope",
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })
+    }
+
+    /// Records a `tracing` event, and sends a `notifications/message` log event if
+    /// `level` is at least as severe as the level the client chose with
+    /// `logging/setLevel`.
+    fn log(&self, level: &str, logger: &str, data: Value) {
+        let kind = match logger {
+            "ferroscope.debugger" => "debugger",
+            _ => "session",
+        };
+        self.history.lock().unwrap().push(kind, data.clone());
+        match level {
+            "debug" => tracing::debug!(logger, %data),
+            "info" | "notice" => tracing::info!(logger, %data),
+            "warning" => tracing::warn!(logger, %data),
+            _ => tracing::error!(logger, %data),
+        }
+        let severity = LOG_LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+        if severity < self.log_level.load(Ordering::SeqCst) {
+            return;
+        }
+        let _ = self.notifications.send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": {
+                "level": level,
+                "logger": logger,
+                "data": data
+            }
+        }));
+    }
+
+    /// Logs a session state transition, once per change.
+    fn log_state(&self, state: &DebugState) {
+        let mut logged = self.logged_state.lock().unwrap();
+        if *logged == *state {
+            return;
+        }
+        self.log(
+            "info",
+            "ferroscope.session",
+            json!({
+                "from": format!("{:?}", *logged).to_lowercase(),
+                "to": format!("{:?}", state).to_lowercase()
+            }),
+        );
+        *logged = state.clone();
+    }
+
+    /// Whether the negotiated protocol revision is `version` or newer.
+    fn protocol_at_least(&self, version: &str) -> bool {
+        // Revisions are dates, so they order as strings
+        *self.protocol_version.lock().unwrap() >= version
+    }
+
+    /// Renders one of [`PROMPTS`] with the client's arguments.
+    fn handle_get_prompt(params: &Value) -> Result<Value, Value> {
+        let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let template = PROMPTS
+            .iter()
+            .find(|template| template.name == name)
+            .ok_or_else(|| {
+                json!({
+                    "code": -32602,
+                    "message": format!("Unknown prompt: {}", name)
+                })
+            })?;
+
+        let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+        let arg = |name: &str| {
+            arguments
+                .get(name)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string()
+        };
+        if let Some((missing, _, _)) = template
+            .arguments
+            .iter()
+            .find(|(name, _, required)| *required && arg(name).is_empty())
+        {
+            return Err(json!({
+                "code": -32602,
+                "message": format!("Missing required argument: {}", missing)
+            }));
+        }
+
+        Ok(json!({
+            "description": template.description,
+            "messages": [{
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": template.render(arg)
+                }
+            }]
+        }))
+    }
+
+    /// Lists the session resources and, for a session started on a project
+    /// directory, its Rust source files.
+    async fn handle_list_resources(&self) -> Value {
+        let mut resources = vec![
+            json!({
+                "uri": BACKTRACE_RESOURCE,
+                "name": "Backtrace",
+                "description": "Call stack of the selected thread while the program is stopped",
+                "mimeType": "application/json"
+            }),
+            json!({
+                "uri": BREAKPOINTS_RESOURCE,
+                "name": "Breakpoints",
+                "description": "Breakpoints and watchpoints with their hit counts",
+                "mimeType": "application/json"
+            }),
+            json!({
+                "uri": LOG_RESOURCE,
+                "name": "Session log",
+                "description": "Debugger commands sent this session and the output received",
+                "mimeType": "text/plain"
+            }),
+        ];
+        for path in self.source_files().await {
+            resources.push(json!({
+                "uri": format!("file://{}", path.display()),
+                "name": path.file_name().map(|name| name.to_string_lossy()),
+                "mimeType": "text/x-rust"
+            }));
+        }
+        json!({ "resources": resources })
+    }
+
+    /// Reads a resource listed by `resources/list`.
+    async fn handle_read_resource(&self, uri: &str) -> Result<Value, Value> {
+        let (mime_type, text) = match uri {
+            BACKTRACE_RESOURCE => (
+                "application/json",
+                self.debug_backtrace(None, false)
+                    .await
+                    .map(|backtrace| backtrace.to_string()),
+            ),
+            BREAKPOINTS_RESOURCE => (
+                "application/json",
+                self.debug_list_breakpoints()
+                    .await
+                    .map(|breakpoints| breakpoints.to_string()),
+            ),
+            LOG_RESOURCE => {
+                let session_guard = self.session.lock().await;
+                let log = session_guard
+                    .as_ref()
+                    .map(|session| Vec::from(session.log.clone()).join("\n"))
+                    .unwrap_or_default();
+                ("text/plain", Ok(log))
+            }
+            _ => {
+                // Only the project's own sources are served, not arbitrary files
+                let path = uri
+                    .strip_prefix("file://")
+                    .and_then(|path| std::fs::canonicalize(path).ok());
+                let listed = match path {
+                    Some(path) => self.source_files().await.contains(&path).then_some(path),
+                    None => None,
+                };
+                let Some(path) = listed else {
+                    return Err(json!({
+                        "code": -32002,
+                        "message": format!("Resource not found: {}", uri)
+                    }));
+                };
+                (
+                    "text/x-rust",
+                    std::fs::read_to_string(path).map_err(anyhow::Error::from),
+                )
+            }
+        };
+
+        let text = text.map_err(|e| {
+            json!({
+                "code": -32603,
+                "message": format!("Failed to read {}: {}", uri, e)
+            })
+        })?;
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": mime_type,
+                "text": text
+            }]
+        }))
+    }
+
+    /// Rust sources of the project `debug_run` built, skipping `target` and hidden
+    /// directories.
+    async fn source_files(&self) -> Vec<std::path::PathBuf> {
+        let source_dir = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .and_then(|session| session.source_dir.clone())
+        };
+        let Some(root) = source_dir.and_then(|dir| std::fs::canonicalize(dir).ok()) else {
+            return Vec::new();
+        };
+
+        let mut files = Vec::new();
+        let mut dirs = vec![root];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if path.is_dir() {
+                    if name != "target" && !name.starts_with('.') {
+                        dirs.push(path);
+                    }
+                } else if name.ends_with(".rs") {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// Reads the `ferroscope.toml` settings for `target` and fills in the `debug_run`
+    /// `arguments` they give defaults for. The debugger path and timeout are kept for
+    /// the session and the source map is added to the server's.
+    fn load_project_config(&self, target: &str, arguments: &mut Value) -> Result<ProjectConfig> {
+        let config =
+            ProjectConfig::load(std::path::Path::new(target), self.options.config.as_deref())?;
+        config.apply(arguments);
+        {
+            let mut source_map = self.source_map.lock().unwrap();
+            for (from, to) in &config.source_map {
+                match source_map.iter_mut().find(|(existing, _)| existing == from) {
+                    Some(entry) => entry.1 = to.clone(),
+                    None => source_map.push((from.clone(), to.clone())),
+                }
+            }
+        }
+        *self.config.lock().unwrap() = config.clone();
+        Ok(config)
+    }
+
+    /// Finds the source file a debugger names: an absolute path as is, otherwise the
+    /// project source whose path ends with it (LLDB reports only `main.rs`), or a path
+    /// relative to the working directory.
+    async fn resolve_source_path(&self, file: &str) -> Option<std::path::PathBuf> {
+        let remapped = self.remap_source_path(file);
+        let path = remapped.as_deref().unwrap_or(std::path::Path::new(file));
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        let project_file = self
+            .source_files()
+            .await
+            .into_iter()
+            .filter(|candidate| candidate.ends_with(path))
+            .min_by_key(|candidate| candidate.components().count());
+        project_file.or_else(|| {
+            path.is_file()
+                .then(|| std::fs::canonicalize(path).ok())
+                .flatten()
+        })
+    }
+
+    /// Reads lines `start..=end` of `path` (clamped to the file), numbered and with
+    /// `current` marked. Returns the last line actually shown and the listing.
+    fn source_listing(
+        path: &std::path::Path,
+        start: u32,
+        end: u32,
+        current: Option<u32>,
+    ) -> Result<(u32, String)> {
+        let source = std::fs::read_to_string(path)?;
+        let width = end.to_string().len();
+        let mut last = start;
+        let mut text = String::new();
+        for (number, line) in (1..).zip(source.lines()) {
+            if number < start {
+                continue;
+            }
+            if number > end {
+                break;
+            }
+            let marker = if Some(number) == current { '>' } else { ' ' };
+            text.push_str(&format!("{} {:>width$} | {}\n", marker, number, line));
+            last = number;
+        }
+        Ok((last, text))
+    }
+
+    /// The `source` of a stop report: `--source-context` lines on each side of the line
+    /// the program stopped at, or `None` if the file cannot be found.
+    async fn stop_source(&self, location: &str) -> Option<Value> {
+        let (file, line) = Self::parse_stop_location(location)?;
+        if self.options.source_context == 0 {
+            return None;
+        }
+        let path = self.resolve_source_path(file).await?;
+        self.source_around(&path, line)
+    }
+
+    /// `--source-context` lines of `path` on each side of `line`, with `line` marked, or
+    /// `None` if they cannot be read or source context is off.
+    fn source_around(&self, path: &std::path::Path, line: u32) -> Option<Value> {
+        let context = self.options.source_context;
+        if context == 0 {
+            return None;
+        }
+        let start = line.saturating_sub(context).max(1);
+        let (_, text) = Self::source_listing(path, start, line + context, Some(line)).ok()?;
+        Some(json!({
+            "path": path,
+            "line": line,
+            "text": text
+        }))
+    }
+
+    /// Shows lines of a source file, by default those around where the program stopped.
+    ///
+    /// `file` may be a path or a file name such as `main.rs`, which is looked up among
+    /// the project's sources. Without it, the file of the current stop location is
+    /// shown; without lines, the lines around the stop (or the start of the file).
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_source", "arguments": {}}
+    /// ```
+    ///
+    /// ```json
+    /// {"name": "debug_source", "arguments": {"file": "src/parser.rs", "start_line": 120, "end_line": 160}}
+    /// ```
+    async fn debug_source(
+        &self,
+        file: Option<&str>,
+        start_line: Option<u32>,
+        end_line: Option<u32>,
+    ) -> Result<Value> {
+        let location = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .and_then(|session| session.current_location.clone())
+        };
+        let stop = location.as_deref().and_then(Self::parse_stop_location);
+
+        let Some(file) = file.or(stop.map(|(file, _)| file)) else {
+            return Err(ErrorKind::InvalidArgument
+                .error("file required when the program is not stopped at a source line"));
+        };
+        let Some(path) = self.resolve_source_path(file).await else {
+            return Ok(json!({
+                "success": false,
+                "error": format!("Source file not found: {}", file),
+                "error_kind": ErrorKind::LocationNotFound.name()
+            }));
+        };
+
+        // The stop line, if the program is stopped in this file
+        let current = match stop {
+            Some((stop_file, line))
+                if self.resolve_source_path(stop_file).await.as_ref() == Some(&path) =>
+            {
+                Some(line)
+            }
+            _ => None,
+        };
+        let start = start_line
+            .or(current.map(|line| line.saturating_sub(SOURCE_LINES / 2)))
+            .unwrap_or(1)
+            .max(1);
+        let end = end_line.unwrap_or(start + SOURCE_LINES);
+        if end < start {
+            return Err(ErrorKind::InvalidArgument.error("end_line must not be before start_line"));
+        }
+        let end = end.min(start + MAX_SOURCE_LINES - 1);
+
+        let (end, text) = Self::source_listing(&path, start, end, current)?;
+        Ok(json!({
+            "success": true,
+            "path": path,
+            "start_line": start,
+            "end_line": end,
+            "line": current,
+            "text": text
+        }))
+    }
+
+    /// Rewrites `file` by the first source map entry whose `from` prefix it starts with.
+    fn remap_source_path(&self, file: &str) -> Option<std::path::PathBuf> {
+        let source_map = self.source_map.lock().unwrap();
+        source_map.iter().find_map(|(from, to)| {
+            std::path::Path::new(file)
+                .strip_prefix(from)
+                .ok()
+                .map(|rest| std::path::Path::new(to).join(rest))
+        })
+    }
+
+    /// Maps source paths starting with `from`, as recorded in the binary's debug info,
+    /// to `to` on this machine.
+    ///
+    /// Binaries built in CI or a container name sources by paths that do not exist
+    /// locally, so line breakpoints miss and no source context is shown. The mapping
+    /// applies to the current session, if any, and to every later one; mapping the same
+    /// `from` again replaces its `to`. Start the server with `--source-map <from>=<to>`
+    /// to have mappings from the start.
+    ///
+    /// # Examples
+    ///
+    /// ```json
+    /// {"name": "debug_set_source_map", "arguments": {"from": "/build/app", "to": "/home/me/app"}}
+    /// ```
+    async fn debug_set_source_map(&self, from: &str, to: &str) -> Result<Value> {
+        let source_map = {
+            let mut source_map = self.source_map.lock().unwrap();
+            match source_map.iter_mut().find(|(existing, _)| existing == from) {
+                Some(entry) => entry.1 = to.to_string(),
+                None => source_map.push((from.to_string(), to.to_string())),
+            }
+            source_map.clone()
+        };
+        let entries: Vec<Value> = source_map
+            .iter()
+            .map(|(from, to)| json!({"from": from, "to": to}))
+            .collect();
+
+        let Some(kind) = self.debugger_kind().await else {
+            return Ok(json!({
+                "success": true,
+                "applied": false,
+                "source_map": entries,
+                "message": "No active session; the mapping applies to the next one"
+            }));
+        };
+        let (success, output) = self.send_source_map(kind, &source_map).await?;
+        let mut result = json!({
+            "success": success,
+            "applied": success,
+            "source_map": entries,
+            "output": output.trim()
+        });
+        if !success {
+            result["error"] = json!("The debugger rejected the source map");
+            result["error_kind"] = json!(ErrorKind::CommandFailed.name());
+        }
+        Ok(result)
+    }
+
+    /// Fingerprints of what the session resources show, compared before and after
+    /// a tool call to tell subscribers which ones changed, along with the project
+    /// directory whose sources are listed.
+    async fn resource_versions(&self) -> (Option<String>, Vec<(&'static str, String)>) {
+        let session_guard = self.session.lock().await;
+        let Some(session) = session_guard.as_ref() else {
+            return (None, Vec::new());
+        };
+        let breakpoints: Vec<Value> = session
+            .breakpoints
+            .iter()
+            .map(Breakpoint::to_json)
+            .chain(session.watchpoints.iter().map(Watchpoint::to_json))
+            .collect();
+        let versions = vec![
+            (
+                BACKTRACE_RESOURCE,
+                format!(
+                    "{:?} {:?} {:?} {}",
+                    session.state,
+                    session.current_location,
+                    session.selected_thread,
+                    session.selected_frame
+                ),
+            ),
+            (BREAKPOINTS_RESOURCE, Value::from(breakpoints).to_string()),
+            (
+                LOG_RESOURCE,
+                format!("{} {:?}", session.log.len(), session.log.back()),
+            ),
+        ];
+        (session.source_dir.clone(), versions)
+    }
+
+    /// Sends `notifications/resources/updated` for each subscribed resource whose
+    /// fingerprint changed, and `notifications/resources/list_changed` when the
+    /// project, and so the list of source files, did.
+    fn notify_resource_changes(
+        &self,
+        (source_dir_before, before): &(Option<String>, Vec<(&'static str, String)>),
+        (source_dir_after, after): &(Option<String>, Vec<(&'static str, String)>),
+    ) {
+        if source_dir_before != source_dir_after {
+            let _ = self.notifications.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/resources/list_changed"
+            }));
+        }
+
+        let subscriptions = self.subscriptions.lock().unwrap();
+        for (uri, version) in after {
+            let changed = !before.contains(&(*uri, version.clone()));
+            if changed && subscriptions.contains(*uri) {
+                let _ = self.notifications.send(json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/resources/updated",
+                    "params": { "uri": uri }
+                }));
+            }
+        }
+    }
+
+    async fn handle_list_tools(&self) -> Value {
+        let mut tools = json!({
+            "tools": [
+                {
+                    "name": "debug_run",
+                    "description": "Load and prepare a Rust program for debugging, with defaults from the project's ferroscope.toml",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "binary_path": {
+                                "type": "string",
+                                "description": "Path to the Rust binary or source directory to debug"
+                            },
+                            "project_path": {
+                                "type": "string",
+                                "description": "Source directory to build and debug; an alternative to binary_path"
+                            },
+                            "package": {
+                                "type": "string",
+                                "description": "Workspace member to build, like cargo's --package (source directories only)"
+                            },
+                            "bin": {
+                                "type": "string",
+                                "description": "Binary target to build and debug, like cargo's --bin"
+                            },
+                            "example": {
+                                "type": "string",
+                                "description": "Example to build and debug instead of a binary, like cargo's --example"
+                            },
+                            "bench": {
+                                "type": "string",
+                                "description": "Benchmark to build and debug instead of a binary, like cargo's --bench; it runs with --bench unless args are given"
+                            },
+                            "features": {
+                                "type": ["array", "string"],
+                                "items": {"type": "string"},
+                                "description": "Features to enable, like cargo's --features (e.g., ['tls'] or 'tls,json')"
+                            },
+                            "profile": {
+                                "type": "string",
+                                "description": "Cargo profile to build with, like cargo's --profile (e.g., 'release'; default: dev)"
+                            },
+                            "debuginfo": {
+                                "type": "boolean",
+                                "description": "Build the profile with debug info (e.g., CARGO_PROFILE_RELEASE_DEBUG=true), so optimized binaries keep their symbols (default: false)"
+                            },
+                            "sanitizers": {
+                                "type": ["array", "string"],
+                                "items": {"type": "string", "enum": ["address", "thread", "memory"]},
+                                "description": "Build with -Zsanitizer on nightly and stop when the sanitizer reports a memory error or data race; stop reports then carry sanitizer_findings (e.g., ['address'])"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: lldb). lldb-mi and gdb use the GDB/MI machine interface for structured responses"
+                            },
+                            "args": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Command-line arguments for the program"
+                            },
+                            "env": {
+                                "type": "object",
+                                "additionalProperties": {"type": "string"},
+                                "description": "Environment variables to set for the program"
+                            },
+                            "cwd": {
+                                "type": "string",
+                                "description": "Working directory for the program"
+                            },
+                            "stdin_file": {
+                                "type": "string",
+                                "description": "File to feed to the program's standard input"
+                            },
+                            "follow_fork": {
+                                "type": "string",
+                                "enum": ["parent", "child", "both"],
+                                "description": "Process to keep debugging when the program forks: parent (default), child, or both, which keeps the child as a second inferior (gdb only)"
+                            },
+                            "break_on_panic": {
+                                "type": "boolean",
+                                "description": "Stop on rust_panic, core::panicking::panic_fmt, and abort so panics can be inspected (default: true)"
+                            },
+                            "restore_breakpoints": {
+                                "type": "boolean",
+                                "description": "Set the breakpoints saved in the project's .ferroscope/breakpoints.json (default: true)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_test",
+                    "description": "Build a project's tests and debug one of them, stopped at the start of the test function",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "project_path": {
+                                "type": "string",
+                                "description": "Path to the Rust project directory"
+                            },
+                            "test_name": {
+                                "type": "string",
+                                "description": "Test path as listed by cargo test, or a unique suffix of it (e.g., 'tests::parses_empty_input' or 'parses_empty_input')"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: lldb). lldb-mi and gdb use the GDB/MI machine interface for structured responses"
+                            }
+                        },
+                        "required": ["project_path", "test_name"]
+                    }
+                },
+                {
+                    "name": "debug_miri",
+                    "description": "Run a project or its tests under Miri (cargo miri run/test) and report undefined behavior with kind, span, source, help, and backtrace",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "project_path": {
+                                "type": "string",
+                                "description": "Path to the Rust project directory"
+                            },
+                            "test_name": {
+                                "type": "string",
+                                "description": "Run the tests matching this name with cargo miri test instead of the binary with cargo miri run"
+                            }
+                        },
+                        "required": ["project_path"]
+                    }
+                },
+                {
+                    "name": "debug_launch_command",
+                    "description": "Launch a command, such as a launcher script, under the debugger and stop at its first instruction",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "command": {
+                                "type": "string",
+                                "description": "Executable or #! script to run, as a path or a name on PATH"
+                            },
+                            "args": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Command-line arguments for the command"
+                            },
+                            "env": {
+                                "type": "object",
+                                "additionalProperties": {"type": "string"},
+                                "description": "Environment variables to set for the command"
+                            },
+                            "cwd": {
+                                "type": "string",
+                                "description": "Working directory for the command"
+                            },
+                            "stdin_file": {
+                                "type": "string",
+                                "description": "File to feed to the command's standard input"
+                            },
+                            "follow_fork": {
+                                "type": "string",
+                                "enum": ["parent", "child", "both"],
+                                "description": "Process to keep debugging when the command forks: parent (default), child, or both (gdb only)"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: lldb)"
+                            }
+                        },
+                        "required": ["command"]
+                    }
+                },
+                {
+                    "name": "debug_attach",
+                    "description": "Attach the debugger to a running process and stop it for inspection",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "pid": {
+                                "type": "integer",
+                                "description": "Process ID to attach to"
+                            },
+                            "process_name": {
+                                "type": "string",
+                                "description": "Executable name to attach to (used when pid is not given)"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: lldb)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_open_core",
+                    "description": "Open a core dump for post-mortem inspection: backtraces, frames, locals, and memory reads work, but the program cannot be run or stepped",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "binary_path": {
+                                "type": "string",
+                                "description": "Path to the binary that produced the core file"
+                            },
+                            "core_path": {
+                                "type": "string",
+                                "description": "Path to the core file"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: lldb)"
+                            }
+                        },
+                        "required": ["binary_path", "core_path"]
+                    }
+                },
+                {
+                    "name": "debug_connect_target",
+                    "description": "Connect to an embedded target through a GDB server (OpenOCD, J-Link, probe-rs), optionally resetting and flashing it. The target is left halted; there is no OS process",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "binary_path": {
+                                "type": "string",
+                                "description": "Path to the firmware ELF with debug symbols"
+                            },
+                            "remote": {
+                                "type": "string",
+                                "description": "GDB server address, e.g. localhost:3333 (OpenOCD) or localhost:1337 (probe-rs)"
+                            },
+                            "debugger": {
+                                "type": "string",
+                                "enum": ["lldb", "lldb-mi", "gdb"],
+                                "description": "Debugger to use (default: gdb)"
+                            },
+                            "reset": {
+                                "type": "boolean",
+                                "description": "Halt the core with 'monitor reset halt' after connecting (default: true)"
+                            },
+                            "flash": {
+                                "type": "boolean",
+                                "description": "Flash the firmware to the target after connecting (default: false)"
+                            }
+                        },
+                        "required": ["binary_path", "remote"]
+                    }
+                },
+                {
+                    "name": "debug_monitor",
+                    "description": "Send a monitor command to the GDB server of an embedded target, e.g. 'reset halt'",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "command": {
+                                "type": "string",
+                                "description": "Server-specific monitor command"
+                            }
+                        },
+                        "required": ["command"]
+                    }
+                },
+                {
+                    "name": "debug_record",
+                    "description": "Record a run of the program with rr (Linux) so it can be replayed and executed backwards with debug_replay",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "binary_path": {
+                                "type": "string",
+                                "description": "Path to the Rust binary or source directory to record"
+                            },
+                            "args": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Command-line arguments for the program"
+                            },
+                            "env": {
+                                "type": "object",
+                                "additionalProperties": {"type": "string"},
+                                "description": "Environment variables to set for the program"
+                            },
+                            "cwd": {
+                                "type": "string",
+                                "description": "Working directory for the program"
+                            },
+                            "stdin_file": {
+                                "type": "string",
+                                "description": "File to feed to the program's standard input"
+                            }
+                        },
+                        "required": ["binary_path"]
+                    }
+                },
+                {
+                    "name": "debug_replay",
+                    "description": "Replay an rr recording under GDB, stopped at its start; enables debug_reverse_continue, debug_reverse_step, and debug_reverse_step_into",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "trace_dir": {
+                                "type": "string",
+                                "description": "Trace directory reported by debug_record (default: the latest recording)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_reverse_continue",
+                    "description": "Run a replay backwards until a breakpoint, watchpoint, or the start of the recording",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_reverse_step",
+                    "description": "Step a replay back to the previous line, over function calls",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_reverse_step_into",
+                    "description": "Step a replay back to the previous line, into function calls",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_break",
+                    "description": "Set a breakpoint at the specified function or line",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "location": {
+                                "type": "string",
+                                "description": "Function name or file:line to break at"
+                            },
+                            "condition": {
+                                "type": "string",
+                                "description": "Only stop when this expression is true (e.g. \"i == 42\")"
+                            },
+                            "ignore_count": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "description": "Number of times to skip the breakpoint before stopping"
+                            },
+                            "log_only": {
+                                "type": "boolean",
+                                "description": "Tracepoint: instead of stopping, record log_expressions with a timestamp for debug_trace_log and continue (while debug_continue waits)"
+                            },
+                            "log_expressions": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Expressions evaluated on each hit of a log_only breakpoint (implies log_only)"
+                            },
+                            "on_hit_commands": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Debugger commands to run on each hit (e.g. [\"frame variable -T config\", \"continue\"]); ending with continue keeps the program running"
+                            }
+                        },
+                        "required": ["location"]
+                    }
+                },
+                {
+                    "name": "debug_break_module",
+                    "description": "Stop whenever any function of a crate or module runs, e.g. my_crate::db, methods and trait impls included",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "Module path (e.g., 'my_crate::db'), or a regular expression matched against function names"
+                            }
+                        },
+                        "required": ["pattern"]
+                    }
+                },
+                {
+                    "name": "debug_break_on_error",
+                    "description": "Stop where errors originate: on every line of a function that constructs Err(..), and/or whenever unwinding begins for a panic or foreign exception",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "function": {
+                                "type": "string",
+                                "description": "Function whose Err(..) constructions to break on (e.g., 'my_crate::config::load')"
+                            },
+                            "unwind": {
+                                "type": "boolean",
+                                "description": "Also break whenever unwinding begins (default: false)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_break_delete",
+                    "description": "Delete breakpoints by ID or by location",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "integer",
+                                "description": "Breakpoint ID as returned by debug_break"
+                            },
+                            "location": {
+                                "type": "string",
+                                "description": "Location the breakpoint was set at (used when id is not given)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_break_disable",
+                    "description": "Disable breakpoints by ID or by location without deleting them",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "integer",
+                                "description": "Breakpoint ID as returned by debug_break"
+                            },
+                            "location": {
+                                "type": "string",
+                                "description": "Location the breakpoint was set at (used when id is not given)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_break_enable",
+                    "description": "Re-enable disabled breakpoints by ID or by location",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "integer",
+                                "description": "Breakpoint ID as returned by debug_break"
+                            },
+                            "location": {
+                                "type": "string",
+                                "description": "Location the breakpoint was set at (used when id is not given)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_watch",
+                    "description": "Set a watchpoint that stops the program when a variable or address is written or read",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "variable": {
+                                "type": "string",
+                                "description": "Variable in the current frame to watch"
+                            },
+                            "address": {
+                                "type": "string",
+                                "description": "Memory address to watch (used when variable is not given)"
+                            },
+                            "size": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Number of bytes to watch at address (default: 8)"
+                            },
+                            "mode": {
+                                "type": "string",
+                                "enum": ["write", "read", "read_write"],
+                                "description": "Which accesses trigger the watchpoint (default: write)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_watch_field",
+                    "description": "Watch one field of a struct for writes; each hit reports the old and new value and the writing backtrace",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "object_expr": {
+                                "type": "string",
+                                "description": "Expression for the struct in the current frame, e.g. self.stats or *node"
+                            },
+                            "field": {
+                                "type": "string",
+                                "description": "Field to watch, e.g. hits or inner.len"
+                            }
+                        },
+                        "required": ["object_expr", "field"]
+                    }
+                },
+                {
+                    "name": "debug_modules",
+                    "description": "List the loaded binary and shared libraries with load addresses and whether each has debug info",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_symbols",
+                    "description": "Find functions, types, or global variables whose names match a regex, e.g. to get a breakpoint name right",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "Regular expression matched against names (e.g., 'parse_', 'Config::new$')"
+                            },
+                            "kind": {
+                                "type": "string",
+                                "enum": ["functions", "types", "variables"],
+                                "description": "What to search for (default: functions)"
+                            },
+                            "max_results": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "maximum": 1000,
+                                "description": "Most matches to return (default: 50)"
+                            }
+                        },
+                        "required": ["pattern"]
+                    }
+                },
+                {
+                    "name": "debug_source",
+                    "description": "Show numbered source lines, by default around where the program stopped",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "file": {
+                                "type": "string",
+                                "description": "Path or file name such as 'main.rs' (default: file of the current stop)"
+                            },
+                            "start_line": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "First line to show (default: 10 lines before the stop, or 1)"
+                            },
+                            "end_line": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Last line to show (default: 20 lines after start_line, at most 500)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_set_source_map",
+                    "description": "Map source paths recorded at build time (e.g. in CI or a container) to local paths, for line breakpoints and source context",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "from": {
+                                "type": "string",
+                                "description": "Path prefix recorded in the debug info (e.g., '/build/app')"
+                            },
+                            "to": {
+                                "type": "string",
+                                "description": "Local directory to use instead (e.g., '/home/me/app')"
+                            }
+                        },
+                        "required": ["from", "to"]
+                    }
+                },
+                {
+                    "name": "debug_watch_expr",
+                    "description": "Re-evaluate an expression at every stop and report its value in step and continue results",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expression": {
+                                "type": "string",
+                                "description": "Expression to watch (e.g., 'count', 'self.items.len()')"
+                            }
+                        },
+                        "required": ["expression"]
+                    }
+                },
+                {
+                    "name": "debug_unwatch_expr",
+                    "description": "Stop re-evaluating an expression added with debug_watch_expr",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expression": {
+                                "type": "string",
+                                "description": "Watched expression to remove"
+                            }
+                        },
+                        "required": ["expression"]
+                    }
+                },
+                {
+                    "name": "debug_continue",
+                    "description": "Launch program (if not started) or continue execution until next breakpoint",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "args": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Override the program arguments given to debug_run (first launch only)"
+                            },
+                            "env": {
+                                "type": "object",
+                                "additionalProperties": {"type": "string"},
+                                "description": "Override the environment given to debug_run (first launch only)"
+                            },
+                            "cwd": {
+                                "type": "string",
+                                "description": "Override the working directory given to debug_run (first launch only)"
+                            },
+                            "stdin_file": {
+                                "type": "string",
+                                "description": "Override the stdin file given to debug_run (first launch only)"
+                            },
+                            "wait": {
+                                "type": "boolean",
+                                "description": "Wait for the program to stop (default: true). With false, return as soon as it is running and use debug_poll or debug_wait_for_stop"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_run_to",
+                    "description": "Run until a function or file:line is reached, using a temporary breakpoint",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "location": {
+                                "type": "string",
+                                "description": "Function name or file:line to run to (e.g., 'parse_config', 'src/main.rs:42')"
+                            }
+                        },
+                        "required": ["location"]
+                    }
+                },
+                {
+                    "name": "debug_return",
+                    "description": "Return from the current function immediately, optionally with a value, skipping the rest of it (requires the --allow-control-flow server flag)",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "value": {
+                                "type": "string",
+                                "description": "Return value, as an expression (default: none, for functions returning ())"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_jump",
+                    "description": "Move execution to another line without running the code in between (requires the --allow-control-flow server flag)",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "location": {
+                                "type": "string",
+                                "description": "Line in the current file, file:line, or address to continue from (e.g., '42', 'src/cache.rs:88')"
+                            }
+                        },
+                        "required": ["location"]
+                    }
+                },
+                {
+                    "name": "debug_step",
+                    "description": "Step to the next line of code (step over function calls)",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_step_into",
+                    "description": "Step into function calls",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_step_out",
+                    "description": "Step out of the current function",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_finish",
+                    "description": "Run until the current function returns and report its return value",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_until",
+                    "description": "Run until a later line in the current frame is reached, without a persistent breakpoint",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "line": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Line in the current source file to run to"
+                            }
+                        },
+                        "required": ["line"]
+                    }
+                },
+                {
+                    "name": "debug_step_instruction",
+                    "description": "Step a single machine instruction",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "over": {
+                                "type": "boolean",
+                                "description": "Step over call instructions instead of into them (default: false)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_eval",
+                    "description": "Evaluate an expression or inspect a variable in the current debugging context",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expression": {
+                                "type": "string",
+                                "description": "Expression or variable name to evaluate"
+                            },
+                            "format": {
+                                "type": "string",
+                                "enum": ["hex", "binary", "decimal", "char"],
+                                "description": "Format for integer values (default: the debugger's)"
+                            },
+                            "language": {
+                                "type": "string",
+                                "enum": ["rust", "c"],
+                                "description": "Language to parse the expression in, e.g. c in FFI frames (default: the frame's)"
+                            },
+                            "in_frame": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "description": "Frame to evaluate in, 0 being innermost (default: the selected frame)"
+                            },
+                            "in_thread": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Thread to evaluate in (default: the selected thread)"
+                            },
+                            "allow_side_effects": {
+                                "type": "boolean",
+                                "description": "Allow function calls and assignments, which can change the program (default: false)"
+                            }
+                        },
+                        "required": ["expression"]
+                    }
+                },
+                {
+                    "name": "debug_eval_many",
+                    "description": "Evaluate several expressions in one call, returning each value and type",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expressions": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "minItems": 1,
+                                "maxItems": 100,
+                                "description": "Expressions or variable names to evaluate in the current frame"
+                            },
+                            "allow_side_effects": {
+                                "type": "boolean",
+                                "description": "Allow function calls and assignments, which can change the program (default: false)"
+                            }
+                        },
+                        "required": ["expressions"]
+                    }
+                },
+                {
+                    "name": "debug_set_var",
+                    "description": "Assign a new value to a variable in the current frame, to test a fix without recompiling",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "name": {
+                                "type": "string",
+                                "description": "Variable or place to assign, e.g. len or self.items[2]"
+                            },
+                            "value": {
+                                "type": "string",
+                                "description": "New value, as an expression, e.g. 0 or other.len"
+                            }
+                        },
+                        "required": ["name", "value"]
+                    }
+                },
+                {
+                    "name": "debug_backtrace",
+                    "description": "Show the current call stack as structured frames",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "max_frames": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "description": "Only report this many of the innermost frames"
+                            },
+                            "all_threads": {
+                                "type": "boolean",
+                                "description": "Report the stacks of all threads (default: false)"
+                            },
+                            "async": {
+                                "type": "boolean",
+                                "description": "Show the await chain of the async task being polled instead of the physical stack, leaving out executor frames (default: false)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_locals",
+                    "description": "List all arguments and local variables of the selected frame as structured JSON",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "depth": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "description": "How many levels of struct fields and elements to expand (default: 1)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_eval_slice",
+                    "description": "Show the elements behind a raw pointer and a length, e.g. a *const T buffer",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "ptr_expr": {
+                                "type": "string",
+                                "description": "Expression for a pointer to the first element (e.g., 'self.buf', 'v.as_ptr()')"
+                            },
+                            "len_expr": {
+                                "type": "string",
+                                "description": "Expression or number giving the element count (e.g., 'self.len', '16')"
+                            },
+                            "type": {
+                                "type": "string",
+                                "description": "Element type to cast the pointer to (e.g., 'u32'); defaults to the pointer's own"
+                            }
+                        },
+                        "required": ["ptr_expr", "len_expr"]
+                    }
+                },
+                {
+                    "name": "debug_inspect",
+                    "description": "Expand a struct, enum, or collection into a nested JSON tree, with limits for large or cyclic data",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expression": {
+                                "type": "string",
+                                "description": "Variable path or expression to expand (e.g., 'self', 'config.servers')"
+                            },
+                            "depth": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "maximum": 10,
+                                "description": "How many levels of fields and elements to expand (default: 3)"
+                            },
+                            "max_children": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "maximum": 1000,
+                                "description": "Most fields or elements to list per node (default: 20)"
+                            }
+                        },
+                        "required": ["expression"]
+                    }
+                },
+                {
+                    "name": "debug_memory_read",
+                    "description": "Read raw memory at an address or pointer expression as hex, ASCII, and optionally typed values",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "address": {
+                                "type": "string",
+                                "description": "Address or expression evaluating to one (e.g., '0x7ffeefbff5c0', '&value', 'buf.as_ptr()')"
+                            },
+                            "count": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "maximum": 4096,
+                                "description": "Number of bytes to read (default: 64)"
+                            },
+                            "type": {
+                                "type": "string",
+                                "enum": ["u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64", "usize", "isize", "f32", "f64", "pointer"],
+                                "description": "Also decode the bytes as little-endian values of this type"
+                            }
+                        },
+                        "required": ["address"]
+                    }
+                },
+                {
+                    "name": "debug_memory_write",
+                    "description": "Write raw bytes to an address or pointer expression (requires the --allow-memory-write server flag)",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "address": {
+                                "type": "string",
+                                "description": "Address or expression evaluating to one"
+                            },
+                            "bytes": {
+                                "type": "string",
+                                "description": "Hex bytes to write (e.g., 'deadbeef' or 'de ad be ef')"
+                            }
+                        },
+                        "required": ["address", "bytes"]
+                    }
+                },
+                {
+                    "name": "debug_signals",
+                    "description": "Show or change whether a signal stops the program, is passed to it, and is reported",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "signal": {
+                                "type": "string",
+                                "description": "Signal name (e.g., 'SIGPIPE', 'SIGSEGV', 'SIGINT')"
+                            },
+                            "stop": {
+                                "type": "boolean",
+                                "description": "Stop the program when the signal arrives"
+                            },
+                            "pass": {
+                                "type": "boolean",
+                                "description": "Deliver the signal to the program"
+                            },
+                            "notify": {
+                                "type": "boolean",
+                                "description": "Report the signal when it arrives"
+                            }
+                        },
+                        "required": ["signal"]
+                    }
+                },
+                {
+                    "name": "debug_threads",
+                    "description": "List all threads with their IDs, names, stop reasons, and top frames",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_inferiors",
+                    "description": "List the processes of the session (GDB inferiors or LLDB targets), e.g. a parent and its forked child",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_select_inferior",
+                    "description": "Switch to another process of the session, so execution control and inspection apply to it",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "integer",
+                                "description": "Inferior ID as reported by debug_inferiors"
+                            }
+                        },
+                        "required": ["id"]
+                    }
+                },
+                {
+                    "name": "debug_checkpoint",
+                    "description": "Snapshot the stopped program by forking a copy of it (GDB on Linux), to rewind to later with debug_restore_checkpoint",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_restore_checkpoint",
+                    "description": "Rewind the program to a checkpoint taken with debug_checkpoint",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "type": "integer",
+                                "description": "Checkpoint ID as returned by debug_checkpoint"
+                            }
+                        },
+                        "required": ["id"]
+                    }
+                },
+                {
+                    "name": "debug_async_tasks",
+                    "description": "List the tokio tasks the program spawns, with where each is polled and the threads polling it now, and optionally break when one is polled",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "break_on": {
+                                "type": "string",
+                                "description": "Future or poll function of a listed task to set a breakpoint on"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_analyze_deadlock",
+                    "description": "Diagnose a hang: pause the program, find threads blocked on Mutex, RwLock, Condvar, join, or channel receive, and report who waits on which lock held by whom, and any deadlock cycle",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_triage_crash",
+                    "description": "Report on a crash in one call: fatal signal and fault address, the crashing thread's backtrace, disassembly around the PC, registers, and the locals of the innermost user frame",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_panic_info",
+                    "description": "Explain the panic the program is stopped in: its message, file/line/column, and the backtrace of your own code with internal panic frames filtered out",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_heap_profile",
+                    "description": "Track heap allocations (Linux): the first call turns tracking on for the next launch; later calls report live allocations grouped by the backtrace that made them, to chase leaks and unbounded growth",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "top": {
+                                "type": "integer",
+                                "description": "Allocation sites to report, those holding the most bytes first (default: 10)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_profile",
+                    "description": "Sample where the program spends CPU time (perf on Linux, sample on macOS) and report the hottest functions and flame-graph-ready folded stacks; a stopped program runs while sampled",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "duration_ms": {
+                                "type": "integer",
+                                "description": "How long to sample, in milliseconds (default: 1000)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_syscalls",
+                    "description": "Trace the program's system calls with arguments, paths, return values, and errno names while it runs (GDB on Linux, dtruss on macOS), to debug file and network misbehavior without breakpoints",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "duration_ms": {
+                                "type": "integer",
+                                "description": "How long to trace, in milliseconds (default: 1000, or 60000 with until_stop)"
+                            },
+                            "until_stop": {
+                                "type": "boolean",
+                                "description": "Trace until the program stops at a breakpoint, on a signal, or exits, waiting up to duration_ms"
+                            },
+                            "syscalls": {
+                                "type": "array",
+                                "items": {"type": "string"},
+                                "description": "Only trace these syscalls, e.g. [\"openat\", \"connect\"]"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_select_thread",
+                    "description": "Select the thread that stepping, backtraces, and evaluation apply to",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "thread_id": {
+                                "type": "integer",
+                                "description": "Thread ID as reported by debug_threads"
+                            }
+                        },
+                        "required": ["thread_id"]
+                    }
+                },
+                {
+                    "name": "debug_select_frame",
+                    "description": "Select a stack frame of the current thread so evaluation sees its variables",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "index": {
+                                "type": "integer",
+                                "minimum": 0,
+                                "description": "Frame number from debug_backtrace (0 is the innermost frame)"
+                            }
+                        },
+                        "required": ["index"]
+                    }
+                },
+                {
+                    "name": "debug_frame_info",
+                    "description": "Describe the currently selected stack frame",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_list_breakpoints",
+                    "description": "List all active breakpoints",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_record_values",
+                    "description": "Step through a function or line range and return the value an expression took at each line, with the steps where it changed; answers \"when does x become negative?\" without manual stepping",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "expression": {
+                                "type": "string",
+                                "description": "Expression to record at each line, e.g. a local variable"
+                            },
+                            "function": {
+                                "type": "string",
+                                "description": "Record through this function until it returns"
+                            },
+                            "file": {
+                                "type": "string",
+                                "description": "Source file of a line range to record through, instead of a function"
+                            },
+                            "start_line": {
+                                "type": "integer",
+                                "description": "First line of the range"
+                            },
+                            "end_line": {
+                                "type": "integer",
+                                "description": "Last line of the range (default: start_line)"
+                            },
+                            "max_steps": {
+                                "type": "integer",
+                                "description": "Most lines to step through (default: 500)"
+                            }
+                        },
+                        "required": ["expression"]
+                    }
+                },
+                {
+                    "name": "debug_trace_calls",
+                    "description": "Trace every call of the functions matching a regex, e.g. my_crate::parser::, with auto-continuing breakpoints that log each call (and optionally its arguments) for debug_trace_log",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "pattern": {
+                                "type": "string",
+                                "description": "Regular expression matched against function names"
+                            },
+                            "args": {
+                                "type": "boolean",
+                                "description": "Also record each call's arguments, which slows every call (default: false)"
+                            },
+                            "max_functions": {
+                                "type": "integer",
+                                "description": "Most functions to trace (default: 100)"
+                            }
+                        },
+                        "required": ["pattern"]
+                    }
+                },
+                {
+                    "name": "debug_trace_log",
+                    "description": "Get the values recorded at each hit of log_only breakpoints, oldest first",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "breakpoint_id": {
+                                "type": "integer",
+                                "description": "Only return hits of this breakpoint"
+                            },
+                            "clear": {
+                                "type": "boolean",
+                                "description": "Remove the returned entries from the log (default: false)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_program_output",
+                    "description": "Get everything the debugged program has written to stdout and stderr",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_stdin_write",
+                    "description": "Write text to the debugged program's stdin, e.g. to answer a prompt; can be called before debug_continue",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "data": {
+                                "type": "string",
+                                "description": "Text to write, including any trailing newline"
+                            },
+                            "eof": {
+                                "type": "boolean",
+                                "description": "Close stdin after writing so the program sees end-of-file (default: false)"
+                            }
+                        },
+                        "required": ["data"]
+                    }
+                },
+                {
+                    "name": "debug_detach",
+                    "description": "Detach from the process, leaving it running, and end the debugging session",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_wait_for_stop",
+                    "description": "Wait until the running program hits a breakpoint, crashes, or exits",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "timeout_secs": {
+                                "type": "integer",
+                                "minimum": 1,
+                                "maximum": 3600,
+                                "description": "Longest time to wait in seconds (default: 60)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_pause",
+                    "description": "Interrupt the running program so it can be inspected, e.g. to find where it hangs",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_poll",
+                    "description": "Report the latest state and any new debugger and program output without waiting",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_restart",
+                    "description": "Kill and relaunch the program, keeping breakpoints, watchpoints, and launch configuration",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "rebuild": {
+                                "type": "boolean",
+                                "description": "Rebuild the project with cargo build before relaunching (default: false)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_rebuild",
+                    "description": "Rebuild the project and, if the binary changed, reload it with the same breakpoints, ready for debug_continue",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_recover",
+                    "description": "Start a new debugger after the session's one exited (state debuggerdied), loading the same program and setting its breakpoints again, ready for debug_continue",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_kill",
+                    "description": "Terminate the debugged process and end the debugging session",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_save_breakpoints",
+                    "description": "Save this session's breakpoints, with conditions, trace settings, and hit commands, to a file; by default the project's .ferroscope/breakpoints.json, which is also kept up to date automatically and loaded by debug_run",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "File to save to (default: .ferroscope/breakpoints.json in the project)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_load_breakpoints",
+                    "description": "Set the breakpoints saved in a file, re-resolving their file:line and function locations against the loaded binary; breakpoints already set are skipped",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "path": {
+                                "type": "string",
+                                "description": "File to load from (default: .ferroscope/breakpoints.json in the project)"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_export_session",
+                    "description": "Export everything that happened on this server, every tool call with its result, debugger command and response, state transition, and breakpoint hit, as one JSON or Markdown document to attach to a bug report",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "format": {
+                                "type": "string",
+                                "enum": ["json", "markdown"],
+                                "description": "Document format (default: json)"
+                            },
+                            "path": {
+                                "type": "string",
+                                "description": "File to write the document to instead of returning it"
+                            }
+                        }
+                    }
+                },
+                {
+                    "name": "debug_doctor",
+                    "description": "Check the debugging setup: debuggers and their versions, the Rust formatters, attach permissions, the configuration files, and a probe program built and stopped end to end. Failed checks carry a fix",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_sessions",
+                    "description": "List the active debugging sessions with their debugger and program PIDs, state, age, and how long they have been idle. Idle sessions are shut down after idle_timeout_secs",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                },
+                {
+                    "name": "debug_more_output",
+                    "description": "Page through the complete result of a tool call that came back with truncated: true, as JSON text, max_output_bytes at a time",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "cursor": {
+                                "type": "string",
+                                "description": "The cursor of the truncated result, or of the previous page"
+                            }
+                        },
+                        "required": ["cursor"]
+                    }
+                },
+                {
+                    "name": "debug_raw_command",
+                    "description": "Send one command line to the underlying LLDB or GDB and get its raw, unparsed output. Needs --allow-raw-commands; commands that reach the host, run the program, or change breakpoints are refused unless the server allows them",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "command": {
+                                "type": "string",
+                                "description": "Debugger command line, e.g. 'image lookup -a 0x1000' or 'info frame'"
+                            }
+                        },
+                        "required": ["command"]
+                    }
+                },
+                {
+                    "name": "debug_state",
+                    "description": "Get current debugging session state",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {}
+                    }
+                }
+            ]
+        });
+
+        // Every tool accepts a timeout for the debugger commands it sends and a cap on
+        // the size of its result
+        if let Some(tools) = tools["tools"].as_array_mut() {
+            for tool in tools {
+                // Structured tool output arrived in the 2025-06-18 revision
+                if self.protocol_at_least("2025-06-18") {
+                    let name = tool["name"].as_str().unwrap_or("").to_string();
+                    tool["outputSchema"] = tool_output_schema(&name);
+                }
+                tool["inputSchema"]["properties"]["timeout_ms"] = json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": format!(
+                        "How long to wait for each debugger command in milliseconds (default: {})",
+                        self.options.command_timeout.as_millis()
+                    )
+                });
+                tool["inputSchema"]["properties"]["max_output_bytes"] = json!({
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": match self.options.max_output_bytes {
+                        Some(max) => format!("Largest result to return whole, in bytes of JSON; larger ones are truncated and paged with debug_more_output (default: {})", max),
+                        None => "Largest result to return whole, in bytes of JSON; larger ones are truncated and paged with debug_more_output (default: no limit)".to_string(),
+                    }
+                });
+            }
+        }
+        tools
+    }
+
+    /// Binary or attach target of the current session.
+    async fn binary_path(&self) -> Option<String> {
+        let session_guard = self.session.lock().await;
+        session_guard.as_ref().map(|s| s.binary_path.clone())
+    }
+
+    /// Core file of the current session, if it is a post-mortem session.
+    async fn core_path(&self) -> Option<String> {
+        let session_guard = self.session.lock().await;
+        session_guard.as_ref().and_then(|s| s.core_path.clone())
+    }
+
+    /// Reads the optional `debugger` tool argument, defaulting to `--debugger` or LLDB.
+    pub(crate) fn debugger_argument(&self, arguments: &Value) -> Result<DebuggerKind> {
+        match arguments.get("debugger").and_then(|v| v.as_str()) {
+            Some(name) => DebuggerKind::from_name(name),
+            None => Ok(self.options.debugger.unwrap_or(DebuggerKind::Lldb)),
+        }
+    }
+
+    /// Sends a `notifications/progress` for the tool call running on this task, if
+    /// its request asked for progress with a `_meta.progressToken`.
+    ///
+    /// Without `progress`, the step after the last one reported is used; values that
+    /// would not increase the progress are dropped.
+    fn report_progress(&self, progress: Option<u64>, total: Option<u64>, message: &str) {
+        let _ = PROGRESS.try_with(|report| {
+            let mut last = report.last.lock().unwrap();
+            let progress = progress.unwrap_or_else(|| last.map_or(0, |last| last + 1));
+            if last.is_some_and(|last| progress <= last) {
+                return;
+            }
+            *last = Some(progress);
+
+            let mut params = json!({
+                "progressToken": report.token,
+                "progress": progress
+            });
+            if let Some(total) = total {
+                params["total"] = json!(total);
+            }
+            // Progress messages arrived in the 2025-03-26 revision
+            if self.protocol_at_least("2025-03-26") {
+                params["message"] = json!(message);
+            }
+            let _ = self.notifications.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": params
+            }));
+        });
+    }
+
+    /// Runs the tool `name` with its JSON `arguments` and returns its result, as a
+    /// `tools/call` request would without the MCP envelope.
+    ///
+    /// `timeout_ms` in `arguments` applies to this call's debugger commands.
+    /// Failures the agent can act on, such as a program that is not running, come
+    /// back as `success: false` results; errors are for unusable arguments or a
+    /// broken debugger connection.
+    pub async fn handle_call_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+        let timeout = match arguments.get("timeout_ms") {
+            Some(timeout) => timeout
+                .as_u64()
+                .filter(|&ms| ms > 0)
+                .map(std::time::Duration::from_millis)
+                .ok_or_else(|| {
+                    ErrorKind::InvalidArgument.error("timeout_ms must be a positive integer")
+                })?,
+            None => self.default_timeout(),
+        };
+        let max_output_bytes = self.max_output_bytes(&arguments)?;
+        let span = tracing::info_span!("tool", tool = name);
+        tracing::debug!(parent: &span, %arguments, "tool call");
+        // The export would otherwise contain its own, ever longer, predecessors
+        let recorded = name != "debug_export_session";
+        if recorded {
+            self.history
+                .lock()
+                .unwrap()
+                .push("tool", json!({"tool": name, "arguments": arguments}));
+        }
+        let tool_lock = self.tool_lock(name).await;
+        let _tool_guard = match &tool_lock {
+            Some(tool_lock) => Some(tool_lock.lock().await),
+            None => None,
+        };
+        let before = self.resource_versions().await;
+        let result: Result<Value> = COMMAND_TIMEOUT
+            .scope(timeout, async {
+                let mut result = self.call_tool(name, arguments).await?;
+                if let Some(stderr) = self.take_debugger_stderr().await {
+                    result["debugger_stderr"] = stderr;
+                }
+                if STOP_REPORT_TOOLS.contains(&name) {
+                    if let Some(inferiors) = self.take_new_inferiors().await {
+                        result["new_inferiors"] = inferiors;
+                    }
+                    if let Some(exceeded) = self.take_limit_exceeded().await {
+                        result["success"] = json!(false);
+                        result["error"] = json!(format!(
+                            "The program went over its {} limit of {} (used {}) and was killed",
+                            exceeded["limit"].as_str().unwrap_or("resource"),
+                            exceeded["max"],
+                            exceeded["used"]
+                        ));
+                        result["error_kind"] = json!(ErrorKind::ResourceLimitExceeded.name());
+                        result["resource_limit"] = exceeded;
+                    }
+                    if let Some(findings) =
+                        result["program_stderr"].as_str().map(sanitizer_findings)
+                    {
+                        if !findings.is_empty() {
+                            result["sanitizer_findings"] = json!(findings);
+                        }
+                    }
+                }
+                if STOP_REPORT_TOOLS.contains(&name) && result["state"] == "stopped" {
+                    if let Some(watches) = self.evaluate_watches().await? {
+                        result["watches"] = watches;
+                    }
+                    if let Some(change) = self.field_change().await? {
+                        result["field_change"] = change;
+                    }
+                    if let Some(location) = result["location"].as_str() {
+                        if let Some(source) = self.stop_source(location).await {
+                            result["source"] = source;
+                        }
+                    }
+                }
+                Ok(result)
+            })
+            .instrument(span.clone())
+            .await;
+        let after = self.resource_versions().await;
+        self.mark_session_used().await;
+        self.notify_resource_changes(&before, &after);
+        // Tools also set the state directly, e.g. to loaded or not_loaded
+        self.log_state(&self.current_state().await);
+
+        // Failures the tool did not classify are the debugger refusing the command, and
+        // a tool that found the program in the wrong state may have found no session
+        let mut result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if recorded {
+                    self.history
+                        .lock()
+                        .unwrap()
+                        .push("tool_result", json!({"tool": name, "error": e.to_string()}));
+                }
+                return Err(e);
+            }
+        };
+        if result["success"] == false {
+            match result["error_kind"].as_str() {
+                None => result["error_kind"] = json!(ErrorKind::CommandFailed.name()),
+                Some("wrong_state") if self.session.lock().await.is_none() => {
+                    result["error_kind"] = json!(ErrorKind::NoSession.name())
+                }
+                _ => {}
+            }
+        }
+        if result["success"] == true {
+            if let Err(e) = self.autosave_breakpoints().await {
+                tracing::warn!("cannot save breakpoints: {}", e);
+            }
+        }
+        tracing::debug!(parent: &span, %result, "tool result");
+        if recorded {
+            self.history
+                .lock()
+                .unwrap()
+                .push("tool_result", json!({"tool": name, "result": result}));
+        }
+        // Pages are already cut to size
+        if let Some(max_bytes) = max_output_bytes.filter(|_| name != "debug_more_output") {
+            let complete = result.to_string();
+            if complete.len() > max_bytes {
+                truncate_json(&mut result, max_bytes.saturating_sub(TRUNCATION_NOTE_BYTES));
+                result["truncated"] = json!(true);
+                result["total_bytes"] = json!(complete.len());
+                result["cursor"] = json!(self.output_pages.lock().unwrap().store(complete));
+            }
+        }
+        Ok(result)
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value> {
+        if EXECUTION_TOOLS.contains(&name) {
+            if let Some(core_path) = self.core_path().await {
+                return Ok(json!({
+                    "success": false,
+                    "error": format!(
+                        "{} is unavailable in a post-mortem session; the program in {} cannot run. Inspect it with debug_backtrace, debug_locals, debug_eval, or debug_memory_read",
+                        name, core_path
+                    ),
+                    "error_kind": ErrorKind::Unsupported.name(),
+                    "state": "stopped",
+                    "post_mortem": true
+                }));
+            }
+        }
+
+        match name {
+            "debug_run" => {
+                let binary_path = arguments
+                    .get("binary_path")
+                    .or_else(|| arguments.get("project_path"))
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| {
+                        ErrorKind::InvalidArgument.error("binary_path or project_path required")
+                    })?
+                    .to_string();
+                let mut arguments = arguments;
+                let config = self.load_project_config(&binary_path, &mut arguments)?;
+                let debugger = self.debugger_argument(&arguments)?;
+                let launch = LaunchConfig::from_arguments(&arguments)?;
+                let build = BuildConfig::from_arguments(&arguments)?;
+                let break_on_panic = arguments
+                    .get("break_on_panic")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let run = self.debug_run(&binary_path, debugger, launch, build, break_on_panic);
+                // The configured timeout already applies to the session being started
+                let mut result = match config.timeout {
+                    Some(timeout) if arguments.get("timeout_ms").is_none() => {
+                        COMMAND_TIMEOUT.scope(timeout, run).await?
+                    }
+                    _ => run.await?,
+                };
+                if result["success"] == true && !config.breakpoints.is_empty() {
+                    let mut breakpoints = Vec::new();
+                    for location in &config.breakpoints {
+                        let set = self.debug_break(location, None, None).await?;
+                        breakpoints.push(json!({
+                            "location": location,
+                            "id": set["id"],
+                            "success": set["success"]
+                        }));
+                    }
+                    result["config_breakpoints"] = json!(breakpoints);
+                }
+                if !config.files.is_empty() {
+                    result["config"] = config.to_json();
+                }
+                let restore = arguments
+                    .get("restore_breakpoints")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                if result["success"] == true && restore {
+                    let saved = self.breakpoints_file().await.filter(|path| path.is_file());
+                    if let Some(path) = saved {
+                        result["restored_breakpoints"] = json!(self.load_breakpoints(&path).await?);
+                    }
+                }
+                Ok(result)
+            }
+            "debug_test" => {
+                let argument = |name: &str| {
+                    arguments.get(name).and_then(|v| v.as_str()).ok_or_else(|| {
+                        ErrorKind::InvalidArgument.error(format!("{} required", name))
+                    })
+                };
+                let debugger = self.debugger_argument(&arguments)?;
+                self.debug_test(argument("project_path")?, argument("test_name")?, debugger)
+                    .await
+            }
+            "debug_miri" => {
+                let project_path = arguments
+                    .get("project_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("project_path required"))?;
+                let test_name = arguments.get("test_name").and_then(|v| v.as_str());
+                self.debug_miri(project_path, test_name).await
+            }
+            "debug_launch_command" => {
+                let command = arguments
+                    .get("command")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("command required"))?;
+                let debugger = self.debugger_argument(&arguments)?;
+                let launch = LaunchConfig::from_arguments(&arguments)?;
+                self.debug_launch_command(command, debugger, launch).await
+            }
+            "debug_attach" => {
+                let pid = arguments
+                    .get("pid")
+                    .and_then(|v| v.as_u64())
+                    .map(u32::try_from)
+                    .transpose()?;
+                let process_name = arguments.get("process_name").and_then(|v| v.as_str());
+                let debugger = self.debugger_argument(&arguments)?;
+                self.debug_attach(pid, process_name, debugger).await
+            }
+            "debug_open_core" => {
+                let binary_path = arguments
+                    .get("binary_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("binary_path required"))?;
+                let core_path = arguments
+                    .get("core_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("core_path required"))?;
+                let debugger = self.debugger_argument(&arguments)?;
+                self.debug_open_core(binary_path, core_path, debugger).await
+            }
+            "debug_record" => {
+                let binary_path = arguments
+                    .get("binary_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("binary_path required"))?;
+                let launch = LaunchConfig::from_arguments(&arguments)?;
+                self.debug_record(binary_path, launch).await
+            }
+            "debug_replay" => {
+                let trace_dir = arguments.get("trace_dir").and_then(|v| v.as_str());
+                self.debug_replay(trace_dir).await
+            }
+            "debug_reverse_continue" => self.debug_reverse("-exec-continue --reverse").await,
+            "debug_reverse_step" => self.debug_reverse("-exec-next --reverse").await,
+            "debug_reverse_step_into" => self.debug_reverse("-exec-step --reverse").await,
+            "debug_connect_target" => {
+                let binary_path = arguments
+                    .get("binary_path")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("binary_path required"))?;
+                let remote = arguments
+                    .get("remote")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("remote required"))?;
+                // Embedded toolchains ship GDB, so default to it here
+                let debugger = match arguments.get("debugger") {
+                    Some(_) => self.debugger_argument(&arguments)?,
+                    None => DebuggerKind::Gdb,
+                };
+                let reset = arguments
+                    .get("reset")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                let flash = arguments
+                    .get("flash")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_connect_target(binary_path, remote, debugger, reset, flash)
+                    .await
+            }
+            "debug_monitor" => {
+                let command = arguments
+                    .get("command")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("command required"))?;
+                self.debug_monitor(command).await
+            }
+            "debug_break" => {
+                let location = arguments
+                    .get("location")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("location required"))?;
+                let condition = arguments.get("condition").and_then(|v| v.as_str());
+                let ignore_count = arguments.get("ignore_count").and_then(|v| v.as_u64());
+                let log_expressions = string_array(&arguments, "log_expressions")?.or_else(|| {
+                    arguments
+                        .get("log_only")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                        .then(Vec::new)
+                });
+                let on_hit_commands =
+                    string_array(&arguments, "on_hit_commands")?.unwrap_or_default();
+                let mut result = self.debug_break(location, condition, ignore_count).await?;
+                let id = result["id"].as_u64().and_then(|id| u32::try_from(id).ok());
+                if let (Some(id), Some(expressions)) = (id, log_expressions.clone()) {
+                    self.set_log_expressions(id, expressions).await;
+                }
+                result["log_only"] = json!(id.is_some() && log_expressions.is_some());
+                result["log_expressions"] = json!(log_expressions);
+                if let (Some(id), false) = (id, on_hit_commands.is_empty()) {
+                    let mut commands = self.set_on_hit_commands(id, on_hit_commands).await?;
+                    if commands["success"] != true {
+                        result["success"] = json!(false);
+                        result["error"] = json!(format!(
+                            "The breakpoint was set, but its commands were rejected: {}",
+                            commands["output"].as_str().unwrap_or("")
+                        ));
+                        result["error_kind"] = json!(ErrorKind::CommandFailed.name());
+                    }
+                    result["on_hit_commands"] = commands["on_hit_commands"].take();
+                    result["auto_continue"] = commands["auto_continue"].take();
+                }
+                Ok(result)
+            }
+            "debug_trace_log" => {
+                let breakpoint_id = arguments
+                    .get("breakpoint_id")
+                    .and_then(|v| v.as_u64())
+                    .map(u32::try_from)
+                    .transpose()?;
+                let clear = arguments
+                    .get("clear")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_trace_log(breakpoint_id, clear).await
+            }
+            "debug_break_delete" | "debug_break_disable" | "debug_break_enable" => {
+                let action = match name {
+                    "debug_break_delete" => BreakpointAction::Delete,
+                    "debug_break_disable" => BreakpointAction::Disable,
+                    _ => BreakpointAction::Enable,
+                };
+                let id = arguments
+                    .get("id")
+                    .and_then(|v| v.as_u64())
+                    .map(u32::try_from)
+                    .transpose()?;
+                let location = arguments.get("location").and_then(|v| v.as_str());
+                self.debug_break_modify(action, id, location).await
+            }
+            "debug_watch" => {
+                let variable = arguments.get("variable").and_then(|v| v.as_str());
+                let address = arguments.get("address").and_then(|v| v.as_str());
+                let size = arguments.get("size").and_then(|v| v.as_u64()).unwrap_or(8);
+                let mode = match arguments.get("mode").and_then(|v| v.as_str()) {
+                    Some(mode) => WatchMode::from_name(mode)?,
+                    None => WatchMode::Write,
+                };
+                self.debug_watch(variable, address, size, mode).await
+            }
+            "debug_watch_field" => {
+                let object_expr = arguments
+                    .get("object_expr")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("object_expr required"))?;
+                let field = arguments
+                    .get("field")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("field required"))?;
+                self.debug_watch_field(object_expr, field).await
+            }
+            "debug_modules" => self.debug_modules().await,
+            "debug_break_on_error" => {
+                let function = arguments.get("function").and_then(|v| v.as_str());
+                let unwind = arguments
+                    .get("unwind")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if function.is_none() && !unwind {
+                    return Err(ErrorKind::InvalidArgument.error("function or unwind required"));
+                }
+                self.debug_break_on_error(function, unwind).await
+            }
+            "debug_break_module" => {
+                let pattern = arguments
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("pattern required"))?;
+                self.debug_break_module(pattern).await
+            }
+            "debug_trace_calls" => {
+                let pattern = arguments
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("pattern required"))?;
+                let log_arguments = arguments
+                    .get("args")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let max_functions = arguments
+                    .get("max_functions")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(100);
+                if !(1..=1000).contains(&max_functions) {
+                    return Err(ErrorKind::InvalidArgument
+                        .error("max_functions must be between 1 and 1000"));
+                }
+                self.debug_trace_calls(pattern, log_arguments, max_functions as usize)
+                    .await
+            }
+            "debug_symbols" => {
+                let pattern = arguments
+                    .get("pattern")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("pattern required"))?;
+                let kind = match arguments.get("kind").and_then(|v| v.as_str()) {
+                    Some(kind) => SymbolKind::from_name(kind)?,
+                    None => SymbolKind::Function,
+                };
+                let max_results = arguments
+                    .get("max_results")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(50);
+                if !(1..=1000).contains(&max_results) {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("max_results must be between 1 and 1000")
+                    );
+                }
+                self.debug_symbols(pattern, kind, max_results as usize)
+                    .await
+            }
+            "debug_source" => {
+                let file = arguments.get("file").and_then(|v| v.as_str());
+                let line = |name: &str| {
+                    arguments
+                        .get(name)
+                        .and_then(|v| v.as_u64())
+                        .map(u32::try_from)
+                        .transpose()
+                };
+                self.debug_source(file, line("start_line")?, line("end_line")?)
+                    .await
+            }
+            "debug_set_source_map" => {
+                let path = |name: &str| {
+                    arguments
+                        .get(name)
+                        .and_then(|v| v.as_str())
+                        .filter(|path| !path.is_empty())
+                        .ok_or_else(|| {
+                            ErrorKind::InvalidArgument.error(format!("{} required", name))
+                        })
+                };
+                self.debug_set_source_map(path("from")?, path("to")?).await
+            }
+            "debug_watch_expr" | "debug_unwatch_expr" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("expression required"))?;
+                if name == "debug_watch_expr" {
+                    self.debug_watch_expr(expression).await
+                } else {
+                    self.debug_unwatch_expr(expression).await
+                }
+            }
+            "debug_continue" => {
+                let overrides_launch = ["args", "env", "cwd", "stdin_file"]
+                    .iter()
+                    .any(|key| arguments.get(key).is_some());
+                let launch = if overrides_launch {
+                    Some(LaunchConfig::from_arguments(&arguments)?)
+                } else {
+                    None
+                };
+                let wait = arguments
+                    .get("wait")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                self.debug_continue(launch, wait).await
+            }
+            "debug_run_to" => {
+                let location = arguments
+                    .get("location")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("location required"))?;
+                self.debug_run_to(location).await
+            }
+            "debug_return" => {
+                let value = arguments.get("value").and_then(|v| v.as_str());
+                self.debug_return(value).await
+            }
+            "debug_jump" => {
+                let location = arguments
+                    .get("location")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("location required"))?;
+                self.debug_jump(location).await
+            }
+            "debug_record_values" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("expression required"))?;
+                let region = ValueRegion::from_arguments(&arguments)?;
+                let max_steps = arguments
+                    .get("max_steps")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(500);
+                if !(1..=10_000).contains(&max_steps) {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("max_steps must be between 1 and 10000")
+                    );
+                }
+                self.debug_record_values(expression, &region, max_steps as usize)
+                    .await
+            }
+            "debug_step" => self.debug_step().await,
+            "debug_step_into" => self.debug_step_into().await,
+            "debug_step_out" => self.debug_step_out().await,
+            "debug_finish" => self.debug_finish().await,
+            "debug_until" => {
+                let line = arguments
+                    .get("line")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("line required"))?;
+                self.debug_until(u32::try_from(line)?).await
+            }
+            "debug_step_instruction" => {
+                let over = arguments
+                    .get("over")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_step_instruction(over).await
+            }
+            "debug_eval" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("expression required"))?;
+                let options = EvalOptions::from_arguments(&arguments)?;
+                self.debug_eval(expression, &options).await
+            }
+            "debug_eval_many" => {
+                let expressions = string_array(&arguments, "expressions")?
+                    .filter(|expressions| !expressions.is_empty())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("expressions required"))?;
+                if expressions.len() > 100 {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("At most 100 expressions per call")
+                    );
+                }
+                let allow_side_effects = arguments
+                    .get("allow_side_effects")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_eval_many(&expressions, allow_side_effects).await
+            }
+            "debug_set_var" => {
+                let name = arguments
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("name required"))?;
+                let value = arguments
+                    .get("value")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("value required"))?;
+                self.debug_set_var(name, value).await
+            }
+            "debug_backtrace" => {
+                let max_frames = arguments
+                    .get("max_frames")
+                    .and_then(|v| v.as_u64())
+                    .map(u32::try_from)
+                    .transpose()?;
+                let all_threads = arguments
+                    .get("all_threads")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if arguments.get("async").and_then(|v| v.as_bool()) == Some(true) {
+                    self.debug_async_backtrace(all_threads).await
+                } else {
+                    self.debug_backtrace(max_frames, all_threads).await
+                }
+            }
+            "debug_locals" => {
+                let depth = arguments.get("depth").and_then(|v| v.as_u64()).unwrap_or(1);
+                self.debug_locals(u32::try_from(depth)?).await
+            }
+            "debug_eval_slice" => {
+                let ptr_expr = arguments
+                    .get("ptr_expr")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("ptr_expr required"))?;
+                let len_expr = arguments
+                    .get("len_expr")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("len_expr required"))?;
+                let element_type = arguments.get("type").and_then(|v| v.as_str());
+                self.debug_eval_slice(ptr_expr, len_expr, element_type)
+                    .await
+            }
+            "debug_inspect" => {
+                let expression = arguments
+                    .get("expression")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("expression required"))?;
+                let depth = arguments.get("depth").and_then(|v| v.as_u64()).unwrap_or(3);
+                if depth > 10 {
+                    return Err(ErrorKind::InvalidArgument.error("depth must be at most 10"));
+                }
+                let max_children = arguments
+                    .get("max_children")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(20);
+                if !(1..=1000).contains(&max_children) {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("max_children must be between 1 and 1000")
+                    );
+                }
+                self.debug_inspect(expression, depth as u32, max_children as u32)
+                    .await
+            }
+            "debug_memory_read" => {
+                let address = arguments
+                    .get("address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("address required"))?;
+                let count = arguments
+                    .get("count")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(64);
+                if !(1..=4096).contains(&count) {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("count must be between 1 and 4096")
+                    );
+                }
+                let value_type = arguments
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .map(MemoryType::from_name)
+                    .transpose()?;
+                self.debug_memory_read(address, count, value_type).await
+            }
+            "debug_memory_write" => {
+                let address = arguments
+                    .get("address")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("address required"))?;
+                let bytes = arguments
+                    .get("bytes")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("bytes required"))?;
+                self.debug_memory_write(address, &parse_hex_bytes(bytes)?)
+                    .await
+            }
+            "debug_signals" => {
+                let signal = arguments
+                    .get("signal")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("signal required"))?;
+                let flag = |name: &str| arguments.get(name).and_then(|v| v.as_bool());
+                self.debug_signals(signal, flag("stop"), flag("pass"), flag("notify"))
+                    .await
+            }
+            "debug_threads" => self.debug_threads().await,
+            "debug_inferiors" => self.debug_inferiors().await,
+            "debug_analyze_deadlock" => self.debug_analyze_deadlock().await,
+            "debug_triage_crash" => self.debug_triage_crash().await,
+            "debug_panic_info" => self.debug_panic_info().await,
+            "debug_heap_profile" => {
+                let top = match arguments.get("top") {
+                    Some(top) => top.as_u64().filter(|&top| top > 0).ok_or_else(|| {
+                        ErrorKind::InvalidArgument.error("top must be a positive integer")
+                    })?,
+                    None => HEAP_PROFILE_SITES,
+                };
+                self.debug_heap_profile(usize::try_from(top).unwrap_or(usize::MAX))
+                    .await
+            }
+            "debug_syscalls" => {
+                let until_stop = arguments
+                    .get("until_stop")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                let duration_ms = arguments
+                    .get("duration_ms")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(if until_stop { 60_000 } else { 1000 });
+                if !(1..=600_000).contains(&duration_ms) {
+                    return Err(ErrorKind::InvalidArgument
+                        .error("duration_ms must be between 1 and 600000"));
+                }
+                let syscalls = string_array(&arguments, "syscalls")?.unwrap_or_default();
+                self.debug_syscalls(std::time::Duration::from_millis(duration_ms), &syscalls)
+                    .await
+            }
+            "debug_profile" => {
+                let duration_ms = arguments
+                    .get("duration_ms")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(1000);
+                if !(1..=600_000).contains(&duration_ms) {
+                    return Err(ErrorKind::InvalidArgument
+                        .error("duration_ms must be between 1 and 600000"));
+                }
+                self.debug_profile(duration_ms).await
+            }
+            "debug_async_tasks" => {
+                let break_on = arguments.get("break_on").and_then(|v| v.as_str());
+                self.debug_async_tasks(break_on).await
+            }
+            "debug_checkpoint" => self.debug_checkpoint().await,
+            "debug_restore_checkpoint" => {
+                let id = arguments
+                    .get("id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("id required"))?;
+                self.debug_restore_checkpoint(u32::try_from(id)?).await
+            }
+            "debug_select_inferior" => {
+                let id = arguments
+                    .get("id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("id required"))?;
+                self.debug_select_inferior(u32::try_from(id)?).await
+            }
+            "debug_select_thread" => {
+                let thread_id = arguments
+                    .get("thread_id")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("thread_id required"))?;
+                self.debug_select_thread(u32::try_from(thread_id)?).await
+            }
+            "debug_select_frame" => {
+                let index = arguments
+                    .get("index")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("index required"))?;
+                self.debug_select_frame(u32::try_from(index)?).await
+            }
+            "debug_frame_info" => self.debug_frame_info().await,
+            "debug_list_breakpoints" => self.debug_list_breakpoints().await,
+            "debug_program_output" => self.debug_program_output().await,
+            "debug_stdin_write" => {
+                let data = arguments
+                    .get("data")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("data required"))?;
+                let eof = arguments
+                    .get("eof")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_stdin_write(data, eof).await
+            }
+            "debug_detach" => self.debug_detach().await,
+            "debug_wait_for_stop" => {
+                let timeout_secs = arguments
+                    .get("timeout_secs")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(60);
+                if !(1..=3600).contains(&timeout_secs) {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("timeout_secs must be between 1 and 3600")
+                    );
+                }
+                self.debug_wait_for_stop(timeout_secs).await
+            }
+            "debug_poll" => self.debug_poll().await,
+            "debug_pause" => self.debug_pause().await,
+            "debug_rebuild" => self.debug_rebuild().await,
+            "debug_recover" => self.debug_recover().await,
+            "debug_restart" => {
+                let rebuild = arguments
+                    .get("rebuild")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                self.debug_restart(rebuild).await
+            }
+            "debug_kill" => self.debug_kill().await,
+            "debug_save_breakpoints" => {
+                let path = arguments.get("path").and_then(|v| v.as_str());
+                self.debug_save_breakpoints(path).await
+            }
+            "debug_load_breakpoints" => {
+                let path = arguments.get("path").and_then(|v| v.as_str());
+                self.debug_load_breakpoints(path).await
+            }
+            "debug_export_session" => {
+                let format = arguments
+                    .get("format")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("json");
+                if !matches!(format, "json" | "markdown") {
+                    return Err(ErrorKind::InvalidArgument.error(format!(
+                        "Unknown format {}; expected json or markdown",
+                        format
+                    )));
+                }
+                let path = arguments.get("path").and_then(|v| v.as_str());
+                self.debug_export_session(format, path).await
+            }
+            "debug_doctor" => self.debug_doctor().await,
+            "debug_sessions" => self.debug_sessions().await,
+            "debug_more_output" => {
+                let cursor = arguments
+                    .get("cursor")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("cursor required"))?;
+                let max_bytes = self.max_output_bytes(&arguments)?;
+                self.debug_more_output(cursor, max_bytes)
+            }
+            "debug_raw_command" => {
+                let command = arguments
+                    .get("command")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| ErrorKind::InvalidArgument.error("command required"))?;
+                self.debug_raw_command(command).await
+            }
+            "debug_state" => self.get_debug_state().await,
+            _ => Err(ErrorKind::InvalidArgument.error(format!("Unknown tool: {}", name))),
+        }
+    }
+
+    /// Answers a JSON-RPC request with its response, for embedders that bring
+    /// their own transport.
+    ///
+    /// Unlike [`DebugServer::run`], this does not check that the client sent
+    /// `notifications/initialized`; pass notifications to
+    /// [`DebugServer::handle_notification`].
+    pub async fn handle_request(&self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or_default();
+        let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        let span = tracing::info_span!("request", %id, method);
+        self.respond(request).instrument(span).await
+    }
+
+    async fn respond(&self, request: Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.handle_initialize(params).await),
+            "ping" => Ok(json!({})),
+            "shutdown" => {
+                self.shutdown().await;
+                Ok(json!({}))
+            }
+            "logging/setLevel" => {
+                let level = params.get("level").and_then(|v| v.as_str()).unwrap_or("");
+                match LOG_LEVELS.iter().position(|l| *l == level) {
+                    Some(severity) => {
+                        self.log_level.store(severity, Ordering::SeqCst);
+                        Ok(json!({}))
+                    }
+                    None => Err(json!({
+                        "code": -32602,
+                        "message": format!(
+                            "Unknown log level {:?}; expected one of {}",
+                            level,
+                            LOG_LEVELS.join(", ")
+                        )
+                    })),
+                }
+            }
+            "tools/list" => Ok(self.handle_list_tools().await),
+            "resources/list" => Ok(self.handle_list_resources().await),
+            "prompts/list" => Ok(json!({
+                "prompts": PROMPTS.iter().map(PromptTemplate::to_json).collect::<Vec<_>>()
+            })),
+            "prompts/get" => Self::handle_get_prompt(&params),
+            "resources/read" => {
+                let uri = params.get("uri").and_then(|v| v.as_str()).unwrap_or("");
+                self.handle_read_resource(uri).await
+            }
+            "resources/subscribe" | "resources/unsubscribe" => {
+                match params.get("uri").and_then(|v| v.as_str()) {
+                    Some(uri) => {
+                        let mut subscriptions = self.subscriptions.lock().unwrap();
+                        if method == "resources/subscribe" {
+                            subscriptions.insert(uri.to_string());
+                        } else {
+                            subscriptions.remove(uri);
+                        }
+                        Ok(json!({}))
+                    }
+                    None => Err(json!({
+                        "code": -32602,
+                        "message": "Missing resource uri"
+                    })),
+                }
+            }
+            "tools/call" => {
+                let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                let arguments = params.get("arguments").cloned().unwrap_or(Value::Null);
+                let call = self.handle_call_tool(name, arguments);
+                let result = match params.pointer("/_meta/progressToken") {
+                    Some(token) => {
+                        let report = ProgressReport {
+                            token: token.clone(),
+                            last: Default::default(),
+                        };
+                        PROGRESS.scope(report, call).await
+                    }
+                    None => call.await,
+                };
+
+                match result {
+                    Ok(result) => {
+                        let mut response = json!({
+                            "content": [
+                                {
+                                    "type": "text",
+                                    "text": serde_json::to_string_pretty(&result).unwrap_or_else(|_| "Error serializing result".to_string())
+                                }
+                            ]
+                        });
+                        if self.protocol_at_least("2025-06-18") {
+                            response["structuredContent"] = result;
+                        }
+                        Ok(response)
+                    }
+                    Err(e) => Err(json!({
+                        "code": ErrorKind::of(&e).code(),
+                        "message": format!("Tool execution failed: {}", e),
+                        "data": ToolError::data(&e)
+                    })),
+                }
+            }
+            _ => Err(json!({
+                "code": -32601,
+                "message": format!("Method not found: {}", method)
+            })),
+        };
+
+        match result {
+            Ok(result) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": result
+            }),
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": error
+            }),
+        }
+    }
+
+    /// Refuses a request that arrived before the client's `notifications/initialized`,
+    /// other than `initialize` and `ping`.
+    ///
+    /// Called as each message is read, before requests are spawned, so a request is
+    /// judged against the notifications that preceded it.
+    pub(crate) fn reject_uninitialized(&self, request: &Value) -> Option<Value> {
+        let method = request.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        if matches!(method, "initialize" | "ping") || self.initialized.load(Ordering::SeqCst) {
+            return None;
+        }
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": request.get("id").cloned().unwrap_or(Value::Null),
+            "error": {
+                "code": -32600,
+                "message": format!(
+                    "Server not initialized: send initialize and notifications/initialized before {}",
+                    method
+                )
+            }
+        }))
+    }
+
+    /// Handles a request in its own task, which a cancellation notification can abort.
+    ///
+    /// Returns `None` if the request was cancelled, since MCP sends no response then.
+    pub(crate) async fn handle_cancellable(self: &Arc<Self>, request: Value) -> Option<Value> {
+        let key = request.get("id").map(Value::to_string);
+        let server = self.clone();
+        let task = tokio::spawn(async move { server.handle_request(request).await });
+        if let Some(key) = &key {
+            self.in_flight
+                .lock()
+                .unwrap()
+                .insert(key.clone(), task.abort_handle());
+        }
+
+        let response = task.await;
+        if let Some(key) = &key {
+            self.in_flight.lock().unwrap().remove(key);
+        }
+        response.ok()
+    }
+
+    /// Handles a JSON-RPC notification from the client, which gets no response.
+    ///
+    /// `notifications/initialized` completes the handshake; both MCP's
+    /// `notifications/cancelled` and LSP-style `$/cancelRequest` cancel an in-flight
+    /// request. It returns at once, so requests after it see its effect.
+    pub fn handle_notification(self: &Arc<Self>, notification: Value) {
+        let method = notification
+            .get("method")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let params = notification.get("params").cloned().unwrap_or(Value::Null);
+        let id = match method {
+            "notifications/initialized" => {
+                self.initialized.store(true, Ordering::SeqCst);
+                None
+            }
+            "notifications/cancelled" => params.get("requestId"),
+            "$/cancelRequest" => params.get("id"),
+            _ => None,
+        };
+        if let Some(id) = id.cloned() {
+            let server = self.clone();
+            tokio::spawn(async move { server.cancel_request(&id).await });
+        }
+    }
+
+    /// Aborts the in-flight request with JSON-RPC id `id`.
+    ///
+    /// If a debugger command was in flight, the debugger gets `SIGINT`, which
+    /// interrupts the command or the running program, and the session state is
+    /// brought up to date with the resulting stop.
+    async fn cancel_request(&self, id: &Value) {
+        let Some(task) = self.in_flight.lock().unwrap().remove(&id.to_string()) else {
+            return;
+        };
+        // Checked before aborting, which releases the output lock
+        let busy = match self.debugger_pipes().await {
+            Ok((_, output)) => output.try_lock().is_err(),
+            Err(_) => false,
+        };
+        task.abort();
+        if !busy {
+            return;
+        }
+
+        let debugger_pid = {
+            let session_guard = self.session.lock().await;
+            session_guard
+                .as_ref()
+                .and_then(|session| session.process.id())
+        };
+        if let Some(pid) = debugger_pid {
+            let _ = tokio::process::Command::new("kill")
+                .args(["-INT", &pid.to_string()])
+                .status()
+                .await;
+            let _ = self.wait_for_stop(self.command_timeout()).await;
+        }
+    }
+
+    /// Serves MCP on stdio until stdin closes.
+    ///
+    /// Each request runs in its own task, so `tools/list`, `debug_state`, or a
+    /// `debug_pause` are answered while a long `debug_continue` is still in flight,
+    /// and `notifications/cancelled` can abort it. Responses are written as they
+    /// complete, which may differ from request order.
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        tracing::info!("serving MCP on stdio");
+
+        let mut notifications = self.notifications.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match notifications.recv().await {
+                    Ok(notification) => println!("{}", notification),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let result = tokio::select! {
+            result = self.read_requests() => result,
+            _ = self.pump_debugger_output() => Ok(()),
+            _ = self.reap_idle_session() => Ok(()),
+        };
+        // The client is gone; leave no debugger or program behind
+        self.shutdown().await;
+        result
+    }
+
+    /// Handles the JSON-RPC messages on stdin until it closes.
+    async fn read_requests(self: &Arc<Self>) -> Result<()> {
+        let mut lines = BufReader::new(io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<Value>(&line) {
+                Ok(message) if message.get("id").is_none() => self.handle_notification(message),
+                Ok(request) => {
+                    if let Some(error) = self.reject_uninitialized(&request) {
+                        println!("{}", error);
+                        continue;
+                    }
+                    let server = self.clone();
+                    tokio::spawn(async move {
+                        if let Some(response) = server.handle_cancellable(request).await {
+                            println!("{}", response);
+                        }
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("invalid JSON: {}", e);
+                    let error_response = json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": {
+                            "code": -32700,
+                            "message": format!("Parse error: {}", e)
+                        }
+                    });
+                    println!("{}", serde_json::to_string(&error_response)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        // Clean up any running debugging session
+        if let Ok(mut session_guard) = self.session.try_lock() {
+            if let Some(mut session) = session_guard.take() {
+                let _ = futures::executor::block_on(session.process.kill());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::shell_quote;
+
+    /// The command line a tool argument tries to smuggle in after its own.
+    const INJECTED: &str = "platform shell id";
+
+    /// Starts an LLDB session whose "debugger" is [`fake_debugger`], stopped as if at a
+    /// breakpoint.
+    async fn fake_lldb_session(input: &std::path::Path) -> DebugServer {
+        fake_session(DebuggerKind::Lldb, input).await
+    }
+
+    /// Starts a `kind` session whose "debugger" is [`fake_debugger`], stopped as if at a
+    /// breakpoint.
+    async fn fake_session(kind: DebuggerKind, input: &std::path::Path) -> DebugServer {
+        let _ = std::fs::remove_file(input);
+        let server = DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            command_timeout: std::time::Duration::from_millis(500),
+            ..ServerOptions::default()
+        });
+        server
+            .spawn_debugger_with("/bin/true", kind, fake_debugger(kind, input))
+            .await
+            .unwrap();
+        server.session.lock().await.as_mut().unwrap().state = DebugState::Stopped;
+        server
+    }
+
+    /// A stand-in for a `kind` debugger that keeps every line it reads in `input`. As
+    /// LLDB it answers only sentinel markers and `breakpoint set`, and as GDB/MI every
+    /// command with `^done`; neither ever finishes a step.
+    fn fake_debugger(kind: DebuggerKind, input: &std::path::Path) -> tokio::process::Command {
+        let script = if kind.uses_mi() {
+            r#"sed -u -n -e '/^[0-9]*-exec-\(next\|step\|finish\)/d' -e 's/^\([0-9][0-9]*\).*/\1^done/p'"#
+        } else {
+            r#"sed -u -n -e '/^thread step/{n;d;}' -e 's/^script print("\(.*\)" "\(.*\)")$/\1\2/p' -e 's/^breakpoint set .*/Breakpoint 1: where = true`main at main.rs:3:5, address = 0x1000/p'"#
+        };
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "tee -a {} | {}",
+            shell_quote(&input.display().to_string()),
+            script
+        ));
+        cmd
+    }
+
+    /// A file of its own for the fake debugger of a call to tool `name` to record its
+    /// input in, as tests run in parallel.
+    fn input_file(name: &str) -> std::path::PathBuf {
+        static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let call = CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        std::env::temp_dir().join(format!(
+            "ferroscope-input-{}-{}-{}",
+            std::process::id(),
+            name,
+            call
+        ))
+    }
+
+    /// Calls tool `name` with an argument ending in an injected command line, and
+    /// checks the debugger never read that line.
+    async fn assert_refuses_line_breaks(name: &str, arguments: Value) {
+        let input = input_file(name);
+        let server = fake_lldb_session(&input).await;
+        let result = server.handle_call_tool(name, arguments).await;
+        server.shutdown().await;
+
+        let read = std::fs::read_to_string(&input).unwrap_or_default();
+        let _ = std::fs::remove_file(&input);
+        assert!(
+            !read
+                .lines()
+                .any(|line| line.trim_start().starts_with(INJECTED)),
+            "{} passed on a line break: {:?}",
+            name,
+            read
+        );
+        if let Err(e) = result {
+            assert_eq!(ErrorKind::of(&e), ErrorKind::InvalidArgument, "{}", e);
+        }
+    }
+
+    /// Calls tool `name` in a [`fake_lldb_session`], returning its result and every
+    /// line the debugger read.
+    async fn lldb_input(name: &str, arguments: Value) -> (Result<Value>, String) {
+        let input = input_file(name);
+        let server = fake_lldb_session(&input).await;
+        let result = server.handle_call_tool(name, arguments).await;
+        server.shutdown().await;
+
+        let read = std::fs::read_to_string(&input).unwrap_or_default();
+        let _ = std::fs::remove_file(&input);
+        (result, read)
+    }
+
+    fn injected(value: &str) -> String {
+        format!("{}\n{}", value, INJECTED)
+    }
+
+    #[test]
+    fn single_line_refuses_line_breaks() {
+        assert!(single_line("frame variable x").is_ok());
+        for command in ["p x\nplatform shell id", "p x\rplatform shell id"] {
+            let e = single_line(command).unwrap_err();
+            assert_eq!(ErrorKind::of(&e), ErrorKind::InvalidArgument);
+        }
+        assert!(mi_console_command(&injected("p x")).contains('\n'));
+    }
+
+    #[tokio::test]
+    async fn send_functions_refuse_line_breaks() {
+        let server = DebugServer::new(ServerOptions::default());
+        let command = injected("p x");
+        for e in [
+            server.send_debugger_command(&command).await.unwrap_err(),
+            server.send_mi_command(&command).await.unwrap_err(),
+            server
+                .send_mi_batch(&["-stack-list-frames".to_string(), command.clone()])
+                .await
+                .unwrap_err(),
+            server
+                .send_mi_command(&mi_console_command(&command))
+                .await
+                .unwrap_err(),
+        ] {
+            assert_eq!(ErrorKind::of(&e), ErrorKind::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn debug_eval_refuses_line_breaks() {
+        let arguments = json!({ "expression": injected("x") });
+        assert_refuses_line_breaks("debug_eval", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_set_var_refuses_line_breaks() {
+        let arguments = json!({ "name": "x", "value": injected("1") });
+        assert_refuses_line_breaks("debug_set_var", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_break_refuses_line_breaks_in_the_location() {
+        let arguments = json!({ "location": injected("main") });
+        assert_refuses_line_breaks("debug_break", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_break_refuses_line_breaks_in_the_condition() {
+        let arguments = json!({ "location": "main.rs:3", "condition": injected("x > 1") });
+        assert_refuses_line_breaks("debug_break", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_break_refuses_line_breaks_in_on_hit_commands() {
+        let arguments = json!({ "location": "main.rs:3", "on_hit_commands": [injected("bt")] });
+        assert_refuses_line_breaks("debug_break", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_watch_expr_refuses_line_breaks() {
+        let arguments = json!({ "expression": injected("x") });
+        assert_refuses_line_breaks("debug_watch_expr", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_raw_command_refuses_line_breaks() {
+        let arguments = json!({ "command": injected("bt") });
+        assert_refuses_line_breaks("debug_raw_command", arguments).await;
+    }
+
+    #[tokio::test]
+    async fn debug_break_quotes_function_names() {
+        let (result, read) = lldb_input(
+            "debug_break",
+            json!({ "location": "<Vec<u8> as Drop>::drop" }),
+        )
+        .await;
+        result.unwrap();
+        assert!(
+            read.lines()
+                .any(|line| line == r#"breakpoint set --name "<Vec<u8> as Drop>::drop""#),
+            "{:?}",
+            read
+        );
+    }
+
+    #[tokio::test]
+    async fn debug_run_to_quotes_function_names() {
+        let (_, read) = lldb_input(
+            "debug_run_to",
+            json!({ "location": "<Vec<u8> as Drop>::drop" }),
+        )
+        .await;
+        assert!(
+            read.lines()
+                .any(|line| line
+                    == r#"breakpoint set --one-shot true --name "<Vec<u8> as Drop>::drop""#),
+            "{:?}",
+            read
+        );
+    }
+
+    #[tokio::test]
+    async fn autosave_leaves_out_the_breakpoints_ferroscope_sets_itself() {
+        let input = input_file("autosave");
+        let project = std::env::temp_dir().join(format!(
+            "ferroscope-project-{}-autosave",
+            std::process::id()
+        ));
+        let server = fake_lldb_session(&input).await;
+        if let Some(session) = server.session.lock().await.as_mut() {
+            session.source_dir = Some(project.display().to_string());
+        }
+        for (id, location) in [
+            (1, "src/main.rs:3"),
+            (2, PANIC_BREAKPOINTS[0]),
+            (3, UNWIND_BREAKPOINTS[0]),
+        ] {
+            server
+                .record_breakpoint(Some(id), location, None, None)
+                .await;
+        }
+        let saved = server.autosave_breakpoints().await;
+        server.shutdown().await;
+
+        let file = project.join(".ferroscope").join("breakpoints.json");
+        let document = std::fs::read_to_string(&file).unwrap_or_default();
+        let _ = std::fs::remove_dir_all(&project);
+        let _ = std::fs::remove_file(&input);
+        saved.unwrap();
+        let document: Value = serde_json::from_str(&document).unwrap();
+        let locations: Vec<&str> = document["breakpoints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|breakpoint| breakpoint["location"].as_str())
+            .collect();
+        assert_eq!(locations, ["src/main.rs:3"]);
+    }
+
+    fn raw_command_server() -> DebugServer {
+        DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            ..ServerOptions::default()
+        })
+    }
+
+    #[test]
+    fn raw_commands_cannot_write_memory_registers_or_variables() {
+        let server = raw_command_server();
+        for command in [
+            "memory write 0x1000 0x41",
+            "mem w 0x1000 0x41",
+            "register write rip 0x1000",
+            "set var x = 1",
+            "set variable x = 1",
+        ] {
+            assert!(server.raw_command_refusal(command).is_some(), "{}", command);
+        }
+        assert_eq!(server.raw_command_refusal("memory read 0x1000"), None);
+        assert_eq!(server.raw_command_refusal("register read rip"), None);
+    }
+
+    #[test]
+    fn raw_commands_cannot_evaluate_side_effects() {
+        let server = raw_command_server();
+        for command in [
+            "p x = 1",
+            "print reset(counter)",
+            "p/x next_id()",
+            "call free(ptr)",
+            "expression -- n += 1",
+            "expr -T -- v.clear()",
+        ] {
+            assert!(server.raw_command_refusal(command).is_some(), "{}", command);
+        }
+        for command in [
+            "p x",
+            "p/x flags",
+            "expression -T -- v.len",
+            "po self.count == 3",
+        ] {
+            assert_eq!(server.raw_command_refusal(command), None, "{}", command);
+        }
+
+        let server = DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            raw_command_allow: vec!["print".to_string()],
+            ..ServerOptions::default()
+        });
+        assert_eq!(server.raw_command_refusal("print reset(counter)"), None);
+    }
+
+    #[test]
+    fn host_commands_include_command_definitions() {
+        for command in [
+            "command alias sh platform shell",
+            "com a sh platform shell",
+            "command regex sh 's/(.*)/platform shell %1/'",
+            "alias sh = shell",
+            "define sh",
+        ] {
+            assert!(
+                matching_command(command, HOST_COMMANDS).is_some(),
+                "{}",
+                command
+            );
+        }
+        assert_eq!(matching_command("bt", HOST_COMMANDS), None);
+    }
+
+    #[tokio::test]
+    async fn a_new_session_kills_the_one_it_replaces() {
+        let input = std::env::temp_dir().join(format!("ferroscope-input-{}", std::process::id()));
+        let server = fake_lldb_session(&input).await;
+        let first = server.session.lock().await.as_ref().unwrap().process.id();
+        server
+            .spawn_debugger_with(
+                "/bin/true",
+                DebuggerKind::Lldb,
+                fake_debugger(DebuggerKind::Lldb, &input),
+            )
+            .await
+            .unwrap();
+        let second = server.session.lock().await.as_ref().unwrap().process.id();
+        server.shutdown().await;
+        let _ = std::fs::remove_file(&input);
+
+        assert_ne!(first, second);
+        let alive = tokio::process::Command::new("kill")
+            .args(["-0", &first.unwrap().to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .unwrap()
+            .success();
+        assert!(!alive, "the replaced debugger still runs");
+    }
+
+    #[tokio::test]
+    async fn tool_calls_wait_for_the_one_holding_the_tool_lock() {
+        let input =
+            std::env::temp_dir().join(format!("ferroscope-input-{}-lock", std::process::id()));
+        let server = fake_lldb_session(&input).await;
+        let tool_lock = server.tool_lock("debug_eval").await.unwrap();
+        let held = tool_lock.lock().await;
+
+        let wait = std::time::Duration::from_millis(300);
+        let raw = json!({ "command": "image list" });
+        let blocked = tokio::time::timeout(
+            wait,
+            server.handle_call_tool("debug_raw_command", raw.clone()),
+        )
+        .await;
+        assert!(
+            blocked.is_err(),
+            "debug_raw_command ran while another call held the lock"
+        );
+        let state =
+            tokio::time::timeout(wait, server.handle_call_tool("debug_state", json!({}))).await;
+        assert!(state.is_ok(), "debug_state waited for the lock");
+
+        drop(held);
+        let raw =
+            tokio::time::timeout(wait, server.handle_call_tool("debug_raw_command", raw)).await;
+        server.shutdown().await;
+        let _ = std::fs::remove_file(&input);
+        assert!(raw.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn steps_that_time_out_fail() {
+        for kind in [DebuggerKind::Lldb, DebuggerKind::Gdb] {
+            let input = std::env::temp_dir().join(format!(
+                "ferroscope-input-{}-step-{}",
+                std::process::id(),
+                kind.name()
+            ));
+            let server = fake_session(kind, &input).await;
+            for tool in [
+                "debug_step",
+                "debug_step_into",
+                "debug_step_out",
+                "debug_step_instruction",
+                "debug_finish",
+            ] {
+                let result = server.handle_call_tool(tool, json!({})).await.unwrap();
+                assert_eq!(result["success"], false, "{} with {:?}", tool, kind);
+                assert_eq!(result["error_kind"], "timeout", "{} with {:?}", tool, kind);
+                server.session.lock().await.as_mut().unwrap().state = DebugState::Stopped;
+            }
+            server.shutdown().await;
+            let _ = std::fs::remove_file(&input);
+        }
+    }
+
+    #[test]
+    fn file_line_locations_split_at_the_last_colon() {
+        assert_eq!(
+            DebugServer::parse_file_line("src/main.rs:25"),
+            Some(("src/main.rs", 25))
+        );
+        assert_eq!(
+            DebugServer::parse_file_line("C:/app/src/main.rs:7"),
+            Some(("C:/app/src/main.rs", 7))
+        );
+    }
+
+    #[test]
+    fn function_paths_are_not_file_line_locations() {
+        for location in [
+            "main",
+            "my_crate::parse",
+            "my_crate::3",
+            ":25",
+            "src/main.rs:x",
+        ] {
+            assert_eq!(DebugServer::parse_file_line(location), None, "{}", location);
+        }
+    }
+
+    #[test]
+    fn breakpoint_ids_come_from_the_first_breakpoint_line() {
+        let response = "Breakpoint 7: where = app`main + 12 at main.rs:3:5, address = 0x1000\n";
+        assert_eq!(DebugServer::parse_breakpoint_id(response), Some(7));
+        let response = "warning: pending\nBreakpoint 2: 3 locations.\n";
+        assert_eq!(DebugServer::parse_breakpoint_id(response), Some(2));
+        let response = "error: No selected frame to use to find the default file.\n";
+        assert_eq!(DebugServer::parse_breakpoint_id(response), None);
+    }
+
+    #[test]
+    fn resolved_locations_are_counted_from_lldb_output() {
+        let single = "Breakpoint 1: where = app`main + 12 at main.rs:3:5, address = 0x1000";
+        assert_eq!(DebugServer::parse_resolved_locations(single), 1);
+        let several = "Breakpoint 2: 3 locations.";
+        assert_eq!(DebugServer::parse_resolved_locations(several), 3);
+        let pending = "Breakpoint 3: no locations (pending).\nWARNING:  Unable to resolve breakpoint to any actual locations.";
+        assert_eq!(DebugServer::parse_resolved_locations(pending), 0);
+        assert_eq!(DebugServer::parse_resolved_locations("error: bad"), 0);
+    }
+
+    #[test]
+    fn raw_commands_need_allow_raw_commands() {
+        let server = DebugServer::new(ServerOptions::default());
+        assert!(server.raw_command_refusal("bt").is_some());
+        assert_eq!(raw_command_server().raw_command_refusal("bt"), None);
+    }
+
+    #[test]
+    fn raw_command_allow_lifts_the_default_denials() {
+        let server = raw_command_server();
+        assert!(server.raw_command_refusal("continue").is_some());
+        assert!(server.raw_command_refusal("shell id").is_some());
+
+        let server = DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            raw_command_allow: vec!["continue".to_string(), "shell".to_string()],
+            ..ServerOptions::default()
+        });
+        assert_eq!(server.raw_command_refusal("continue"), None);
+        assert_eq!(server.raw_command_refusal("shell id"), None);
+    }
+
+    #[test]
+    fn raw_command_deny_wins_over_raw_command_allow() {
+        let server = DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            raw_command_allow: vec!["image dump".to_string()],
+            raw_command_deny: vec!["image dump".to_string()],
+            ..ServerOptions::default()
+        });
+        assert!(server.raw_command_refusal("image dump symtab").is_some());
+        assert_eq!(server.raw_command_refusal("image lookup -n main"), None);
+    }
+
+    #[test]
+    fn sandbox_refuses_host_commands_even_when_allowed() {
+        let server = DebugServer::new(ServerOptions {
+            allow_raw_commands: true,
+            sandbox: true,
+            raw_command_allow: vec!["shell".to_string()],
+            ..ServerOptions::default()
+        });
+        assert!(server.raw_command_refusal("shell id").is_some());
+        assert_eq!(server.raw_command_refusal("bt"), None);
+    }
+
+    #[test]
+    fn lldb_responses_end_at_the_prompt() {
+        let server = raw_command_server();
+        assert!(server.is_response_complete("(lldb) ", "bt", ""));
+        assert!(!server.is_response_complete("* thread #1, stop reason = step over", "bt", ""));
+    }
+
+    #[test]
+    fn lldb_responses_end_at_the_commands_last_line() {
+        let server = raw_command_server();
+        let line = "Breakpoint 1: where = app`main at main.rs:3:5, address = 0x1000";
+        assert!(server.is_response_complete(line, "breakpoint set --name main", line));
+        assert!(server.is_response_complete("Process 4242 stopped", "process continue", ""));
+        assert!(server.is_response_complete(
+            "Process 4242 exited with status = 0 (0x00000000)",
+            "process continue",
+            ""
+        ));
+        assert!(!server.is_response_complete("Process 4242 resuming", "process continue", ""));
+    }
+
+    #[test]
+    fn lldb_values_end_when_their_blocks_close() {
+        let server = raw_command_server();
+        let command = "frame variable point";
+        let open = "(app::Point) point = {\n  x = 1\n";
+        assert!(!server.is_response_complete("  x = 1", command, open));
+        let closed = "(app::Point) point = {\n  x = 1\n  y = 2\n}\n";
+        assert!(server.is_response_complete("}", command, closed));
+        assert!(server.is_response_complete("(i32) n = 5", command, "(i32) n = 5\n"));
+    }
+}
@@ -0,0 +1,1758 @@
+//! State of a debugging session: the debugger process, the program's launch
+//! configuration and captured output, breakpoints, and watchpoints.
+
+use crate::backend::{lldb_quote, mi_console_command, mi_quote, shell_quote, DebuggerKind};
+use crate::config::ResourceLimits;
+use crate::error::ErrorKind;
+use crate::parse::{DebuggerEvent, ExitStatus, StopSignal, WatchpointHit};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::{mpsc, Mutex};
+
+/// Environment variable naming the file the heap tracker logs allocations to.
+pub(crate) const HEAP_LOG_VARIABLE: &str = "FERROSCOPE_HEAP_LOG";
+
+/// Represents the current state of a debugging session.
+///
+/// The debug state tracks the lifecycle of a program being debugged,
+/// from initial loading through execution and completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugState {
+    /// No program has been loaded for debugging
+    NotLoaded,
+    /// Program is loaded but not yet running
+    Loaded,
+    /// Program is currently executing
+    Running,
+    /// Program execution is paused (e.g., at a breakpoint)
+    Stopped,
+    /// Program crashed or encountered an error
+    Crashed,
+    /// Program execution completed successfully
+    Completed,
+    /// The debugger itself exited; `debug_recover` starts a new one
+    DebuggerDied,
+}
+
+impl DebugState {
+    /// Returns the state `event` leads to, or `None` when the event cannot happen in
+    /// this state and must be ignored.
+    ///
+    /// A program is stopped either by running into something or by attaching to it,
+    /// so `Stopped` is reachable before anything was launched. Once the program has
+    /// finished, only a new launch changes the state again. Nothing the output of a
+    /// dead debugger says changes it.
+    pub fn on_event(&self, event: &DebuggerEvent) -> Option<DebugState> {
+        use DebugState::*;
+        match (self, event) {
+            (NotLoaded | DebuggerDied, DebuggerEvent::Launched { .. }) => None,
+            (_, DebuggerEvent::Launched { .. }) => Some(Running),
+            (Loaded | Running | Stopped, DebuggerEvent::Resumed) => Some(Running),
+            (NotLoaded | Loaded | Running | Stopped, DebuggerEvent::Stopped { .. }) => {
+                Some(Stopped)
+            }
+            (Running | Stopped, DebuggerEvent::Crashed) => Some(Crashed),
+            (Running | Stopped, DebuggerEvent::Exited { signalled: true }) => Some(Crashed),
+            (Running | Stopped, DebuggerEvent::Exited { signalled: false }) => Some(Completed),
+            _ => None,
+        }
+    }
+}
+
+/// How the debuggee is launched when `debug_continue` first starts it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LaunchConfig {
+    /// Command-line arguments passed to the program
+    pub(crate) args: Vec<String>,
+    /// Extra environment variables for the program
+    pub(crate) env: Vec<(String, String)>,
+    /// Working directory for the program
+    pub(crate) cwd: Option<String>,
+    /// File whose contents are fed to the program's stdin
+    pub(crate) stdin_file: Option<String>,
+    /// Stop at the program's first instruction instead of letting it run
+    pub(crate) stop_at_entry: bool,
+    /// Which process the debugger stays with when the program forks
+    pub(crate) follow_fork: FollowFork,
+}
+
+/// Which process the debugger follows when the program forks, e.g. through
+/// `std::process::Command`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum FollowFork {
+    /// Stay with the parent and let the child run free, as debuggers do by default
+    #[default]
+    Parent,
+    /// Follow the child and let the parent run free
+    Child,
+    /// Stay with the parent and keep the child as a second, stopped inferior (GDB only)
+    Both,
+}
+
+impl FollowFork {
+    /// Parses the `follow_fork` tool argument.
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "parent" => Ok(FollowFork::Parent),
+            "child" => Ok(FollowFork::Child),
+            "both" => Ok(FollowFork::Both),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown follow_fork: {} (expected parent, child or both)",
+                name
+            ))),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            FollowFork::Parent => "parent",
+            FollowFork::Child => "child",
+            FollowFork::Both => "both",
+        }
+    }
+}
+
+/// A sanitizer `debug_run` builds the program with, which reports memory errors and
+/// data races as they happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Sanitizer {
+    /// AddressSanitizer: out-of-bounds accesses, use after free, double free
+    Address,
+    /// ThreadSanitizer: data races
+    Thread,
+    /// MemorySanitizer: reads of uninitialized memory
+    Memory,
+}
+
+impl Sanitizer {
+    /// Parses a name in the `sanitizers` tool argument.
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "address" => Ok(Sanitizer::Address),
+            "thread" => Ok(Sanitizer::Thread),
+            "memory" => Ok(Sanitizer::Memory),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown sanitizer: {} (expected address, thread or memory)",
+                name
+            ))),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Memory => "memory",
+        }
+    }
+
+    /// The runtime function that reports an error, which a breakpoint stops on with the
+    /// faulting stack intact. AddressSanitizer and MemorySanitizer print their report
+    /// after it returns; ThreadSanitizer calls it once the report is printed.
+    pub(crate) fn report_function(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "__asan::ReportGenericError",
+            Sanitizer::Thread => "__tsan_on_report",
+            Sanitizer::Memory => "__msan::ReportUMR",
+        }
+    }
+
+    /// Runtime options for the program: reports end in `abort` rather than `exit`, so the
+    /// process stays to be inspected, and leak checking is off, since it refuses to run
+    /// under a debugger.
+    pub(crate) fn options_env(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Sanitizer::Address => Some(("ASAN_OPTIONS", "abort_on_error=1:detect_leaks=0")),
+            Sanitizer::Memory => Some(("MSAN_OPTIONS", "abort_on_error=1")),
+            Sanitizer::Thread => None,
+        }
+    }
+}
+
+/// Which of a project's targets `debug_run` builds, as cargo's target selection flags.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BuildConfig {
+    /// Workspace member to build (`--package`)
+    pub(crate) package: Option<String>,
+    /// Binary target to debug (`--bin`)
+    pub(crate) bin: Option<String>,
+    /// Example to debug instead of a binary (`--example`)
+    pub(crate) example: Option<String>,
+    /// Benchmark to debug instead of a binary (`--bench`)
+    pub(crate) bench: Option<String>,
+    /// Features to enable (`--features`)
+    pub(crate) features: Vec<String>,
+    /// Cargo profile to build with, e.g. `release` (`--profile`; `dev` when absent)
+    pub(crate) profile: Option<String>,
+    /// Force debug info into the profile, so optimized builds keep their symbols
+    pub(crate) debuginfo: bool,
+    /// Sanitizers to instrument the program with (`-Zsanitizer`, on a nightly toolchain)
+    pub(crate) sanitizers: Vec<Sanitizer>,
+}
+
+impl BuildConfig {
+    /// Reads the `package`, `bin`, `example`, `bench`, `features`, `profile`,
+    /// `debuginfo`, and `sanitizers` tool arguments; features and sanitizers are an array
+    /// or a comma- or space-separated string.
+    pub(crate) fn from_arguments(arguments: &Value) -> Result<Self> {
+        let name =
+            |key: &str| -> Result<Option<String>> {
+                match arguments.get(key) {
+                    Some(Value::String(name)) if !name.is_empty() => Ok(Some(name.clone())),
+                    Some(Value::Null) | None => Ok(None),
+                    Some(_) => Err(ErrorKind::InvalidArgument
+                        .error(format!("{} must be a non-empty string", key))),
+                }
+            };
+        let list = |key: &str| -> Result<Vec<String>> {
+            match arguments.get(key) {
+                Some(Value::String(items)) => Ok(items
+                    .split([',', ' '])
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string)
+                    .collect()),
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| {
+                        item.as_str().map(str::to_string).ok_or_else(|| {
+                            ErrorKind::InvalidArgument.error(format!("{} must be strings", key))
+                        })
+                    })
+                    .collect(),
+                Some(Value::Null) | None => Ok(Vec::new()),
+                Some(_) => Err(ErrorKind::InvalidArgument
+                    .error(format!("{} must be a string or an array of strings", key))),
+            }
+        };
+        let features = list("features")?;
+        let sanitizers = list("sanitizers")?
+            .iter()
+            .map(|name| Sanitizer::from_name(name))
+            .collect::<Result<Vec<_>>>()?;
+        // Each sanitizer needs its own runtime, and they cannot share a process
+        if sanitizers.len() > 1 {
+            return Err(ErrorKind::InvalidArgument
+                .error("Only one of the address, thread, and memory sanitizers can be used"));
+        }
+
+        let build = Self {
+            package: name("package")?,
+            bin: name("bin")?,
+            example: name("example")?,
+            bench: name("bench")?,
+            features,
+            profile: name("profile")?,
+            debuginfo: arguments
+                .get("debuginfo")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            sanitizers,
+        };
+        let targets = [&build.bin, &build.example, &build.bench];
+        if targets.iter().filter(|target| target.is_some()).count() > 1 {
+            return Err(ErrorKind::InvalidArgument
+                .error("Only one of bin, example, and bench can be given"));
+        }
+        Ok(build)
+    }
+
+    /// The cargo flags selecting these targets.
+    pub(crate) fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (flag, value) in [
+            ("--package", &self.package),
+            ("--bin", &self.bin),
+            ("--example", &self.example),
+            ("--bench", &self.bench),
+            ("--profile", &self.profile),
+        ] {
+            if let Some(value) = value {
+                args.push(flag.to_string());
+                args.push(value.clone());
+            }
+        }
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        args
+    }
+
+    /// Environment for cargo: with `debuginfo`, the profile's `debug` setting is
+    /// overridden, e.g. `CARGO_PROFILE_RELEASE_DEBUG=true`; with `sanitizers`, the
+    /// `-Zsanitizer` flag is added to `RUSTFLAGS` and the nightly toolchain selected,
+    /// unless `RUSTUP_TOOLCHAIN` already picks one.
+    pub(crate) fn cargo_env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if self.debuginfo {
+            let profile = self.profile.as_deref().unwrap_or("dev");
+            env.push((
+                format!(
+                    "CARGO_PROFILE_{}_DEBUG",
+                    profile.to_uppercase().replace('-', "_")
+                ),
+                "true".to_string(),
+            ));
+        }
+        if !self.sanitizers.is_empty() {
+            let names: Vec<&str> = self.sanitizers.iter().map(|s| s.name()).collect();
+            let mut flags = std::env::var("RUSTFLAGS").unwrap_or_default();
+            flags.push_str(&format!(" -Zsanitizer={}", names.join(",")));
+            env.push(("RUSTFLAGS".to_string(), flags.trim_start().to_string()));
+            if std::env::var_os("RUSTUP_TOOLCHAIN").is_none() {
+                env.push(("RUSTUP_TOOLCHAIN".to_string(), "nightly".to_string()));
+            }
+        }
+        env
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "package": self.package,
+            "bin": self.bin,
+            "example": self.example,
+            "bench": self.bench,
+            "features": self.features,
+            "profile": self.profile.as_deref().unwrap_or("dev"),
+            "debuginfo": self.debuginfo,
+            "sanitizers": self.sanitizers.iter().map(|s| s.name()).collect::<Vec<_>>()
+        })
+    }
+}
+
+impl LaunchConfig {
+    /// Reads the `args`, `env`, `cwd`, `stdin_file`, and `follow_fork` tool arguments.
+    pub(crate) fn from_arguments(arguments: &Value) -> Result<Self> {
+        let args = match arguments.get("args") {
+            Some(Value::Array(args)) => args
+                .iter()
+                .map(|arg| {
+                    arg.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| ErrorKind::InvalidArgument.error("args must be strings"))
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => {
+                return Err(ErrorKind::InvalidArgument.error("args must be an array of strings"))
+            }
+            None => Vec::new(),
+        };
+
+        let env = match arguments.get("env") {
+            Some(Value::Object(env)) => env
+                .iter()
+                .map(|(key, value)| {
+                    value
+                        .as_str()
+                        .map(|value| (key.clone(), value.to_string()))
+                        .ok_or_else(|| {
+                            ErrorKind::InvalidArgument
+                                .error(format!("env value for {} must be a string", key))
+                        })
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => {
+                return Err(ErrorKind::InvalidArgument.error("env must be an object of strings"))
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            args,
+            env,
+            cwd: arguments
+                .get("cwd")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            stdin_file: arguments
+                .get("stdin_file")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            stop_at_entry: false,
+            follow_fork: arguments
+                .get("follow_fork")
+                .and_then(|v| v.as_str())
+                .map(FollowFork::from_name)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Confines the program to `dir` under `--sandbox`: it starts there unless given a
+    /// working directory, and `HOME` and `TMPDIR` point there unless set.
+    pub(crate) fn sandbox(&mut self, dir: &std::path::Path) {
+        let dir = dir.to_string_lossy().to_string();
+        if self.cwd.is_none() {
+            self.cwd = Some(dir.clone());
+        }
+        for name in ["HOME", "TMPDIR"] {
+            if !self.env.iter().any(|(key, _)| key == name) {
+                self.env.push((name.to_string(), dir.clone()));
+            }
+        }
+    }
+
+    /// The log `debug_heap_profile` has the program's allocations written to, once it
+    /// turned tracking on.
+    pub(crate) fn heap_log(&self) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(key, _)| key == HEAP_LOG_VARIABLE)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "args": self.args,
+            "env": self
+                .env
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                .collect::<serde_json::Map<String, Value>>(),
+            "cwd": self.cwd,
+            "stdin_file": self.stdin_file,
+            "stop_at_entry": self.stop_at_entry,
+            "follow_fork": self.follow_fork.name()
+        })
+    }
+
+    /// Builds LLDB's `process launch` command for this configuration.
+    ///
+    /// When `output` is given, the program's stdout and stderr are redirected into it.
+    /// Returns the file the program reads as stdin: `stdin_file` if given, otherwise the
+    /// pipe that `debug_stdin_write` feeds.
+    fn stdin_source(&self, output: Option<&ProgramOutput>) -> Option<String> {
+        self.stdin_file
+            .clone()
+            .or_else(|| output.and_then(ProgramOutput::stdin_path))
+    }
+
+    pub(crate) fn lldb_launch_command(&self, output: Option<&ProgramOutput>) -> String {
+        let mut command = String::from("process launch");
+        if self.stop_at_entry {
+            command.push_str(" --stop-at-entry");
+        }
+        if let Some(output) = output {
+            command.push_str(&format!(
+                " --stdout {} --stderr {}",
+                lldb_quote(&output.stdout_path()),
+                lldb_quote(&output.stderr_path())
+            ));
+        }
+        if let Some(cwd) = &self.cwd {
+            command.push_str(&format!(" --working-dir {}", lldb_quote(cwd)));
+        }
+        if let Some(stdin_file) = self.stdin_source(output) {
+            command.push_str(&format!(" --stdin {}", lldb_quote(&stdin_file)));
+        }
+        for (key, value) in &self.env {
+            command.push_str(&format!(
+                " --environment {}",
+                lldb_quote(&format!("{}={}", key, value))
+            ));
+        }
+        if !self.args.is_empty() {
+            command.push_str(" --");
+            for arg in &self.args {
+                command.push(' ');
+                command.push_str(&lldb_quote(arg));
+            }
+        }
+        command
+    }
+
+    /// Builds the MI commands that configure the next `-exec-run`.
+    ///
+    /// When `output` is given, the program's stdout and stderr are redirected into it.
+    pub(crate) fn mi_setup_commands(
+        &self,
+        debugger: DebuggerKind,
+        output: Option<&ProgramOutput>,
+    ) -> Vec<String> {
+        let mut commands = Vec::new();
+
+        if let Some(cwd) = &self.cwd {
+            commands.push(format!("-environment-cd {}", mi_quote(cwd)));
+        }
+
+        for (key, value) in &self.env {
+            commands.push(match debugger {
+                DebuggerKind::Gdb => format!("-gdb-set environment {}={}", key, value),
+                _ => mi_console_command(&format!(
+                    "settings set target.env-vars {}",
+                    lldb_quote(&format!("{}={}", key, value))
+                )),
+            });
+        }
+
+        match debugger {
+            DebuggerKind::Gdb => {
+                // GDB starts the program through a shell, so quote for the shell and let
+                // it handle stdin redirection too.
+                let mut args: Vec<String> = self.args.iter().map(|arg| shell_quote(arg)).collect();
+                if let Some(stdin_file) = self.stdin_source(output) {
+                    args.push(format!("< {}", shell_quote(&stdin_file)));
+                }
+                if let Some(output) = output {
+                    args.push(format!("> {}", shell_quote(&output.stdout_path())));
+                    args.push(format!("2> {}", shell_quote(&output.stderr_path())));
+                }
+                if !args.is_empty() {
+                    commands.push(format!("-exec-arguments {}", args.join(" ")));
+                }
+            }
+            _ => {
+                if !self.args.is_empty() {
+                    let args: Vec<String> = self.args.iter().map(|arg| mi_quote(arg)).collect();
+                    commands.push(format!("-exec-arguments {}", args.join(" ")));
+                }
+                if let Some(stdin_file) = self.stdin_source(output) {
+                    commands.push(mi_console_command(&format!(
+                        "settings set target.input-path {}",
+                        lldb_quote(&stdin_file)
+                    )));
+                }
+                if let Some(output) = output {
+                    commands.push(mi_console_command(&format!(
+                        "settings set target.output-path {}",
+                        lldb_quote(&output.stdout_path())
+                    )));
+                    commands.push(mi_console_command(&format!(
+                        "settings set target.error-path {}",
+                        lldb_quote(&output.stderr_path())
+                    )));
+                }
+            }
+        }
+
+        commands.extend(self.follow_fork_commands(debugger));
+        commands
+    }
+
+    /// Builds the commands that make `debugger` follow forks as `follow_fork` asks;
+    /// following the parent is the default and needs none. LLDB can only follow one
+    /// process, so [`FollowFork::Both`] must be rejected before launching under it.
+    pub(crate) fn follow_fork_commands(&self, debugger: DebuggerKind) -> Vec<String> {
+        match (self.follow_fork, debugger) {
+            (FollowFork::Parent, _) => Vec::new(),
+            (FollowFork::Child, DebuggerKind::Gdb) => {
+                vec!["-gdb-set follow-fork-mode child".to_string()]
+            }
+            (FollowFork::Both, DebuggerKind::Gdb) => {
+                vec!["-gdb-set detach-on-fork off".to_string()]
+            }
+            (_, DebuggerKind::Lldb) => {
+                vec!["settings set target.process.follow-fork-mode child".to_string()]
+            }
+            (_, DebuggerKind::LldbMi) => vec![mi_console_command(
+                "settings set target.process.follow-fork-mode child",
+            )],
+        }
+    }
+}
+
+/// Files capturing the debuggee's stdout and stderr, kept apart from debugger output, and
+/// the pipe feeding its stdin.
+///
+/// The files live in a private temporary directory that is removed with the session.
+#[derive(Debug)]
+pub(crate) struct ProgramOutput {
+    /// Directory holding `stdout` and `stderr`
+    dir: std::path::PathBuf,
+    /// Bytes of stdout already returned by [`ProgramOutput::take_new`]
+    stdout_seen: u64,
+    /// Bytes of stderr already returned by [`ProgramOutput::take_new`]
+    stderr_seen: u64,
+    /// Write end of the `stdin` FIFO, or `None` once closed or if it could not be created
+    pub(crate) stdin: Option<std::fs::File>,
+}
+
+impl ProgramOutput {
+    pub(crate) fn create() -> Result<Self> {
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("ferroscope-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::File::create(dir.join("stdout"))?;
+        std::fs::File::create(dir.join("stderr"))?;
+        let stdin = Self::create_stdin_pipe(&dir.join("stdin"));
+        Ok(Self {
+            dir,
+            stdout_seen: 0,
+            stderr_seen: 0,
+            stdin,
+        })
+    }
+
+    /// Creates a FIFO for the program's stdin and opens its write end.
+    ///
+    /// Opening read-write keeps the open from blocking until the program starts, and
+    /// keeps the program from seeing end-of-file between writes.
+    fn create_stdin_pipe(path: &std::path::Path) -> Option<std::fs::File> {
+        let created = std::process::Command::new("mkfifo")
+            .arg(path)
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if !created {
+            return None;
+        }
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .ok()
+    }
+
+    /// The directory a `--sandbox` program works in, created on first use.
+    pub(crate) fn sandbox_dir(&self) -> Result<std::path::PathBuf> {
+        let dir = self.dir.join("sandbox");
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Bytes the program has written to stdout and stderr together.
+    pub(crate) fn bytes_written(&self) -> u64 {
+        [self.stdout_path(), self.stderr_path()]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Path of the stdin FIFO, while its write end is still open.
+    fn stdin_path(&self) -> Option<String> {
+        self.stdin
+            .as_ref()
+            .map(|_| self.dir.join("stdin").to_string_lossy().to_string())
+    }
+
+    fn stdout_path(&self) -> String {
+        self.dir.join("stdout").to_string_lossy().to_string()
+    }
+
+    fn stderr_path(&self) -> String {
+        self.dir.join("stderr").to_string_lossy().to_string()
+    }
+
+    /// Reads a capture file from `offset`, restarting from 0 if it was truncated.
+    fn read_from(path: &str, offset: &mut u64) -> String {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let Ok(mut file) = std::fs::File::open(path) else {
+            return String::new();
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len < *offset {
+            *offset = 0;
+        }
+
+        let mut bytes = Vec::new();
+        if file.seek(SeekFrom::Start(*offset)).is_ok() && file.read_to_end(&mut bytes).is_ok() {
+            *offset += bytes.len() as u64;
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Returns the stdout and stderr written since the previous call.
+    pub(crate) fn take_new(&mut self) -> (String, String) {
+        let stdout = Self::read_from(&self.stdout_path(), &mut self.stdout_seen);
+        let stderr = Self::read_from(&self.stderr_path(), &mut self.stderr_seen);
+        (stdout, stderr)
+    }
+
+    /// Returns everything the program has written so far.
+    pub(crate) fn read_all(&self) -> (String, String) {
+        let stdout = Self::read_from(&self.stdout_path(), &mut 0);
+        let stderr = Self::read_from(&self.stderr_path(), &mut 0);
+        (stdout, stderr)
+    }
+}
+
+impl Drop for ProgramOutput {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A breakpoint created through `debug_break`, as tracked by the session.
+#[derive(Debug, Clone)]
+pub(crate) struct Breakpoint {
+    /// The debugger's breakpoint number
+    pub(crate) id: u32,
+    /// Location as given by the user (function name or file:line)
+    pub(crate) location: String,
+    /// Condition expression, if any
+    pub(crate) condition: Option<String>,
+    /// Number of hits skipped before stopping, if any
+    pub(crate) ignore_count: Option<u64>,
+    /// Whether the breakpoint is currently enabled
+    pub(crate) enabled: bool,
+    /// Expressions recorded in the trace log on each hit, for a `log_only` breakpoint
+    /// that continues instead of stopping
+    pub(crate) log_expressions: Option<Vec<String>>,
+    /// Hits recorded in the trace log so far
+    pub(crate) log_hits: u64,
+    /// Debugger commands run on each hit, from `on_hit_commands`
+    pub(crate) on_hit_commands: Vec<String>,
+    /// Whether `on_hit_commands` ended in `continue`, so the program continues past hits
+    pub(crate) auto_continue: bool,
+    /// Set by `debug_trace_calls`: each hit is logged as a call of the function at
+    /// `location`
+    pub(crate) call_trace: bool,
+    /// Whether the function's arguments are logged with each call
+    pub(crate) log_arguments: bool,
+}
+
+impl Breakpoint {
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "location": self.location,
+            "condition": self.condition,
+            "ignore_count": self.ignore_count,
+            "enabled": self.enabled,
+            "log_only": self.log_expressions.is_some(),
+            "log_expressions": self.log_expressions,
+            "on_hit_commands": self.on_hit_commands,
+            "auto_continue": self.auto_continue,
+            "call_trace": self.call_trace,
+            "log_arguments": self.log_arguments
+        })
+    }
+
+    /// Reads a breakpoint saved by `debug_save_breakpoints`, as [`Breakpoint::to_json`]
+    /// wrote it. The id is the one it had when saved.
+    pub(crate) fn from_json(value: &Value) -> Result<Self> {
+        let location = value["location"]
+            .as_str()
+            .ok_or_else(|| ErrorKind::InvalidArgument.error("Saved breakpoint has no location"))?;
+        let strings = |key: &str| {
+            value[key].as_array().map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+        };
+        let flag = |key: &str| value[key].as_bool().unwrap_or(false);
+        Ok(Breakpoint {
+            id: value["id"]
+                .as_u64()
+                .and_then(|id| u32::try_from(id).ok())
+                .unwrap_or(0),
+            location: location.to_string(),
+            condition: value["condition"].as_str().map(str::to_string),
+            ignore_count: value["ignore_count"].as_u64(),
+            enabled: value["enabled"].as_bool().unwrap_or(true),
+            log_expressions: strings("log_expressions"),
+            log_hits: 0,
+            on_hit_commands: strings("on_hit_commands").unwrap_or_default(),
+            auto_continue: flag("auto_continue"),
+            call_trace: flag("call_trace"),
+            log_arguments: flag("log_arguments"),
+        })
+    }
+
+    /// Whether `debug_continue` continues past hits of this breakpoint instead of stopping.
+    pub(crate) fn continues(&self) -> bool {
+        self.log_expressions.is_some() || self.auto_continue
+    }
+}
+
+/// Which kind of memory access triggers a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum WatchMode {
+    Write,
+    Read,
+    ReadWrite,
+}
+
+impl WatchMode {
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "write" => Ok(WatchMode::Write),
+            "read" => Ok(WatchMode::Read),
+            "read_write" => Ok(WatchMode::ReadWrite),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown watch mode: {} (expected write, read or read_write)",
+                name
+            ))),
+        }
+    }
+
+    /// The value LLDB expects for `watchpoint set -w`, which matches the tool argument.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            WatchMode::Write => "write",
+            WatchMode::Read => "read",
+            WatchMode::ReadWrite => "read_write",
+        }
+    }
+
+    /// The `-break-watch` flag selecting this mode in GDB/MI.
+    pub(crate) fn mi_flag(&self) -> &'static str {
+        match self {
+            WatchMode::Write => "",
+            WatchMode::Read => "-r ",
+            WatchMode::ReadWrite => "-a ",
+        }
+    }
+}
+
+/// A watchpoint created through `debug_watch`, as tracked by the session.
+#[derive(Debug, Clone)]
+pub(crate) struct Watchpoint {
+    /// The debugger's watchpoint number
+    pub(crate) id: u32,
+    /// Watched variable name or address expression
+    pub(crate) target: String,
+    /// Access that triggers the watchpoint
+    pub(crate) mode: WatchMode,
+    /// `object.field` expression of a watchpoint set by `debug_watch_field`, whose hits
+    /// stop reports describe under `field_change`
+    pub(crate) field: Option<String>,
+    /// The field's value at the last hit or when it was watched, the old value of the
+    /// next hit if the debugger reports none
+    pub(crate) last_value: Option<String>,
+}
+
+impl Watchpoint {
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "target": self.target,
+            "mode": self.mode.name(),
+            "field": self.field
+        })
+    }
+}
+
+/// An operation applied to existing breakpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BreakpointAction {
+    Delete,
+    Disable,
+    Enable,
+}
+
+impl BreakpointAction {
+    /// The verb shared by LLDB (`breakpoint <verb>`) and GDB/MI (`-break-<verb>`).
+    pub(crate) fn verb(&self) -> &'static str {
+        match self {
+            BreakpointAction::Delete => "delete",
+            BreakpointAction::Disable => "disable",
+            BreakpointAction::Enable => "enable",
+        }
+    }
+}
+
+/// Entries the session log keeps before dropping the oldest.
+const LOG_CAPACITY: usize = 1000;
+
+/// The code `debug_record_values` steps through.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ValueRegion {
+    /// A function, until it returns
+    Function(String),
+    /// Lines `start` to `end` of a source file
+    Lines { file: String, start: u32, end: u32 },
+}
+
+impl ValueRegion {
+    /// Reads the `function` tool argument, or `file` with `start_line` and `end_line`.
+    pub(crate) fn from_arguments(arguments: &Value) -> Result<Self> {
+        let line = |name: &str| {
+            arguments
+                .get(name)
+                .and_then(|v| v.as_u64())
+                .and_then(|line| u32::try_from(line).ok())
+                .filter(|&line| line > 0)
+        };
+        match (
+            arguments.get("function").and_then(|v| v.as_str()),
+            arguments.get("file").and_then(|v| v.as_str()),
+        ) {
+            (Some(function), None) => Ok(ValueRegion::Function(function.to_string())),
+            (None, Some(file)) => {
+                let start = line("start_line").ok_or_else(|| {
+                    ErrorKind::InvalidArgument.error("start_line required with file")
+                })?;
+                let end = line("end_line").unwrap_or(start);
+                if end < start {
+                    return Err(
+                        ErrorKind::InvalidArgument.error("end_line must not be before start_line")
+                    );
+                }
+                Ok(ValueRegion::Lines {
+                    file: file.to_string(),
+                    start,
+                    end,
+                })
+            }
+            _ => Err(ErrorKind::InvalidArgument.error("Give either function or file")),
+        }
+    }
+}
+
+/// How `debug_eval` prints integer values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ValueFormat {
+    Hex,
+    Binary,
+    Decimal,
+    Char,
+}
+
+impl ValueFormat {
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "hex" => Ok(ValueFormat::Hex),
+            "binary" => Ok(ValueFormat::Binary),
+            "decimal" => Ok(ValueFormat::Decimal),
+            "char" => Ok(ValueFormat::Char),
+            _ => Err(ErrorKind::InvalidArgument.error(format!(
+                "Unknown format: {} (expected hex, binary, decimal or char)",
+                name
+            ))),
+        }
+    }
+
+    /// The value LLDB expects for `expression --format` and `frame variable --format`.
+    pub(crate) fn lldb_format(&self) -> &'static str {
+        match self {
+            ValueFormat::Hex => "x",
+            ValueFormat::Binary => "b",
+            ValueFormat::Decimal => "d",
+            ValueFormat::Char => "c",
+        }
+    }
+
+    /// The letter selecting this format in GDB's `print /<letter>`.
+    pub(crate) fn gdb_letter(&self) -> char {
+        match self {
+            ValueFormat::Hex => 'x',
+            ValueFormat::Binary => 't',
+            ValueFormat::Decimal => 'd',
+            ValueFormat::Char => 'c',
+        }
+    }
+}
+
+/// The language `debug_eval` parses an expression in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EvalLanguage {
+    Rust,
+    /// For frames of C code called over FFI, where Rust syntax does not apply
+    C,
+}
+
+impl EvalLanguage {
+    pub(crate) fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "rust" => Ok(EvalLanguage::Rust),
+            "c" => Ok(EvalLanguage::C),
+            _ => Err(ErrorKind::InvalidArgument
+                .error(format!("Unknown language: {} (expected rust or c)", name))),
+        }
+    }
+
+    /// The value GDB expects for `set language`, which matches the tool argument.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            EvalLanguage::Rust => "rust",
+            EvalLanguage::C => "c",
+        }
+    }
+}
+
+/// Where and how `debug_eval` evaluates; the default is the debugger's own formatting in
+/// the selected frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct EvalOptions {
+    /// Format for integer values
+    pub(crate) format: Option<ValueFormat>,
+    /// Language to parse the expression in
+    pub(crate) language: Option<EvalLanguage>,
+    /// Frame of the thread to evaluate in, instead of the selected frame
+    pub(crate) frame: Option<u32>,
+    /// Thread to evaluate in, instead of the selected thread
+    pub(crate) thread: Option<u32>,
+    /// Whether the expression may call functions and assign; otherwise such expressions
+    /// are refused and the debugger is kept from running code
+    pub(crate) allow_side_effects: bool,
+}
+
+impl EvalOptions {
+    /// Reads the `format`, `language`, `in_frame`, `in_thread`, and `allow_side_effects`
+    /// tool arguments.
+    pub(crate) fn from_arguments(arguments: &Value) -> Result<Self> {
+        let index = |name: &str| {
+            arguments
+                .get(name)
+                .and_then(|v| v.as_u64())
+                .map(u32::try_from)
+                .transpose()
+        };
+        Ok(Self {
+            format: arguments
+                .get("format")
+                .and_then(|v| v.as_str())
+                .map(ValueFormat::from_name)
+                .transpose()?,
+            language: arguments
+                .get("language")
+                .and_then(|v| v.as_str())
+                .map(EvalLanguage::from_name)
+                .transpose()?,
+            frame: index("in_frame")?,
+            thread: index("in_thread")?,
+            allow_side_effects: arguments
+                .get("allow_side_effects")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// Hits of `log_only` breakpoints the trace log keeps before dropping the oldest.
+const TRACE_LOG_CAPACITY: usize = 10_000;
+
+/// Represents an active debugging session with a spawned debugger process.
+///
+/// A `DebugSession` manages the communication with an LLDB or GDB process,
+/// tracking the state of the debugging session and the program being debugged.
+pub(crate) struct DebugSession {
+    /// The spawned debugger process (LLDB or GDB)
+    pub(crate) process: Child,
+    /// Standard input pipe to send commands to the debugger
+    ///
+    /// Locked separately from the session so an interrupt can be written while another
+    /// command waits for its reply.
+    pub(crate) stdin: Arc<Mutex<ChildStdin>>,
+    /// Lines of the debugger's standard output, forwarded by [`forward_debugger_output`]
+    ///
+    /// Holding this lock means owning the debugger's replies, i.e. running a command;
+    /// the session itself is only locked briefly to apply what the command returned.
+    pub(crate) output: Arc<Mutex<mpsc::UnboundedReceiver<String>>>,
+    /// Set while the program runs with no command waiting, so the next stop is announced
+    pub(crate) notify_on_stop: Arc<AtomicBool>,
+    /// Output that arrived with no command waiting, already applied to the state but not
+    /// yet reported by `debug_poll` or `debug_wait_for_stop`
+    pub(crate) unread_output: String,
+    /// Current state of the debugging session
+    pub(crate) state: DebugState,
+    /// Path to the binary being debugged
+    pub(crate) binary_path: String,
+    /// Current location in the program (file:line or function name)
+    pub(crate) current_location: Option<String>,
+    /// Which debugger this session drives
+    pub(crate) kind: DebuggerKind,
+    /// Token for the next MI command, used to match result records to commands, or
+    /// number of the next LLDB sentinel marker
+    pub(crate) next_token: u64,
+    /// Whether LLDB replies are framed by sentinel markers, which needs its Python
+    /// support; without it they are framed by prompt detection
+    pub(crate) sentinels: bool,
+    /// Breakpoints set during this session
+    pub(crate) breakpoints: Vec<Breakpoint>,
+    /// Watchpoints set during this session
+    pub(crate) watchpoints: Vec<Watchpoint>,
+    /// Expressions from `debug_watch_expr`, re-evaluated whenever the program stops
+    pub(crate) watch_expressions: Vec<String>,
+    /// Arguments, environment, and I/O used when launching the program
+    pub(crate) launch: LaunchConfig,
+    /// Capture files for the program's stdout/stderr (absent for attached processes)
+    pub(crate) program_output: Option<ProgramOutput>,
+    /// Thread that stepping, backtraces, and evaluation apply to
+    pub(crate) selected_thread: Option<u32>,
+    /// Frame of the selected thread that evaluation applies to (0 is innermost)
+    pub(crate) selected_frame: u32,
+    /// Signal or exception behind the most recent stop, if it was one
+    pub(crate) stop_signal: Option<StopSignal>,
+    /// Watchpoint behind the most recent stop, if it was one; recorded from GDB/MI stop
+    /// records, while LLDB is asked when the stop is reported
+    pub(crate) watchpoint_hit: Option<WatchpointHit>,
+    /// How the program terminated, once it has
+    pub(crate) exit_status: Option<ExitStatus>,
+    /// Project directory `debug_run` built the binary from, if it was given one
+    pub(crate) source_dir: Option<String>,
+    /// Targets and features `debug_run` built from `source_dir`, used again on rebuilds
+    pub(crate) build: BuildConfig,
+    /// Test run by `debug_test`, whose binary a rebuild must build with `cargo test`
+    pub(crate) test_name: Option<String>,
+    /// Process ID of the program being debugged, once the debugger has reported it
+    pub(crate) inferior_pid: Option<u32>,
+    /// Processes forked by the program that the debugger picked up, as `pid` and MI
+    /// `inferior` id, not yet reported by a tool result
+    pub(crate) new_inferiors: Vec<Value>,
+    /// Core file loaded by `debug_open_core`; such sessions cannot run the program
+    pub(crate) core_path: Option<String>,
+    /// rr trace being replayed by `debug_replay`, which enables reverse execution
+    pub(crate) replay_trace: Option<String>,
+    /// GDB remote stub (`host:port`) of an embedded target from `debug_connect_target`;
+    /// there is no OS process to launch, signal, or restart
+    pub(crate) remote_target: Option<String>,
+    /// Debugger commands and their output, oldest first, served as [`LOG_RESOURCE`]
+    pub(crate) log: std::collections::VecDeque<String>,
+    /// Hits of `log_only` breakpoints with the values recorded, oldest first, served by
+    /// `debug_trace_log`
+    pub(crate) trace_log: std::collections::VecDeque<Value>,
+    /// Trace log entries dropped because the log was full
+    pub(crate) trace_dropped: u64,
+    /// Breakpoints as last saved to or loaded from the project's breakpoints file,
+    /// so they are saved again only when they change
+    pub(crate) saved_breakpoints: Option<Value>,
+    /// The resource limit the program was killed at, with `max` and `used`, until a
+    /// tool result reports it
+    pub(crate) limit_exceeded: Option<Value>,
+    /// Whether `debug_attach` attached to the program, which ending the session must
+    /// leave running
+    pub(crate) attached: bool,
+    /// When the debugger was started
+    pub(crate) started: std::time::Instant,
+    /// When a tool call last finished, for the idle timeout
+    pub(crate) last_used: std::time::Instant,
+    /// How the debugger process ended, once it has died under the session
+    pub(crate) debugger_exit: Option<String>,
+    /// Notable lines of the debugger's stderr, forwarded by [`forward_debugger_stderr`],
+    /// that no tool result has reported yet
+    pub(crate) stderr: mpsc::UnboundedReceiver<String>,
+    /// Held by a tool call for all the commands it sends, so another call's commands
+    /// do not run between, e.g., selecting a frame, evaluating in it, and selecting the
+    /// previous frame again
+    pub(crate) tool_lock: Arc<Mutex<()>>,
+}
+
+impl DebugSession {
+    /// Whether the debugger process has exited. The first time it is found gone, the
+    /// session moves to [`DebugState::DebuggerDied`] and records how it ended.
+    pub(crate) fn debugger_died(&mut self) -> bool {
+        if self.state == DebugState::DebuggerDied {
+            return true;
+        }
+        match self.process.try_wait() {
+            Ok(Some(status)) => {
+                self.state = DebugState::DebuggerDied;
+                self.debugger_exit = Some(status.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Appends to the session log, dropping the oldest entry once it is full.
+    pub(crate) fn log(&mut self, entry: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(entry);
+    }
+
+    /// Returns the notable stderr lines not yet reported, adding them to the session log.
+    pub(crate) fn take_stderr(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        while let Ok(line) = self.stderr.try_recv() {
+            self.log(line.clone());
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Appends a `log_only` breakpoint hit to the trace log, dropping the oldest entry
+    /// once it is full.
+    pub(crate) fn trace(&mut self, entry: Value) {
+        if self.trace_log.len() == TRACE_LOG_CAPACITY {
+            self.trace_log.pop_front();
+            self.trace_dropped += 1;
+        }
+        self.trace_log.push_back(entry);
+    }
+}
+
+/// Events the server history keeps before dropping the oldest.
+const HISTORY_CAPACITY: usize = 10_000;
+
+/// One event of the server history.
+#[derive(Debug, Clone)]
+pub(crate) struct HistoryEntry {
+    /// Milliseconds since the Unix epoch
+    pub(crate) time_ms: u64,
+    /// `tool`, `tool_result`, `debugger`, `session`, or `breakpoint_hit`
+    pub(crate) kind: &'static str,
+    /// The tool called and its arguments, or its result or error; the debugger
+    /// command and its response; the state transition; or the breakpoint and where
+    /// it stopped
+    pub(crate) data: Value,
+}
+
+/// Everything that happened on a server, oldest first, exported by
+/// `debug_export_session`: tool calls, debugger traffic, state transitions, and
+/// breakpoint hits, across every session the server ran.
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    pub(crate) entries: std::collections::VecDeque<HistoryEntry>,
+    /// Entries dropped because the history was full
+    pub(crate) dropped: u64,
+}
+
+impl History {
+    /// Records an event, dropping the oldest once the history is full.
+    pub(crate) fn push(&mut self, kind: &'static str, data: Value) {
+        if self.entries.len() == HISTORY_CAPACITY {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        let time_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        self.entries.push_back(HistoryEntry {
+            time_ms,
+            kind,
+            data,
+        });
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "dropped": self.dropped,
+            "events": self.entries.iter().map(|entry| json!({
+                "time_ms": entry.time_ms,
+                "kind": entry.kind,
+                "data": entry.data
+            })).collect::<Vec<_>>()
+        })
+    }
+
+    /// The history as a Markdown narrative: a section per tool call with its
+    /// arguments, the debugger traffic, state transitions, and breakpoint hits it
+    /// caused, and its result. Times are seconds since the first event.
+    pub(crate) fn to_markdown(&self) -> String {
+        let start = self.entries.front().map_or(0, |entry| entry.time_ms);
+        let mut text = format!(
+            "# Debugging session\n\n{} events over {:.1}s.\n",
+            self.entries.len(),
+            self.entries.back().map_or(0, |entry| entry.time_ms - start) as f64 / 1000.0
+        );
+        if self.dropped > 0 {
+            text.push_str(&format!(
+                "The {} events before these were dropped.\n",
+                self.dropped
+            ));
+        }
+        let pretty = |value: &Value| serde_json::to_string_pretty(value).unwrap_or_default();
+        for entry in &self.entries {
+            let time = format!("+{:.3}s", (entry.time_ms - start) as f64 / 1000.0);
+            let data = &entry.data;
+            match entry.kind {
+                "tool" => text.push_str(&format!(
+                    "\n## {} `{}`\n\n```json\n{}\n```\n",
+                    time,
+                    data["tool"].as_str().unwrap_or(""),
+                    pretty(&data["arguments"])
+                )),
+                "tool_result" => match data["error"].as_str() {
+                    Some(error) => text.push_str(&format!("\n{} error: {}\n", time, error)),
+                    None => text.push_str(&format!(
+                        "\n{} result:\n\n```json\n{}\n```\n",
+                        time,
+                        pretty(&data["result"])
+                    )),
+                },
+                "debugger" => {
+                    if let Some(command) = data["command"].as_str() {
+                        text.push_str(&format!(
+                            "\n{} debugger `{}`:\n\n```text\n{}\n```\n",
+                            time,
+                            command,
+                            data["response"].as_str().unwrap_or("").trim_end()
+                        ));
+                    } else if let Some(output) = data["output"].as_str() {
+                        text.push_str(&format!(
+                            "\n{} debugger output:\n\n```text\n{}\n```\n",
+                            time,
+                            output.trim_end()
+                        ));
+                    }
+                }
+                "breakpoint_hit" => text.push_str(&format!(
+                    "\n**{} breakpoint {} hit at {}**\n",
+                    time,
+                    data["id"],
+                    data["location"].as_str().unwrap_or("an unknown location")
+                )),
+                _ => {
+                    if let (Some(from), Some(to)) = (data["from"].as_str(), data["to"].as_str()) {
+                        text.push_str(&format!("\n*{} {} → {}*\n", time, from, to));
+                    } else if let Some(inferior) = data.get("new_inferior") {
+                        text.push_str(&format!("\n*{} new inferior {}*\n", time, inferior));
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// Strings shorter than this, in bytes of JSON, such as a `state`, are never truncated,
+/// and truncated ones keep half of it.
+const MIN_TRUNCATED_STRING: usize = 64;
+
+/// Truncated tool results `debug_more_output` can still page through.
+const OUTPUT_PAGES_CAPACITY: usize = 16;
+
+/// Complete tool results that were too large to return whole, kept as JSON text for
+/// `debug_more_output` to return a page at a time. Cursors are `<output>:<offset>`.
+#[derive(Debug, Default)]
+pub(crate) struct OutputPages {
+    next_id: u64,
+    outputs: std::collections::VecDeque<(u64, String)>,
+}
+
+impl OutputPages {
+    /// Keeps `text`, dropping the oldest output once full, and returns the cursor of
+    /// its first page.
+    pub(crate) fn store(&mut self, text: String) -> String {
+        if self.outputs.len() == OUTPUT_PAGES_CAPACITY {
+            self.outputs.pop_front();
+        }
+        self.next_id += 1;
+        self.outputs.push_back((self.next_id, text));
+        format!("{}:0", self.next_id)
+    }
+
+    /// The page at `cursor`, at most `max_bytes` long and ending on a character
+    /// boundary, with its `offset`, the cursor of the next page, if there is one, and
+    /// the length of the whole output.
+    pub(crate) fn page(&self, cursor: &str, max_bytes: usize) -> Result<Value> {
+        let invalid = || ErrorKind::InvalidArgument.error(format!("Invalid cursor: {}", cursor));
+        let (id, offset) = cursor
+            .split_once(':')
+            .and_then(|(id, offset)| Some((id.parse::<u64>().ok()?, offset.parse().ok()?)))
+            .ok_or_else(invalid)?;
+        let (_, text) = self
+            .outputs
+            .iter()
+            .find(|(kept, _)| *kept == id)
+            .ok_or_else(|| {
+                ErrorKind::InvalidArgument.error(format!(
+                    "Output {} is no longer kept; call the tool that produced it again",
+                    id
+                ))
+            })?;
+        if offset > text.len() || !text.is_char_boundary(offset) {
+            return Err(invalid());
+        }
+        // A character is at most 4 bytes, so every page makes progress
+        let mut end = (offset + max_bytes.max(4)).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let next = (end < text.len()).then(|| format!("{}:{}", id, end));
+        Ok(json!({
+            "page": &text[offset..end],
+            "offset": offset,
+            "total_bytes": text.len(),
+            "next_cursor": next
+        }))
+    }
+}
+
+/// Shrinks `value` until its JSON is at most `max_bytes` long, or nothing is left to
+/// cut, and returns whether anything was cut. The largest strings and arrays are cut
+/// first: strings keep their start, followed by `…`, and arrays their first elements.
+/// Short strings are left whole.
+pub(crate) fn truncate_json(value: &mut Value, max_bytes: usize) -> bool {
+    let mut truncated = false;
+    loop {
+        let size = json_len(value);
+        if size <= max_bytes {
+            return truncated;
+        }
+        let mut parts = Vec::new();
+        collect_parts(value, String::new(), &mut parts);
+        parts.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        let cut = parts.iter().any(|(pointer, part_size)| {
+            let target = part_size.saturating_sub(size - max_bytes);
+            value
+                .pointer_mut(pointer)
+                .is_some_and(|part| shrink(part, target))
+        });
+        if !cut {
+            return truncated;
+        }
+        truncated = true;
+    }
+}
+
+fn json_len(value: &Value) -> usize {
+    serde_json::to_string(value).map_or(0, |text| text.len())
+}
+
+/// Collects the JSON pointer and size of every string and array within `value`.
+fn collect_parts(value: &Value, pointer: String, parts: &mut Vec<(String, usize)>) {
+    match value {
+        Value::String(_) => {
+            let size = json_len(value);
+            if size >= MIN_TRUNCATED_STRING {
+                parts.push((pointer, size));
+            }
+        }
+        Value::Array(items) => {
+            parts.push((pointer.clone(), json_len(value)));
+            for (index, item) in items.iter().enumerate() {
+                collect_parts(item, format!("{}/{}", pointer, index), parts);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect_parts(field, format!("{}/{}", pointer, key), parts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Cuts a string or array down towards `target` bytes of JSON; false if it cannot be
+/// cut any further.
+fn shrink(value: &mut Value, target: usize) -> bool {
+    let mut size = json_len(value);
+    match value {
+        Value::String(text) => {
+            let marker = '…'.len_utf8();
+            // A cut string keeps enough of its start to say what it was
+            let mut keep = text
+                .len()
+                .saturating_sub(size.saturating_sub(target) + marker)
+                .max(MIN_TRUNCATED_STRING / 2);
+            while !text.is_char_boundary(keep) {
+                keep -= 1;
+            }
+            if keep + marker >= text.len() {
+                return false;
+            }
+            text.truncate(keep);
+            text.push('…');
+            true
+        }
+        Value::Array(items) => {
+            let before = items.len();
+            while items.len() > 1 && size > target {
+                let last = items.pop().map_or(0, |item| json_len(&item));
+                size -= last + 1;
+            }
+            items.len() < before
+        }
+        _ => false,
+    }
+}
+
+/// How often [`enforce_resource_limits`] measures the program.
+const RESOURCE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Measures the running program of the session whose debugger is `debugger_pid` until
+/// that session ends, and kills the program once it goes over one of `limits`, leaving
+/// the limit in [`DebugSession::limit_exceeded`].
+pub(crate) async fn enforce_resource_limits(
+    session: Arc<Mutex<Option<DebugSession>>>,
+    debugger_pid: Option<u32>,
+    limits: ResourceLimits,
+) {
+    loop {
+        tokio::time::sleep(RESOURCE_POLL_INTERVAL).await;
+        let (pid, output_bytes) = {
+            let session_guard = session.lock().await;
+            let Some(session) = session_guard
+                .as_ref()
+                .filter(|session| session.process.id() == debugger_pid)
+            else {
+                return;
+            };
+            // Replayed and remote programs are not processes of this host to measure
+            let pid = session.inferior_pid.filter(|_| {
+                session.state == DebugState::Running
+                    && session.replay_trace.is_none()
+                    && session.remote_target.is_none()
+            });
+            let Some(pid) = pid else {
+                continue;
+            };
+            let output_bytes = session
+                .program_output
+                .as_ref()
+                .map_or(0, ProgramOutput::bytes_written);
+            (pid, output_bytes)
+        };
+
+        let (rss_kib, cpu_seconds) = process_usage(pid).await.unzip();
+        let used = [
+            cpu_seconds,
+            rss_kib.map(|kib| kib / 1024),
+            open_file_count(pid),
+            Some(output_bytes / (1024 * 1024)),
+        ];
+        let exceeded = limits
+            .to_pairs()
+            .into_iter()
+            .zip(used)
+            .find_map(|((name, limit), used)| match (limit, used) {
+                (Some(limit), Some(used)) if used > limit => Some((name, limit, used)),
+                _ => None,
+            });
+        let Some((name, limit, used)) = exceeded else {
+            continue;
+        };
+
+        let _ = tokio::process::Command::new("kill")
+            .args(["-KILL", &pid.to_string()])
+            .status()
+            .await;
+        tracing::warn!(
+            target: "ferroscope.session",
+            pid,
+            limit = name,
+            used,
+            "killed the program at its resource limit"
+        );
+        if let Some(session) = session.lock().await.as_mut() {
+            session.limit_exceeded = Some(json!({
+                "limit": name,
+                "max": limit,
+                "used": used,
+                "pid": pid
+            }));
+        }
+    }
+}
+
+/// The resident memory in KiB and the CPU time in seconds of process `pid`.
+async fn process_usage(pid: u32) -> Option<(u64, u64)> {
+    let output = tokio::process::Command::new("ps")
+        .stdin(std::process::Stdio::null())
+        .args(["-o", "rss=", "-o", "time=", "-p", &pid.to_string()])
+        .output()
+        .await
+        .ok()?;
+    parse_ps_usage(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the `rss` and `time` columns `ps` prints, e.g. `1024 01:02:03` on Linux or
+/// `1024 0:05.31` on macOS, into KiB and whole seconds.
+fn parse_ps_usage(text: &str) -> Option<(u64, u64)> {
+    let mut columns = text.split_whitespace();
+    let rss = columns.next()?.parse().ok()?;
+    let time = columns.next()?;
+    let (days, time) = match time.split_once('-') {
+        Some((days, time)) => (days.parse::<u64>().ok()?, time),
+        None => (0, time),
+    };
+    let mut seconds = days * 86_400;
+    for (field, unit) in time.rsplit(':').zip([1, 60, 3600]) {
+        seconds += field.split('.').next()?.parse::<u64>().ok()? * unit;
+    }
+    Some((rss, seconds))
+}
+
+/// How many files process `pid` has open; only known on Linux.
+fn open_file_count(pid: u32) -> Option<u64> {
+    let entries = std::fs::read_dir(format!("/proc/{}/fd", pid)).ok()?;
+    Some(entries.count() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [DebugState; 7] = [
+        DebugState::NotLoaded,
+        DebugState::Loaded,
+        DebugState::Running,
+        DebugState::Stopped,
+        DebugState::Crashed,
+        DebugState::Completed,
+        DebugState::DebuggerDied,
+    ];
+
+    /// Checks `event` against every state; `edges` lists the states it leaves from.
+    fn assert_edges(event: DebuggerEvent, edges: &[(DebugState, DebugState)]) {
+        for state in STATES {
+            let expected = edges
+                .iter()
+                .find(|(from, _)| *from == state)
+                .map(|(_, to)| to.clone());
+            assert_eq!(
+                state.on_event(&event),
+                expected,
+                "{:?} on {:?}",
+                state,
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn launched_runs_a_loaded_or_finished_program() {
+        assert_edges(
+            DebuggerEvent::Launched { pid: Some(1) },
+            &[
+                (DebugState::Loaded, DebugState::Running),
+                (DebugState::Running, DebugState::Running),
+                (DebugState::Stopped, DebugState::Running),
+                (DebugState::Crashed, DebugState::Running),
+                (DebugState::Completed, DebugState::Running),
+            ],
+        );
+    }
+
+    #[test]
+    fn resumed_needs_a_live_program() {
+        assert_edges(
+            DebuggerEvent::Resumed,
+            &[
+                (DebugState::Loaded, DebugState::Running),
+                (DebugState::Running, DebugState::Running),
+                (DebugState::Stopped, DebugState::Running),
+            ],
+        );
+    }
+
+    #[test]
+    fn stopped_is_ignored_after_the_program_finished() {
+        assert_edges(
+            DebuggerEvent::Stopped { pid: None },
+            &[
+                (DebugState::NotLoaded, DebugState::Stopped),
+                (DebugState::Loaded, DebugState::Stopped),
+                (DebugState::Running, DebugState::Stopped),
+                (DebugState::Stopped, DebugState::Stopped),
+            ],
+        );
+    }
+
+    #[test]
+    fn crashed_needs_a_started_program() {
+        assert_edges(
+            DebuggerEvent::Crashed,
+            &[
+                (DebugState::Running, DebugState::Crashed),
+                (DebugState::Stopped, DebugState::Crashed),
+            ],
+        );
+    }
+
+    #[test]
+    fn exited_completes_or_crashes_a_started_program() {
+        assert_edges(
+            DebuggerEvent::Exited { signalled: false },
+            &[
+                (DebugState::Running, DebugState::Completed),
+                (DebugState::Stopped, DebugState::Completed),
+            ],
+        );
+        assert_edges(
+            DebuggerEvent::Exited { signalled: true },
+            &[
+                (DebugState::Running, DebugState::Crashed),
+                (DebugState::Stopped, DebugState::Crashed),
+            ],
+        );
+    }
+
+    #[test]
+    fn history_drops_the_oldest_and_narrates_in_markdown() {
+        let mut history = History::default();
+        for _ in 0..HISTORY_CAPACITY {
+            history.push("session", json!({"from": "loaded", "to": "running"}));
+        }
+        history.push("tool", json!({"tool": "debug_continue", "arguments": {}}));
+        history.push(
+            "breakpoint_hit",
+            json!({"id": 2, "location": "src/main.rs:7"}),
+        );
+        assert_eq!(history.entries.len(), HISTORY_CAPACITY);
+        assert_eq!(history.dropped, 2);
+
+        let markdown = history.to_markdown();
+        assert!(markdown.contains("The 2 events before these were dropped."));
+        assert!(markdown.contains("loaded → running"));
+        assert!(markdown.contains("`debug_continue`"));
+        assert!(markdown.contains("breakpoint 2 hit at src/main.rs:7"));
+    }
+
+    #[test]
+    fn saved_breakpoints_read_back() {
+        let breakpoint = Breakpoint {
+            id: 4,
+            location: "src/db.rs:40".to_string(),
+            condition: Some("id == 7".to_string()),
+            ignore_count: Some(2),
+            enabled: false,
+            log_expressions: Some(vec!["id".to_string()]),
+            log_hits: 9,
+            on_hit_commands: vec!["bt".to_string()],
+            auto_continue: true,
+            call_trace: false,
+            log_arguments: false,
+        };
+        let read = Breakpoint::from_json(&breakpoint.to_json()).unwrap();
+        assert_eq!(read.to_json(), breakpoint.to_json());
+        assert_eq!(read.log_hits, 0);
+
+        let defaults = Breakpoint::from_json(&json!({"location": "main"})).unwrap();
+        assert!(defaults.enabled && defaults.condition.is_none());
+        assert!(Breakpoint::from_json(&json!({"id": 1})).is_err());
+    }
+
+    #[test]
+    fn ps_usage_parses_both_time_formats() {
+        assert_eq!(parse_ps_usage("  2048 01:02:03\n"), Some((2048, 3723)));
+        assert_eq!(parse_ps_usage("512 2-00:00:01"), Some((512, 172_801)));
+        assert_eq!(parse_ps_usage("4096   0:05.31"), Some((4096, 5)));
+        assert_eq!(parse_ps_usage(""), None);
+    }
+
+    #[test]
+    fn truncate_json_cuts_the_largest_parts() {
+        let frames: Vec<Value> = (0..1000)
+            .map(|level| json!({"level": level, "function": "app::recurse"}))
+            .collect();
+        let mut result = json!({
+            "success": true,
+            "frames": frames,
+            "output": "x".repeat(5000)
+        });
+        assert!(truncate_json(&mut result, 2000));
+        assert!(result.to_string().len() <= 2000);
+        assert_eq!(result["success"], true);
+        assert!(!result["frames"].as_array().unwrap().is_empty());
+        assert!(result["output"].as_str().unwrap().ends_with('…'));
+
+        let mut state = json!({"success": true, "state": "stopped"});
+        assert!(!truncate_json(&mut state, 10));
+        let mut text = json!({"success": true, "output": "é".repeat(100)});
+        assert!(!truncate_json(&mut text, 1000));
+        assert!(truncate_json(&mut text, 120));
+        assert!(text.to_string().len() <= 120);
+        assert!(text["output"].as_str().unwrap().ends_with("éé…"));
+    }
+
+    #[test]
+    fn output_pages_end_on_character_boundaries() {
+        let mut pages = OutputPages::default();
+        let cursor = pages.store("aé".repeat(3));
+        let first = pages.page(&cursor, 4).unwrap();
+        assert_eq!(first["page"], "aéa");
+        assert_eq!(first["total_bytes"], 9);
+        let next = first["next_cursor"].as_str().unwrap();
+        let second = pages.page(next, 100).unwrap();
+        assert_eq!(second["page"], "éaé");
+        assert_eq!(second["offset"], 4);
+        assert_eq!(second["next_cursor"], Value::Null);
+        assert!(pages.page("1:2", 4).is_err());
+        assert!(pages.page("9:0", 4).is_err());
+    }
+}